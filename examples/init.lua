@@ -67,3 +67,127 @@ pinel.ui.show_sidebar(true) -- coukd be either true or false depending on whethe
 -- Sidebar width ranges from 20.0 to 120.0
 -- You do not need to have the decimal
 pinel.ui.set_sidebar_width(220)
+
+--[[ Keymaps
+pinel.keymap.set(mode, keys, action) binds a key sequence to an action.
+
+`mode` is one of "" (all modes), "n" (normal), "i" (insert) or "v" (visual).
+`keys` is one or more chords separated by spaces, e.g. "ctrl+shift+k" or "ctrl+k ctrl+s".
+Modifiers are ctrl, shift, alt and cmd; named keys include esc, enter, tab, space and f1-f12.
+`action` is either the name of a command palette entry or a Lua function.
+User bindings are checked before the built-in shortcuts.
+]]
+pinel.keymap.set("", "ctrl+k ctrl+s", "Save File")
+pinel.keymap.set("", "ctrl+shift+k", function()
+    pinel.ui.show_sidebar(false)
+end)
+
+--[[ Events
+pinel.on(event, fn) calls `fn` every time the event happens for the rest of the session.
+The handler receives a table with `event` and, when a buffer is involved, its `path`.
+
+Events:
+buf_write_pre  -- an explicit save is about to write the active buffer (not autosave)
+buf_open       -- a file was opened into a new tab
+tab_changed    -- a different tab became active
+focus_lost     -- the Pinel window lost focus
+]]
+pinel.on("buf_open", function(ev)
+    if ev.path:match("%.md$") then
+        pinel.ui.show_sidebar(false)
+    end
+end)
+
+--[[ Buffer
+pinel.buf works on the active editor tab. Lines and columns start at 1.
+Edits go through the editor like typing does, so Ctrl+Z undoes them.
+
+pinel.buf.get_text()                  -- whole buffer as a string
+pinel.buf.get_lines(start, end)       -- table of lines start..end (inclusive), defaults to all
+pinel.buf.set_lines(start, end, lines) -- replace lines start..end; use end = start - 1 to insert
+pinel.buf.insert(text[, line, col])   -- insert at the cursor or at line/col
+pinel.buf.cursor()                    -- returns line, col
+pinel.buf.set_cursor(line, col)
+pinel.buf.path()                      -- file path, or nil for an unsaved buffer
+pinel.buf.language()                  -- e.g. "rust", "python"
+]]
+pinel.keymap.set("", "ctrl+alt+s", function()
+    local lines = pinel.buf.get_lines()
+    table.sort(lines)
+    pinel.buf.set_lines(1, #lines, lines)
+end)
+
+--[[ Commands
+pinel.command.register{ name, description, run } adds a command to the
+Ctrl+Shift+P palette and the `:` command input (type its name after `:`).
+Registering the same name again replaces the earlier command; built-in names are reserved.
+Keymaps can run it by name too: pinel.keymap.set("", "ctrl+alt+h", "Insert Header")
+]]
+pinel.command.register{
+    name = "Insert Header",
+    description = "Insert a file header comment",
+    run = function()
+        local path = pinel.buf.path() or "untitled"
+        pinel.buf.set_lines(1, 0, { "-- " .. path:match("[^/]*$"), "" })
+    end,
+}
+
+--[[ Plugins
+Plugins live in ~/.config/pinel/plugins/<name>/init.lua and load before this file.
+Each plugin gets its own globals (it can still read the shared ones like `pinel`),
+and its `require("foo.bar")` only looks in its own folder (foo/bar.lua or foo/bar/init.lua).
+A plugin that fails to load is reported in Settings > Plugins without affecting the others,
+and can be disabled there (takes effect after a restart).
+]]
+
+--[[ Reloading
+Saving this file, a plugin or theme.lua applies the change without a restart:
+keymaps, commands and hooks are cleared and every script runs again in a fresh state.
+If a script fails, the error and its line number (e.g. "init.lua:12: ...") show up
+in a notification, and whatever ran before the error stays applied.
+]]
+
+--[[ Console
+"Lua Console" in the command palette opens a REPL under the editor that runs in the
+same state as this file, so anything defined here is reachable from it.
+Expressions print their value (tables are expanded), Up/Down walk the history,
+and `print(...)` from this file, plugins, hooks or keymaps shows up there too.
+]]
+
+--[[ Jobs
+pinel.job.spawn runs a program in the background and returns its id; output
+arrives line by line without blocking the editor. `cwd` defaults to the open folder.
+Running jobs are listed in the Lua Console pane, where they can be cancelled,
+and they are stopped when scripts reload.
+]]
+pinel.command.register{
+    name = "Git Status",
+    description = "Print `git status --short` to the Lua Console",
+    run = function()
+        pinel.job.spawn{
+            cmd = "git",
+            args = { "status", "--short" },
+            on_stdout = function(line, id) print(line) end,
+            on_stderr = function(line, id) print("git: " .. line) end,
+            -- `code` is nil if the job was cancelled or failed to start; `err` says why
+            on_exit = function(code, err, id) print("git exited with " .. tostring(code or err)) end,
+        }
+        -- pinel.job.cancel(id) stops a job early
+    end,
+}
+
+--[[ Status bar
+pinel.statusline.add adds a segment to the status bar. `render` gets a table with
+path, line, col, language, encoding, line_ending, lsp and git_branch, and returns the
+text to show (nil hides the segment). Segments are rendered again when that state
+changes; call pinel.statusline.refresh() when something else they show changes.
+The order of segments, built-in or added here, is set by statusline_left and
+statusline_right in preferences.lua; segments not listed there go on their `align` side.
+]]
+pinel.statusline.add{
+    id = "indent",
+    align = "right",
+    render = function(ctx)
+        if ctx.path then return "Spaces: 4" end
+    end,
+}
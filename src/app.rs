@@ -17,12 +17,16 @@ use std::path::PathBuf;
 use std::time::{Duration, Instant};
 
 use crate::autocomplete::engine::Autocomplete;
+use crate::config::lsp_config::{self, LspRegistry};
 use crate::config::preferences::{self as prefs, EditorPreferences};
+use crate::config::watcher::{ConfigChanges, ConfigWatcher};
 use crate::features::command_input::CommandInput;
-use crate::features::command_palette::CommandPalette;
+use crate::features::command_palette::{CommandPalette, CommandRegistry};
 use crate::features::file_tree::FileTree;
 use crate::features::find_replace::FindReplace;
-use crate::features::fuzzy_finder::FuzzyFinder;
+use crate::features::fuzzy_finder::{FinderItem, FuzzyFinder};
+use crate::features::lua_console::{ConsoleLineKind, LuaConsole};
+use crate::features::statusline::Statusline;
 use crate::features::terminal::Terminal;
 use crate::features::updater::UpdateInfo;
 use crate::message::Message;
@@ -36,15 +40,34 @@ use crate::ui::{
 use crate::wakatime::{self, WakaTimeConfig};
 
 mod commands;
+mod editing;
+mod format;
+mod hints;
+mod inspector;
+mod jobs;
 mod lifecycle;
+mod lsp;
+mod statusline;
 mod subscription;
+mod symbols;
 mod update;
+mod view_console;
 mod view_editor;
 mod view_finders;
+mod view_inspector;
 mod view_integrations;
+mod view_lsp;
+mod view_outline;
 mod view_overlays;
+mod view_problems;
 mod view_root;
 mod view_settings;
+mod vim;
+
+use vim::{
+    JumpList, VimBlockInsert, VimConfirm, VimCursorShape, VimFindState, VimInput, VimMarks,
+    VimMode, VimRegisters, VimSearch, VimSearchStart, VimSubstitute,
+};
 
 pub enum TabKind {
     Editor {
@@ -59,6 +82,7 @@ pub enum TabKind {
 pub enum FocusPane {
     Editor,
     Terminal,
+    Console,
 }
 
 impl std::fmt::Debug for TabKind {
@@ -88,9 +112,16 @@ pub struct MarkdownPreviewPane {
 #[derive(Debug, Clone)]
 pub struct Notification {
     pub message: String,
+    pub kind: NotificationKind,
     pub shown_at: Instant,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationKind {
+    Success,
+    Error,
+}
+
 #[derive(Debug, Clone)]
 struct PendingHoverRequest {
     path: PathBuf,
@@ -102,12 +133,21 @@ struct PendingHoverRequest {
 
 const HOVER_TRIGGER_DELAY: Duration = Duration::from_secs(2);
 
+/// The code editor's font, also used for text drawn over it.
+const EDITOR_FONT: iced::Font = iced::Font {
+    family: iced::font::Family::Name("Fira Code"),
+    weight: iced::font::Weight::Normal,
+    ..iced::Font::DEFAULT
+};
+
 pub struct App {
     tabs: Vec<Tab>,
     active_tab: Option<usize>,
 
     cursor_line: usize,
     cursor_col: usize,
+    /// Where to put the cursor once a file that is being opened loads.
+    pending_location: Option<(PathBuf, (usize, usize))>,
 
     file_tree: Option<FileTree>,
 
@@ -135,6 +175,7 @@ pub struct App {
     fuzzy_finder: FuzzyFinder,
 
     command_palette: CommandPalette,
+    command_registry: CommandRegistry,
     command_palette_selected: usize,
     command_palette_input_id: iced::widget::Id,
 
@@ -153,6 +194,40 @@ pub struct App {
     command_input: CommandInput,
     command_input_id: iced::widget::Id,
 
+    vim_enabled: bool,
+    vim_mode: VimMode,
+    /// Keys of an unfinished command, count included, such as `2d` or `f`.
+    vim_pending: String,
+    vim_last_find: Option<VimFindState>,
+    /// Where visual mode started, 1-based like the cursor.
+    vim_visual_anchor: (usize, usize),
+    vim_registers: VimRegisters,
+    /// Keys of the change being typed, and of the last finished one for `.`.
+    vim_change: Vec<VimInput>,
+    vim_last_change: Vec<VimInput>,
+    /// The register a macro is being recorded into, and its keys so far.
+    vim_recording: Option<(char, Vec<VimInput>)>,
+    vim_last_macro: Option<char>,
+    /// How many `.` and `@` replays are running; replayed keys are not
+    /// recorded into a macro again.
+    vim_replay_depth: usize,
+    /// `.` is running, so the change it replays stays the last change.
+    vim_repeating: bool,
+    vim_block_insert: Option<VimBlockInsert>,
+    /// The last `/`, `?`, `*` or `#` search, which `n` and `N` repeat.
+    vim_search: Option<VimSearch>,
+    /// Where `/` or `?` was typed, while the search is being typed.
+    vim_search_start: Option<VimSearchStart>,
+    vim_marks: VimMarks,
+    vim_jumps: JumpList,
+    /// The last `:s`, which `:s` without arguments repeats.
+    vim_last_substitute: Option<VimSubstitute>,
+    /// A `:s///c` asking about each match.
+    vim_confirm: Option<VimConfirm>,
+    /// How many keys of the insert-mode escape sequence were just typed,
+    /// and when the last one was.
+    vim_escape_typed: Option<(usize, Instant)>,
+
     settings_open: bool,
     settings_section: String,
     editor_preferences: EditorPreferences,
@@ -169,29 +244,79 @@ pub struct App {
 
     lsp: crate::features::lsp::LspManager,
     lsp_diagnostics: HashMap<PathBuf, Vec<crate::features::lsp::InlineDiagnostic>>,
+    problems_open: bool,
+    problems_filter: crate::features::problems::ProblemFilter,
     lsp_overlay: iced_code_editor::LspOverlayState,
     lsp_enabled: bool,
-    lsp_server_keys: HashMap<PathBuf, &'static str>,
+    lsp_server_keys: HashMap<PathBuf, String>,
     pending_hover_request: Option<PendingHoverRequest>,
+    location_results: Option<lsp::LocationResults>,
+    rename: Option<lsp::RenameState>,
+    rename_input_id: iced::widget::Id,
+    code_actions: lsp::CodeActions,
+    /// Formats waiting for their formatter, by file.
+    formatting: HashMap<PathBuf, format::PendingFormat>,
+    outline: symbols::Outline,
+    symbol_index: symbols::SymbolIndex,
+    signature: hints::SignatureState,
+    inlay_hints: hints::InlayHints,
+    lsp_inspector: inspector::LspInspector,
+
+    statusline: Statusline,
+    git_branch: Option<String>,
 
     pending_sensitive_open: Option<PathBuf>,
+    /// Names of the tabs with unsaved changes while quitting waits for
+    /// confirmation.
+    pending_quit: Option<Vec<String>>,
+    /// The file `:wq` is saving, and whether it was `:wq!`; quitting
+    /// continues once the save finishes.
+    quit_after_save: Option<(PathBuf, bool)>,
 
     autocomplete: Autocomplete,
     modifier_state: Modifiers,
 
     developer_logs: VecDeque<(Instant, String)>,
+    /// Whether the Lua console pane is shown below the editor.
     developer_panel_visible: bool,
+    lua_console: LuaConsole,
+    lua_console_input_id: iced::widget::Id,
+
+    script_runtime: Option<scripting::ScriptRuntime>,
+    /// Processes started with `pinel.job.spawn` that have not exited yet.
+    jobs: Vec<scripting::jobs::RunningJob>,
+    config_watcher: ConfigWatcher,
+    plugins: Vec<scripting::plugins::PluginStatus>,
 }
 
 impl Default for App {
     fn default() -> Self {
-        let editor_preferences = prefs::load_preferences();
+        let mut config_errors = Vec::new();
+        let editor_preferences = prefs::load_preferences().unwrap_or_else(|err| {
+            config_errors.push(err);
+            EditorPreferences::default()
+        });
+        let wakatime = wakatime::load().unwrap_or_else(|err| {
+            config_errors.push(err);
+            WakaTimeConfig::default()
+        });
+        let vim_marks = VimMarks::load().unwrap_or_else(|err| {
+            config_errors.push(err);
+            VimMarks::default()
+        });
+        let lsp_registry = lsp_config::load().unwrap_or_else(|err| {
+            config_errors.push(err);
+            LspRegistry::default()
+        });
 
         let active_theme_name = {
             let name = &editor_preferences.theme_name;
             if name == "Custom (theme.lua)" {
                 use crate::config::theme_manager;
-                let lua_theme = theme_manager::load_theme();
+                let lua_theme = theme_manager::load_theme().unwrap_or_else(|err| {
+                    config_errors.push(err);
+                    theme_manager::ThemeColors::default()
+                });
                 let t = crate::theme::ThemeColors::from_lua_theme(&lua_theme);
                 crate::theme::set_theme(t);
                 "Custom (theme.lua)".to_string()
@@ -222,6 +347,7 @@ impl Default for App {
             active_tab: None,
             cursor_line: 1,
             cursor_col: 1,
+            pending_location: None,
             file_tree: None,
             sidebar_visible: true,
             sidebar_width: SIDEBAR_DEFAULT_WIDTH,
@@ -242,6 +368,7 @@ impl Default for App {
             fuzzy_finder: FuzzyFinder::default(),
 
             command_palette: CommandPalette::default(),
+            command_registry: CommandRegistry::default(),
             command_palette_selected: 0,
             command_palette_input_id: iced::widget::Id::unique(),
 
@@ -280,57 +407,90 @@ impl Default for App {
             replace_input_id: iced::widget::Id::unique(),
             command_input: CommandInput::default(),
             command_input_id: iced::widget::Id::unique(),
+            vim_enabled: editor_preferences.vim_mode,
+            vim_mode: if editor_preferences.vim_mode {
+                VimMode::Normal
+            } else {
+                VimMode::Insert
+            },
+            vim_pending: String::new(),
+            vim_last_find: None,
+            vim_visual_anchor: (1, 1),
+            vim_registers: VimRegisters::default(),
+            vim_change: Vec::new(),
+            vim_last_change: Vec::new(),
+            vim_recording: None,
+            vim_last_macro: None,
+            vim_replay_depth: 0,
+            vim_repeating: false,
+            vim_block_insert: None,
+            vim_search: None,
+            vim_search_start: None,
+            vim_marks,
+            vim_jumps: JumpList::default(),
+            vim_last_substitute: None,
+            vim_confirm: None,
+            vim_escape_typed: None,
             settings_open: false,
             settings_section: "general".to_string(),
             editor_preferences,
             active_theme_name,
             theme_dropdown_open: false,
-            wakatime: wakatime::load(),
+            wakatime,
             wakatime_api_key_hovered: false,
             last_wakatime_entity: None,
             last_wakatime_sent_at: None,
             notification: None,
             update_banner: None,
-            lsp: crate::features::lsp::LspManager::new(),
+            lsp: crate::features::lsp::LspManager::new(lsp_registry),
             lsp_diagnostics: HashMap::new(),
+            problems_open: false,
+            problems_filter: Default::default(),
             lsp_overlay: iced_code_editor::LspOverlayState::new(),
             lsp_enabled: true,
             lsp_server_keys: HashMap::new(),
             pending_hover_request: None,
+            location_results: None,
+            rename: None,
+            rename_input_id: iced::widget::Id::unique(),
+            code_actions: Default::default(),
+            formatting: HashMap::new(),
+            outline: symbols::Outline::default(),
+            symbol_index: symbols::SymbolIndex::default(),
+            signature: hints::SignatureState::default(),
+            inlay_hints: hints::InlayHints::default(),
+            lsp_inspector: inspector::LspInspector::default(),
+            statusline: Statusline::default(),
+            git_branch: None,
             pending_sensitive_open: None,
+            pending_quit: None,
+            quit_after_save: None,
             autocomplete: Autocomplete::new(),
             modifier_state: Modifiers::default(),
             developer_logs: VecDeque::new(),
             developer_panel_visible: false,
+            lua_console: LuaConsole::default(),
+            lua_console_input_id: iced::widget::Id::unique(),
+            script_runtime: None,
+            jobs: Vec::new(),
+            config_watcher: ConfigWatcher::new(),
+            plugins: Vec::new(),
         };
 
-        let startup_script = scripting::load_startup_script();
-        app.push_developer_log(format!(
-            "startup lua path: {}",
-            startup_script.path.display()
-        ));
-
-        match &startup_script.source {
-            Some(source) => {
-                app.push_developer_log("startup lua source begin".to_string());
-                for line in source.lines() {
-                    app.push_developer_log(format!("lua> {line}"));
-                }
-                app.push_developer_log("startup lua source end".to_string());
-            }
-            None => {
-                app.push_developer_log("startup lua source not found".to_string());
-            }
-        }
-
-        if let Some(error) = &startup_script.error {
-            app.push_developer_log(format!("startup lua error: {error}"));
+        for error in &config_errors {
+            eprintln!("Config error: {error}");
+            app.push_developer_log(format!("config error: {error}"));
         }
 
-        for command in startup_script.commands {
-            app.push_developer_log(format!("startup lua command: {:?}", command));
-            app.apply_editor_command(command);
+        let script_error = app.load_scripts();
+        if let Some(error) = config_errors.into_iter().next().or(script_error) {
+            app.notification = Some(Notification {
+                message: error,
+                kind: NotificationKind::Error,
+                shown_at: Instant::now(),
+            });
         }
+        app.refresh_statusline();
 
         app
     }
@@ -354,11 +514,7 @@ impl App {
     pub(super) fn configured_code_editor(&self, content: &str, syntax: &str) -> CodeEditor {
         let mut editor = iced_code_editor::CodeEditor::new(content, syntax);
         editor.set_theme(theme().editor_style);
-        editor.set_font(iced::Font {
-            family: iced::font::Family::Name("Fira Code"),
-            weight: iced::font::Weight::Normal,
-            ..iced::Font::DEFAULT
-        });
+        editor.set_font(EDITOR_FONT);
         editor.set_search_replace_enabled(false);
         editor.set_line_numbers_enabled(true);
         editor.set_wrap_enabled(false);
@@ -411,6 +567,290 @@ impl App {
             EditorCommand::SetSidebarWidth(width) => {
                 self.sidebar_width = width.clamp(SIDEBAR_MIN_WIDTH, SIDEBAR_MAX_WIDTH);
             }
+            EditorCommand::SetKeymap { mode, keys, action } => {
+                use crate::scripting::keymap::{self, KeyBinding, KeymapMode};
+
+                let binding = KeymapMode::parse(&mode).and_then(|mode| {
+                    keymap::parse_sequence(&keys).map(|sequence| KeyBinding {
+                        mode,
+                        sequence,
+                        action,
+                    })
+                });
+
+                match binding {
+                    Ok(binding) => keymap::keymap_mut().set(binding),
+                    Err(err) => {
+                        eprintln!("Lua keymap error: {err}");
+                        self.push_developer_log(format!("lua keymap error: {err}"));
+                    }
+                }
+            }
+            EditorCommand::EditBuffer { edit, cursor } => {
+                if self.apply_text_edit(&edit) {
+                    self.set_active_cursor(cursor);
+                }
+            }
+            EditorCommand::SetCursor(position) => {
+                self.set_active_cursor(position);
+            }
+            EditorCommand::RegisterCommand {
+                name,
+                description,
+                callback,
+            } => {
+                let command = crate::features::command_palette::Command {
+                    name,
+                    description,
+                    action: crate::features::command_palette::CommandAction::Lua(callback),
+                    markdown_only: false,
+                };
+                if let Err(err) = self.command_registry.register(command) {
+                    eprintln!("Lua command error: {err}");
+                    self.push_developer_log(format!("lua command error: {err}"));
+                }
+            }
+            EditorCommand::SpawnJob(spec) => self.start_job(spec),
+            EditorCommand::CancelJob(id) => self.cancel_job(id),
+            EditorCommand::AddStatusSegment { id, align, render } => {
+                self.add_status_segment(id, align, render)
+            }
+            EditorCommand::RefreshStatusline => self.statusline.invalidate(),
+        }
+    }
+
+    /// Runs plugins and `init.lua` in a fresh runtime and applies what they set
+    /// up. Returns a one-line summary of the first error, if any; a missing
+    /// `init.lua` is not an error.
+    fn load_scripts(&mut self) -> Option<String> {
+        let startup_script =
+            scripting::load_startup_script(&self.editor_preferences.disabled_plugins);
+        self.push_developer_log(format!(
+            "startup lua path: {}",
+            startup_script.path.display()
+        ));
+
+        match &startup_script.source {
+            Some(source) => {
+                self.push_developer_log("startup lua source begin".to_string());
+                for line in source.lines() {
+                    self.push_developer_log(format!("lua> {line}"));
+                }
+                self.push_developer_log("startup lua source end".to_string());
+            }
+            None => {
+                self.push_developer_log("startup lua source not found".to_string());
+            }
+        }
+
+        if let Some(error) = &startup_script.error {
+            self.push_developer_log(format!("startup lua error: {error}"));
+        }
+
+        for plugin in &startup_script.plugins {
+            let status = match (&plugin.error, plugin.enabled) {
+                (Some(error), _) => format!("error: {error}"),
+                (None, true) => "loaded".to_string(),
+                (None, false) => "disabled".to_string(),
+            };
+            self.push_developer_log(format!("plugin {}: {status}", plugin.name));
+        }
+
+        for command in startup_script.commands {
+            self.push_developer_log(format!("startup lua command: {:?}", command));
+            self.apply_editor_command(command);
+        }
+        self.script_runtime = startup_script.runtime;
+        self.plugins = startup_script.plugins;
+        self.collect_script_output();
+
+        let config_dir = crate::config::theme_manager::get_config_dir();
+        let plugin_error = self.plugins.iter().find_map(|plugin| {
+            let error = plugin.error.as_ref()?;
+            Some(scripting::error_summary(error, &config_dir))
+        });
+        let script_error = startup_script
+            .error
+            .filter(|_| startup_script.source.is_some())
+            .map(|error| scripting::error_summary(&error, &config_dir));
+        script_error.or(plugin_error)
+    }
+
+    /// Re-applies config files edited while the editor is running.
+    pub(super) fn reload_config(&mut self, changes: ConfigChanges) {
+        let theme_result = (changes.theme && self.active_theme_name == "Custom (theme.lua)")
+            .then(|| self.apply_lua_theme_file());
+        let theme_reloaded = matches!(theme_result, Some(Ok(())));
+        let lsp_result = changes.lsp.then(|| self.reload_lsp_config());
+        let lsp_reloaded = matches!(lsp_result, Some(Ok(())));
+        let mut error = theme_result
+            .and_then(Result::err)
+            .or(lsp_result.and_then(Result::err));
+
+        // Re-running the scripts also restores init.lua colour overrides on
+        // top of a reloaded theme.lua.
+        if changes.scripts || theme_reloaded {
+            scripting::keymap::keymap_mut().clear();
+            self.command_registry.clear_user_commands();
+            self.drop_all_jobs();
+            self.statusline.clear_scripted();
+            error = error.or(self.load_scripts());
+        } else if error.is_none() && !lsp_reloaded {
+            return;
+        }
+
+        let (message, kind) = match error {
+            Some(error) => (error, NotificationKind::Error),
+            None if changes.scripts => ("Reloaded init.lua".to_string(), NotificationKind::Success),
+            None if lsp_reloaded => ("Reloaded lsp.lua".to_string(), NotificationKind::Success),
+            None => ("Reloaded theme.lua".to_string(), NotificationKind::Success),
+        };
+        self.notification = Some(Notification {
+            message,
+            kind,
+            shown_at: Instant::now(),
+        });
+    }
+
+    /// Loads `theme.lua` and makes it the active theme. On error the current
+    /// theme stays.
+    pub(super) fn apply_lua_theme_file(&mut self) -> Result<(), String> {
+        let lua_theme = crate::config::theme_manager::load_theme()?;
+        crate::theme::set_theme(crate::theme::ThemeColors::from_lua_theme(&lua_theme));
+        self.apply_editor_theme_to_tabs();
+        self.active_theme_name = "Custom (theme.lua)".to_string();
+        self.editor_preferences.theme_name = "Custom (theme.lua)".to_string();
+        Ok(())
+    }
+
+    /// Notifies `pinel.on` handlers of an editor event and applies what they queued.
+    pub(super) fn run_script_hook(&mut self, event: scripting::HookEvent, path: Option<PathBuf>) {
+        let snapshot = self.active_buffer_snapshot();
+        let Some(runtime) = &self.script_runtime else {
+            return;
+        };
+        runtime.set_buffer(snapshot);
+
+        let (commands, errors) = runtime.fire(event, path.as_deref());
+        self.collect_script_output();
+        for err in errors {
+            eprintln!("Lua {} hook error: {err}", event.name());
+            self.push_developer_log(format!("lua {} hook error: {err}", event.name()));
+        }
+        for command in commands {
+            self.apply_editor_command(command);
+        }
+    }
+
+    /// Runs the action bound to a user key sequence.
+    pub(super) fn run_keymap_action(
+        &mut self,
+        action: scripting::keymap::KeymapAction,
+    ) -> iced::Task<Message> {
+        match action {
+            scripting::keymap::KeymapAction::Command(name) => self.execute_palette_command(&name),
+            scripting::keymap::KeymapAction::Lua(id) => {
+                self.run_lua_callback(id, "keymap", ());
+                iced::Task::none()
+            }
+        }
+    }
+
+    /// Calls a stored Lua function and applies the commands it queued.
+    /// `context` names the caller in error logs.
+    pub(super) fn run_lua_callback(
+        &mut self,
+        id: usize,
+        context: &str,
+        args: impl mlua::IntoLuaMulti,
+    ) {
+        let snapshot = self.active_buffer_snapshot();
+        let result = match &self.script_runtime {
+            Some(runtime) => {
+                runtime.set_buffer(snapshot);
+                runtime.call_callback(id, args)
+            }
+            None => Err("lua runtime is not loaded".to_string()),
+        };
+        self.collect_script_output();
+        match result {
+            Ok(commands) => {
+                for command in commands {
+                    self.apply_editor_command(command);
+                }
+            }
+            Err(err) => {
+                eprintln!("Lua {context} error: {err}");
+                self.push_developer_log(format!("lua {context} error: {err}"));
+                self.lua_console
+                    .push(ConsoleLineKind::Error, format!("{context}: {err}"));
+            }
+        }
+    }
+
+    /// Evaluates the console input line against the editor's runtime.
+    pub(super) fn submit_lua_console(&mut self) {
+        let Some(input) = self.lua_console.submit() else {
+            return;
+        };
+
+        let snapshot = self.active_buffer_snapshot();
+        let result = match &self.script_runtime {
+            Some(runtime) => {
+                runtime.set_buffer(snapshot);
+                runtime.eval_console(&input)
+            }
+            None => Err("lua runtime is not loaded".to_string()),
+        };
+        self.collect_script_output();
+
+        match result {
+            Ok(values) => {
+                for value in values {
+                    self.lua_console.push(ConsoleLineKind::Result, value);
+                }
+            }
+            Err(err) => self.lua_console.push(ConsoleLineKind::Error, err),
+        }
+
+        let commands = self
+            .script_runtime
+            .as_ref()
+            .map(|runtime| runtime.take_commands())
+            .unwrap_or_default();
+        for command in commands {
+            self.apply_editor_command(command);
+        }
+    }
+
+    /// Moves whatever scripts printed into the Lua console.
+    fn collect_script_output(&mut self) {
+        let Some(runtime) = &self.script_runtime else {
+            return;
+        };
+        for line in runtime.take_output() {
+            self.lua_console.push(ConsoleLineKind::Output, line);
         }
     }
 }
+
+#[cfg(test)]
+impl App {
+    /// Opens `content` in a new editor tab for `path` and makes it active.
+    pub(super) fn open_test_tab(&mut self, path: impl Into<PathBuf>, content: &str) {
+        let path = path.into();
+        let syntax = path.extension().unwrap_or_default().to_string_lossy();
+        let code_editor = self.configured_code_editor(content, &syntax);
+        self.tabs.push(Tab {
+            name: path.file_name().unwrap().to_string_lossy().into(),
+            path,
+            kind: TabKind::Editor {
+                code_editor,
+                buffer: crate::features::editor_buffer::EditorBuffer::from_text(content),
+            },
+            autosave_requested_at: None,
+            autosave_in_flight: false,
+        });
+        self.active_tab = Some(self.tabs.len() - 1);
+    }
+}
@@ -0,0 +1,174 @@
+use super::*;
+use crate::features::editor_buffer::{EditorBuffer, TextEdit};
+use crate::scripting::buffer::BufferSnapshot;
+use iced_code_editor::{ArrowDirection, Message as EditorMessage};
+
+impl App {
+    /// Captures the active editor for scripts that read or edit it.
+    pub(super) fn active_buffer_snapshot(&self) -> Option<BufferSnapshot> {
+        let tab = self.tabs.get(self.active_tab?)?;
+        let TabKind::Editor { code_editor, .. } = &tab.kind else {
+            return None;
+        };
+
+        let path = (tab.path != std::path::Path::new("untitled")).then(|| tab.path.clone());
+        let language = self
+            .lsp
+            .language_for_path(&tab.path)
+            .map(|language| language.language.clone())
+            .or_else(|| {
+                let syntax = code_editor.syntax();
+                (!syntax.is_empty()).then(|| syntax.to_string())
+            });
+
+        Some(BufferSnapshot {
+            path,
+            language,
+            buffer: EditorBuffer::from_text(&code_editor.content()),
+            cursor: code_editor.cursor_position(),
+        })
+    }
+
+    /// Applies an edit to the active editor through its own selection and
+    /// paste handling, so it lands in the undo history like typed text.
+    pub(super) fn apply_text_edit(&mut self, edit: &TextEdit) -> bool {
+        let Some(idx) = self.active_tab else {
+            return false;
+        };
+        let Some(tab) = self.tabs.get_mut(idx) else {
+            return false;
+        };
+        let TabKind::Editor {
+            code_editor,
+            buffer,
+        } = &mut tab.kind
+        else {
+            return false;
+        };
+
+        let (start, end) = if edit.end < edit.start {
+            (edit.end, edit.start)
+        } else {
+            (edit.start, edit.end)
+        };
+        select_range(code_editor, start, end);
+
+        if !edit.text.is_empty() {
+            let _ = code_editor.update(&EditorMessage::Paste(edit.text.clone()));
+        } else if end != start {
+            let _ = code_editor.update(&EditorMessage::DeleteSelection);
+        }
+
+        buffer.set_text(&code_editor.content());
+        tab.autosave_requested_at = Some(Instant::now());
+        self.sync_cursor_from_active_editor();
+        true
+    }
+
+    /// Moves the active editor's cursor to a 0-based `(line, column)`.
+    pub(super) fn set_active_cursor(&mut self, (line, col): (usize, usize)) {
+        if let Some(tab) = self.active_tab.and_then(|idx| self.tabs.get_mut(idx)) {
+            if let TabKind::Editor { code_editor, .. } = &mut tab.kind {
+                let _ = code_editor.update(&EditorMessage::GotoPosition(line, col));
+            }
+        }
+        self.sync_cursor_from_active_editor();
+    }
+
+    /// Selects `start..end` (0-based, `start` first) in the active editor.
+    pub(super) fn select_active_range(&mut self, start: (usize, usize), end: (usize, usize)) {
+        if let Some(tab) = self.active_tab.and_then(|idx| self.tabs.get_mut(idx)) {
+            if let TabKind::Editor { code_editor, .. } = &mut tab.kind {
+                select_range(code_editor, start, end);
+            }
+        }
+    }
+
+    /// Selects `path` in the file tree and moves the cursor to `pos`,
+    /// remembering where it was for the jump list.
+    pub(super) fn reveal_location(
+        &mut self,
+        path: PathBuf,
+        pos: (usize, usize),
+    ) -> iced::Task<Message> {
+        if let Some(ref mut tree) = self.file_tree {
+            tree.select(path.clone());
+        }
+        self.vim_push_jump();
+        self.go_to_location(path, pos)
+    }
+
+    /// Puts the cursor at the 0-based `pos` in `path`, switching to its tab,
+    /// or opening the file and moving there once it has loaded.
+    pub(super) fn go_to_location(
+        &mut self,
+        path: PathBuf,
+        pos: (usize, usize),
+    ) -> iced::Task<Message> {
+        let Some(idx) = self.tabs.iter().position(|tab| tab.path == path) else {
+            self.pending_location = Some((path.clone(), pos));
+            return self.update(Message::FileClicked(path));
+        };
+        let switch = if self.active_tab == Some(idx) {
+            iced::Task::none()
+        } else {
+            self.update(Message::TabSelected(idx))
+        };
+        let TabKind::Editor {
+            code_editor,
+            buffer,
+        } = &mut self.tabs[idx].kind
+        else {
+            return switch;
+        };
+        let line = pos.0.min(buffer.line_count() - 1);
+        let col = pos.1.min(buffer.line_len(line));
+        let scroll = code_editor
+            .update(&EditorMessage::GotoPosition(line, col))
+            .map(Message::CodeEditorEvent);
+        self.cursor_line = line + 1;
+        self.cursor_col = col + 1;
+        iced::Task::batch([switch, scroll])
+    }
+
+    /// Finishes a [`App::go_to_location`] that had to open `path` first.
+    pub(super) fn go_to_pending_location(&mut self, path: &std::path::Path) -> iced::Task<Message> {
+        match self.pending_location.take() {
+            Some((pending, pos)) if pending == path => self.go_to_location(pending, pos),
+            other => {
+                self.pending_location = other;
+                iced::Task::none()
+            }
+        }
+    }
+
+    pub(super) fn sync_cursor_from_active_editor(&mut self) {
+        if let Some(tab) = self.active_tab.and_then(|idx| self.tabs.get(idx)) {
+            if let TabKind::Editor { code_editor, .. } = &tab.kind {
+                let (line, col) = code_editor.cursor_position();
+                self.cursor_line = line + 1;
+                self.cursor_col = col + 1;
+            }
+        }
+    }
+}
+
+/// Selects `start..end` with the editor's own cursor moves; it has no API to
+/// set a selection directly.
+fn select_range(code_editor: &mut CodeEditor, start: (usize, usize), end: (usize, usize)) {
+    let _ = code_editor.update(&EditorMessage::GotoPosition(start.0, start.1));
+    if end == start {
+        return;
+    }
+    for _ in start.0..end.0 {
+        let _ = code_editor.update(&EditorMessage::ArrowKey(ArrowDirection::Down, true));
+    }
+    let mut col = start.1;
+    if end.0 > start.0 {
+        let _ = code_editor.update(&EditorMessage::Home(true));
+        col = 0;
+    }
+    for _ in col..end.1 {
+        let _ = code_editor.update(&EditorMessage::ArrowKey(ArrowDirection::Right, true));
+    }
+}
@@ -0,0 +1,255 @@
+//! Format Document and format-on-save. The language server formats when it
+//! can, an external command from the `formatters` preference otherwise, and
+//! the result goes in as the few edits that differ.
+
+use super::*;
+use crate::features::editor_buffer::{EditorBuffer, TextEdit};
+use crate::features::format;
+use crate::features::lsp::FileChange;
+
+/// How long a save waits for its formatter before writing the file as is.
+const FORMAT_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub(super) struct PendingFormat {
+    /// The text handed to the formatter; a result for anything else is stale.
+    original: String,
+    /// Started by `SaveFile`, which writes the file once this is done.
+    save_after: bool,
+    started_at: Instant,
+}
+
+impl App {
+    /// Starts formatting the active document. The result arrives as a
+    /// language server event or `Message::FormatFinished`.
+    pub(super) fn format_document(
+        &mut self,
+        save_after: bool,
+    ) -> Result<iced::Task<Message>, String> {
+        let tab = self
+            .active_tab
+            .and_then(|idx| self.tabs.get_mut(idx))
+            .ok_or("No file is open")?;
+        let TabKind::Editor { code_editor, .. } = &mut tab.kind else {
+            return Err("No file is open".to_string());
+        };
+        let path = tab.path.clone();
+        if let Some(pending) = self.formatting.get_mut(&path) {
+            pending.save_after |= save_after;
+            return Ok(iced::Task::none());
+        }
+        code_editor.lsp_flush_pending_changes();
+        let original = code_editor.content();
+
+        let mut reason = None;
+        if let Some(server_key) = self.lsp_server_keys.get(&path) {
+            let prefs = &self.editor_preferences;
+            match self
+                .lsp
+                .format(server_key, &path, prefs.tab_size, prefs.use_spaces)
+            {
+                Ok(()) => {
+                    self.start_format(path, original, save_after);
+                    return Ok(iced::Task::none());
+                }
+                Err(message) => reason = Some(message),
+            }
+        }
+
+        let language = format::language_id(&path);
+        let Some(command) = language
+            .and_then(|language| self.editor_preferences.formatters.get(language))
+            .cloned()
+        else {
+            return Err(reason.unwrap_or_else(|| match language {
+                Some(language) => format!("No formatter is configured for {language}"),
+                None => "No formatter for this file type".to_string(),
+            }));
+        };
+        if let Some(reason) = reason {
+            self.dev_log(format!("Format: {reason}, using `{command}`"));
+        }
+        self.start_format(path.clone(), original.clone(), save_after);
+        Ok(iced::Task::perform(
+            async move {
+                let result = format::run_external(command, &path, original).await;
+                (path, result)
+            },
+            |(path, result)| Message::FormatFinished(path, result),
+        ))
+    }
+
+    fn start_format(&mut self, path: PathBuf, original: String, save_after: bool) {
+        self.formatting.insert(
+            path,
+            PendingFormat {
+                original,
+                save_after,
+                started_at: Instant::now(),
+            },
+        );
+    }
+
+    /// Formats first when the active file's language is in `format_on_save`.
+    /// `None` means there is nothing to wait for and the save goes ahead.
+    pub(super) fn format_before_save(&mut self) -> Option<iced::Task<Message>> {
+        let path = self
+            .active_tab
+            .and_then(|idx| self.tabs.get(idx))?
+            .path
+            .clone();
+        let language = format::language_id(&path)?;
+        if !self.editor_preferences.formats_on_save(language) {
+            return None;
+        }
+        match self.format_document(true) {
+            Ok(task) => Some(task),
+            Err(message) => {
+                self.show_format_error(message);
+                None
+            }
+        }
+    }
+
+    /// A language server's answer: its edits are applied to the text it was
+    /// given, and the outcome diffed like an external formatter's output.
+    pub(super) fn lsp_formatted(
+        &mut self,
+        path: PathBuf,
+        edits: Result<Vec<TextEdit>, String>,
+    ) -> iced::Task<Message> {
+        let Some(pending) = self.formatting.get(&path) else {
+            return iced::Task::none();
+        };
+        let result = edits.map(|edits| {
+            let mut buffer = EditorBuffer::from_text(&pending.original);
+            FileChange {
+                path: path.clone(),
+                edits,
+            }
+            .apply(&mut buffer);
+            buffer.text()
+        });
+        self.format_finished(path, result)
+    }
+
+    pub(super) fn format_finished(
+        &mut self,
+        path: PathBuf,
+        result: Result<String, String>,
+    ) -> iced::Task<Message> {
+        let Some(pending) = self.formatting.remove(&path) else {
+            return iced::Task::none();
+        };
+        let Some(idx) = self.tabs.iter().position(|tab| tab.path == path) else {
+            return iced::Task::none();
+        };
+        let current = match &self.tabs[idx].kind {
+            TabKind::Editor { code_editor, .. } => code_editor.content(),
+            _ => return iced::Task::none(),
+        };
+
+        match result {
+            Ok(formatted) if current == pending.original => {
+                let edits = format::minimal_edits(&pending.original, &formatted);
+                self.dev_log(format!(
+                    "Format: {} changes in {}",
+                    edits.len(),
+                    path.display()
+                ));
+                if !edits.is_empty() {
+                    self.apply_tab_edits(idx, &edits);
+                }
+            }
+            Ok(_) => self.dev_log(format!(
+                "Format: {} changed while formatting; result dropped",
+                path.display()
+            )),
+            Err(message) => self.show_format_error(message),
+        }
+
+        if pending.save_after {
+            self.save_after_format(idx)
+        } else {
+            iced::Task::none()
+        }
+    }
+
+    /// Gives up on formatters that haven't answered, saving each file as
+    /// it is if a save was waiting.
+    pub(super) fn check_format_timeout(&mut self) -> iced::Task<Message> {
+        let expired: Vec<PathBuf> = self
+            .formatting
+            .iter()
+            .filter(|(_, pending)| pending.started_at.elapsed() >= FORMAT_TIMEOUT)
+            .map(|(path, _)| path.clone())
+            .collect();
+        let mut tasks = Vec::new();
+        for path in expired {
+            let Some(pending) = self.formatting.remove(&path) else {
+                continue;
+            };
+            self.show_format_error(format!(
+                "Formatting {} timed out",
+                path.file_name().unwrap_or_default().to_string_lossy()
+            ));
+            match self.tabs.iter().position(|tab| tab.path == path) {
+                Some(idx) if pending.save_after => tasks.push(self.save_after_format(idx)),
+                _ => {}
+            }
+        }
+        iced::Task::batch(tasks)
+    }
+
+    /// Finishes the save that waited for formatting, even if the user has
+    /// moved to another tab since.
+    fn save_after_format(&mut self, idx: usize) -> iced::Task<Message> {
+        if self.active_tab == Some(idx) {
+            self.write_active_file()
+        } else {
+            self.autosave_task_for_tab(idx)
+        }
+    }
+
+    pub(super) fn show_format_error(&mut self, message: String) {
+        self.dev_log(format!("Format: {message}"));
+        self.notification = Some(Notification {
+            message,
+            kind: NotificationKind::Error,
+            shown_at: Instant::now(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn content(app: &App, idx: usize) -> String {
+        match &app.tabs[idx].kind {
+            TabKind::Editor { code_editor, .. } => code_editor.content(),
+            TabKind::Preview { .. } => String::new(),
+        }
+    }
+
+    #[test]
+    fn formats_of_different_files_do_not_replace_each_other() {
+        let mut app = App::default();
+        app.open_test_tab("/tmp/a.rs", "fn a(){}");
+        app.open_test_tab("/tmp/b.rs", "fn b(){}");
+        app.start_format(PathBuf::from("/tmp/a.rs"), "fn a(){}".into(), true);
+        app.start_format(PathBuf::from("/tmp/b.rs"), "fn b(){}".into(), true);
+
+        let _ = app.format_finished(PathBuf::from("/tmp/a.rs"), Ok("fn a() {}".into()));
+        assert_eq!(content(&app, 0), "fn a() {}");
+        assert!(app.formatting.contains_key(Path::new("/tmp/b.rs")));
+
+        app.formatting
+            .get_mut(Path::new("/tmp/b.rs"))
+            .unwrap()
+            .started_at -= FORMAT_TIMEOUT;
+        let _ = app.check_format_timeout();
+        assert!(app.formatting.is_empty());
+        assert_eq!(content(&app, 1), "fn b(){}");
+    }
+}
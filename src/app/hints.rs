@@ -0,0 +1,181 @@
+//! Signature help while typing a call, and the language server's inlay
+//! hints for the active file.
+
+use super::*;
+use crate::features::lsp::{InlayHint, SignatureHelp};
+use iced_code_editor::Message as EditorMessage;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+
+/// How often the active file is checked for edits that need new hints.
+const INLAY_HINT_REFRESH: Duration = Duration::from_millis(500);
+
+pub(super) struct SignaturePopup {
+    pub(super) path: PathBuf,
+    pub(super) help: SignatureHelp,
+}
+
+#[derive(Default)]
+pub(super) struct SignatureState {
+    pub(super) popup: Option<SignaturePopup>,
+    /// Set while requests are out; Escape clears it so late answers don't
+    /// bring the popup back.
+    wanted: bool,
+}
+
+impl SignatureState {
+    pub(super) fn close(&mut self) {
+        self.popup = None;
+        self.wanted = false;
+    }
+}
+
+#[derive(Default)]
+pub(super) struct InlayHints {
+    pub(super) path: Option<PathBuf>,
+    /// Hints by 0-based line, in column order.
+    pub(super) lines: HashMap<usize, Vec<InlayHint>>,
+    /// File and text hash the hints were last asked for.
+    source: Option<(PathBuf, u64)>,
+    checked_at: Option<Instant>,
+}
+
+impl InlayHints {
+    pub(super) fn invalidate(&mut self) {
+        self.source = None;
+        self.checked_at = None;
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.path = None;
+        self.lines.clear();
+        self.invalidate();
+    }
+}
+
+impl App {
+    /// Opens the signature popup when a trigger character is typed, and
+    /// keeps it up to date while the cursor moves inside the call.
+    pub(super) fn update_signature_help(&mut self, event: &EditorMessage, path: &Path) {
+        let typed = match event {
+            EditorMessage::CharacterInput(c) => Some(*c),
+            EditorMessage::Backspace
+            | EditorMessage::Delete
+            | EditorMessage::Paste(_)
+            | EditorMessage::Enter
+            | EditorMessage::ArrowKey(..)
+            | EditorMessage::MouseClick(_) => None,
+            _ => return,
+        };
+        let Some(server_key) = self.lsp_server_keys.get(path).cloned() else {
+            return;
+        };
+        let showing = self
+            .signature
+            .popup
+            .as_ref()
+            .is_some_and(|popup| popup.path == path);
+        let (triggers, retriggers) = self.lsp.signature_triggers(&server_key, path);
+        let trigger = typed.filter(|c| triggers.contains(c) || (showing && retriggers.contains(c)));
+        if trigger.is_none() && !showing {
+            return;
+        }
+
+        let Some(tab) = self.active_tab.and_then(|idx| self.tabs.get_mut(idx)) else {
+            return;
+        };
+        let TabKind::Editor { code_editor, .. } = &mut tab.kind else {
+            return;
+        };
+        code_editor.lsp_flush_pending_changes();
+        let position = code_editor.cursor_position();
+        match self
+            .lsp
+            .signature_help(&server_key, path, position, trigger, showing)
+        {
+            Ok(()) => self.signature.wanted = true,
+            Err(reason) => {
+                self.dev_log(format!("Signature help: {reason}"));
+                self.signature.close();
+            }
+        }
+    }
+
+    pub(super) fn signature_help_received(&mut self, path: PathBuf, help: Option<SignatureHelp>) {
+        if !self.signature.wanted {
+            return;
+        }
+        match help {
+            Some(help) => self.signature.popup = Some(SignaturePopup { path, help }),
+            None => self.signature.close(),
+        }
+    }
+
+    /// Asks the active file's language server for hints whenever its text
+    /// has changed.
+    pub(super) fn refresh_inlay_hints(&mut self) {
+        if !self.lsp_enabled || !self.editor_preferences.inlay_hints {
+            return;
+        }
+        if self
+            .inlay_hints
+            .checked_at
+            .is_some_and(|at| at.elapsed() < INLAY_HINT_REFRESH)
+        {
+            return;
+        }
+        self.inlay_hints.checked_at = Some(Instant::now());
+
+        let Some(tab) = self.active_tab.and_then(|idx| self.tabs.get_mut(idx)) else {
+            return;
+        };
+        let TabKind::Editor {
+            code_editor,
+            buffer,
+        } = &mut tab.kind
+        else {
+            return;
+        };
+        let Some(server_key) = self.lsp_server_keys.get(&tab.path) else {
+            return;
+        };
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        code_editor.content().hash(&mut hasher);
+        let source = (tab.path.clone(), hasher.finish());
+        if self.inlay_hints.source.as_ref() == Some(&source) {
+            return;
+        }
+        code_editor.lsp_flush_pending_changes();
+        // Not asked again until the text changes, unless the server isn't
+        // ready yet.
+        if self
+            .lsp
+            .inlay_hints(server_key, &tab.path, buffer.line_count())
+            .is_ok()
+        {
+            self.inlay_hints.source = Some(source);
+        }
+    }
+
+    pub(super) fn inlay_hints_received(&mut self, path: PathBuf, hints: Vec<InlayHint>) {
+        let active = self.active_tab.and_then(|idx| self.tabs.get(idx));
+        if !self.editor_preferences.inlay_hints || active.is_none_or(|tab| tab.path != path) {
+            return;
+        }
+        self.inlay_hints.lines.clear();
+        for hint in hints {
+            self.inlay_hints
+                .lines
+                .entry(hint.position.0)
+                .or_default()
+                .push(hint);
+        }
+        self.inlay_hints.path = Some(path);
+    }
+
+    pub(super) fn toggle_inlay_hints(&mut self) {
+        self.editor_preferences.inlay_hints = !self.editor_preferences.inlay_hints;
+        self.inlay_hints.clear();
+        self.refresh_inlay_hints();
+    }
+}
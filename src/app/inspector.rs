@@ -0,0 +1,107 @@
+//! The Language Servers panel: each server's status, and the JSON-RPC
+//! traffic it exchanged while the panel was open.
+
+use super::*;
+use crate::features::lsp::{LspServerState, ServerStatus, TrafficEntry};
+use std::sync::Arc;
+
+pub(super) struct LspInspector {
+    pub(super) open: bool,
+    /// Showing the traffic rather than the status.
+    pub(super) traffic: bool,
+    /// Index of the selected server in `servers`.
+    pub(super) server: usize,
+    pub(super) filter: String,
+    /// The message whose payload is shown, pretty-printed.
+    pub(super) selected: Option<(Arc<TrafficEntry>, String)>,
+    /// Snapshots taken on every LSP tick while open.
+    pub(super) servers: Vec<ServerStatus>,
+    pub(super) messages: Vec<Arc<TrafficEntry>>,
+    /// Message times are shown relative to this.
+    pub(super) opened_at: Instant,
+}
+
+impl Default for LspInspector {
+    fn default() -> Self {
+        Self {
+            open: false,
+            traffic: false,
+            server: 0,
+            filter: String::new(),
+            selected: None,
+            servers: Vec::new(),
+            messages: Vec::new(),
+            opened_at: Instant::now(),
+        }
+    }
+}
+
+impl App {
+    /// Opens or closes the panel. Traffic is only recorded while it is
+    /// open, and dropped when it closes.
+    pub(super) fn toggle_lsp_inspector(&mut self) {
+        let inspector = &mut self.lsp_inspector;
+        inspector.open = !inspector.open;
+        inspector.selected = None;
+        inspector.messages.clear();
+        inspector.opened_at = Instant::now();
+        self.lsp.set_recording(inspector.open);
+        self.refresh_lsp_inspector();
+    }
+
+    pub(super) fn refresh_lsp_inspector(&mut self) {
+        let inspector = &mut self.lsp_inspector;
+        if !inspector.open {
+            return;
+        }
+        inspector.servers = self.lsp.server_statuses();
+        if inspector.server >= inspector.servers.len() {
+            inspector.server = 0;
+        }
+        inspector.messages = self.lsp.traffic(inspector.server);
+    }
+
+    pub(super) fn select_lsp_server(&mut self, index: usize) {
+        self.lsp_inspector.server = index;
+        self.lsp_inspector.selected = None;
+        self.refresh_lsp_inspector();
+    }
+
+    pub(super) fn select_lsp_message(&mut self, index: usize) {
+        let inspector = &mut self.lsp_inspector;
+        let Some(entry) = inspector.messages.get(index) else {
+            return;
+        };
+        let payload = serde_json::to_string_pretty(&entry.message).unwrap_or_default();
+        inspector.selected = Some((entry.clone(), payload));
+    }
+
+    pub(super) fn clear_lsp_traffic(&mut self) {
+        self.lsp.clear_traffic(self.lsp_inspector.server);
+        self.lsp_inspector.selected = None;
+        self.refresh_lsp_inspector();
+    }
+
+    pub(super) fn restart_lsp_server(&mut self, index: usize) {
+        if let Err(err) = self.lsp.restart_server(index) {
+            self.dev_log(format!("LSP: Failed to restart: {err}"));
+            self.notification = Some(Notification {
+                message: err,
+                kind: NotificationKind::Error,
+                shown_at: Instant::now(),
+            });
+        }
+        self.refresh_lsp_inspector();
+    }
+
+    /// Stops a server and drops the diagnostics it published; it starts
+    /// again on restart or when a file it handles is opened.
+    pub(super) fn stop_lsp_server(&mut self, index: usize) {
+        if let Some(server_key) = self.lsp.stop_server(index) {
+            self.lsp_diagnostics
+                .retain(|path, _| self.lsp_server_keys.get(path) != Some(&server_key));
+            self.lsp_state_changed(&server_key, LspServerState::Stopped);
+        }
+        self.refresh_lsp_inspector();
+    }
+}
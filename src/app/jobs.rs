@@ -0,0 +1,82 @@
+use super::*;
+use crate::scripting::jobs::{JobEvent, JobSpec, RunningJob};
+
+impl App {
+    /// Starts tracking a job; its subscription spawns the process.
+    /// Jobs without a `cwd` run in the open folder.
+    pub(super) fn start_job(&mut self, mut spec: JobSpec) {
+        if spec.cwd.is_none() {
+            spec.cwd = self.file_tree.as_ref().map(|tree| tree.root.clone());
+        }
+        self.dev_log(format!("job {} started: {}", spec.id, spec.command_line()));
+        self.jobs.push(RunningJob {
+            spec,
+            started_at: Instant::now(),
+        });
+    }
+
+    /// Stops a job. Removing it drops its subscription, which kills the
+    /// process; `on_exit` is told it was cancelled.
+    pub(super) fn cancel_job(&mut self, id: u64) {
+        if let Some(job) = self.take_job(id) {
+            self.dev_log(format!("job {id} cancelled"));
+            self.finish_job(job.spec, None, Some("cancelled".to_string()));
+        }
+    }
+
+    pub(super) fn handle_job_event(&mut self, event: JobEvent) {
+        match event {
+            JobEvent::Stdout { id, line } => {
+                let callback = self.job(id).and_then(|job| job.spec.on_stdout);
+                if let Some(callback) = callback {
+                    self.run_lua_callback(callback, "job stdout", (line, id));
+                }
+            }
+            JobEvent::Stderr { id, line } => {
+                let callback = self.job(id).and_then(|job| job.spec.on_stderr);
+                if let Some(callback) = callback {
+                    self.run_lua_callback(callback, "job stderr", (line, id));
+                }
+            }
+            JobEvent::Exit { id, code, error } => {
+                if let Some(job) = self.take_job(id) {
+                    self.dev_log(format!("job {id} exited: {code:?}"));
+                    self.finish_job(job.spec, code, error);
+                }
+            }
+        }
+    }
+
+    /// Stops every job, e.g. before the runtime holding their callbacks is
+    /// replaced.
+    pub(super) fn drop_all_jobs(&mut self) {
+        self.jobs.clear();
+    }
+
+    fn job(&self, id: u64) -> Option<&RunningJob> {
+        self.jobs.iter().find(|job| job.spec.id == id)
+    }
+
+    fn take_job(&mut self, id: u64) -> Option<RunningJob> {
+        let index = self.jobs.iter().position(|job| job.spec.id == id)?;
+        Some(self.jobs.remove(index))
+    }
+
+    fn finish_job(&mut self, spec: JobSpec, code: Option<i32>, error: Option<String>) {
+        match spec.on_exit {
+            Some(callback) => self.run_lua_callback(callback, "job exit", (code, error, spec.id)),
+            None => {
+                if let Some(error) = error {
+                    self.lua_console
+                        .push(ConsoleLineKind::Error, format!("job {}: {error}", spec.id));
+                }
+            }
+        }
+
+        if let Some(runtime) = &self.script_runtime {
+            for callback in spec.callbacks() {
+                runtime.release_callback(callback);
+            }
+        }
+    }
+}
@@ -9,6 +9,66 @@ impl App {
         Self::new_with_path(None)
     }
 
+    /// Exits the editor. Tabs still waiting on autosave are written out
+    /// first; if any tab still has unsaved changes after that, the user is
+    /// asked before they are discarded.
+    pub(super) fn request_quit(&mut self) -> iced::Task<Message> {
+        let mut failures = Vec::new();
+        if self.editor_preferences.autosave_enabled {
+            for tab in &mut self.tabs {
+                if tab.autosave_requested_at.is_none()
+                    || tab.path == std::path::Path::new("untitled")
+                {
+                    continue;
+                }
+                let TabKind::Editor { code_editor, .. } = &mut tab.kind else {
+                    continue;
+                };
+                match std::fs::write(&tab.path, code_editor.content()) {
+                    Ok(()) => {
+                        code_editor.mark_saved();
+                        tab.autosave_requested_at = None;
+                    }
+                    Err(err) => failures.push(format!("{}: {err}", tab.path.display())),
+                }
+            }
+        }
+        for failure in failures {
+            self.dev_log(format!("Autosave on quit failed: {failure}"));
+        }
+
+        let unsaved: Vec<String> = self
+            .tabs
+            .iter()
+            .filter(|tab| {
+                matches!(&tab.kind, TabKind::Editor { code_editor, .. } if code_editor.is_modified())
+            })
+            .map(|tab| tab.name.clone())
+            .collect();
+        if unsaved.is_empty() {
+            return iced::exit();
+        }
+        self.pending_quit = Some(unsaved);
+        iced::Task::none()
+    }
+
+    /// Carries on with the `:wq` waiting for `path` to be saved. A failed
+    /// save cancels the quit.
+    pub(super) fn quit_after_save(
+        &mut self,
+        path: &std::path::Path,
+        saved: bool,
+    ) -> iced::Task<Message> {
+        if !matches!(&self.quit_after_save, Some((waiting, _)) if waiting == path) {
+            return iced::Task::none();
+        }
+        match self.quit_after_save.take() {
+            Some((_, true)) if saved => iced::exit(),
+            Some((_, false)) if saved => self.request_quit(),
+            _ => iced::Task::none(),
+        }
+    }
+
     pub fn new_with_path(startup_path: Option<std::path::PathBuf>) -> (Self, iced::Task<Message>) {
         let app = Self::default();
 
@@ -0,0 +1,541 @@
+//! Attaching editors to the shared language servers, reacting to their
+//! lifecycle, and the navigation, rename and code action commands.
+
+use super::*;
+use crate::features::editor_buffer::{EditorBuffer, TextEdit};
+use crate::features::lsp::{
+    CodeAction, LocationKind, LspLocation, LspServerState, WorkspaceChange,
+};
+use std::path::Path;
+
+/// Results of "Find All References" and of jumps with several targets, with
+/// the text of each line they point at.
+pub(super) struct LocationResults {
+    pub(super) title: String,
+    pub(super) files: Vec<(PathBuf, Vec<(LspLocation, String)>)>,
+}
+
+pub(super) struct RenameState {
+    server_key: String,
+    path: PathBuf,
+    /// Where the symbol starts, 0-based.
+    position: (usize, usize),
+    pub(super) old_name: String,
+    pub(super) input: String,
+    /// The server's edit once it answered.
+    pub(super) preview: Option<RenamePreview>,
+}
+
+pub(super) struct RenamePreview {
+    pub(super) change: WorkspaceChange,
+    /// Each file's changed lines (0-based) with their new text.
+    pub(super) files: Vec<(PathBuf, Vec<(usize, String)>)>,
+}
+
+#[derive(Default)]
+pub(super) struct CodeActions {
+    /// The file and cursor (0-based) the last request was for, and the line
+    /// its range started on.
+    requested: Option<(PathBuf, (usize, usize), usize)>,
+    answered: bool,
+    pub(super) available: Vec<CodeAction>,
+    /// Ctrl+. came before the answer; open the picker when it arrives.
+    open_on_answer: bool,
+    /// The highlighted entry while the picker is open.
+    pub(super) picker: Option<usize>,
+}
+
+impl CodeActions {
+    /// Asks again on the next tick, unless the picker is showing the
+    /// current answer.
+    pub(super) fn invalidate(&mut self) {
+        if self.picker.is_none() {
+            self.requested = None;
+        }
+    }
+
+    /// The 0-based line the lightbulb goes on, if `path` has actions there.
+    pub(super) fn lightbulb_line(&self, path: &Path) -> Option<usize> {
+        match &self.requested {
+            Some((requested, (line, _), _)) if requested == path && !self.available.is_empty() => {
+                Some(*line)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl App {
+    /// Opens the tab's document on the language server for its file type.
+    /// The server is shared with every other tab in the same workspace, so
+    /// this only costs a `didOpen` once it runs.
+    pub(super) fn attach_lsp(&mut self, idx: usize) {
+        let Some(path) = self.tabs.get(idx).map(|tab| tab.path.clone()) else {
+            return;
+        };
+        if !self.lsp_enabled || !path.is_absolute() {
+            self.dev_log(format!(
+                "LSP: Not attaching (lsp_enabled={}, is_absolute={})",
+                self.lsp_enabled,
+                path.is_absolute()
+            ));
+            return;
+        }
+        let Some(language) = self.lsp.language_for_path(&path).cloned() else {
+            self.dev_log(format!(
+                "LSP: No language server found for {}",
+                path.display()
+            ));
+            return;
+        };
+
+        match self.lsp.client(&language, &path) {
+            Ok(client) => {
+                let uri = format!("file://{}", path.display());
+                let document = iced_code_editor::LspDocument::new(uri, &language.language);
+                if let Some(TabKind::Editor { code_editor, .. }) =
+                    self.tabs.get_mut(idx).map(|tab| &mut tab.kind)
+                {
+                    code_editor.set_lsp_enabled(true);
+                    code_editor.attach_lsp(client, document);
+                }
+                self.dev_log(format!(
+                    "LSP: Opened {} on {}",
+                    path.display(),
+                    language.name
+                ));
+                self.lsp_server_keys.insert(path.clone(), language.name);
+            }
+            Err(e) => {
+                self.dev_log(format!("LSP: Failed to attach: {}", e));
+                eprintln!("LSP: {}", e);
+            }
+        }
+    }
+
+    /// Applies an edited `lsp.lua`. Servers are started afresh and every tab
+    /// reopened on the one its new entry names.
+    pub(super) fn reload_lsp_config(&mut self) -> Result<(), String> {
+        let registry = crate::config::lsp_config::load()?;
+        if &registry == self.lsp.registry() {
+            return Ok(());
+        }
+        self.lsp.set_registry(registry);
+        if self.lsp_enabled {
+            self.detach_all_lsp();
+            self.lsp.shutdown();
+            self.lsp_diagnostics.clear();
+            for idx in 0..self.tabs.len() {
+                self.attach_lsp(idx);
+            }
+        }
+        Ok(())
+    }
+
+    /// Closes every document on its server, leaving the servers running.
+    pub(super) fn detach_all_lsp(&mut self) {
+        for tab in &mut self.tabs {
+            if let TabKind::Editor {
+                ref mut code_editor,
+                ..
+            } = tab.kind
+            {
+                code_editor.detach_lsp();
+            }
+        }
+        self.lsp_server_keys.clear();
+    }
+
+    pub(super) fn lsp_state_changed(&mut self, server_key: &str, state: LspServerState) {
+        match state {
+            LspServerState::Starting => {}
+            LspServerState::Ready => {
+                self.code_actions.invalidate();
+                self.outline.invalidate();
+                self.inlay_hints.invalidate();
+                self.dev_log(format!("LSP: {server_key} is ready"));
+            }
+            LspServerState::Crashed(reason) => {
+                self.dev_log(format!("LSP: {server_key} crashed: {reason}"));
+                self.notification = Some(Notification {
+                    message: format!(
+                        "{server_key} stopped ({reason}). Run \"Restart Language Servers\" to start it again."
+                    ),
+                    kind: NotificationKind::Error,
+                    shown_at: Instant::now(),
+                });
+            }
+            LspServerState::Stopped => self.dev_log(format!("LSP: {server_key} stopped")),
+        }
+    }
+
+    /// Jumps to the next (or previous) problem after the cursor, moving on
+    /// to other files and wrapping around at the end.
+    pub(super) fn step_problem(&mut self, forward: bool) -> iced::Task<Message> {
+        let from = self
+            .active_tab
+            .and_then(|idx| self.tabs.get(idx))
+            .map(|tab| (tab.path.as_path(), self.cursor_line, self.cursor_col));
+        let Some((path, line, column)) = crate::features::problems::step(
+            &self.lsp_diagnostics,
+            &self.problems_filter,
+            from,
+            forward,
+        ) else {
+            return iced::Task::none();
+        };
+        self.update(Message::ProblemClicked(path, line, column))
+    }
+
+    /// The active document's server, path and 0-based cursor, with the
+    /// editor's pending changes sent so the server sees the current text.
+    fn lsp_target(&mut self) -> Result<(String, PathBuf, (usize, usize)), String> {
+        let tab = self
+            .active_tab
+            .and_then(|idx| self.tabs.get_mut(idx))
+            .ok_or("No file is open")?;
+        let server_key = self
+            .lsp_server_keys
+            .get(&tab.path)
+            .cloned()
+            .ok_or("No language server handles this file")?;
+        let TabKind::Editor { code_editor, .. } = &mut tab.kind else {
+            return Err("No language server handles this file".to_string());
+        };
+        code_editor.lsp_flush_pending_changes();
+        Ok((server_key, tab.path.clone(), code_editor.cursor_position()))
+    }
+
+    pub(super) fn show_lsp_error(&mut self, message: String) {
+        self.dev_log(format!("LSP: {message}"));
+        self.notification = Some(Notification {
+            message,
+            kind: NotificationKind::Error,
+            shown_at: Instant::now(),
+        });
+    }
+
+    /// The current text of `path`: the open tab's, or the file on disk.
+    fn file_text(&self, path: &Path) -> Option<String> {
+        let open = self.tabs.iter().find_map(|tab| match &tab.kind {
+            TabKind::Editor { code_editor, .. } if tab.path == path => Some(code_editor.content()),
+            _ => None,
+        });
+        open.or_else(|| std::fs::read_to_string(path).ok())
+    }
+
+    pub(super) fn request_locations(&mut self, kind: LocationKind) {
+        let result = self.lsp_target().and_then(|(server_key, path, cursor)| {
+            self.lsp.request_locations(&server_key, &path, kind, cursor)
+        });
+        if let Err(message) = result {
+            self.show_lsp_error(message);
+        }
+    }
+
+    /// Jumps straight to a single target and lists several in the results
+    /// panel. References are always listed.
+    pub(super) fn show_locations(
+        &mut self,
+        kind: LocationKind,
+        locations: Vec<LspLocation>,
+    ) -> iced::Task<Message> {
+        if locations.is_empty() {
+            self.show_lsp_error(format!("No {} found", kind.label().to_lowercase()));
+            return iced::Task::none();
+        }
+        if kind != LocationKind::References && locations.len() == 1 {
+            let location = &locations[0];
+            self.vim_push_jump();
+            return self.go_to_location(location.path.clone(), location.start);
+        }
+
+        let title = format!("{} ({})", kind.label(), locations.len());
+        let mut files: Vec<(PathBuf, Vec<(LspLocation, String)>)> = Vec::new();
+        for location in locations {
+            match files.iter_mut().find(|(path, _)| *path == location.path) {
+                Some((_, items)) => items.push((location, String::new())),
+                None => files.push((location.path.clone(), vec![(location, String::new())])),
+            }
+        }
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+        for (path, items) in &mut files {
+            items.sort_by_key(|(location, _)| location.start);
+            let text = self.file_text(path).unwrap_or_default();
+            let lines: Vec<&str> = text.lines().collect();
+            for (location, line) in items {
+                *line = lines
+                    .get(location.start.0)
+                    .map_or(String::new(), |line| line.trim().to_string());
+            }
+        }
+        self.location_results = Some(LocationResults { title, files });
+        iced::Task::none()
+    }
+
+    /// Opens the rename panel for the symbol under the cursor.
+    pub(super) fn open_rename(&mut self) -> iced::Task<Message> {
+        let (server_key, path, (line, col)) = match self.lsp_target() {
+            Ok(target) => target,
+            Err(message) => {
+                self.show_lsp_error(message);
+                return iced::Task::none();
+            }
+        };
+        let text = self.file_text(&path).unwrap_or_default();
+        let Some((start, word)) = text
+            .lines()
+            .nth(line)
+            .and_then(|text| super::vim::word_at(text, col))
+        else {
+            self.show_lsp_error("No symbol under the cursor".to_string());
+            return iced::Task::none();
+        };
+        self.location_results = None;
+        self.rename = Some(RenameState {
+            server_key,
+            path,
+            position: (line, start),
+            old_name: word.clone(),
+            input: word,
+            preview: None,
+        });
+        iced::widget::operation::focus(self.rename_input_id.clone())
+    }
+
+    pub(super) fn submit_rename(&mut self) {
+        let Some(rename) = &self.rename else {
+            return;
+        };
+        let new_name = rename.input.trim();
+        if new_name.is_empty() || new_name == rename.old_name {
+            return;
+        }
+        if let Err(message) =
+            self.lsp
+                .rename(&rename.server_key, &rename.path, rename.position, new_name)
+        {
+            self.show_lsp_error(message);
+        }
+    }
+
+    /// Shows what the server's rename would change before applying it.
+    pub(super) fn show_rename_preview(&mut self, change: WorkspaceChange) {
+        if self.rename.is_none() {
+            return;
+        }
+        let files = change
+            .files
+            .iter()
+            .map(|file| {
+                let mut buffer =
+                    EditorBuffer::from_text(&self.file_text(&file.path).unwrap_or_default());
+                let lines = file
+                    .apply(&mut buffer)
+                    .into_iter()
+                    .map(|line| (line, buffer.line(line).trim().to_string()))
+                    .collect();
+                (file.path.clone(), lines)
+            })
+            .collect();
+        if let Some(rename) = &mut self.rename {
+            rename.preview = Some(RenamePreview { change, files });
+        }
+    }
+
+    pub(super) fn apply_rename(&mut self) {
+        let Some(preview) = self.rename.take().and_then(|rename| rename.preview) else {
+            return;
+        };
+        self.apply_workspace_change(&preview.change, "Renamed");
+    }
+
+    /// Applies a server's edit: open files through their editors, so it can
+    /// be undone, and the rest on disk.
+    pub(super) fn apply_workspace_change(&mut self, change: &WorkspaceChange, verb: &str) {
+        let mut errors = Vec::new();
+        for file in &change.files {
+            if let Some(idx) = self
+                .tabs
+                .iter()
+                .position(|tab| tab.path == file.path && matches!(tab.kind, TabKind::Editor { .. }))
+            {
+                self.apply_tab_edits(idx, &file.edits);
+                continue;
+            }
+            let result = std::fs::read_to_string(&file.path).and_then(|text| {
+                let mut buffer = EditorBuffer::from_text(&text);
+                file.apply(&mut buffer);
+                std::fs::write(&file.path, buffer.text())
+            });
+            if let Err(err) = result {
+                errors.push(format!("{}: {err}", file.path.display()));
+            }
+        }
+        if change.skipped_operations > 0 {
+            errors.push(format!(
+                "{} file create/rename/delete operations were skipped",
+                change.skipped_operations
+            ));
+        }
+
+        if !errors.is_empty() {
+            self.show_lsp_error(errors.join("\n"));
+        } else if change.files.len() > 1 {
+            self.notification = Some(Notification {
+                message: format!("{verb} in {} files", change.files.len()),
+                kind: NotificationKind::Success,
+                shown_at: Instant::now(),
+            });
+        }
+    }
+
+    /// Applies edits that all refer to the tab's current text, keeping its
+    /// cursor on the same code.
+    pub(super) fn apply_tab_edits(&mut self, idx: usize, edits: &[TextEdit]) {
+        let mut edits: Vec<&TextEdit> = edits.iter().collect();
+        edits.sort_by_key(|edit| std::cmp::Reverse(edit.start));
+        let active = self.active_tab.replace(idx);
+        let cursor = match &self.tabs[idx].kind {
+            TabKind::Editor { code_editor, .. } => code_editor.cursor_position(),
+            _ => (0, 0),
+        };
+        let cursor = edits.iter().fold(cursor, |cursor, edit| {
+            self.apply_text_edit(edit);
+            edit.map_position(cursor)
+        });
+        self.set_active_cursor(cursor);
+        self.active_tab = active;
+        self.sync_cursor_from_active_editor();
+    }
+
+    /// Keeps the lightbulb current: asks for the actions at the cursor
+    /// whenever it moves. The range is the diagnostic under the cursor, or
+    /// else the first one on its line, so quick fixes show from anywhere on
+    /// the line.
+    pub(super) fn refresh_code_actions(&mut self) {
+        if self.code_actions.picker.is_some() {
+            return;
+        }
+        let Some(tab) = self.active_tab.and_then(|idx| self.tabs.get(idx)) else {
+            return;
+        };
+        let Some(server_key) = self.lsp_server_keys.get(&tab.path).cloned() else {
+            return;
+        };
+        let cursor = (
+            self.cursor_line.saturating_sub(1),
+            self.cursor_col.saturating_sub(1),
+        );
+        if matches!(&self.code_actions.requested, Some((path, at, _)) if *path == tab.path && *at == cursor)
+        {
+            return;
+        }
+        let path = tab.path.clone();
+
+        let on_line: Vec<((usize, usize), (usize, usize))> = self
+            .lsp_diagnostics
+            .get(&path)
+            .into_iter()
+            .flatten()
+            .filter(|d| d.line - 1 <= cursor.0 && cursor.0 < d.end_line)
+            .map(|d| {
+                (
+                    (d.line - 1, d.column - 1),
+                    (d.end_line - 1, d.end_column - 1),
+                )
+            })
+            .collect();
+        let (start, end) = on_line
+            .iter()
+            .find(|(start, end)| *start <= cursor && cursor <= *end)
+            .or(on_line.first())
+            .copied()
+            .unwrap_or((cursor, cursor));
+
+        self.code_actions.requested = Some((path.clone(), cursor, start.0));
+        self.code_actions.answered = false;
+        self.code_actions.available.clear();
+        if let Err(message) = self.lsp.code_actions(&server_key, &path, start, end) {
+            self.dev_log(format!("LSP: {message}"));
+        }
+    }
+
+    pub(super) fn code_actions_received(
+        &mut self,
+        path: PathBuf,
+        line: usize,
+        actions: Vec<CodeAction>,
+    ) {
+        match &self.code_actions.requested {
+            Some((requested, _, requested_line))
+                if *requested == path && *requested_line == line => {}
+            _ => return,
+        }
+        self.code_actions.answered = true;
+        self.code_actions.available = actions;
+        if std::mem::take(&mut self.code_actions.open_on_answer) {
+            self.open_code_action_picker();
+        }
+    }
+
+    /// Ctrl+. and the lightbulb: picks from the actions at the cursor,
+    /// asking for them first if the last answer is for somewhere else.
+    pub(super) fn show_code_actions(&mut self) {
+        if let Err(message) = self.lsp_target() {
+            self.show_lsp_error(message);
+            return;
+        }
+        self.refresh_code_actions();
+        if self.code_actions.answered {
+            self.open_code_action_picker();
+        } else {
+            self.code_actions.open_on_answer = true;
+        }
+    }
+
+    fn open_code_action_picker(&mut self) {
+        if self.code_actions.available.is_empty() {
+            self.show_lsp_error("No code actions available here".to_string());
+            return;
+        }
+        let preferred = self
+            .code_actions
+            .available
+            .iter()
+            .position(|action| action.preferred);
+        self.code_actions.picker = Some(preferred.unwrap_or(0));
+    }
+
+    pub(super) fn move_code_action_selection(&mut self, delta: i32) {
+        let count = self.code_actions.available.len() as i32;
+        if let Some(selected) = self.code_actions.picker.as_mut().filter(|_| count > 0) {
+            *selected = (*selected as i32 + delta).rem_euclid(count) as usize;
+        }
+    }
+
+    /// Applies the action's edit, then runs its command on the server.
+    pub(super) fn run_code_action(&mut self, idx: usize) {
+        self.code_actions.picker = None;
+        let Some(action) = self.code_actions.available.get(idx).cloned() else {
+            return;
+        };
+        self.code_actions.requested = None;
+        if let Some(edit) = &action.edit {
+            self.apply_workspace_change(edit, "Changed");
+        }
+        let Some(command) = &action.command else {
+            return;
+        };
+        let result = self.lsp_target().and_then(|(server_key, path, _)| {
+            self.lsp.execute_command(&server_key, &path, command)
+        });
+        match result {
+            // Commands meant for the client, like rust-analyzer's
+            // parameter hints, don't matter once the edit went in.
+            Err(message) if action.edit.is_some() => self.dev_log(format!("LSP: {message}")),
+            Err(message) => self.show_lsp_error(message),
+            Ok(()) => {}
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use super::*;
+use crate::features::statusline::{self, ScriptedSegment, SegmentAlign, Slot, StatusContext};
+
+impl App {
+    /// Renders the status bar segments again if the editor state they show
+    /// changed. Runs after every update, so the view only reads cached text.
+    pub(super) fn refresh_statusline(&mut self) {
+        let context = self.status_context();
+        if !self.statusline.needs_render(&context) {
+            return;
+        }
+
+        let (left, right) = self.statusline.layout(
+            &self.editor_preferences.statusline_left,
+            &self.editor_preferences.statusline_right,
+        );
+        let left = self.render_status_slots(&left, &context);
+        let right = self.render_status_slots(&right, &context);
+        self.statusline.set_rendered(context, left, right);
+    }
+
+    pub(super) fn add_status_segment(&mut self, id: String, align: SegmentAlign, render: usize) {
+        self.statusline.add(ScriptedSegment { id, align, render });
+    }
+
+    /// Re-reads the branch of the repository the workspace, or else the
+    /// active file, is in.
+    pub(super) fn refresh_git_branch(&mut self) {
+        let dir = match &self.file_tree {
+            Some(tree) => Some(tree.root.clone()),
+            None => self
+                .active_tab
+                .and_then(|idx| self.tabs.get(idx))
+                .and_then(|tab| tab.path.parent().map(PathBuf::from)),
+        };
+        self.git_branch = dir.and_then(|dir| statusline::git_branch(&dir));
+    }
+
+    fn status_context(&self) -> StatusContext {
+        let mut context = StatusContext {
+            line: self.cursor_line,
+            col: self.cursor_col,
+            git_branch: self.git_branch.clone(),
+            problems: crate::features::problems::count(&self.lsp_diagnostics).summary(),
+            ..Default::default()
+        };
+
+        let Some(tab) = self.active_tab.and_then(|idx| self.tabs.get(idx)) else {
+            return context;
+        };
+        context.file = tab.name.clone();
+        context.language = statusline::language_name(&tab.path);
+        context.diagnostic = self
+            .lsp_diagnostics
+            .get(&tab.path)
+            .and_then(|items| items.iter().find(|d| d.line == self.cursor_line))
+            .map(|d| d.message.clone());
+        context.lsp = self
+            .lsp_server_keys
+            .get(&tab.path)
+            .filter(|_| self.lsp_enabled)
+            .map(|server| match self.lsp.status(server, &tab.path) {
+                Some(status) => format!("{server}: {status}"),
+                None => server.to_string(),
+            });
+
+        if let TabKind::Editor { buffer, .. } = &tab.kind {
+            // Files are read as UTF-8; the first line decides the line endings.
+            context.encoding = Some("UTF-8");
+            context.line_ending = Some(if buffer.line(0).ends_with("\r\n") {
+                "CRLF"
+            } else {
+                "LF"
+            });
+        }
+        context.path = Some(tab.path.clone());
+        context
+    }
+
+    fn render_status_slots(&mut self, slots: &[Slot], context: &StatusContext) -> Vec<String> {
+        let mut texts = Vec::new();
+        for slot in slots {
+            let text = match slot {
+                Slot::Builtin(id) => context.builtin(id),
+                Slot::Scripted { id, render } => self.render_scripted_segment(id, *render, context),
+            };
+            texts.extend(text);
+        }
+        texts
+    }
+
+    fn render_scripted_segment(
+        &mut self,
+        id: &str,
+        render: usize,
+        context: &StatusContext,
+    ) -> Option<String> {
+        let snapshot = self.active_buffer_snapshot();
+        let runtime = self.script_runtime.as_ref()?;
+        runtime.set_buffer(snapshot);
+        let result = runtime.render_status_segment(render, context);
+        let commands = runtime.take_commands();
+        self.collect_script_output();
+        for command in commands {
+            self.apply_editor_command(command);
+        }
+
+        match result {
+            Ok(text) => text,
+            Err(err) => {
+                if !self.statusline.note_error(id, &err) {
+                    return Some(format!("{id}: error"));
+                }
+                eprintln!("Lua statusline error: {err}");
+                self.push_developer_log(format!("lua statusline '{id}' error: {err}"));
+                self.lua_console
+                    .push(ConsoleLineKind::Error, format!("statusline '{id}': {err}"));
+                Some(format!("{id}: error"))
+            }
+        }
+    }
+}
@@ -12,6 +12,7 @@ impl App {
             crate::subscriptions::window::resizes(),
             crate::subscriptions::window::focus_refresh(),
             iced::time::every(Duration::from_millis(150)).map(|_| Message::LspTick),
+            iced::time::every(Duration::from_secs(1)).map(|_| Message::ConfigWatchTick),
         ];
 
         if self.editor_preferences.autosave_enabled {
@@ -23,6 +24,13 @@ impl App {
             );
         }
 
+        for job in &self.jobs {
+            subs.push(
+                Subscription::run_with(job.spec.clone(), crate::scripting::jobs::run)
+                    .map(Message::JobEvent),
+            );
+        }
+
         if let Some(term) = &self.terminal_pane {
             subs.push(term.subscription().map(Message::TerminalEvent));
         }
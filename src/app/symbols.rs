@@ -0,0 +1,188 @@
+//! The Outline sidebar section and the fuzzy finder's `@` mode. Symbols
+//! come from language servers when they can answer, and from tree-sitter
+//! otherwise.
+
+use super::*;
+use crate::features::symbols::{Symbol, SyntaxSymbols};
+use std::hash::{Hash, Hasher};
+
+/// How often the outline checks the active file for changes.
+const OUTLINE_REFRESH: Duration = Duration::from_millis(400);
+
+pub(super) struct Outline {
+    pub(super) expanded: bool,
+    /// The file the symbols belong to.
+    pub(super) path: Option<PathBuf>,
+    pub(super) symbols: Vec<Symbol>,
+    /// File and text hash the symbols were last asked for.
+    source: Option<(PathBuf, u64)>,
+    checked_at: Option<Instant>,
+    syntax: SyntaxSymbols,
+}
+
+impl Default for Outline {
+    fn default() -> Self {
+        Self {
+            expanded: true,
+            path: None,
+            symbols: Vec::new(),
+            source: None,
+            checked_at: None,
+            syntax: SyntaxSymbols::default(),
+        }
+    }
+}
+
+impl Outline {
+    /// Asks again on the next check, e.g. once a language server is ready.
+    pub(super) fn invalidate(&mut self) {
+        self.source = None;
+        self.checked_at = None;
+    }
+}
+
+/// Tree-sitter symbols for the whole workspace, used by `@` when no
+/// language server can search it. Built on first use and dropped whenever
+/// files are saved or the folder changes.
+#[derive(Default)]
+pub(super) struct SymbolIndex {
+    symbols: Option<Vec<Symbol>>,
+    pub(super) building: bool,
+    /// Bumped on every invalidation so a build for older files is dropped.
+    generation: u64,
+}
+
+impl SymbolIndex {
+    pub(super) fn invalidate(&mut self) {
+        self.symbols = None;
+        self.building = false;
+        self.generation += 1;
+    }
+}
+
+impl App {
+    /// Keeps the outline in step with the active file, asking its language
+    /// server when there is one and reading it with tree-sitter otherwise.
+    pub(super) fn refresh_outline(&mut self) {
+        if !self.sidebar_visible || !self.outline.expanded {
+            return;
+        }
+        if self
+            .outline
+            .checked_at
+            .is_some_and(|at| at.elapsed() < OUTLINE_REFRESH)
+        {
+            return;
+        }
+        self.outline.checked_at = Some(Instant::now());
+
+        let Some(tab) = self.active_tab.and_then(|idx| self.tabs.get_mut(idx)) else {
+            self.outline.path = None;
+            self.outline.symbols.clear();
+            return;
+        };
+        let TabKind::Editor { code_editor, .. } = &mut tab.kind else {
+            self.outline.path = None;
+            self.outline.symbols.clear();
+            return;
+        };
+        let path = tab.path.clone();
+        let text = code_editor.content();
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        text.hash(&mut hasher);
+        let source = (path.clone(), hasher.finish());
+        if self.outline.source.as_ref() == Some(&source) {
+            return;
+        }
+        self.outline.source = Some(source);
+        if self.outline.path.as_ref() != Some(&path) {
+            self.outline.path = Some(path.clone());
+            self.outline.symbols.clear();
+        }
+
+        if self.lsp_enabled {
+            if let Some(server_key) = self.lsp_server_keys.get(&path) {
+                code_editor.lsp_flush_pending_changes();
+                if self.lsp.document_symbols(server_key, &path).is_ok() {
+                    return;
+                }
+            }
+        }
+        self.outline.symbols = self
+            .outline
+            .syntax
+            .outline(&path, &text)
+            .unwrap_or_default();
+    }
+
+    pub(super) fn document_symbols_received(&mut self, path: PathBuf, symbols: Vec<Symbol>) {
+        if self.outline.path.as_ref() == Some(&path) {
+            self.outline.symbols = symbols;
+        }
+    }
+
+    /// The deepest outline symbol the cursor is inside.
+    pub(super) fn outline_current(&self) -> Option<usize> {
+        let active = self.active_tab.and_then(|idx| self.tabs.get(idx))?;
+        if self.outline.path.as_ref() != Some(&active.path) {
+            return None;
+        }
+        let cursor = (
+            self.cursor_line.saturating_sub(1),
+            self.cursor_col.saturating_sub(1),
+        );
+        self.outline
+            .symbols
+            .iter()
+            .enumerate()
+            .filter(|(_, symbol)| symbol.start.0 <= cursor.0 && cursor <= symbol.end)
+            .max_by_key(|(idx, symbol)| (symbol.depth, *idx))
+            .map(|(idx, _)| idx)
+    }
+
+    /// Looks up the finder's `@` query: with the language servers that can
+    /// search the workspace, or in the tree-sitter index otherwise.
+    pub(super) fn search_workspace_symbols(&mut self) -> iced::Task<Message> {
+        let Some(query) = self.fuzzy_finder.symbol_query().map(str::to_string) else {
+            return iced::Task::none();
+        };
+        if self.lsp_enabled && self.lsp.workspace_symbols(&query) > 0 {
+            return iced::Task::none();
+        }
+        if let Some(symbols) = &self.symbol_index.symbols {
+            self.fuzzy_finder.show_symbols(symbols, false);
+            return iced::Task::none();
+        }
+        if self.symbol_index.building {
+            return iced::Task::none();
+        }
+        self.symbol_index.building = true;
+        let generation = self.symbol_index.generation;
+        let files = self.fuzzy_finder.files();
+        iced::Task::perform(
+            async move { SyntaxSymbols::default().index(&files) },
+            move |symbols| Message::SymbolIndexBuilt(generation, symbols),
+        )
+    }
+
+    pub(super) fn workspace_symbols_received(&mut self, query: &str, symbols: Vec<Symbol>) {
+        if self.fuzzy_finder.open && self.fuzzy_finder.symbol_query() == Some(query) {
+            self.fuzzy_finder.show_symbols(&symbols, true);
+        }
+    }
+
+    pub(super) fn symbol_index_built(&mut self, generation: u64, symbols: Vec<Symbol>) {
+        if generation != self.symbol_index.generation {
+            return;
+        }
+        self.dev_log(format!(
+            "Symbols: indexed {} with tree-sitter",
+            symbols.len()
+        ));
+        self.symbol_index.building = false;
+        if self.fuzzy_finder.open && self.fuzzy_finder.symbol_query().is_some() {
+            self.fuzzy_finder.show_symbols(&symbols, false);
+        }
+        self.symbol_index.symbols = Some(symbols);
+    }
+}
@@ -1,6 +1,8 @@
 use super::*;
 use crate::autocomplete::engine::Autocomplete;
+use crate::message::VimKey;
 use iced_code_editor::Message as EditorMessage;
+use std::path::Path;
 
 impl App {
     fn should_confirm_sensitive_open(path: &std::path::Path) -> bool {
@@ -74,7 +76,7 @@ impl App {
         }
     }
 
-    fn autosave_task_for_tab(&mut self, idx: usize) -> iced::Task<Message> {
+    pub(super) fn autosave_task_for_tab(&mut self, idx: usize) -> iced::Task<Message> {
         let Some(tab) = self.tabs.get_mut(idx) else {
             return iced::Task::none();
         };
@@ -106,32 +108,33 @@ impl App {
         )
     }
 
-    pub(super) fn vim_refresh_cursor_style(&mut self) {
-        if self.terminal_open && self.focused_pane == FocusPane::Terminal {
-            if let Some(idx) = self.active_tab {
-                if let Some(tab) = self.tabs.get_mut(idx) {
-                    if let TabKind::Editor {
-                        ref mut code_editor,
-                        ..
-                    } = tab.kind
-                    {
-                        code_editor.lose_focus();
-                    }
-                }
-            }
-            return;
-        }
-
+    /// The second half of `SaveFile`, once any formatting is done.
+    pub(super) fn write_active_file(&mut self) -> iced::Task<Message> {
         if let Some(idx) = self.active_tab {
-            if let Some(tab) = self.tabs.get_mut(idx) {
+            if let Some(tab) = self.tabs.get(idx) {
                 if let TabKind::Editor {
                     ref code_editor, ..
                 } = tab.kind
                 {
-                    code_editor.request_focus();
+                    let entity = tab.path.to_string_lossy().to_string();
+                    let _ = wakatime::client::send_heartbeat(&entity, true, &self.wakatime);
+                    self.last_wakatime_entity = Some(entity);
+                    self.last_wakatime_sent_at = Some(Instant::now());
+
+                    let path = tab.path.clone();
+                    let content = code_editor.content();
+                    if path == Path::new("untitled") {
+                        return iced::Task::perform(async {}, |_| Message::SaveAs);
+                    }
+                    return iced::Task::perform(
+                        async move { std::fs::write(&path, content).map_err(|e| e.to_string()) },
+                        Message::FileSaved,
+                    );
                 }
             }
         }
+        self.lsp_overlay = iced_code_editor::LspOverlayState::new();
+        iced::Task::none()
     }
 
     pub(super) fn toggle_terminal_panel(&mut self) -> iced::Task<Message> {
@@ -166,6 +169,13 @@ impl App {
     ///
     /// * `message` - The event to process.
     pub fn update(&mut self, message: Message) -> iced::Task<Message> {
+        let task = self.handle_message(message);
+        self.vim_sync_after_update();
+        self.refresh_statusline();
+        task
+    }
+
+    fn handle_message(&mut self, message: Message) -> iced::Task<Message> {
         match message {
             Message::ModifierStateChanged(modifiers) => {
                 self.modifier_state = modifiers;
@@ -173,16 +183,7 @@ impl App {
             }
             Message::FocusEditor => {
                 self.focused_pane = FocusPane::Editor;
-                if let Some(idx) = self.active_tab {
-                    if let Some(tab) = self.tabs.get_mut(idx) {
-                        if let TabKind::Editor {
-                            ref code_editor, ..
-                        } = tab.kind
-                        {
-                            code_editor.request_focus();
-                        }
-                    }
-                }
+                self.vim_refresh_cursor_style();
                 iced::Task::none()
             }
             Message::FocusTerminal => {
@@ -196,12 +197,36 @@ impl App {
                 iced::Task::none()
             }
             Message::CodeEditorEvent(event) => {
+                if let Some(task) = self.vim_escape_sequence(&event) {
+                    return task;
+                }
+                self.vim_editor_event(&event);
                 if matches!(event, EditorMessage::CharacterInput(_))
                     && (self.modifier_state.command() || self.modifier_state.control())
                 {
                     return iced::Task::none();
                 }
 
+                // The code action picker takes the arrows and Enter while open;
+                // anything else closes it and reaches the editor.
+                if self.code_actions.picker.is_some() {
+                    match &event {
+                        EditorMessage::ArrowKey(iced_code_editor::ArrowDirection::Up, _) => {
+                            self.move_code_action_selection(-1);
+                            return iced::Task::none();
+                        }
+                        EditorMessage::ArrowKey(iced_code_editor::ArrowDirection::Down, _) => {
+                            self.move_code_action_selection(1);
+                            return iced::Task::none();
+                        }
+                        EditorMessage::Enter => {
+                            let selected = self.code_actions.picker.unwrap_or_default();
+                            return self.update(Message::CodeActionSelected(selected));
+                        }
+                        _ => self.code_actions.picker = None,
+                    }
+                }
+
                 // Autocomplete keyboard navigation — intercept before editor processing
                 if self.autocomplete.active && !self.lsp_enabled {
                     if let EditorMessage::ArrowKey(dir, false) = &event {
@@ -518,6 +543,9 @@ impl App {
                                     }
                                 }
                             }
+                            if let Some((event, _, _)) = &cursor_sync {
+                                self.update_signature_help(event, &path);
+                            }
                         }
                     }
 
@@ -664,8 +692,8 @@ impl App {
                     {
                         self.markdown_preview = None;
                     }
-                    self.lsp_diagnostics.remove(&path);
                     self.lsp_server_keys.remove(&path);
+                    self.vim_jumps.forget(&path);
                     self.tabs.remove(idx);
                     if self.tabs.is_empty() {
                         self.active_tab = None;
@@ -700,8 +728,8 @@ impl App {
                         self.markdown_preview = None;
                     }
 
-                    self.lsp_diagnostics.remove(&path);
                     self.lsp_server_keys.remove(&path);
+                    self.vim_jumps.forget(&path);
                     self.tabs.remove(idx);
                     if self.tabs.is_empty() {
                         self.active_tab = None;
@@ -716,8 +744,12 @@ impl App {
             }
             Message::FileOpened(path, content) => {
                 if let Some(idx) = self.tabs.iter().position(|t| t.path == path) {
+                    let changed = self.active_tab != Some(idx);
                     self.active_tab = Some(idx);
                     self.vim_refresh_cursor_style();
+                    if changed {
+                        self.run_script_hook(scripting::HookEvent::TabChanged, Some(path));
+                    }
                     return iced::Task::none();
                 }
 
@@ -762,17 +794,6 @@ impl App {
                     autosave_in_flight: false,
                 });
 
-                // Detach LSP from all existing tabs before switching to the new one
-                for tab in &mut self.tabs {
-                    if let TabKind::Editor {
-                        ref mut code_editor,
-                        ..
-                    } = tab.kind
-                    {
-                        code_editor.detach_lsp();
-                    }
-                }
-
                 self.active_tab = Some(self.tabs.len() - 1);
                 self.cursor_line = 1;
                 self.cursor_col = 1;
@@ -780,139 +801,32 @@ impl App {
                 self.pending_hover_request = None;
                 self.vim_refresh_cursor_style();
 
-                // Attach LSP client to the editor
-                if self.lsp_enabled && opened_path.is_absolute() {
-                    if let Some(language) = iced_code_editor::lsp_language_for_path(&opened_path) {
-                        self.dev_log(format!(
-                            "LSP: Attempting to attach {} server for {}",
-                            language.server_key,
-                            opened_path.display()
-                        ));
-                        let root_hint = opened_path.parent();
-                        match self.lsp.create_client(language.server_key, root_hint) {
-                            Ok(client) => {
-                                let uri = format!("file://{}", opened_path.display());
-                                let document =
-                                    iced_code_editor::LspDocument::new(uri, language.language_id);
-                                if let Some(tab) = self.tabs.last_mut() {
-                                    if let TabKind::Editor {
-                                        ref mut code_editor,
-                                        ..
-                                    } = tab.kind
-                                    {
-                                        code_editor.set_lsp_enabled(true);
-                                        code_editor.attach_lsp(client, document);
-                                    }
-                                }
-                                self.lsp_server_keys
-                                    .insert(opened_path, language.server_key);
-                                self.dev_log(format!(
-                                    "LSP: Successfully attached {} server",
-                                    language.server_key
-                                ));
-                            }
-                            Err(e) => {
-                                self.dev_log(format!("LSP: Failed to attach: {}", e));
-                                eprintln!("LSP: {}", e);
-                            }
-                        }
-                    } else {
-                        self.dev_log(format!(
-                            "LSP: No language server found for {}",
-                            opened_path.display()
-                        ));
-                    }
-                } else {
-                    self.dev_log(format!(
-                        "LSP: Not attaching (lsp_enabled={}, is_absolute={})",
-                        self.lsp_enabled,
-                        opened_path.is_absolute()
-                    ));
+                self.attach_lsp(self.tabs.len() - 1);
+                if self.file_tree.is_none() {
+                    self.refresh_git_branch();
                 }
-                iced::Task::none()
+                self.run_script_hook(scripting::HookEvent::BufOpen, Some(opened_path.clone()));
+                self.go_to_pending_location(&opened_path)
             }
             Message::TabSelected(idx) => {
                 if idx < self.tabs.len() {
-                    // Detach LSP from all tabs first
-                    for tab in &mut self.tabs {
-                        if let TabKind::Editor {
-                            ref mut code_editor,
-                            ..
-                        } = tab.kind
-                        {
-                            code_editor.detach_lsp();
-                        }
-                    }
-
+                    let changed = self.active_tab != Some(idx);
                     self.active_tab = Some(idx);
 
-                    // Get path and server_key before mutable borrow
-                    let (tab_path, has_lsp) = if let Some(tab) = self.tabs.get(idx) {
-                        (
-                            tab.path.clone(),
-                            self.lsp_server_keys.get(&tab.path).copied(),
-                        )
-                    } else {
-                        return iced::Task::none();
-                    };
-
-                    // Create LSP client if needed (before mutable borrow)
-                    let lsp_client_data = if self.lsp_enabled && tab_path.is_absolute() {
-                        if let Some(server_key) = has_lsp {
-                            self.dev_log(format!(
-                                "LSP: Reattaching {} server for {}",
-                                server_key,
-                                tab_path.display()
-                            ));
-                            let root_hint = tab_path.parent();
-                            match self.lsp.create_client(server_key, root_hint) {
-                                Ok(client) => {
-                                    let uri = format!("file://{}", tab_path.display());
-                                    if let Some(language) =
-                                        iced_code_editor::lsp_language_for_path(&tab_path)
-                                    {
-                                        let document = iced_code_editor::LspDocument::new(
-                                            uri,
-                                            language.language_id,
-                                        );
-                                        Some((client, document, server_key))
-                                    } else {
-                                        None
-                                    }
-                                }
-                                Err(e) => {
-                                    self.dev_log(format!("LSP: Failed to reattach: {}", e));
-                                    None
-                                }
-                            }
-                        } else {
-                            None
-                        }
-                    } else {
-                        None
-                    };
-
-                    // Now attach to the tab
-                    if let Some(tab) = self.tabs.get_mut(idx) {
-                        if let TabKind::Editor {
-                            ref mut code_editor,
-                            ..
-                        } = tab.kind
-                        {
-                            code_editor.request_focus();
-
-                            if let Some((client, document, server_key)) = lsp_client_data {
-                                code_editor.set_lsp_enabled(true);
-                                code_editor.attach_lsp(client, document);
-                                self.dev_log(format!(
-                                    "LSP: Successfully reattached {} server",
-                                    server_key
-                                ));
-                            }
-                        }
+                    let tab_path = self.tabs[idx].path.clone();
+                    if let TabKind::Editor {
+                        ref mut code_editor,
+                        ..
+                    } = self.tabs[idx].kind
+                    {
+                        code_editor.request_focus();
                     }
                     self.vim_refresh_cursor_style();
                     self.pending_hover_request = None;
+
+                    if changed {
+                        self.run_script_hook(scripting::HookEvent::TabChanged, Some(tab_path));
+                    }
                 }
                 iced::Task::none()
             }
@@ -923,6 +837,7 @@ impl App {
                     self.all_workspace_files =
                         crate::features::search::collect_all_files(&tree.root);
                     self.fuzzy_finder.set_folder(tree.root.clone());
+                    self.symbol_index.invalidate();
 
                     if let Some(selected) = tree.selected.clone() {
                         if !selected.exists() {
@@ -930,6 +845,7 @@ impl App {
                         }
                     }
                 }
+                self.refresh_git_branch();
 
                 iced::Task::none()
             }
@@ -950,38 +866,27 @@ impl App {
                 self.file_tree = Some(FileTree::new(path.clone()));
                 self.all_workspace_files = crate::features::search::collect_all_files(&path);
                 self.fuzzy_finder.set_folder(path.clone());
+                self.symbol_index.invalidate();
                 self.lsp.set_workspace_root(path.clone());
                 self.lsp_enabled = true;
+                self.refresh_git_branch();
                 iced::Task::none()
             }
             Message::SaveFile => {
-                if let Some(idx) = self.active_tab {
-                    if let Some(tab) = self.tabs.get(idx) {
-                        if let TabKind::Editor {
-                            ref code_editor, ..
-                        } = tab.kind
-                        {
-                            let entity = tab.path.to_string_lossy().to_string();
-                            let _ = wakatime::client::send_heartbeat(&entity, true, &self.wakatime);
-                            self.last_wakatime_entity = Some(entity);
-                            self.last_wakatime_sent_at = Some(Instant::now());
-
-                            let path = tab.path.clone();
-                            let content = code_editor.content();
-                            if path == PathBuf::from("untitled") {
-                                return iced::Task::perform(async {}, |_| Message::SaveAs);
-                            }
-                            return iced::Task::perform(
-                                async move { std::fs::write(&path, content).map_err(|e| e.to_string()) },
-                                Message::FileSaved,
-                            );
-                        }
-                    }
+                let path = self
+                    .active_tab
+                    .and_then(|idx| self.tabs.get(idx))
+                    .map(|tab| tab.path.clone());
+                if path.is_some() {
+                    self.run_script_hook(scripting::HookEvent::BufWritePre, path);
                 }
-                self.lsp_overlay = iced_code_editor::LspOverlayState::new();
-                iced::Task::none()
+                if let Some(task) = self.format_before_save() {
+                    return task;
+                }
+                self.write_active_file()
             }
             Message::SaveCurrentFileAs(path) => {
+                self.run_script_hook(scripting::HookEvent::BufWritePre, Some(path.clone()));
                 if let Some(idx) = self.active_tab {
                     if let Some(tab) = self.tabs.get(idx) {
                         if let TabKind::Editor {
@@ -1047,9 +952,11 @@ impl App {
                 iced::Task::none()
             }
             Message::FileSaved(result) => {
+                let saved = result.is_ok();
                 if let Err(e) = result {
                     eprintln!("Failed to save file: {}", e);
                 } else if let Some(idx) = self.active_tab {
+                    self.symbol_index.invalidate();
                     if let Some(tab) = self.tabs.get_mut(idx) {
                         if let TabKind::Editor {
                             ref mut code_editor,
@@ -1063,7 +970,13 @@ impl App {
                         tab.autosave_in_flight = false;
                     }
                 }
-                iced::Task::none()
+                match self.active_tab.and_then(|idx| self.tabs.get(idx)) {
+                    Some(tab) => {
+                        let path = tab.path.clone();
+                        self.quit_after_save(&path, saved)
+                    }
+                    None => iced::Task::none(),
+                }
             }
             Message::SidebarResizeStart => {
                 self.resizing_sidebar = true;
@@ -1164,23 +1077,11 @@ impl App {
                 self.search_results = results;
                 iced::Task::none()
             }
-            Message::SearchResultClicked(path, _line_number) => {
+            Message::SearchResultClicked(path, line_number) => {
                 self.search_visible = false;
                 self.search_query.clear();
                 self.search_results.clear();
-                if let Some(ref mut tree) = self.file_tree {
-                    tree.select(path.clone());
-                }
-                if let Some(idx) = self.tabs.iter().position(|t| t.path == path) {
-                    self.active_tab = Some(idx);
-                    self.vim_refresh_cursor_style();
-                    return iced::Task::none();
-                }
-                if Self::should_confirm_sensitive_open(&path) {
-                    self.pending_sensitive_open = Some(path);
-                    return iced::Task::none();
-                }
-                Self::open_path_task(path)
+                self.reveal_location(path, (line_number.saturating_sub(1), 0))
             }
             Message::ToggleFileFinder => {
                 self.file_finder_visible = !self.file_finder_visible;
@@ -1264,7 +1165,10 @@ impl App {
                 self.fuzzy_finder.input = query;
                 self.fuzzy_finder.filter();
                 self.fuzzy_finder.update_preview();
-                iced::widget::operation::focus(self.fuzzy_finder.input_id.clone())
+                iced::Task::batch([
+                    self.search_workspace_symbols(),
+                    iced::widget::operation::focus(self.fuzzy_finder.input_id.clone()),
+                ])
             }
             Message::FuzzyFinderNavigate(delta) => {
                 if self.command_palette.open {
@@ -1278,6 +1182,11 @@ impl App {
                     return iced::Task::none();
                 }
 
+                if self.developer_panel_visible && self.focused_pane == FocusPane::Console {
+                    self.lua_console.navigate_history(delta);
+                    return iced::Task::none();
+                }
+
                 if !self.fuzzy_finder.open {
                     return iced::Task::none();
                 }
@@ -1301,10 +1210,14 @@ impl App {
                 if !self.fuzzy_finder.open {
                     return iced::Task::none();
                 }
-                if let Some(path) = self.fuzzy_finder.select() {
-                    return self.update(Message::FileClicked(path));
+                match self.fuzzy_finder.select() {
+                    Some(FinderItem::File(path)) => self.update(Message::FileClicked(path)),
+                    Some(FinderItem::Symbol(symbol)) => {
+                        self.vim_refresh_cursor_style();
+                        self.reveal_location(symbol.path, symbol.start)
+                    }
+                    None => iced::Task::none(),
                 }
-                iced::Task::none()
             }
             Message::EscapePressed => {
                 if self.autocomplete.active {
@@ -1312,12 +1225,26 @@ impl App {
                 } else if self.lsp_overlay.completion_visible || self.lsp_overlay.hover_visible {
                     self.lsp_overlay = iced_code_editor::LspOverlayState::new();
                     self.pending_hover_request = None;
+                } else if self.signature.popup.is_some() {
+                    self.signature.close();
+                } else if self.code_actions.picker.is_some() {
+                    self.code_actions.picker = None;
+                } else if self.rename.is_some() {
+                    self.rename = None;
+                } else if self.location_results.is_some() {
+                    self.location_results = None;
                 } else if self.command_palette.open {
                     self.command_palette.close();
                 } else if self.pending_sensitive_open.is_some() {
                     self.pending_sensitive_open = None;
+                } else if self.pending_quit.is_some() {
+                    self.pending_quit = None;
                 } else if self.command_input.open {
                     self.command_input.close();
+                    self.vim_confirm = None;
+                    return self.vim_search_cancel();
+                } else if self.developer_panel_visible && self.focused_pane == FocusPane::Console {
+                    self.focused_pane = FocusPane::Editor;
                 } else if self.find_replace.open {
                     self.find_replace.close();
                 } else if self.fuzzy_finder.open {
@@ -1335,6 +1262,8 @@ impl App {
                     self.theme_dropdown_open = false;
                 } else if self.settings_open {
                     self.settings_open = false;
+                } else if self.vim_enabled && self.vim_context_active() {
+                    self.vim_escape();
                 }
                 self.vim_refresh_cursor_style();
                 iced::Task::none()
@@ -1349,8 +1278,10 @@ impl App {
                 iced::Task::none()
             }
             Message::ToggleCommandPalette => {
-                let include_markdown_render = self.active_tab_supports_markdown_preview();
-                self.command_palette.toggle(include_markdown_render);
+                let commands = self
+                    .command_registry
+                    .available(self.active_tab_supports_markdown_preview());
+                self.command_palette.toggle(commands);
                 self.command_palette_selected = 0;
                 if self.command_palette.open {
                     self.vim_refresh_cursor_style();
@@ -1361,11 +1292,14 @@ impl App {
             }
             Message::CommandPaletteQueryChanged(query) => {
                 self.command_palette.input = query;
-                self.command_palette
-                    .filter_commands(self.active_tab_supports_markdown_preview());
+                let commands = self
+                    .command_registry
+                    .available(self.active_tab_supports_markdown_preview());
+                self.command_palette.filter_commands(commands);
                 self.command_palette_selected = 0;
                 iced::widget::operation::focus(self.command_palette_input_id.clone())
             }
+            Message::RunKeymapAction(action) => self.run_keymap_action(action),
             Message::CommandPaletteSelect(command_name) => {
                 self.command_palette.close();
                 self.execute_palette_command(&command_name)
@@ -1494,6 +1428,13 @@ impl App {
                 self.vim_refresh_cursor_style();
                 iced::Task::none()
             }
+            Message::OpenSettingsSection(section) => {
+                self.settings_open = true;
+                self.settings_section = section;
+                self.theme_dropdown_open = false;
+                self.vim_refresh_cursor_style();
+                iced::Task::none()
+            }
             Message::SettingsNavigate(section) => {
                 if section == "__toggle_theme_dropdown__" {
                     self.theme_dropdown_open = !self.theme_dropdown_open;
@@ -1525,10 +1466,17 @@ impl App {
                 iced::Task::none()
             }
             Message::SettingsSavePreferences => {
-                let _ = prefs::save_preferences(&self.editor_preferences);
-                self.notification = Some(Notification {
-                    message: "Preferences saved".to_string(),
-                    shown_at: Instant::now(),
+                self.notification = Some(match prefs::save_preferences(&self.editor_preferences) {
+                    Ok(()) => Notification {
+                        message: "Preferences saved".to_string(),
+                        kind: NotificationKind::Success,
+                        shown_at: Instant::now(),
+                    },
+                    Err(err) => Notification {
+                        message: format!("Preferences not saved: {err}"),
+                        kind: NotificationKind::Error,
+                        shown_at: Instant::now(),
+                    },
                 });
                 iced::Task::none()
             }
@@ -1543,13 +1491,14 @@ impl App {
                 iced::Task::none()
             }
             Message::SettingsReloadTheme => {
-                use crate::config::theme_manager;
-                let lua_theme = theme_manager::load_theme();
-                let t = crate::theme::ThemeColors::from_lua_theme(&lua_theme);
-                crate::theme::set_theme(t);
-                self.apply_editor_theme_to_tabs();
-                self.active_theme_name = "Custom (theme.lua)".to_string();
-                self.editor_preferences.theme_name = "Custom (theme.lua)".to_string();
+                if let Err(err) = self.apply_lua_theme_file() {
+                    self.notification = Some(Notification {
+                        message: err,
+                        kind: NotificationKind::Error,
+                        shown_at: Instant::now(),
+                    });
+                    return iced::Task::none();
+                }
                 self.theme_dropdown_open = false;
                 let _ = prefs::save_preferences(&self.editor_preferences);
                 iced::Task::none()
@@ -1560,6 +1509,31 @@ impl App {
                 }
                 iced::Task::none()
             }
+            Message::SettingsVimEscapeChanged(val) => {
+                self.editor_preferences.vim_escape =
+                    val.chars().filter(|c| !c.is_whitespace()).collect();
+                iced::Task::none()
+            }
+            Message::SettingsToggleInlayHints => {
+                self.toggle_inlay_hints();
+                iced::Task::none()
+            }
+            Message::SettingsTogglePlugin(name) => {
+                if let Some(plugin) = self.plugins.iter_mut().find(|p| p.name == name) {
+                    plugin.enabled = !plugin.enabled;
+                    let disabled = &mut self.editor_preferences.disabled_plugins;
+                    disabled.retain(|n| n != &name);
+                    if !plugin.enabled {
+                        disabled.push(name);
+                    }
+                    let _ = prefs::save_preferences(&self.editor_preferences);
+                    self.reload_config(ConfigChanges {
+                        scripts: true,
+                        ..Default::default()
+                    });
+                }
+                iced::Task::none()
+            }
             Message::SettingsToggleDeveloperMode => {
                 self.editor_preferences.developer_mode = !self.editor_preferences.developer_mode;
                 self.dev_log(format!(
@@ -1583,14 +1557,192 @@ impl App {
                         "disabled"
                     }
                 ));
-                if !self.lsp_enabled {
+                if self.lsp_enabled {
+                    for idx in 0..self.tabs.len() {
+                        self.attach_lsp(idx);
+                    }
+                } else {
+                    self.detach_all_lsp();
+                    self.lsp.shutdown();
+                    self.lsp_diagnostics.clear();
                     self.lsp_overlay = iced_code_editor::LspOverlayState::new();
                     self.pending_hover_request = None;
                 }
                 iced::Task::none()
             }
+            Message::RestartLsp => {
+                self.lsp_diagnostics.clear();
+                for err in self.lsp.restart_all() {
+                    self.dev_log(format!("LSP: Failed to restart: {}", err));
+                    self.notification = Some(Notification {
+                        message: err,
+                        kind: NotificationKind::Error,
+                        shown_at: Instant::now(),
+                    });
+                }
+                iced::Task::none()
+            }
+            Message::ToggleProblems => {
+                self.problems_open = !self.problems_open;
+                iced::Task::none()
+            }
+            Message::ProblemsFilterToggled(severity) => {
+                self.problems_filter.toggle(severity);
+                iced::Task::none()
+            }
+            Message::ProblemClicked(path, line, column) => {
+                self.vim_push_jump();
+                self.go_to_location(path, (line.saturating_sub(1), column.saturating_sub(1)))
+            }
+            Message::NextProblem => self.step_problem(true),
+            Message::PreviousProblem => self.step_problem(false),
+            Message::ToggleLspInspector => {
+                self.toggle_lsp_inspector();
+                iced::Task::none()
+            }
+            Message::LspInspectorShowTraffic(traffic) => {
+                self.lsp_inspector.traffic = traffic;
+                iced::Task::none()
+            }
+            Message::LspInspectorServerSelected(index) => {
+                self.select_lsp_server(index);
+                iced::Task::none()
+            }
+            Message::LspTrafficFilterChanged(filter) => {
+                self.lsp_inspector.filter = filter;
+                iced::Task::none()
+            }
+            Message::LspTrafficSelected(index) => {
+                self.select_lsp_message(index);
+                iced::Task::none()
+            }
+            Message::LspTrafficClear => {
+                self.clear_lsp_traffic();
+                iced::Task::none()
+            }
+            Message::LspRestartServer(index) => {
+                self.restart_lsp_server(index);
+                iced::Task::none()
+            }
+            Message::LspStopServer(index) => {
+                self.stop_lsp_server(index);
+                iced::Task::none()
+            }
+            Message::FindLocations(kind) => {
+                self.request_locations(kind);
+                iced::Task::none()
+            }
+            Message::LocationClicked(path, line, column) => {
+                self.location_results = None;
+                self.vim_push_jump();
+                self.go_to_location(path, (line, column))
+            }
+            Message::CloseLocations => {
+                self.location_results = None;
+                iced::Task::none()
+            }
+            Message::RenameSymbol => self.open_rename(),
+            Message::RenameInputChanged(input) => {
+                if let Some(rename) = &mut self.rename {
+                    rename.input = input;
+                    rename.preview = None;
+                }
+                iced::Task::none()
+            }
+            Message::RenameSubmit => {
+                self.submit_rename();
+                iced::Task::none()
+            }
+            Message::RenameApply => {
+                self.apply_rename();
+                iced::Task::none()
+            }
+            Message::RenameCancel => {
+                self.rename = None;
+                iced::Task::none()
+            }
+            Message::FormatDocument => match self.format_document(false) {
+                Ok(task) => task,
+                Err(message) => {
+                    self.show_format_error(message);
+                    iced::Task::none()
+                }
+            },
+            Message::FormatFinished(path, result) => self.format_finished(path, result),
+            Message::ToggleOutline => {
+                self.outline.expanded = !self.outline.expanded;
+                self.outline.invalidate();
+                self.refresh_outline();
+                iced::Task::none()
+            }
+            Message::ShowWorkspaceSymbols => {
+                if !self.fuzzy_finder.open {
+                    self.fuzzy_finder.toggle();
+                    self.vim_refresh_cursor_style();
+                }
+                self.fuzzy_finder.input = "@".to_string();
+                self.fuzzy_finder.filter();
+                iced::Task::batch([
+                    self.search_workspace_symbols(),
+                    iced::widget::operation::focus(self.fuzzy_finder.input_id.clone()),
+                ])
+            }
+            Message::SymbolClicked(path, line, column) => {
+                if self.fuzzy_finder.open {
+                    self.fuzzy_finder.close();
+                    self.vim_refresh_cursor_style();
+                }
+                self.reveal_location(path, (line, column))
+            }
+            Message::SymbolIndexBuilt(generation, symbols) => {
+                self.symbol_index_built(generation, symbols);
+                iced::Task::none()
+            }
+            Message::ShowCodeActions => {
+                self.show_code_actions();
+                iced::Task::none()
+            }
+            Message::CodeActionSelected(idx) => {
+                self.run_code_action(idx);
+                iced::Task::none()
+            }
             Message::ToggleDeveloperPanel => {
                 self.developer_panel_visible = !self.developer_panel_visible;
+                if self.developer_panel_visible {
+                    return self.update(Message::FocusLuaConsole);
+                }
+                if self.focused_pane == FocusPane::Console {
+                    self.focused_pane = FocusPane::Editor;
+                }
+                self.vim_refresh_cursor_style();
+                iced::Task::none()
+            }
+            Message::FocusLuaConsole => {
+                if !self.developer_panel_visible {
+                    return iced::Task::none();
+                }
+                self.focused_pane = FocusPane::Console;
+                self.vim_refresh_cursor_style();
+                iced::widget::operation::focus(self.lua_console_input_id.clone())
+            }
+            Message::LuaConsoleInputChanged(input) => {
+                self.lua_console.input = input;
+                iced::Task::none()
+            }
+            Message::LuaConsoleSubmit => {
+                self.submit_lua_console();
+                iced::widget::operation::focus(self.lua_console_input_id.clone())
+            }
+            Message::LuaConsoleClear => {
+                self.lua_console.clear();
+                iced::Task::none()
+            }
+            Message::JobEvent(event) => {
+                self.handle_job_event(event);
+                iced::Task::none()
+            }
+            Message::CancelJob(id) => {
+                self.cancel_job(id);
                 iced::Task::none()
             }
             Message::ClearDeveloperLogs => {
@@ -1609,24 +1761,77 @@ impl App {
                 self.vim_refresh_cursor_style();
                 iced::Task::none()
             }
+            Message::ToggleVimMode => {
+                self.toggle_vim_mode();
+                iced::Task::none()
+            }
+            Message::VimKey(key) if self.code_actions.picker.is_some() => match key {
+                VimKey::Up | VimKey::Char('k') => {
+                    self.move_code_action_selection(-1);
+                    iced::Task::none()
+                }
+                VimKey::Down | VimKey::Char('j') => {
+                    self.move_code_action_selection(1);
+                    iced::Task::none()
+                }
+                VimKey::Enter => {
+                    let selected = self.code_actions.picker.unwrap_or_default();
+                    self.update(Message::CodeActionSelected(selected))
+                }
+                key => {
+                    self.code_actions.picker = None;
+                    self.handle_vim_key(key)
+                }
+            },
+            Message::VimKey(key) => self.handle_vim_key(key),
+            Message::VimClipboardRead(paste, text) => self.vim_clipboard_read(paste, text),
             Message::CommandInputChanged(input) => {
+                if self.vim_confirm.is_some() {
+                    return match input.chars().next_back() {
+                        Some(answer) => self.vim_confirm_answer(answer),
+                        None => iced::Task::none(),
+                    };
+                }
                 self.command_input.input = input;
+                self.command_input.output.clear();
+                if self.command_input.is_search() {
+                    return self.vim_search_preview();
+                }
                 iced::Task::none()
             }
             Message::CommandInputSubmit => {
-                if let Some(cmd) = self.command_input.process_command() {
-                    self.command_input.close();
-                    return self.execute_palette_command(&cmd);
+                if self.vim_confirm.is_some() {
+                    return iced::Task::none();
                 }
-                self.command_input.close();
-                iced::Task::none()
+                if self.command_input.is_search() {
+                    return self.vim_search_submit();
+                }
+                let input = self.command_input.input.trim().to_string();
+                self.vim_ex_command(&input)
             }
+            Message::CommandInputComplete => self.vim_ex_complete(),
             Message::WindowResized(width, height) => {
                 self.editor_preferences.window_width = (width as f32).max(640.0);
                 self.editor_preferences.window_height = (height as f32).max(480.0);
                 let _ = prefs::save_preferences(&self.editor_preferences);
                 iced::Task::none()
             }
+            Message::Quit => self.request_quit(),
+            Message::QuitConfirm(confirmed) => {
+                self.pending_quit = None;
+                if confirmed {
+                    return iced::exit();
+                }
+                iced::Task::none()
+            }
+            Message::WindowFocusLost => {
+                let path = self
+                    .active_tab
+                    .and_then(|idx| self.tabs.get(idx))
+                    .map(|tab| tab.path.clone());
+                self.run_script_hook(scripting::HookEvent::FocusLost, path);
+                iced::Task::none()
+            }
             Message::NewFile => {
                 let new_path = PathBuf::from("untitled");
                 let editor = self.configured_code_editor("", "txt");
@@ -1677,7 +1882,18 @@ impl App {
                 iced::Task::none()
             }
             Message::SaveWakaTimeSettings => {
-                let _ = wakatime::save(&self.wakatime);
+                if let Err(err) = wakatime::save(&self.wakatime) {
+                    self.notification = Some(Notification {
+                        message: format!("WakaTime settings not saved: {err}"),
+                        kind: NotificationKind::Error,
+                        shown_at: Instant::now(),
+                    });
+                }
+                iced::Task::none()
+            }
+            Message::ConfigWatchTick => {
+                let changes = self.config_watcher.poll();
+                self.reload_config(changes);
                 iced::Task::none()
             }
             Message::DismissNotification => {
@@ -1726,10 +1942,11 @@ impl App {
                     }
                 }
 
-                // Drain LSP events from the shared channel
+                // Drain LSP events from every running server
+                let mut tasks = Vec::new();
                 for event in self.lsp.drain_events() {
                     match event {
-                        iced_code_editor::LspEvent::Hover { text } => {
+                        crate::features::lsp::LspEvent::Hover { text } => {
                             self.dev_log(format!("LSP: Hover received ({} chars)", text.len()));
                             if !text.trim().is_empty() {
                                 self.lsp_overlay.show_hover(text);
@@ -1740,7 +1957,7 @@ impl App {
                                 self.lsp_overlay.clear_hover();
                             }
                         }
-                        iced_code_editor::LspEvent::Completion { items } => {
+                        crate::features::lsp::LspEvent::Completion { items } => {
                             self.dev_log(format!(
                                 "LSP: Completion received ({} items)",
                                 items.len()
@@ -1803,12 +2020,61 @@ impl App {
                                 }
                             }
                         }
-                        iced_code_editor::LspEvent::Definition { uri, range } => {
-                            self.dev_log(format!("LSP: Definition at {} {:?}", uri, range));
-                            eprintln!("Definition: {} at {:?}", uri, range);
+                        crate::features::lsp::LspEvent::Locations { kind, locations } => {
+                            self.dev_log(format!(
+                                "LSP: {} received ({} locations)",
+                                kind.label(),
+                                locations.len()
+                            ));
+                            tasks.push(self.show_locations(kind, locations));
                         }
-                        iced_code_editor::LspEvent::Progress { .. } => {}
-                        iced_code_editor::LspEvent::Log {
+                        crate::features::lsp::LspEvent::Rename(change) => {
+                            self.show_rename_preview(change);
+                        }
+                        crate::features::lsp::LspEvent::CodeActions {
+                            path,
+                            line,
+                            actions,
+                        } => {
+                            self.code_actions_received(path, line, actions);
+                        }
+                        crate::features::lsp::LspEvent::Formatted { path, edits } => {
+                            tasks.push(self.lsp_formatted(path, edits));
+                        }
+                        crate::features::lsp::LspEvent::SignatureHelp { path, help } => {
+                            self.signature_help_received(path, help);
+                        }
+                        crate::features::lsp::LspEvent::InlayHints { path, hints } => {
+                            self.inlay_hints_received(path, hints);
+                        }
+                        crate::features::lsp::LspEvent::InlayHintsRefresh => {
+                            self.inlay_hints.invalidate();
+                        }
+                        crate::features::lsp::LspEvent::DocumentSymbols { path, symbols } => {
+                            self.document_symbols_received(path, symbols);
+                        }
+                        crate::features::lsp::LspEvent::WorkspaceSymbols { query, symbols } => {
+                            self.workspace_symbols_received(&query, symbols);
+                        }
+                        crate::features::lsp::LspEvent::ApplyEdit(change) => {
+                            self.apply_workspace_change(&change, "Changed");
+                        }
+                        crate::features::lsp::LspEvent::Failed(message) => {
+                            self.show_lsp_error(message);
+                        }
+                        crate::features::lsp::LspEvent::Diagnostics(update) => {
+                            // Quick fixes depend on the diagnostics.
+                            self.code_actions.invalidate();
+                            if update.diagnostics.is_empty() {
+                                self.lsp_diagnostics.remove(&update.path);
+                            } else {
+                                self.lsp_diagnostics.insert(update.path, update.diagnostics);
+                            }
+                        }
+                        crate::features::lsp::LspEvent::State { server_key, state } => {
+                            self.lsp_state_changed(&server_key, state);
+                        }
+                        crate::features::lsp::LspEvent::Log {
                             server_key,
                             message,
                         } => {
@@ -1817,7 +2083,14 @@ impl App {
                         }
                     }
                 }
-                iced::Task::none()
+                if self.lsp_enabled {
+                    self.refresh_code_actions();
+                }
+                self.refresh_outline();
+                self.refresh_inlay_hints();
+                self.refresh_lsp_inspector();
+                tasks.push(self.check_format_timeout());
+                iced::Task::batch(tasks)
             }
             Message::AutosaveTick => {
                 if !self.editor_preferences.autosave_enabled {
@@ -1851,11 +2124,13 @@ impl App {
                 };
 
                 tab.autosave_in_flight = false;
+                let saved = result.is_ok();
 
                 match result {
                     Ok(()) => {
                         if let TabKind::Editor { code_editor, .. } = &mut tab.kind {
                             if code_editor.content() == saved_content {
+                                self.symbol_index.invalidate();
                                 code_editor.mark_saved();
                                 code_editor.lsp_did_save();
                                 tab.autosave_requested_at = None;
@@ -1869,12 +2144,13 @@ impl App {
                         tab.autosave_requested_at = Some(Instant::now());
                         self.notification = Some(Notification {
                             message: format!("Autosave failed: {err}"),
+                            kind: NotificationKind::Error,
                             shown_at: Instant::now(),
                         });
                     }
                 }
 
-                iced::Task::none()
+                self.quit_after_save(&path, saved)
             }
             Message::CheckForUpdate => {
                 iced::Task::perform(crate::features::updater::check_for_update(), |result| {
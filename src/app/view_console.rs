@@ -0,0 +1,165 @@
+use super::*;
+use crate::features::lua_console::ConsoleLineKind;
+use iced::widget::column;
+
+const CONSOLE_FONT: iced::Font = iced::Font {
+    family: iced::font::Family::Name("Fira Code"),
+    ..iced::Font::DEFAULT
+};
+
+impl App {
+    pub(super) fn view_lua_console_panel(&self) -> Element<'_, Message> {
+        let header = container(
+            row![
+                text("Lua Console").size(12).color(theme().text_muted),
+                iced::widget::Space::new().width(Length::Fill),
+                button(text("Clear").size(11).color(theme().text_dim))
+                    .style(tab_close_button_style)
+                    .on_press(Message::LuaConsoleClear),
+                button(text("x").size(12).color(theme().text_dim))
+                    .style(tab_close_button_style)
+                    .on_press(Message::ToggleDeveloperPanel),
+            ]
+            .spacing(4)
+            .align_y(iced::Alignment::Center),
+        )
+        .padding(iced::Padding {
+            top: 6.0,
+            right: 8.0,
+            bottom: 6.0,
+            left: 10.0,
+        })
+        .style(|_theme| container::Style {
+            background: Some(Background::Color(theme().bg_secondary)),
+            border: iced::Border {
+                color: theme().border_subtle,
+                width: 1.0,
+                radius: 0.0.into(),
+            },
+            ..Default::default()
+        });
+
+        let jobs: Vec<Element<'_, Message>> = self
+            .jobs
+            .iter()
+            .map(|job| {
+                row![
+                    text(format!("job {}", job.spec.id))
+                        .size(11)
+                        .color(theme().text_dim),
+                    text(job.spec.command_line())
+                        .size(11)
+                        .font(CONSOLE_FONT)
+                        .color(theme().text_primary)
+                        .width(Length::Fill),
+                    text(format!("{}s", job.started_at.elapsed().as_secs()))
+                        .size(11)
+                        .color(theme().text_dim),
+                    button(text("Cancel").size(11).color(theme().text_dim))
+                        .style(tab_close_button_style)
+                        .on_press(Message::CancelJob(job.spec.id)),
+                ]
+                .spacing(10)
+                .align_y(iced::Alignment::Center)
+                .into()
+            })
+            .collect();
+
+        let job_list = container(column(jobs).spacing(2))
+            .padding(iced::Padding {
+                top: 4.0,
+                right: 8.0,
+                bottom: 4.0,
+                left: 10.0,
+            })
+            .width(Length::Fill)
+            .style(|_theme| container::Style {
+                background: Some(Background::Color(theme().bg_secondary)),
+                ..Default::default()
+            });
+
+        let lines: Vec<Element<'_, Message>> = self
+            .lua_console
+            .lines
+            .iter()
+            .map(|line| {
+                let color = match line.kind {
+                    ConsoleLineKind::Input => theme().text_muted,
+                    ConsoleLineKind::Output => theme().text_primary,
+                    ConsoleLineKind::Result => theme().text_secondary,
+                    ConsoleLineKind::Error => Color::from_rgb(0.95, 0.45, 0.45),
+                };
+                text(line.text.as_str())
+                    .size(12)
+                    .font(CONSOLE_FONT)
+                    .color(color)
+                    .into()
+            })
+            .collect();
+
+        let scrollback = scrollable(
+            container(column(lines).spacing(2))
+                .padding(iced::Padding {
+                    top: 6.0,
+                    right: 10.0,
+                    bottom: 6.0,
+                    left: 10.0,
+                })
+                .width(Length::Fill),
+        )
+        .anchor_bottom()
+        .width(Length::Fill)
+        .height(Length::Fill);
+
+        let input = text_input("Lua expression or statement", &self.lua_console.input)
+            .id(self.lua_console_input_id.clone())
+            .on_input(Message::LuaConsoleInputChanged)
+            .on_submit(Message::LuaConsoleSubmit)
+            .size(12)
+            .font(CONSOLE_FONT)
+            .padding(iced::Padding {
+                top: 6.0,
+                right: 10.0,
+                bottom: 6.0,
+                left: 4.0,
+            })
+            .style(search_input_style)
+            .width(Length::Fill);
+
+        let prompt = row![
+            text(">")
+                .size(12)
+                .font(CONSOLE_FONT)
+                .color(theme().text_muted),
+            input
+        ]
+        .padding(iced::Padding {
+            top: 0.0,
+            right: 0.0,
+            bottom: 0.0,
+            left: 10.0,
+        })
+        .align_y(iced::Alignment::Center);
+
+        let body = mouse_area(
+            container(column![scrollback, prompt])
+                .width(Length::Fill)
+                .height(Length::Fill)
+                .style(|_theme| container::Style {
+                    background: Some(Background::Color(theme().bg_editor)),
+                    ..Default::default()
+                }),
+        )
+        .on_press(Message::FocusLuaConsole);
+
+        let mut panel = column![header].spacing(0);
+        if !self.jobs.is_empty() {
+            panel = panel.push(job_list);
+        }
+
+        container(panel.push(body))
+            .width(Length::Fill)
+            .height(Length::Fixed(self.terminal_panel_height))
+            .into()
+    }
+}
@@ -174,10 +174,49 @@ impl App {
             .into();
         }
 
+        if let Some(unsaved) = &self.pending_quit {
+            let button_padding = iced::Padding {
+                top: 8.0,
+                right: 16.0,
+                bottom: 8.0,
+                left: 16.0,
+            };
+            return container(
+                column![
+                    text("Quit without saving?")
+                        .size(18)
+                        .color(theme().text_muted),
+                    text(format!("Unsaved changes in {}", unsaved.join(", ")))
+                        .size(13)
+                        .color(theme().text_dim),
+                    row![
+                        button(text("Quit").size(13))
+                            .on_press(Message::QuitConfirm(true))
+                            .padding(button_padding),
+                        button(text("Cancel").size(13))
+                            .on_press(Message::QuitConfirm(false))
+                            .padding(button_padding),
+                    ]
+                    .spacing(12)
+                    .align_y(iced::Alignment::Center),
+                ]
+                .spacing(16)
+                .align_x(iced::Alignment::Center),
+            )
+            .center_x(Length::Fill)
+            .center_y(Length::Fill)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .into();
+        }
+
         if let Some(idx) = self.active_tab {
             if let Some(tab) = self.tabs.get(idx) {
                 match &tab.kind {
-                    TabKind::Editor { code_editor, .. } => {
+                    TabKind::Editor {
+                        code_editor,
+                        buffer,
+                    } => {
                         let editor = container(code_editor.view().map(Message::CodeEditorEvent))
                             .width(Length::Fill)
                             .height(Length::Fill)
@@ -186,6 +225,20 @@ impl App {
                                 ..Default::default()
                             });
                         let editor = mouse_area(editor).on_press(Message::FocusEditor);
+                        let editor: Element<'_, Message> = match self.vim_cursor_shape() {
+                            Some(shape) => {
+                                stack![editor, self.view_vim_cursor(code_editor, shape)].into()
+                            }
+                            None => editor.into(),
+                        };
+                        let editor = if self.lsp_enabled {
+                            let editor =
+                                self.with_inlay_hints(editor, code_editor, buffer, &tab.path);
+                            let editor = self.with_code_actions(editor, code_editor, &tab.path);
+                            self.with_signature_help(editor, code_editor, &tab.path)
+                        } else {
+                            editor
+                        };
 
                         let lsp_overlay = if self.lsp_enabled {
                             iced_code_editor::view_lsp_overlay(
@@ -504,36 +557,83 @@ impl App {
         .into()
     }
 
-    pub(super) fn view_status_bar(&self) -> Element<'_, Message> {
-        let file_info = self
-            .active_tab
-            .and_then(|idx| self.tabs.get(idx))
-            .map(|tab| tab.name.clone())
-            .unwrap_or_default();
+    /// Vim's cursor outside insert mode, drawn over the editor since it
+    /// shows no caret without focus.
+    fn view_vim_cursor(
+        &self,
+        code_editor: &CodeEditor,
+        shape: VimCursorShape,
+    ) -> Element<'_, Message> {
+        let line_height = code_editor.line_height();
+        let Some(pos) = code_editor
+            .cursor_screen_position()
+            .map(|pos| iced::Point::new(pos.x, pos.y - code_editor.viewport_scroll()))
+            .filter(|pos| pos.y >= 0.0)
+        else {
+            return iced::widget::Space::new().into();
+        };
+        let (offset, height) = match shape {
+            VimCursorShape::Block => (0.0, line_height),
+            VimCursorShape::HalfBlock => (line_height / 2.0, line_height / 2.0),
+        };
+        let color = theme().editor_style.text_color.scale_alpha(0.45);
+
+        let cursor = container(iced::widget::Space::new())
+            .width(Length::Fixed(code_editor.char_width()))
+            .height(Length::Fixed(height))
+            .style(move |_theme| container::Style {
+                background: Some(Background::Color(color)),
+                ..Default::default()
+            });
+        container(cursor)
+            .padding(iced::Padding {
+                top: pos.y + offset,
+                left: pos.x,
+                bottom: 0.0,
+                right: 0.0,
+            })
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .into()
+    }
 
-        let left = row![text(file_info).size(10).color(theme().text_dim),]
+    pub(super) fn view_status_bar(&self) -> Element<'_, Message> {
+        // Segment texts are rendered in `refresh_statusline`, not per frame.
+        fn segments(texts: &[String], color: Color) -> iced::widget::Row<'_, Message> {
+            row(texts
+                .iter()
+                .map(|segment| text(segment.as_str()).size(10).color(color).into()))
             .spacing(8)
-            .align_y(iced::Alignment::Center);
-
-        let current_line_diag = self
-            .active_tab
-            .and_then(|idx| self.tabs.get(idx))
-            .map(|tab| tab.path.clone())
-            .and_then(|path| self.lsp_diagnostics.get(&path))
-            .and_then(|items| items.iter().find(|d| d.line == self.cursor_line))
-            .map(|d| d.message.clone())
-            .unwrap_or_default();
-
-        let right = row![
-            text(format!("Ln {}, Col {}", self.cursor_line, self.cursor_col))
-                .size(10)
-                .color(theme().text_placeholder),
-            text(current_line_diag)
-                .size(10)
-                .color(theme().text_secondary),
-        ]
-        .spacing(8)
-        .align_y(iced::Alignment::Center);
+            .align_y(iced::Alignment::Center)
+        }
+        let mut left = segments(&self.statusline.left, theme().text_dim);
+        let right = segments(&self.statusline.right, theme().text_placeholder);
+
+        if let Some(badge) = self.vim_status_badge() {
+            let badge = container(
+                text(badge)
+                    .size(10)
+                    .font(iced::Font::MONOSPACE)
+                    .color(theme().text_primary),
+            )
+            .padding(iced::Padding {
+                top: 1.0,
+                right: 6.0,
+                bottom: 1.0,
+                left: 6.0,
+            })
+            .style(|_theme| container::Style {
+                background: Some(Background::Color(theme().selection)),
+                border: iced::Border {
+                    radius: 3.0.into(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+            left = row![badge, left]
+                .spacing(8)
+                .align_y(iced::Alignment::Center);
+        }
 
         container(
             row![left, iced::widget::Space::new().width(Length::Fill), right,]
@@ -1,5 +1,6 @@
 use super::*;
 use crate::features::icons::icon_handle;
+use crate::features::symbols::kind_label;
 use iced::widget::column;
 
 impl App {
@@ -10,18 +11,21 @@ impl App {
         };
         use syntect::parsing::{ParseState, ScopeStack, SyntaxSet};
 
-        let input = text_input("Search files...", &self.fuzzy_finder.input)
-            .id(self.fuzzy_finder.input_id.clone())
-            .on_input(Message::FuzzyFinderQueryChanged)
-            .size(15)
-            .padding(iced::Padding {
-                top: 16.0,
-                right: 18.0,
-                bottom: 16.0,
-                left: 18.0,
-            })
-            .style(search_input_style)
-            .width(Length::Fill);
+        let input = text_input(
+            "Search files, or @ for symbols...",
+            &self.fuzzy_finder.input,
+        )
+        .id(self.fuzzy_finder.input_id.clone())
+        .on_input(Message::FuzzyFinderQueryChanged)
+        .size(15)
+        .padding(iced::Padding {
+            top: 16.0,
+            right: 18.0,
+            bottom: 16.0,
+            left: 18.0,
+        })
+        .style(search_input_style)
+        .width(Length::Fill);
 
         let folder_label: Element<'_, Message> =
             if let Some(folder) = &self.fuzzy_finder.current_folder {
@@ -43,7 +47,9 @@ impl App {
 
         let mut items: Vec<Element<'_, Message>> = Vec::new();
 
-        if self.fuzzy_finder.filtered_files.is_empty() {
+        if self.fuzzy_finder.symbol_query().is_some() {
+            self.push_symbol_items(&mut items);
+        } else if self.fuzzy_finder.filtered_files.is_empty() {
             items.push(
                 container(text("No files found").size(13).color(theme().text_dim))
                     .padding(20)
@@ -151,11 +157,17 @@ impl App {
                     )
                     .collect();
 
-                    let line_num: Element<'_, Message> =
-                        container(text(format!("{}", line_idx + 1)).size(11).color(OVERLAY_2))
-                            .width(Length::Fixed(36.0))
-                            .align_right(Length::Fixed(36.0))
-                            .into();
+                    let line_num: Element<'_, Message> = container(
+                        text(format!(
+                            "{}",
+                            self.fuzzy_finder.preview_first_line + line_idx + 1
+                        ))
+                        .size(11)
+                        .color(OVERLAY_2),
+                    )
+                    .width(Length::Fixed(36.0))
+                    .align_right(Length::Fixed(36.0))
+                    .into();
 
                     let mut spans: Vec<iced::widget::text::Span<'_, iced::Font>> = Vec::new();
                     for (style, fragment) in &ranges {
@@ -270,6 +282,71 @@ impl App {
         stack![backdrop, center(opaque(overlay_box))].into()
     }
 
+    /// The `@` mode rows: kind, name, the symbol it sits in, and where.
+    fn push_symbol_items<'a>(&'a self, items: &mut Vec<Element<'a, Message>>) {
+        let results = &self.fuzzy_finder.symbol_results;
+        if results.is_empty() {
+            let status = if self.symbol_index.building {
+                "Indexing symbols..."
+            } else {
+                "No symbols found"
+            };
+            items.push(
+                container(text(status).size(13).color(theme().text_dim))
+                    .padding(20)
+                    .width(Length::Fill)
+                    .center_x(Length::Fill)
+                    .into(),
+            );
+            return;
+        }
+        for (idx, symbol) in results.iter().enumerate() {
+            let is_selected = idx == self.fuzzy_finder.selected_index;
+            let location = format!(
+                "{}:{}",
+                self.workspace_relative(&symbol.path),
+                symbol.start.0 + 1
+            );
+            let detail = symbol.detail.as_deref().unwrap_or("");
+            items.push(
+                button(
+                    row![
+                        container(
+                            text(kind_label(symbol.kind))
+                                .size(10)
+                                .color(theme().text_dim)
+                        )
+                        .width(Length::Fixed(44.0)),
+                        text(&symbol.name).size(13).color(if is_selected {
+                            theme().text_primary
+                        } else {
+                            theme().text_muted
+                        }),
+                        text(detail).size(11).color(theme().text_dim),
+                        iced::widget::Space::new().width(Length::Fill),
+                        text(location).size(10).color(theme().text_dim),
+                    ]
+                    .spacing(8)
+                    .align_y(iced::Alignment::Center),
+                )
+                .style(file_finder_item_style(is_selected))
+                .on_press(Message::SymbolClicked(
+                    symbol.path.clone(),
+                    symbol.start.0,
+                    symbol.start.1,
+                ))
+                .padding(iced::Padding {
+                    top: 6.0,
+                    right: 10.0,
+                    bottom: 6.0,
+                    left: 10.0,
+                })
+                .width(Length::Fill)
+                .into(),
+            );
+        }
+    }
+
     pub(super) fn view_file_finder_overlay(&self) -> Element<'_, Message> {
         use iced::widget::{center, opaque, stack, Space};
 
@@ -0,0 +1,302 @@
+use super::*;
+use crate::features::lsp::{LspServerState, ServerStatus, TrafficEntry};
+use iced::widget::column;
+use std::sync::Arc;
+
+const INSPECTOR_PANEL_HEIGHT: f32 = 260.0;
+const ERROR_RED: Color = Color::from_rgb(0.95, 0.45, 0.45);
+
+impl App {
+    pub(super) fn view_lsp_inspector(&self) -> Element<'_, Message> {
+        let inspector = &self.lsp_inspector;
+        let tab = |label: &'static str, traffic: bool| {
+            let color = if inspector.traffic == traffic {
+                theme().text_primary
+            } else {
+                theme().text_dim
+            };
+            button(text(label).size(11).color(color))
+                .style(tab_close_button_style)
+                .on_press(Message::LspInspectorShowTraffic(traffic))
+        };
+        let servers = inspector.servers.iter().enumerate().map(|(index, server)| {
+            let color = if index == inspector.server {
+                theme().text_primary
+            } else {
+                theme().text_dim
+            };
+            button(text(server_label(server)).size(11).color(color))
+                .style(tab_close_button_style)
+                .on_press(Message::LspInspectorServerSelected(index))
+                .into()
+        });
+
+        let header = container(
+            row![
+                text("Language Servers").size(12).color(theme().text_muted),
+                row![tab("Status", false), tab("Traffic", true)].spacing(4),
+                row(servers).spacing(4),
+                iced::widget::Space::new().width(Length::Fill),
+                button(text("x").size(12).color(theme().text_dim))
+                    .style(tab_close_button_style)
+                    .on_press(Message::ToggleLspInspector),
+            ]
+            .spacing(12)
+            .align_y(iced::Alignment::Center),
+        )
+        .padding(iced::Padding {
+            top: 6.0,
+            right: 8.0,
+            bottom: 6.0,
+            left: 10.0,
+        })
+        .style(|_theme| container::Style {
+            background: Some(Background::Color(theme().bg_secondary)),
+            border: iced::Border {
+                color: theme().border_subtle,
+                width: 1.0,
+                radius: 0.0.into(),
+            },
+            ..Default::default()
+        });
+
+        let content = match inspector.servers.get(inspector.server) {
+            None => container(
+                text("No language server has started yet")
+                    .size(11)
+                    .color(theme().text_dim),
+            )
+            .padding(10)
+            .into(),
+            Some(_) if inspector.traffic => self.view_lsp_traffic(),
+            Some(server) => view_server_status(inspector.server, server),
+        };
+
+        let body = container(content)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .style(|_theme| container::Style {
+                background: Some(Background::Color(theme().bg_editor)),
+                ..Default::default()
+            });
+
+        container(column![header, body].spacing(0))
+            .width(Length::Fill)
+            .height(Length::Fixed(INSPECTOR_PANEL_HEIGHT))
+            .into()
+    }
+
+    fn view_lsp_traffic(&self) -> Element<'_, Message> {
+        let inspector = &self.lsp_inspector;
+        let filter = text_input("Filter by method", &inspector.filter)
+            .on_input(Message::LspTrafficFilterChanged)
+            .size(11)
+            .padding(iced::Padding {
+                top: 4.0,
+                right: 8.0,
+                bottom: 4.0,
+                left: 8.0,
+            })
+            .style(search_input_style)
+            .width(Length::Fixed(220.0));
+        let toolbar = row![
+            filter,
+            text(format!("{} messages", inspector.messages.len()))
+                .size(11)
+                .color(theme().text_dim),
+            iced::widget::Space::new().width(Length::Fill),
+            button(text("Clear").size(11).color(theme().text_dim))
+                .style(tab_close_button_style)
+                .on_press(Message::LspTrafficClear),
+        ]
+        .spacing(8)
+        .padding(iced::Padding {
+            top: 4.0,
+            right: 8.0,
+            bottom: 4.0,
+            left: 6.0,
+        })
+        .align_y(iced::Alignment::Center);
+
+        let rows: Vec<Element<'_, Message>> = inspector
+            .messages
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.matches(&inspector.filter))
+            .map(|(index, entry)| {
+                let selected = inspector
+                    .selected
+                    .as_ref()
+                    .is_some_and(|(shown, _)| Arc::ptr_eq(shown, entry));
+                traffic_row(index, entry, inspector.opened_at, selected)
+            })
+            .collect();
+        let list = scrollable(column(rows).spacing(1))
+            .anchor_bottom()
+            .width(Length::FillPortion(3))
+            .height(Length::Fill);
+
+        let mut split = row![list].spacing(1);
+        if let Some((_, payload)) = &inspector.selected {
+            split = split.push(
+                scrollable(
+                    container(
+                        text(payload)
+                            .size(11)
+                            .font(EDITOR_FONT)
+                            .color(theme().text_secondary),
+                    )
+                    .padding(8)
+                    .width(Length::Fill),
+                )
+                .width(Length::FillPortion(2))
+                .height(Length::Fill),
+            );
+        }
+
+        column![toolbar, split].into()
+    }
+}
+
+fn server_label(server: &ServerStatus) -> String {
+    let folder = server
+        .root_uri
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or_default();
+    format!("{} ({folder})", server.server_key)
+}
+
+fn traffic_row<'a>(
+    index: usize,
+    entry: &'a TrafficEntry,
+    opened_at: Instant,
+    selected: bool,
+) -> Element<'a, Message> {
+    let elapsed = entry.at.saturating_duration_since(opened_at).as_secs_f64();
+    let (arrow, direction_color) = if entry.outgoing {
+        ("→", theme().text_secondary)
+    } else {
+        ("←", theme().text_muted)
+    };
+    let timing = match entry.duration {
+        Some(duration) => format!("{} ms", duration.as_millis()),
+        None => String::new(),
+    };
+    let method = if entry.method.is_empty() {
+        entry
+            .id
+            .as_deref()
+            .map_or_else(String::new, |id| format!("#{id}"))
+    } else {
+        entry.method.clone()
+    };
+
+    button(
+        row![
+            text(format!("{elapsed:.3}"))
+                .size(11)
+                .width(Length::Fixed(64.0))
+                .color(theme().text_dim),
+            text(arrow).size(11).color(direction_color),
+            text(entry.kind.label())
+                .size(11)
+                .width(Length::Fixed(80.0))
+                .color(theme().text_dim),
+            text(method).size(11).color(if entry.failed {
+                ERROR_RED
+            } else {
+                theme().text_primary
+            }),
+            iced::widget::Space::new().width(Length::Fill),
+            text(timing).size(11).color(theme().text_dim),
+        ]
+        .spacing(8)
+        .align_y(iced::Alignment::Center),
+    )
+    .style(tab_button_style(selected))
+    .on_press(Message::LspTrafficSelected(index))
+    .padding(iced::Padding {
+        top: 2.0,
+        right: 10.0,
+        bottom: 2.0,
+        left: 10.0,
+    })
+    .width(Length::Fill)
+    .into()
+}
+
+fn view_server_status(index: usize, server: &ServerStatus) -> Element<'_, Message> {
+    let (state, state_color) = match &server.state {
+        LspServerState::Starting => ("starting".to_string(), theme().text_muted),
+        LspServerState::Ready => ("running".to_string(), theme().text_primary),
+        LspServerState::Crashed(reason) => (format!("crashed: {reason}"), ERROR_RED),
+        LspServerState::Stopped => ("stopped".to_string(), theme().text_dim),
+    };
+    let field = |label: &'static str, value: String| {
+        row![
+            text(label)
+                .size(11)
+                .width(Length::Fixed(96.0))
+                .color(theme().text_dim),
+            text(value).size(11).color(theme().text_primary),
+        ]
+        .spacing(8)
+    };
+    let running = server.pid.is_some();
+    let mut stop =
+        button(text("Stop").size(11).color(theme().text_dim)).style(tab_close_button_style);
+    if running {
+        stop = stop.on_press(Message::LspStopServer(index));
+    }
+
+    let details = column![
+        row![
+            text(state).size(11).color(state_color),
+            iced::widget::Space::new().width(Length::Fill),
+            button(text("Restart").size(11).color(theme().text_dim))
+                .style(tab_close_button_style)
+                .on_press(Message::LspRestartServer(index)),
+            stop,
+        ]
+        .spacing(8)
+        .align_y(iced::Alignment::Center),
+        field(
+            "Process",
+            server
+                .pid
+                .map_or_else(|| "not running".to_string(), |pid| pid.to_string()),
+        ),
+        field("Root", server.root_uri.clone()),
+        field("Capabilities", server.capability_names().join(", ")),
+    ]
+    .spacing(6)
+    .padding(10)
+    .width(Length::FillPortion(1));
+
+    let stderr: Vec<Element<'_, Message>> = if server.stderr.is_empty() {
+        vec![text("No stderr output")
+            .size(11)
+            .color(theme().text_dim)
+            .into()]
+    } else {
+        server
+            .stderr
+            .iter()
+            .map(|line| {
+                text(line.as_str())
+                    .size(11)
+                    .font(EDITOR_FONT)
+                    .color(theme().text_secondary)
+                    .into()
+            })
+            .collect()
+    };
+    let stderr = scrollable(container(column(stderr).spacing(1)).padding(10))
+        .anchor_bottom()
+        .width(Length::FillPortion(1))
+        .height(Length::Fill);
+
+    row![details, stderr].spacing(1).into()
+}
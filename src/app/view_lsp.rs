@@ -0,0 +1,471 @@
+use super::*;
+use crate::features::editor_buffer::EditorBuffer;
+use crate::features::lsp::end_of_line_hints;
+use iced::widget::{canvas, column, rich_text, stack};
+
+const LIGHTBULB_COLOR: Color = Color::from_rgb(0.976, 0.886, 0.686);
+const ACCENT_PURPLE: Color = Color::from_rgb(0.796, 0.651, 0.969);
+
+/// The frame of popups drawn over the editor.
+fn floating_style(_theme: &iced::Theme) -> container::Style {
+    container::Style {
+        background: Some(Background::Color(Color::from_rgb(0.149, 0.149, 0.212))),
+        border: iced::Border {
+            color: ACCENT_PURPLE.scale_alpha(0.35),
+            width: 1.0,
+            radius: 8.0.into(),
+        },
+        shadow: iced::Shadow {
+            color: Color::from_rgba(0.0, 0.0, 0.0, 0.6),
+            offset: iced::Vector::new(0.0, 4.0),
+            blur_radius: 20.0,
+        },
+        ..Default::default()
+    }
+}
+
+/// Inlay hints drawn after the end of their lines, each at a point in the
+/// editor's coordinates.
+struct InlayHintLayer {
+    labels: Vec<(iced::Point, String)>,
+    size: f32,
+}
+
+impl canvas::Program<Message> for InlayHintLayer {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &(),
+        renderer: &iced::Renderer,
+        _theme: &iced::Theme,
+        bounds: iced::Rectangle,
+        _cursor: iced::mouse::Cursor,
+    ) -> Vec<canvas::Geometry> {
+        let mut frame = canvas::Frame::new(renderer, bounds.size());
+        for (position, label) in &self.labels {
+            frame.fill_text(canvas::Text {
+                content: label.clone(),
+                position: *position,
+                color: theme().text_dim.scale_alpha(0.8),
+                size: self.size.into(),
+                font: EDITOR_FONT,
+                ..canvas::Text::default()
+            });
+        }
+        vec![frame.into_geometry()]
+    }
+}
+
+impl App {
+    /// Path shown relative to the open folder, like the Problems panel.
+    pub(super) fn workspace_relative(&self, path: &std::path::Path) -> String {
+        let root = self.file_tree.as_ref().map(|tree| tree.root.as_path());
+        root.and_then(|root| path.strip_prefix(root).ok())
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    }
+
+    fn symbol_panel_header(&self, title: String, close: Message) -> Element<'_, Message> {
+        row![
+            text(title).size(12).color(theme().text_muted),
+            iced::widget::Space::new().width(Length::Fill),
+            button(text("x").size(12).color(theme().text_dim))
+                .style(tab_close_button_style)
+                .on_press(close),
+        ]
+        .align_y(iced::Alignment::Center)
+        .into()
+    }
+
+    fn file_heading(&self, path: &std::path::Path, count: usize) -> Element<'_, Message> {
+        container(
+            text(format!("{}  ({count})", self.workspace_relative(path)))
+                .size(11)
+                .color(theme().text_secondary),
+        )
+        .padding(iced::Padding {
+            top: 6.0,
+            right: 6.0,
+            bottom: 2.0,
+            left: 6.0,
+        })
+        .into()
+    }
+
+    /// "Find All References" and jumps with several targets, laid out like
+    /// the workspace search results.
+    pub(super) fn view_locations_panel(&self) -> Element<'_, Message> {
+        let Some(results) = &self.location_results else {
+            return iced::widget::Space::new().into();
+        };
+        let mut items: Vec<Element<'_, Message>> = Vec::new();
+        for (path, locations) in &results.files {
+            items.push(self.file_heading(path, locations.len()));
+            for (location, line) in locations {
+                items.push(
+                    button(
+                        text(format!("  {}:  {}", location.start.0 + 1, line))
+                            .size(11)
+                            .color(theme().text_muted),
+                    )
+                    .style(tree_button_style)
+                    .on_press(Message::LocationClicked(
+                        location.path.clone(),
+                        location.start.0,
+                        location.start.1,
+                    ))
+                    .padding(iced::Padding {
+                        top: 3.0,
+                        right: 6.0,
+                        bottom: 3.0,
+                        left: 12.0,
+                    })
+                    .width(Length::Fill)
+                    .into(),
+                );
+            }
+        }
+
+        let content = column![
+            self.symbol_panel_header(results.title.clone(), Message::CloseLocations),
+            container(scrollable(column(items).spacing(1)).height(Length::Shrink))
+                .max_height(400.0),
+        ]
+        .spacing(6);
+
+        container(content)
+            .width(Length::Fixed(420.0))
+            .padding(10)
+            .style(search_panel_style)
+            .into()
+    }
+
+    /// The new name, then the lines the server's edit would change.
+    pub(super) fn view_rename_panel(&self) -> Element<'_, Message> {
+        let Some(rename) = &self.rename else {
+            return iced::widget::Space::new().into();
+        };
+        let input = text_input("New name", &rename.input)
+            .id(self.rename_input_id.clone())
+            .on_input(Message::RenameInputChanged)
+            .on_submit(Message::RenameSubmit)
+            .style(search_input_style)
+            .size(13)
+            .padding(10)
+            .width(Length::Fill);
+
+        let mut content = column![
+            self.symbol_panel_header(
+                format!("Rename `{}`", rename.old_name),
+                Message::RenameCancel
+            ),
+            input
+        ]
+        .spacing(6);
+
+        match &rename.preview {
+            None => {
+                content = content.push(
+                    text("Press Enter to preview the changes")
+                        .size(11)
+                        .color(theme().text_dim),
+                );
+            }
+            Some(preview) => {
+                let mut items: Vec<Element<'_, Message>> = Vec::new();
+                for (path, lines) in &preview.files {
+                    let edits = preview
+                        .change
+                        .files
+                        .iter()
+                        .find(|file| file.path == *path)
+                        .map_or(0, |file| file.edits.len());
+                    items.push(self.file_heading(path, edits));
+                    for (line, new_text) in lines {
+                        items.push(
+                            container(
+                                text(format!("  {}:  {}", line + 1, new_text))
+                                    .size(11)
+                                    .color(theme().text_muted),
+                            )
+                            .padding(iced::Padding {
+                                top: 3.0,
+                                right: 6.0,
+                                bottom: 3.0,
+                                left: 12.0,
+                            })
+                            .into(),
+                        );
+                    }
+                }
+                let mut summary = format!(
+                    "{} changes in {} files",
+                    preview.change.edit_count(),
+                    preview.change.files.len()
+                );
+                if preview.change.skipped_operations > 0 {
+                    summary.push_str(" (file renames are skipped)");
+                }
+                content = content
+                    .push(text(summary).size(11).color(theme().text_dim))
+                    .push(
+                        container(scrollable(column(items).spacing(1)).height(Length::Shrink))
+                            .max_height(360.0),
+                    )
+                    .push(
+                        row![
+                            iced::widget::Space::new().width(Length::Fill),
+                            button(text("Cancel").size(12)).on_press(Message::RenameCancel),
+                            button(text("Apply").size(12)).on_press(Message::RenameApply),
+                        ]
+                        .spacing(8),
+                    );
+            }
+        }
+
+        container(content)
+            .width(Length::Fixed(420.0))
+            .padding(10)
+            .style(search_panel_style)
+            .into()
+    }
+
+    /// Stacks the code action lightbulb and picker over the editor. Both sit
+    /// on the cursor's line, the only one actions are known for.
+    pub(super) fn with_code_actions<'a>(
+        &'a self,
+        editor: Element<'a, Message>,
+        code_editor: &CodeEditor,
+        path: &std::path::Path,
+    ) -> Element<'a, Message> {
+        let on_cursor_line =
+            self.code_actions.lightbulb_line(path) == Some(self.cursor_line.saturating_sub(1));
+        let Some(cursor) = code_editor
+            .cursor_screen_position()
+            .map(|pos| iced::Point::new(pos.x, pos.y - code_editor.viewport_scroll()))
+            .filter(|pos| on_cursor_line && pos.y >= 0.0)
+        else {
+            return editor;
+        };
+        let line_height = code_editor.line_height();
+
+        let lightbulb = button(text("●").size(10).color(LIGHTBULB_COLOR))
+            .style(tab_close_button_style)
+            .padding(iced::Padding {
+                top: ((line_height - 12.0) / 2.0).max(0.0),
+                right: 2.0,
+                bottom: 0.0,
+                left: 2.0,
+            })
+            .on_press(Message::ShowCodeActions);
+        let lightbulb = container(lightbulb)
+            .padding(iced::Padding {
+                top: cursor.y,
+                left: 2.0,
+                bottom: 0.0,
+                right: 0.0,
+            })
+            .width(Length::Fill)
+            .height(Length::Fill);
+
+        let Some(selected) = self.code_actions.picker else {
+            return stack![editor, lightbulb].into();
+        };
+        let bg_selected = Color::from_rgba(0.796, 0.651, 0.969, 0.18);
+        let items = self
+            .code_actions
+            .available
+            .iter()
+            .enumerate()
+            .map(|(idx, action)| {
+                let is_selected = idx == selected;
+                let label_color = if is_selected {
+                    theme().text_primary
+                } else {
+                    theme().text_muted
+                };
+                let kind = action.kind.clone().unwrap_or_default();
+                button(
+                    row![
+                        text(&action.title).size(12).color(label_color),
+                        iced::widget::Space::new().width(Length::Fill),
+                        text(kind).size(10).color(theme().text_dim),
+                    ]
+                    .spacing(12)
+                    .align_y(iced::Alignment::Center),
+                )
+                .padding(iced::Padding {
+                    top: 4.0,
+                    right: 10.0,
+                    bottom: 4.0,
+                    left: 8.0,
+                })
+                .width(Length::Fill)
+                .style(move |_theme, _status| button::Style {
+                    background: is_selected.then_some(Background::Color(bg_selected)),
+                    border: iced::Border {
+                        radius: 4.0.into(),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .on_press(Message::CodeActionSelected(idx))
+                .into()
+            });
+
+        let picker = container(scrollable(column(items).spacing(1)).height(Length::Shrink))
+            .padding(4)
+            .max_width(420.0)
+            .max_height(300.0)
+            .style(floating_style);
+        let picker = container(picker)
+            .padding(iced::Padding {
+                top: cursor.y + line_height,
+                left: cursor.x.clamp(0.0, 500.0),
+                bottom: 0.0,
+                right: 0.0,
+            })
+            .width(Length::Fill)
+            .height(Length::Fill);
+
+        stack![editor, lightbulb, picker].into()
+    }
+
+    /// The signature of the call being typed, with the active parameter
+    /// highlighted. It sits above the cursor's line, or below it near the
+    /// top of the editor.
+    pub(super) fn with_signature_help<'a>(
+        &'a self,
+        editor: Element<'a, Message>,
+        code_editor: &CodeEditor,
+        path: &std::path::Path,
+    ) -> Element<'a, Message> {
+        let Some(help) = self
+            .signature
+            .popup
+            .as_ref()
+            .filter(|popup| popup.path == path)
+            .map(|popup| &popup.help)
+        else {
+            return editor;
+        };
+        let Some(cursor) = code_editor
+            .cursor_screen_position()
+            .map(|pos| iced::Point::new(pos.x, pos.y - code_editor.viewport_scroll()))
+            .filter(|pos| pos.y >= 0.0)
+        else {
+            return editor;
+        };
+
+        let chars: Vec<char> = help.label.chars().collect();
+        let active = help
+            .active_parameter
+            .clone()
+            .filter(|range| range.start <= range.end && range.end <= chars.len())
+            .unwrap_or(0..0);
+        let part = |range: std::ops::Range<usize>| chars[range].iter().collect::<String>();
+        let spans: [iced::widget::text::Span<'_, ()>; 3] = [
+            iced::widget::span(part(0..active.start)).color(theme().text_muted),
+            iced::widget::span(part(active.clone()))
+                .color(ACCENT_PURPLE)
+                .underline(true),
+            iced::widget::span(part(active.end..chars.len())).color(theme().text_muted),
+        ];
+        let signature = rich_text(spans).size(12).font(EDITOR_FONT);
+
+        let mut header = row![signature].spacing(12);
+        if help.overload.1 > 1 {
+            header = header.push(
+                text(format!("{}/{}", help.overload.0 + 1, help.overload.1))
+                    .size(10)
+                    .color(theme().text_dim),
+            );
+        }
+        let mut content = column![header].spacing(6);
+        if let Some(documentation) = &help.documentation {
+            // The first paragraph, which is usually the summary.
+            let summary: Vec<&str> = documentation
+                .lines()
+                .take_while(|line| !line.trim().is_empty())
+                .take(4)
+                .collect();
+            content = content.push(text(summary.join("\n")).size(11).color(theme().text_dim));
+        }
+        let popup = container(content)
+            .padding(8)
+            .max_width(560.0)
+            .style(floating_style);
+
+        let left = cursor.x.clamp(0.0, 500.0);
+        let popup = if cursor.y > 80.0 {
+            container(popup)
+                .padding(iced::Padding {
+                    top: 0.0,
+                    left,
+                    bottom: (code_editor.viewport_height() - cursor.y + 2.0).max(0.0),
+                    right: 0.0,
+                })
+                .align_bottom(Length::Fill)
+        } else {
+            container(popup).padding(iced::Padding {
+                top: cursor.y + code_editor.line_height() + 2.0,
+                left,
+                bottom: 0.0,
+                right: 0.0,
+            })
+        };
+        stack![editor, popup.width(Length::Fill).height(Length::Fill)].into()
+    }
+
+    /// Draws the active file's inlay hints as dimmed text after the lines
+    /// they belong to, over the editor.
+    pub(super) fn with_inlay_hints<'a>(
+        &'a self,
+        editor: Element<'a, Message>,
+        code_editor: &CodeEditor,
+        buffer: &EditorBuffer,
+        path: &std::path::Path,
+    ) -> Element<'a, Message> {
+        if !self.editor_preferences.inlay_hints
+            || self.inlay_hints.path.as_deref() != Some(path)
+            || self.inlay_hints.lines.is_empty()
+        {
+            return editor;
+        }
+        let line_height = code_editor.line_height();
+        if line_height <= 0.0 || buffer.line_count() == 0 {
+            return editor;
+        }
+        // Where column 0 is drawn, found from the cursor's own position.
+        let (cursor_line, cursor_col) = code_editor.cursor_position();
+        let Some(cursor) = code_editor.cursor_screen_position() else {
+            return editor;
+        };
+        let before_cursor: String = buffer.line(cursor_line).chars().take(cursor_col).collect();
+        let origin = cursor.x - code_editor.measure_text_width(&before_cursor);
+
+        let scroll = code_editor.viewport_scroll();
+        let first = (scroll / line_height) as usize;
+        let last = ((scroll + code_editor.viewport_height()) / line_height).ceil() as usize;
+        let labels = (first..=last.min(buffer.line_count() - 1))
+            .filter_map(|line| {
+                let hints = self.inlay_hints.lines.get(&line)?;
+                let text = buffer.line(line);
+                let x =
+                    origin + code_editor.measure_text_width(&text) + 2.0 * code_editor.char_width();
+                let y = line as f32 * line_height - scroll + 2.0;
+                Some((iced::Point::new(x, y), end_of_line_hints(&text, hints)))
+            })
+            .collect();
+
+        let layer = canvas(InlayHintLayer {
+            labels,
+            size: code_editor.font_size(),
+        })
+        .width(Length::Fill)
+        .height(Length::Fill);
+        stack![editor, layer].into()
+    }
+}
@@ -0,0 +1,85 @@
+use super::*;
+use crate::features::symbols::kind_label;
+use crate::ui::styles::file_finder_item_style;
+use iced::widget::column;
+
+impl App {
+    /// The "OUTLINE" header and, while expanded, the active file's symbols
+    /// with the one around the cursor highlighted.
+    pub(super) fn view_outline(&self) -> Element<'_, Message> {
+        let arrow = if self.outline.expanded { "▾" } else { "▸" };
+        let header = button(
+            text(format!("{arrow} OUTLINE"))
+                .size(11)
+                .color(theme().text_muted),
+        )
+        .style(tree_button_style)
+        .on_press(Message::ToggleOutline)
+        .padding(iced::Padding {
+            top: 6.0,
+            right: 10.0,
+            bottom: 6.0,
+            left: 10.0,
+        })
+        .width(Length::Fill);
+        if !self.outline.expanded {
+            return header.into();
+        }
+
+        let current = self.outline_current();
+        let mut items: Vec<Element<'_, Message>> = Vec::new();
+        for (idx, symbol) in self.outline.symbols.iter().enumerate() {
+            let is_current = current == Some(idx);
+            items.push(
+                button(
+                    row![
+                        container(text(""))
+                            .width(Length::Fixed(INDENT_WIDTH * symbol.depth as f32)),
+                        text(kind_label(symbol.kind))
+                            .size(10)
+                            .color(theme().text_dim),
+                        text(&symbol.name).size(12),
+                    ]
+                    .spacing(6)
+                    .align_y(iced::Alignment::Center),
+                )
+                .style(file_finder_item_style(is_current))
+                .on_press(Message::SymbolClicked(
+                    symbol.path.clone(),
+                    symbol.start.0,
+                    symbol.start.1,
+                ))
+                .padding(iced::Padding {
+                    top: 3.0,
+                    right: 10.0,
+                    bottom: 3.0,
+                    left: 10.0,
+                })
+                .width(Length::Fill)
+                .into(),
+            );
+        }
+        if items.is_empty() {
+            items.push(
+                container(
+                    text("No symbols in this file")
+                        .size(11)
+                        .color(theme().text_placeholder),
+                )
+                .padding(iced::Padding {
+                    top: 4.0,
+                    right: 10.0,
+                    bottom: 4.0,
+                    left: 10.0,
+                })
+                .into(),
+            );
+        }
+
+        column![
+            header,
+            scrollable(column(items).spacing(1)).height(Length::Fill),
+        ]
+        .into()
+    }
+}
@@ -3,35 +3,56 @@ use iced::widget::column;
 
 impl App {
     pub(super) fn view_notification_toast(&self) -> Element<'_, Message> {
-        let check_circle = container(text("✓").size(14).color(Color::from_rgb(0.40, 0.90, 0.55)))
+        let Some(notification) = &self.notification else {
+            return iced::widget::Space::new().into();
+        };
+
+        let (icon, accent, label_color, surface) = match notification.kind {
+            NotificationKind::Success => (
+                "✓",
+                Color::from_rgb(0.35, 0.88, 0.52),
+                Color::from_rgb(0.85, 0.97, 0.88),
+                Color::from_rgba(0.07, 0.20, 0.10, 0.96),
+            ),
+            NotificationKind::Error => (
+                "!",
+                Color::from_rgb(0.95, 0.42, 0.42),
+                Color::from_rgb(0.99, 0.88, 0.88),
+                Color::from_rgba(0.24, 0.07, 0.08, 0.96),
+            ),
+        };
+
+        let badge = container(text(icon).size(14).color(accent))
             .width(Length::Fixed(26.0))
             .height(Length::Fixed(26.0))
             .center_x(Length::Fixed(26.0))
             .center_y(Length::Fixed(26.0))
-            .style(|_theme| container::Style {
-                background: Some(Background::Color(Color::from_rgba(0.30, 0.85, 0.50, 0.15))),
+            .style(move |_theme| container::Style {
+                background: Some(Background::Color(Color { a: 0.15, ..accent })),
                 border: iced::Border {
-                    color: Color::from_rgba(0.35, 0.88, 0.52, 0.35),
+                    color: Color { a: 0.35, ..accent },
                     width: 1.0,
                     radius: 13.0.into(),
                 },
                 ..Default::default()
             });
 
-        let label = text("Preferences saved")
+        let label = text(notification.message.clone())
             .size(13)
-            .color(Color::from_rgb(0.85, 0.97, 0.88));
+            .color(label_color);
 
-        let dismiss_btn = button(
-            text("×")
-                .size(14)
-                .color(Color::from_rgba(0.65, 0.90, 0.70, 0.7)),
-        )
+        let dismiss_btn = button(text("×").size(14).color(Color {
+            a: 0.7,
+            ..label_color
+        }))
         .on_press(Message::DismissNotification)
-        .style(|_theme, _status| button::Style {
+        .style(move |_theme, _status| button::Style {
             background: None,
             border: iced::Border::default(),
-            text_color: Color::from_rgba(0.65, 0.90, 0.70, 0.7),
+            text_color: Color {
+                a: 0.7,
+                ..label_color
+            },
             ..Default::default()
         })
         .padding(iced::Padding {
@@ -41,7 +62,7 @@ impl App {
             left: 8.0,
         });
 
-        let toast_inner = row![check_circle, label, dismiss_btn]
+        let toast_inner = row![badge, label, dismiss_btn]
             .spacing(10)
             .align_y(iced::Alignment::Center);
 
@@ -52,10 +73,10 @@ impl App {
                 bottom: 10.0,
                 left: 12.0,
             })
-            .style(|_theme| container::Style {
-                background: Some(Background::Color(Color::from_rgba(0.07, 0.20, 0.10, 0.96))),
+            .style(move |_theme| container::Style {
+                background: Some(Background::Color(surface)),
                 border: iced::Border {
-                    color: Color::from_rgba(0.30, 0.78, 0.45, 0.40),
+                    color: Color { a: 0.40, ..accent },
                     width: 1.0,
                     radius: 12.0.into(),
                 },
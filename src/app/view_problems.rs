@@ -0,0 +1,162 @@
+use super::*;
+use crate::features::lsp::InlineDiagnostic;
+use crate::features::problems::{self, ProblemSeverity};
+use iced::widget::column;
+use std::path::Path;
+
+const PROBLEMS_PANEL_HEIGHT: f32 = 200.0;
+
+fn severity_color(severity: ProblemSeverity) -> Color {
+    match severity {
+        ProblemSeverity::Error => Color::from_rgb(0.95, 0.45, 0.45),
+        ProblemSeverity::Warning => Color::from_rgb(0.976, 0.886, 0.686),
+        ProblemSeverity::Info => Color::from_rgb(0.537, 0.863, 0.922),
+    }
+}
+
+impl App {
+    pub(super) fn view_problems_panel(&self) -> Element<'_, Message> {
+        let counts = problems::count(&self.lsp_diagnostics);
+        let filters = [
+            ProblemSeverity::Error,
+            ProblemSeverity::Warning,
+            ProblemSeverity::Info,
+        ]
+        .map(|severity| {
+            let color = if self.problems_filter.shows(severity) {
+                severity_color(severity)
+            } else {
+                theme().text_dim
+            };
+            button(
+                text(format!("{} {}", severity.label(), counts.get(severity)))
+                    .size(11)
+                    .color(color),
+            )
+            .style(tab_close_button_style)
+            .on_press(Message::ProblemsFilterToggled(severity))
+            .into()
+        });
+
+        let header = container(
+            row![
+                text("Problems").size(12).color(theme().text_muted),
+                row(filters).spacing(4),
+                iced::widget::Space::new().width(Length::Fill),
+                button(text("x").size(12).color(theme().text_dim))
+                    .style(tab_close_button_style)
+                    .on_press(Message::ToggleProblems),
+            ]
+            .spacing(12)
+            .align_y(iced::Alignment::Center),
+        )
+        .padding(iced::Padding {
+            top: 6.0,
+            right: 8.0,
+            bottom: 6.0,
+            left: 10.0,
+        })
+        .style(|_theme| container::Style {
+            background: Some(Background::Color(theme().bg_secondary)),
+            border: iced::Border {
+                color: theme().border_subtle,
+                width: 1.0,
+                radius: 0.0.into(),
+            },
+            ..Default::default()
+        });
+
+        let root = self.file_tree.as_ref().map(|tree| tree.root.as_path());
+        let mut items: Vec<Element<'_, Message>> = Vec::new();
+        for (path, diagnostics) in problems::grouped(&self.lsp_diagnostics, &self.problems_filter) {
+            let shown = root
+                .and_then(|root| path.strip_prefix(root).ok())
+                .unwrap_or(path);
+            items.push(
+                container(
+                    text(format!("{}  ({})", shown.display(), diagnostics.len()))
+                        .size(11)
+                        .color(theme().text_secondary),
+                )
+                .padding(iced::Padding {
+                    top: 6.0,
+                    right: 6.0,
+                    bottom: 2.0,
+                    left: 6.0,
+                })
+                .into(),
+            );
+            items.extend(
+                diagnostics
+                    .into_iter()
+                    .map(|diagnostic| problem_row(path, diagnostic)),
+            );
+        }
+        if items.is_empty() {
+            items.push(
+                container(text("No problems").size(11).color(theme().text_dim))
+                    .padding(10)
+                    .into(),
+            );
+        }
+
+        let body = container(scrollable(column(items).spacing(1)).height(Length::Fill))
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .style(|_theme| container::Style {
+                background: Some(Background::Color(theme().bg_editor)),
+                ..Default::default()
+            });
+
+        container(column![header, body].spacing(0))
+            .width(Length::Fill)
+            .height(Length::Fixed(PROBLEMS_PANEL_HEIGHT))
+            .into()
+    }
+}
+
+fn problem_row<'a>(path: &Path, diagnostic: &'a InlineDiagnostic) -> Element<'a, Message> {
+    let severity = ProblemSeverity::of(diagnostic);
+    let origin = match (&diagnostic.source, &diagnostic.code) {
+        (Some(source), Some(code)) => format!("{source}({code})"),
+        (Some(source), None) => source.clone(),
+        (None, Some(code)) => code.clone(),
+        (None, None) => String::new(),
+    };
+    let message = diagnostic.message.lines().next().unwrap_or_default();
+
+    button(
+        row![
+            text(match severity {
+                ProblemSeverity::Error => "error",
+                ProblemSeverity::Warning => "warning",
+                ProblemSeverity::Info => "info",
+            })
+            .size(11)
+            .width(Length::Fixed(52.0))
+            .color(severity_color(severity)),
+            text(message).size(11).color(theme().text_primary),
+            text(origin).size(11).color(theme().text_dim),
+            iced::widget::Space::new().width(Length::Fill),
+            text(format!("{}:{}", diagnostic.line, diagnostic.column))
+                .size(11)
+                .color(theme().text_dim),
+        ]
+        .spacing(8)
+        .align_y(iced::Alignment::Center),
+    )
+    .style(tree_button_style)
+    .on_press(Message::ProblemClicked(
+        path.to_path_buf(),
+        diagnostic.line,
+        diagnostic.column,
+    ))
+    .padding(iced::Padding {
+        top: 3.0,
+        right: 8.0,
+        bottom: 3.0,
+        left: 18.0,
+    })
+    .width(Length::Fill)
+    .into()
+}
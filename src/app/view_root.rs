@@ -22,20 +22,31 @@ impl App {
             if self.terminal_open {
                 editor_col_items.push(self.view_terminal_panel());
             }
+            if self.problems_open {
+                editor_col_items.push(self.view_problems_panel());
+            }
+            if self.lsp_inspector.open {
+                editor_col_items.push(self.view_lsp_inspector());
+            }
+            if self.developer_panel_visible {
+                editor_col_items.push(self.view_lua_console_panel());
+            }
             if self.command_input.open {
                 editor_col_items.push(self.view_command_input_bar());
             }
             editor_col_items.push(status_bar);
 
-            let editor_container =
-                if self.active_tab.is_some() || self.pending_sensitive_open.is_some() {
-                    container(column(editor_col_items))
-                } else {
-                    self.view_welcome_screen()
-                }
-                .width(Length::Fill)
-                .height(Length::Fill)
-                .style(editor_container_style);
+            let editor_container = if self.active_tab.is_some()
+                || self.pending_sensitive_open.is_some()
+                || self.pending_quit.is_some()
+            {
+                container(column(editor_col_items))
+            } else {
+                self.view_welcome_screen()
+            }
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .style(editor_container_style);
 
             container(editor_container)
                 .padding(0)
@@ -44,7 +55,12 @@ impl App {
         };
 
         let base_content: Element<'_, Message> = if self.sidebar_visible {
-            let sidebar = view_sidebar(self.file_tree.as_ref(), self.sidebar_width);
+            let sidebar = view_sidebar(
+                self.file_tree.as_ref(),
+                self.view_outline(),
+                self.outline.expanded,
+                self.sidebar_width,
+            );
 
             let separator = container(text(""))
                 .width(Length::Fixed(1.0))
@@ -89,6 +105,22 @@ impl App {
                 .width(Length::Fill)
                 .height(Length::Fill);
             stack![wrapped, search_panel].into()
+        } else if self.rename.is_some() || self.location_results.is_some() {
+            let panel = if self.rename.is_some() {
+                self.view_rename_panel()
+            } else {
+                self.view_locations_panel()
+            };
+            let panel = container(panel)
+                .padding(iced::Padding {
+                    top: 20.0,
+                    right: 0.0,
+                    bottom: 0.0,
+                    left: 20.0,
+                })
+                .width(Length::Fill)
+                .height(Length::Fill);
+            stack![wrapped, panel].into()
         } else {
             wrapped.into()
         };
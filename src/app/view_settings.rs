@@ -3,7 +3,7 @@ use iced::widget::column;
 
 impl App {
     pub(super) fn view_command_input_bar(&self) -> Element<'_, Message> {
-        let input = text_input(":", &self.command_input.input)
+        let input = text_input("", &self.command_input.input)
             .id(self.command_input_id.clone())
             .on_input(Message::CommandInputChanged)
             .on_submit(Message::CommandInputSubmit)
@@ -12,12 +12,42 @@ impl App {
                 top: 10.0,
                 right: 14.0,
                 bottom: 10.0,
-                left: 14.0,
+                left: 4.0,
             })
             .style(search_input_style)
             .width(Length::Fill);
+        let prompt = text(self.command_input.prompt.to_string())
+            .size(14)
+            .font(iced::Font::MONOSPACE)
+            .color(theme().text_secondary);
+
+        let mut content = column![].width(Length::Fill);
+        if !self.command_input.output.is_empty() {
+            let lines = self.command_input.output.iter().map(|line| {
+                text(line.clone())
+                    .size(13)
+                    .font(iced::Font::MONOSPACE)
+                    .color(theme().text_secondary)
+                    .into()
+            });
+            content = content.push(
+                column(lines)
+                    .spacing(2)
+                    .padding(iced::Padding::from([8.0, 14.0])),
+            );
+        }
+        content = content.push(
+            row![
+                container(prompt).padding(iced::Padding {
+                    left: 14.0,
+                    ..iced::Padding::ZERO
+                }),
+                input
+            ]
+            .align_y(iced::Alignment::Center),
+        );
 
-        container(input)
+        container(content)
             .width(Length::Fill)
             .style(|_theme| container::Style {
                 background: Some(Background::Color(theme().bg_secondary)),
@@ -38,6 +68,7 @@ impl App {
             ("general", "General"),
             ("preferences", "Preferences"),
             ("wakatime", "WakaTime"),
+            ("plugins", "Plugins"),
             ("developer", "Developer"),
         ];
 
@@ -129,6 +160,7 @@ impl App {
             "general" => self.view_settings_general(),
             "preferences" => self.view_settings_preferences(),
             "wakatime" => self.view_settings_wakatime(),
+            "plugins" => self.view_settings_plugins(),
             "developer" => self.view_settings_developer(),
             _ => self.view_settings_general(),
         };
@@ -382,6 +414,102 @@ impl App {
         .spacing(16)
         .align_y(iced::Alignment::Center);
 
+        let vim_label = if self.editor_preferences.vim_mode {
+            "Enabled"
+        } else {
+            "Disabled"
+        };
+        let vim_row = row![
+            column![
+                text("Vim Mode").size(13).color(theme().text_muted),
+                text("Modal editing with vim keys")
+                    .size(11)
+                    .color(theme().text_dim),
+            ]
+            .spacing(2)
+            .width(Length::FillPortion(2)),
+            button(text(vim_label).size(12).color(theme().text_primary))
+                .on_press(Message::ToggleVimMode)
+                .style(|_theme, _status| button::Style {
+                    background: Some(Background::Color(theme().bg_secondary)),
+                    border: iced::Border {
+                        color: Color::from_rgba(1.0, 1.0, 1.0, 0.08),
+                        width: 1.0,
+                        radius: 4.0.into(),
+                    },
+                    text_color: theme().text_primary,
+                    ..Default::default()
+                })
+                .padding(iced::Padding {
+                    top: 6.0,
+                    right: 16.0,
+                    bottom: 6.0,
+                    left: 16.0
+                }),
+        ]
+        .spacing(16)
+        .align_y(iced::Alignment::Center);
+
+        let vim_escape_row = row![
+            column![
+                text("Vim Escape Keys").size(13).color(theme().text_muted),
+                text("Keys that leave insert mode when typed quickly, such as jk")
+                    .size(11)
+                    .color(theme().text_dim),
+            ]
+            .spacing(2)
+            .width(Length::FillPortion(2)),
+            text_input("jk", &self.editor_preferences.vim_escape)
+                .on_input(Message::SettingsVimEscapeChanged)
+                .size(13)
+                .padding(iced::Padding {
+                    top: 8.0,
+                    right: 12.0,
+                    bottom: 8.0,
+                    left: 12.0
+                })
+                .style(search_input_style)
+                .width(Length::Fixed(80.0)),
+        ]
+        .spacing(16)
+        .align_y(iced::Alignment::Center);
+
+        let inlay_hints_label = if self.editor_preferences.inlay_hints {
+            "Enabled"
+        } else {
+            "Disabled"
+        };
+        let inlay_hints_row = row![
+            column![
+                text("Inlay Hints").size(13).color(theme().text_muted),
+                text("Types and parameter names from the language server, after each line")
+                    .size(11)
+                    .color(theme().text_dim),
+            ]
+            .spacing(2)
+            .width(Length::FillPortion(2)),
+            button(text(inlay_hints_label).size(12).color(theme().text_primary))
+                .on_press(Message::SettingsToggleInlayHints)
+                .style(|_theme, _status| button::Style {
+                    background: Some(Background::Color(theme().bg_secondary)),
+                    border: iced::Border {
+                        color: Color::from_rgba(1.0, 1.0, 1.0, 0.08),
+                        width: 1.0,
+                        radius: 4.0.into(),
+                    },
+                    text_color: theme().text_primary,
+                    ..Default::default()
+                })
+                .padding(iced::Padding {
+                    top: 6.0,
+                    right: 16.0,
+                    bottom: 6.0,
+                    left: 16.0
+                }),
+        ]
+        .spacing(16)
+        .align_y(iced::Alignment::Center);
+
         let all_themes: Vec<&str> = {
             let mut v: Vec<&str> = BUILTIN_THEMES.to_vec();
             v.push("Custom (theme.lua)");
@@ -554,6 +682,27 @@ impl App {
                     ..Default::default()
                 }
             ),
+            vim_row,
+            container(Space::new().width(Length::Fill).height(Length::Fixed(1.0))).style(
+                |_theme| container::Style {
+                    background: Some(Background::Color(Color::from_rgba(1.0, 1.0, 1.0, 0.03))),
+                    ..Default::default()
+                }
+            ),
+            vim_escape_row,
+            container(Space::new().width(Length::Fill).height(Length::Fixed(1.0))).style(
+                |_theme| container::Style {
+                    background: Some(Background::Color(Color::from_rgba(1.0, 1.0, 1.0, 0.03))),
+                    ..Default::default()
+                }
+            ),
+            inlay_hints_row,
+            container(Space::new().width(Length::Fill).height(Length::Fixed(1.0))).style(
+                |_theme| container::Style {
+                    background: Some(Background::Color(Color::from_rgba(1.0, 1.0, 1.0, 0.03))),
+                    ..Default::default()
+                }
+            ),
             theme_row,
             container(Space::new().width(Length::Fill).height(Length::Fixed(1.0))).style(
                 |_theme| container::Style {
@@ -569,6 +718,94 @@ impl App {
         .into()
     }
 
+    pub(super) fn view_settings_plugins(&self) -> Element<'_, Message> {
+        use iced::widget::Space;
+
+        let heading = text("Plugins").size(18).color(theme().text_primary);
+        let desc = text(format!(
+            "Folders in {} with an init.lua.",
+            crate::scripting::plugins::plugins_dir().display()
+        ))
+        .size(12)
+        .color(theme().text_dim);
+
+        let separator = container(Space::new().width(Length::Fill).height(Length::Fixed(1.0)))
+            .style(|_theme| container::Style {
+                background: Some(Background::Color(Color::from_rgba(1.0, 1.0, 1.0, 0.06))),
+                ..Default::default()
+            });
+
+        let mut items: Vec<Element<'_, Message>> =
+            vec![heading.into(), desc.into(), separator.into()];
+
+        if self.plugins.is_empty() {
+            items.push(
+                text("No plugins installed.")
+                    .size(13)
+                    .color(theme().text_muted)
+                    .into(),
+            );
+        }
+
+        for plugin in &self.plugins {
+            let (status, status_color) = match (&plugin.error, plugin.enabled) {
+                (Some(error), _) => (
+                    // The first line carries the message and location; the rest is traceback.
+                    error.lines().next().unwrap_or_default().to_string(),
+                    Color::from_rgba(0.9, 0.5, 0.35, 0.9),
+                ),
+                (None, true) => ("Loaded".to_string(), theme().text_dim),
+                (None, false) => ("Disabled".to_string(), theme().text_dim),
+            };
+
+            let toggle_label = if plugin.enabled {
+                "Enabled"
+            } else {
+                "Disabled"
+            };
+            let enabled = plugin.enabled;
+            let toggle_btn = button(text(toggle_label).size(12).color(theme().text_primary))
+                .on_press(Message::SettingsTogglePlugin(plugin.name.clone()))
+                .style(move |_theme, _status| button::Style {
+                    background: Some(Background::Color(if enabled {
+                        Color::from_rgba(0.2, 0.8, 0.2, 0.3)
+                    } else {
+                        theme().bg_secondary
+                    })),
+                    border: iced::Border {
+                        color: Color::from_rgba(1.0, 1.0, 1.0, 0.08),
+                        width: 1.0,
+                        radius: 4.0.into(),
+                    },
+                    text_color: theme().text_primary,
+                    ..Default::default()
+                })
+                .padding(iced::Padding {
+                    top: 6.0,
+                    right: 16.0,
+                    bottom: 6.0,
+                    left: 16.0,
+                });
+
+            items.push(
+                row![
+                    column![
+                        text(plugin.name.clone()).size(13).color(theme().text_muted),
+                        text(status).size(11).color(status_color),
+                    ]
+                    .spacing(2)
+                    .width(Length::FillPortion(2)),
+                    toggle_btn,
+                ]
+                .spacing(16)
+                .align_y(iced::Alignment::Center)
+                .into(),
+            );
+        }
+
+        column(items).spacing(12).width(Length::Fill).into()
+    }
+
     pub(super) fn view_settings_developer(&self) -> Element<'_, Message> {
         use iced::widget::Space;
 
@@ -674,20 +911,20 @@ impl App {
         .align_y(iced::Alignment::Center);
 
         // ── LSP server availability ──────────────────────────────────────────
-        let server_status = crate::lsp_setup::lsp_server_status();
+        let server_status = crate::lsp_setup::lsp_server_status(self.lsp.registry());
 
         let server_status_heading = text("LSP Servers").size(13).color(theme().text_muted);
 
         let server_status_desc = text(
-            "Language servers found on PATH. Install missing servers to enable LSP for those languages."
+            "Language servers from lsp.lua and where they were found. Install missing servers to enable LSP for those languages."
         )
         .size(11)
         .color(theme().text_dim);
 
         let server_rows: Vec<Element<'_, Message>> = server_status
-            .iter()
+            .into_iter()
             .map(|(key, path_opt)| {
-                let (icon, icon_color, status_text) = match path_opt {
+                let (icon, icon_color, status_text) = match &path_opt {
                     Some(p) => (
                         "●",
                         Color::from_rgb(0.30, 0.85, 0.50),
@@ -704,7 +941,7 @@ impl App {
                         .size(10)
                         .color(icon_color)
                         .width(Length::Fixed(14.0)),
-                    text(*key)
+                    text(key)
                         .size(12)
                         .color(theme().text_muted)
                         .width(Length::Fixed(240.0)),
@@ -1,700 +1,2600 @@
+//! Modal editing. Normal and visual mode take keyboard focus away from the
+//! `CodeEditor`, so keys arrive as [`VimKey`]s from the keyboard subscription
+//! instead of being typed; insert mode hands focus back.
+
 use super::*;
+use crate::features::editor_buffer::{EditorBuffer, TextEdit};
+use crate::message::{VimKey, VimPaste};
+use crate::scripting::keymap::{keymap_mut, KeymapMode};
+use ex::{ExCommand, GlobalAction, LineRange};
+use grammar::{
+    leading_count, operator_pending, parse, Action, Motion, Parse, Target, TextObject, VimCommand,
+    VimOperator,
+};
 use iced_code_editor::{ArrowDirection, Message as EditorMessage};
+use jumps::Jump;
+use motion::{change_word_end, motion_target, text_object_range, MotionTarget, MotionText};
+use registers::{is_register_name, RegisterKind, VimRegister};
+use search::{byte_offset, byte_position};
+
+pub use ex::VimSubstitute;
+pub use jumps::JumpList;
+pub use marks::VimMarks;
+pub use recording::VimInput;
+pub use registers::VimRegisters;
+pub(super) use search::word_at;
+pub use search::VimSearch;
+
+mod ex;
+mod grammar;
+mod jumps;
+mod marks;
+mod motion;
+mod recording;
+mod registers;
+mod search;
+
+/// How quickly the keys of the insert-mode escape sequence must follow each
+/// other, as vim's default `timeoutlen`.
+const ESCAPE_SEQUENCE_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// How deeply macros may play other macros, which stops a macro that plays
+/// itself.
+const MAX_REPLAY_DEPTH: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VimMode {
+    Normal,
+    Insert,
+    Visual,
+    VisualLine,
+    VisualBlock,
+}
+
+impl VimMode {
+    pub fn is_visual(self) -> bool {
+        matches!(
+            self,
+            VimMode::Visual | VimMode::VisualLine | VimMode::VisualBlock
+        )
+    }
+
+    /// The name the status bar shows.
+    pub fn label(self) -> &'static str {
+        match self {
+            VimMode::Normal => "NORMAL",
+            VimMode::Insert => "INSERT",
+            VimMode::Visual => "VISUAL",
+            VimMode::VisualLine => "V-LINE",
+            VimMode::VisualBlock => "V-BLOCK",
+        }
+    }
+
+    fn keymap_mode(self) -> KeymapMode {
+        match self {
+            VimMode::Normal => KeymapMode::Normal,
+            VimMode::Insert => KeymapMode::Insert,
+            VimMode::Visual | VimMode::VisualLine | VimMode::VisualBlock => KeymapMode::Visual,
+        }
+    }
+}
+
+/// The cursor drawn outside insert mode, where the editor shows none. Insert
+/// mode keeps the editor's own bar caret.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VimCursorShape {
+    Block,
+    /// The lower half of a block, while an operator waits for its motion.
+    HalfBlock,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct VimFindState {
+    pub kind: VimFindKind,
+    pub needle: char,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VimFindKind {
+    ForwardTo,
+    ForwardTill,
+    BackwardTo,
+    BackwardTill,
+}
+
+impl VimFindKind {
+    /// The same find in the other direction, for `,`.
+    fn reversed(self) -> Self {
+        match self {
+            VimFindKind::ForwardTo => VimFindKind::BackwardTo,
+            VimFindKind::ForwardTill => VimFindKind::BackwardTill,
+            VimFindKind::BackwardTo => VimFindKind::ForwardTo,
+            VimFindKind::BackwardTill => VimFindKind::ForwardTill,
+        }
+    }
+}
+
+/// Text an operator acts on, in 0-based `(line, column)` positions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VimRange {
+    /// From `start` up to, not including, `end`.
+    Chars {
+        start: (usize, usize),
+        end: (usize, usize),
+    },
+    /// Whole lines `start..=end`.
+    Lines { start: usize, end: usize },
+    /// Columns `left..right` of lines `top..=bottom`.
+    Block {
+        top: usize,
+        bottom: usize,
+        left: usize,
+        right: usize,
+    },
+}
+
+/// The cursor (1-based) and count when `/` or `?` was typed. Escape goes
+/// back there, and the search counts from there as it is typed.
+#[derive(Debug, Clone, Copy)]
+pub struct VimSearchStart {
+    cursor: (usize, usize),
+    count: Option<usize>,
+}
+
+/// A `:s///c` waiting for an answer in the command bar.
+#[derive(Debug, Clone)]
+pub struct VimConfirm {
+    regex: regex::Regex,
+    replacement: String,
+    global: bool,
+    /// The 0-based line being searched, and the last line of the range.
+    line: usize,
+    last: usize,
+    /// Byte offset in `line` to search from.
+    from: usize,
+}
+
+/// A column insert started with `I`, `A` or `c` in visual-block mode. Text
+/// typed on the first line is copied to the others when insert mode ends.
+#[derive(Debug, Clone)]
+pub struct VimBlockInsert {
+    top: usize,
+    bottom: usize,
+    col: usize,
+    /// Whether lines too short to reach `col` are padded (`A`) rather than
+    /// skipped (`I`).
+    pad: bool,
+    line_before: String,
+}
 
 impl App {
     pub(super) fn vim_refresh_cursor_style(&mut self) {
-        if self.terminal_open {
-            if let Some(idx) = self.active_tab {
-                if let Some(tab) = self.tabs.get_mut(idx) {
-                    if let TabKind::Editor {
-                        ref mut code_editor,
-                        ..
-                    } = tab.kind
-                    {
-                        code_editor.lose_focus();
-                    }
+        let typing =
+            self.editor_pane_focused() && (!self.vim_enabled || self.vim_mode == VimMode::Insert);
+        if let Some(tab) = self.active_tab.and_then(|idx| self.tabs.get_mut(idx)) {
+            if let TabKind::Editor { code_editor, .. } = &mut tab.kind {
+                if typing {
+                    code_editor.request_focus();
+                } else {
+                    code_editor.lose_focus();
                 }
             }
-            return;
+        }
+    }
+
+    pub(super) fn toggle_vim_mode(&mut self) {
+        self.vim_enabled = !self.vim_enabled;
+        self.editor_preferences.vim_mode = self.vim_enabled;
+        self.vim_block_insert = None;
+        self.vim_set_mode(if self.vim_enabled {
+            VimMode::Normal
+        } else {
+            VimMode::Insert
+        });
+    }
+
+    /// Keeps the keymap's mode and the editor's focus in step with vim.
+    /// Runs after every update, since clicks, tab switches and overlays all
+    /// move focus around.
+    pub(super) fn vim_sync_after_update(&mut self) {
+        let mode = self.vim_enabled.then(|| {
+            if self.vim_context_active() {
+                self.vim_mode.keymap_mode()
+            } else {
+                KeymapMode::Insert
+            }
+        });
+        keymap_mut().set_mode(mode);
+
+        if self.vim_enabled && self.vim_mode != VimMode::Insert {
+            if let Some(tab) = self.active_tab.and_then(|idx| self.tabs.get_mut(idx)) {
+                if let TabKind::Editor { code_editor, .. } = &mut tab.kind {
+                    code_editor.lose_focus();
+                }
+            }
+        }
+    }
+
+    /// The status bar's mode badge, with the keys of a half-typed command
+    /// such as `2d` after it.
+    pub(super) fn vim_status_badge(&self) -> Option<String> {
+        if !self.vim_enabled {
+            return None;
+        }
+        let mut badge = self.vim_mode.label().to_string();
+        if !self.vim_pending.is_empty() {
+            badge = format!("{badge} {}", self.vim_pending);
+        }
+        if let Some((name, _)) = &self.vim_recording {
+            badge = format!("{badge} recording @{name}");
+        }
+        Some(badge)
+    }
+
+    pub(super) fn vim_cursor_shape(&self) -> Option<VimCursorShape> {
+        if !self.vim_enabled || self.vim_mode == VimMode::Insert || !self.vim_context_active() {
+            return None;
+        }
+        if self.vim_mode == VimMode::Normal && operator_pending(&self.vim_pending) {
+            Some(VimCursorShape::HalfBlock)
+        } else {
+            Some(VimCursorShape::Block)
+        }
+    }
+
+    /// Watches insert-mode typing for the escape sequence from the
+    /// preferences, such as `jk`. Its last key is not typed: the keys before
+    /// it are erased and insert mode ends.
+    pub(super) fn vim_escape_sequence(
+        &mut self,
+        event: &EditorMessage,
+    ) -> Option<iced::Task<Message>> {
+        let ch = match event {
+            EditorMessage::CharacterInput(ch)
+                if !self.modifier_state.command() && !self.modifier_state.control() =>
+            {
+                *ch
+            }
+            EditorMessage::Tick
+            | EditorMessage::MouseHover(_)
+            | EditorMessage::Scrolled(_)
+            | EditorMessage::HorizontalScrolled(_) => return None,
+            _ => {
+                self.vim_escape_typed = None;
+                return None;
+            }
+        };
+        let sequence: Vec<char> = self.editor_preferences.vim_escape.chars().collect();
+        if !self.vim_enabled || self.vim_mode != VimMode::Insert || sequence.is_empty() {
+            self.vim_escape_typed = None;
+            return None;
+        }
+
+        let typed = self
+            .vim_escape_typed
+            .filter(|(_, at)| at.elapsed() < ESCAPE_SEQUENCE_TIMEOUT)
+            .map_or(0, |(typed, _)| typed);
+        let typed = escape_progress(&sequence, typed, ch);
+        if typed < sequence.len() {
+            self.vim_escape_typed = (typed > 0).then(|| (typed, Instant::now()));
+            return None;
+        }
+
+        self.vim_escape_typed = None;
+        let erase: Vec<_> = (1..typed)
+            .map(|_| self.update(Message::CodeEditorEvent(EditorMessage::Backspace)))
+            .collect();
+        self.vim_escape();
+        self.vim_refresh_cursor_style();
+        Some(iced::Task::batch(erase))
+    }
+
+    /// A mouse selection replaces the visual one, so it ends visual mode.
+    pub(super) fn vim_editor_event(&mut self, event: &EditorMessage) {
+        if self.vim_mode.is_visual()
+            && matches!(
+                event,
+                EditorMessage::MouseClick(_) | EditorMessage::MouseDrag(_)
+            )
+        {
+            self.vim_mode = VimMode::Normal;
+            self.vim_pending.clear();
         }
 
-        // With iced-code-editor, vim normal mode removes focus from
-        // the canvas so the user cannot type. Insert mode restores it.
+        // Text typed in insert mode is recorded as the keys that type it.
+        if self.vim_enabled && self.vim_mode == VimMode::Insert {
+            let keys: Vec<VimKey> = match event {
+                EditorMessage::CharacterInput(_)
+                    if self.modifier_state.command() || self.modifier_state.control() =>
+                {
+                    Vec::new()
+                }
+                EditorMessage::CharacterInput(ch) => vec![VimKey::Char(*ch)],
+                EditorMessage::Enter => vec![VimKey::Enter],
+                EditorMessage::Backspace => vec![VimKey::Backspace],
+                EditorMessage::Tab => vec![VimKey::Ctrl('i')],
+                EditorMessage::ArrowKey(direction, false) => vec![match direction {
+                    ArrowDirection::Up => VimKey::Up,
+                    ArrowDirection::Down => VimKey::Down,
+                    ArrowDirection::Left => VimKey::Left,
+                    ArrowDirection::Right => VimKey::Right,
+                }],
+                EditorMessage::Paste(text) => recording::decode(&text.replace('<', "<lt>"))
+                    .into_iter()
+                    .filter_map(|input| match input {
+                        VimInput::Key(key) => Some(key),
+                        VimInput::Escape => None,
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+            for key in keys {
+                self.vim_record(VimInput::Key(key));
+            }
+        }
+    }
+
+    pub(super) fn handle_vim_key(&mut self, key: VimKey) -> iced::Task<Message> {
+        if !self.vim_enabled || self.vim_mode == VimMode::Insert || !self.vim_context_active() {
+            return iced::Task::none();
+        }
+
+        // `q` ends a recording rather than starting a command.
+        if key == VimKey::Char('q') && self.vim_pending.is_empty() && self.vim_replay_depth == 0 {
+            if let Some((name, keys)) = self.vim_recording.take() {
+                self.vim_registers.record(name, recording::encode(&keys));
+                return iced::Task::none();
+            }
+        }
+        if self.vim_pending.is_empty() && self.vim_mode == VimMode::Normal && !self.vim_repeating {
+            self.vim_change.clear();
+        }
+        self.vim_record(VimInput::Key(key));
+
+        let task = match key {
+            VimKey::Char(ch) => self.vim_handle_char(ch),
+            VimKey::Ctrl(ch) => self.vim_handle_ctrl(ch),
+            VimKey::Enter => self.vim_handle_char('+'),
+            VimKey::Backspace | VimKey::Left => self.vim_handle_char('h'),
+            VimKey::Right => self.vim_handle_char('l'),
+            VimKey::Up => self.vim_handle_char('k'),
+            VimKey::Down => self.vim_handle_char('j'),
+        };
+
+        if self.vim_mode.is_visual() {
+            self.vim_paint_selection();
+        }
+        task
+    }
+
+    /// Escape leaves insert and visual mode, or drops a half-typed command.
+    pub(super) fn vim_escape(&mut self) {
+        self.vim_record(VimInput::Escape);
         match self.vim_mode {
+            VimMode::Insert => {
+                self.sync_cursor_from_active_editor();
+                let (line, col) = match self.vim_block_insert.take() {
+                    Some(insert) => {
+                        let corner = (insert.top + 1, insert.col + 1);
+                        self.vim_finish_block_insert(insert);
+                        corner
+                    }
+                    // Like vim, step back onto the last inserted character.
+                    None => (self.cursor_line, self.cursor_col.saturating_sub(1)),
+                };
+                self.vim_set_mode(VimMode::Normal);
+                let _ = self.vim_goto_position(line, col);
+                self.vim_change_done();
+            }
             VimMode::Normal => {
-                if let Some(idx) = self.active_tab {
-                    if let Some(tab) = self.tabs.get_mut(idx) {
-                        if let TabKind::Editor {
-                            ref mut code_editor,
-                            ..
-                        } = tab.kind
+                self.vim_pending.clear();
+            }
+            _ => self.vim_set_mode(VimMode::Normal),
+        }
+    }
+
+    pub(super) fn vim_context_active(&self) -> bool {
+        self.active_tab
+            .and_then(|idx| self.tabs.get(idx))
+            .is_some_and(|tab| matches!(tab.kind, TabKind::Editor { .. }))
+            && self.editor_pane_focused()
+            && !self.settings_open
+            && !self.command_palette.open
+            && !self.fuzzy_finder.open
+            && !self.file_finder_visible
+            && !self.search_visible
+            && !self.command_input.open
+            && !self.find_replace.open
+            && self.pending_sensitive_open.is_none()
+            && self.pending_quit.is_none()
+    }
+
+    fn editor_pane_focused(&self) -> bool {
+        match self.focused_pane {
+            FocusPane::Editor => true,
+            FocusPane::Terminal => !self.terminal_open,
+            FocusPane::Console => !self.developer_panel_visible,
+        }
+    }
+
+    fn vim_set_mode(&mut self, mode: VimMode) {
+        let leaving_visual = self.vim_mode.is_visual() && !mode.is_visual();
+        if leaving_visual {
+            // `'<` and `'>` remember the selection, for `:'<,'>`.
+            let anchor = self.vim_visual_anchor;
+            let cursor = (self.cursor_line, self.cursor_col);
+            if let Some(path) = self.vim_path() {
+                let (start, end) = (anchor.min(cursor), anchor.max(cursor));
+                self.vim_marks.set(&path, '<', (start.0 - 1, start.1 - 1));
+                self.vim_marks.set(&path, '>', (end.0 - 1, end.1 - 1));
+            }
+        }
+        self.vim_mode = mode;
+        self.vim_pending.clear();
+
+        if let Some(tab) = self.active_tab.and_then(|idx| self.tabs.get_mut(idx)) {
+            if let TabKind::Editor { code_editor, .. } = &mut tab.kind {
+                if leaving_visual || mode == VimMode::Insert {
+                    // Moving the cursor drops the selection, which may also
+                    // be a search match.
+                    let (line, col) = (self.cursor_line - 1, self.cursor_col - 1);
+                    let _ = code_editor.update(&EditorMessage::GotoPosition(line, col));
+                }
+                if mode == VimMode::Insert {
+                    let _ = code_editor.update(&EditorMessage::CanvasFocusGained);
+                }
+            }
+        }
+        self.vim_refresh_cursor_style();
+    }
+
+    /// Adds a key to the pending command and runs the command once it is
+    /// complete.
+    fn vim_handle_char(&mut self, ch: char) -> iced::Task<Message> {
+        self.vim_pending.push(ch);
+        match parse(&self.vim_pending, self.vim_mode.is_visual()) {
+            Parse::Pending => iced::Task::none(),
+            Parse::Invalid => {
+                self.vim_pending.clear();
+                iced::Task::none()
+            }
+            Parse::Done(command) => {
+                self.vim_pending.clear();
+                self.vim_execute(command)
+            }
+        }
+    }
+
+    fn vim_execute(&mut self, command: VimCommand) -> iced::Task<Message> {
+        let VimCommand {
+            count,
+            register,
+            action,
+        } = command;
+        let changes_text = action.changes_text(self.vim_mode.is_visual());
+        let task = self.vim_dispatch(action, count, register);
+        if changes_text {
+            self.vim_change_done();
+        }
+        task
+    }
+
+    fn vim_dispatch(
+        &mut self,
+        action: Action,
+        count: Option<usize>,
+        register: Option<char>,
+    ) -> iced::Task<Message> {
+        match action {
+            Action::Move(motion) => self.vim_move(motion, count),
+            Action::Operate(operator, target) => {
+                self.vim_operate(operator, target, count, register)
+            }
+            Action::OperateVisual(operator) => {
+                let range = self.vim_visual_range();
+                self.vim_set_mode(VimMode::Normal);
+                self.vim_apply_operator(operator, range, count.unwrap_or(1), register)
+            }
+            Action::Select(object) => self.vim_select_object(object, count),
+            Action::Record(name) => {
+                if self.vim_replay_depth == 0 {
+                    self.vim_recording = Some((name, Vec::new()));
+                }
+                iced::Task::none()
+            }
+            Action::Replay(name) => self.vim_play_macro(name, count.unwrap_or(1)),
+            Action::SetMark(name) => {
+                self.vim_set_mark(name);
+                iced::Task::none()
+            }
+            Action::Key(key @ ('p' | 'P')) => {
+                self.vim_put(register, key == 'P', count.unwrap_or(1))
+            }
+            Action::Key(key) if self.vim_mode.is_visual() => self.vim_visual_key(key, register),
+            Action::Key(key) => self.vim_normal_key(key, count),
+        }
+    }
+
+    /// Normal-mode keys that are neither motions nor operators.
+    fn vim_normal_key(&mut self, key: char, count: Option<usize>) -> iced::Task<Message> {
+        match key {
+            'i' => {
+                self.vim_set_mode(VimMode::Insert);
+                iced::Task::none()
+            }
+            'a' => {
+                self.vim_set_mode(VimMode::Insert);
+                self.vim_goto_position(self.cursor_line, self.cursor_col + 1)
+            }
+            'A' => {
+                self.vim_set_mode(VimMode::Insert);
+                self.vim_goto_position(self.cursor_line, usize::MAX)
+            }
+            'I' => {
+                let col = self.vim_buffer().map_or(0, |buffer| {
+                    first_nonblank(&line_text(&buffer, self.cursor_line - 1))
+                });
+                self.vim_set_mode(VimMode::Insert);
+                self.vim_goto_position(self.cursor_line, col + 1)
+            }
+            'o' => self.vim_open_line(false),
+            'O' => self.vim_open_line(true),
+            'v' => self.vim_toggle_visual(VimMode::Visual),
+            'V' => self.vim_toggle_visual(VimMode::VisualLine),
+            ':' => iced::Task::done(Message::ToggleCommandInput),
+            '/' | '?' => self.vim_start_search(key, count),
+            '.' => self.vim_repeat_change(count),
+            'u' => self.vim_undo(false, count.unwrap_or(1)),
+            '~' => {
+                // Toggles the case under the cursor and moves past it.
+                let (line, col) = (self.cursor_line - 1, self.cursor_col - 1);
+                let len = self.vim_buffer().map_or(0, |buffer| buffer.line_len(line));
+                if len == 0 {
+                    return iced::Task::none();
+                }
+                let end = (col + count.unwrap_or(1)).min(len);
+                let range = VimRange::Chars {
+                    start: (line, col),
+                    end: (line, end),
+                };
+                let task = self.vim_apply_operator(VimOperator::ToggleCase, range, 1, None);
+                iced::Task::batch([task, self.vim_goto_position(line + 1, end + 1)])
+            }
+            // Screen-relative motions and scrolling (`H`, `M`, `L`, `z`) are
+            // not supported.
+            _ => iced::Task::none(),
+        }
+    }
+
+    fn vim_handle_ctrl(&mut self, ch: char) -> iced::Task<Message> {
+        let count = leading_count(&self.vim_pending);
+        self.vim_pending.clear();
+        match ch {
+            'v' => self.vim_toggle_visual(VimMode::VisualBlock),
+            'r' => self.vim_undo(true, count.unwrap_or(1)),
+            'o' => self.vim_jump_back(count.unwrap_or(1)),
+            'i' => self.vim_jump_forward(count.unwrap_or(1)),
+            'f' | 'b' | 'd' | 'u' => self.vim_apply_ctrl_motion(ch, count.unwrap_or(1)),
+            _ => iced::Task::none(),
+        }
+    }
+
+    /// Send a message to the active tab's CodeEditor and return the resulting Task.
+    fn vim_send_editor_msg(&mut self, msg: EditorMessage) -> iced::Task<Message> {
+        if let Some(tab) = self.active_tab.and_then(|idx| self.tabs.get_mut(idx)) {
+            if let TabKind::Editor { code_editor, .. } = &mut tab.kind {
+                return code_editor.update(&msg).map(Message::CodeEditorEvent);
+            }
+        }
+        iced::Task::none()
+    }
+
+    fn vim_buffer(&self) -> Option<EditorBuffer> {
+        let tab = self.tabs.get(self.active_tab?)?;
+        match &tab.kind {
+            TabKind::Editor { buffer, .. } => Some(buffer.clone()),
+            TabKind::Preview { .. } => None,
+        }
+    }
+
+    fn vim_move_lines(&mut self, delta: isize) -> iced::Task<Message> {
+        let line = self.cursor_line.saturating_add_signed(delta).max(1);
+        self.vim_goto_position(line, self.cursor_col)
+    }
+
+    /// Number of lines the editor shows at once.
+    fn vim_page_lines(&self) -> usize {
+        self.active_tab
+            .and_then(|idx| self.tabs.get(idx))
+            .and_then(|tab| match &tab.kind {
+                TabKind::Editor { code_editor, .. } if code_editor.line_height() > 0.0 => {
+                    Some((code_editor.viewport_height() / code_editor.line_height()) as usize)
+                }
+                _ => None,
+            })
+            .unwrap_or(40)
+            .max(2)
+    }
+
+    fn vim_apply_ctrl_motion(&mut self, ch: char, count: usize) -> iced::Task<Message> {
+        let count = count as isize;
+        let page = self.vim_page_lines() as isize;
+        let delta = match ch {
+            'f' => page * count,
+            'b' => -page * count,
+            'd' => page / 2,
+            'u' => -page / 2,
+            _ => return iced::Task::none(),
+        };
+        self.vim_move_lines(delta)
+    }
+
+    // --- Motions --- //
+
+    fn vim_content_text(&self) -> Option<String> {
+        self.vim_buffer().map(|buffer| buffer.text())
+    }
+
+    /// The cursor as a 0-based position.
+    fn vim_cursor(&self) -> (usize, usize) {
+        (self.cursor_line - 1, self.cursor_col - 1)
+    }
+
+    fn vim_move(&mut self, motion: Motion, count: Option<usize>) -> iced::Task<Message> {
+        if let Motion::Mark { name, exact } = motion {
+            // A file mark can take the cursor to another tab.
+            let current = self.vim_path();
+            let mark = current
+                .as_ref()
+                .and_then(|path| self.vim_marks.get(path, name));
+            if let Some((path, (line, col))) =
+                mark.filter(|(path, _)| Some(path) != current.as_ref())
+            {
+                self.vim_push_jump();
+                return self.go_to_location(path, (line, if exact { col } else { 0 }));
+            }
+        }
+        let Some(text) = self.vim_content_text() else {
+            return iced::Task::none();
+        };
+        let Some(target) = self.vim_motion_target(&MotionText::new(&text), motion, count) else {
+            return iced::Task::none();
+        };
+        if motion.is_jump() {
+            self.vim_push_jump();
+        }
+        match (motion, &self.vim_search) {
+            (Motion::SearchNext { .. } | Motion::SearchWord { .. }, Some(search)) => {
+                let len = search.pattern.chars().count();
+                self.vim_show_match(target.pos, len)
+            }
+            _ => self.vim_goto_position(target.pos.0 + 1, target.pos.1 + 1),
+        }
+    }
+
+    /// Resolves `;` and `,` against the last `f`/`t`, and remembers new ones.
+    /// Marks and searches are resolved here too.
+    fn vim_motion_target(
+        &mut self,
+        text: &MotionText,
+        motion: Motion,
+        count: Option<usize>,
+    ) -> Option<MotionTarget> {
+        let motion = match motion {
+            Motion::RepeatFind { reverse } => {
+                let last = self.vim_last_find?;
+                let kind = if reverse {
+                    last.kind.reversed()
+                } else {
+                    last.kind
+                };
+                Motion::Find(kind, last.needle)
+            }
+            Motion::Find(kind, needle) => {
+                self.vim_last_find = Some(VimFindState { kind, needle });
+                motion
+            }
+            Motion::Mark { name, exact } => {
+                let current = self.vim_path()?;
+                let (path, (line, col)) = self.vim_marks.get(&current, name)?;
+                if path != current {
+                    return None;
+                }
+                let line = line.min(text.last_line());
+                return Some(MotionTarget {
+                    pos: if exact {
+                        (line, col.min(text.line_len(line)))
+                    } else {
+                        (line, text.first_nonblank(line))
+                    },
+                    linewise: !exact,
+                    inclusive: false,
+                });
+            }
+            Motion::SearchWord { backward } => {
+                let (line, col) = self.vim_cursor();
+                let line_text = text.as_str().split('\n').nth(line).unwrap_or("");
+                let (start, word) = word_at(line_text, col)?;
+                self.vim_search = Some(VimSearch {
+                    pattern: word,
+                    backward,
+                    whole_word: true,
+                });
+                // Counting from the start of the word skips the word itself.
+                let search = self.vim_search.as_ref()?;
+                return search_target(search, text.as_str(), (line, start), false, count);
+            }
+            Motion::SearchNext { reverse } => {
+                let search = self.vim_search.as_ref()?;
+                return search_target(search, text.as_str(), self.vim_cursor(), reverse, count);
+            }
+            _ => motion,
+        };
+        motion_target(text, self.vim_cursor(), motion, count)
+    }
+
+    /// Runs an operator typed in normal mode on the text its target covers.
+    fn vim_operate(
+        &mut self,
+        operator: VimOperator,
+        target: Target,
+        count: Option<usize>,
+        register: Option<char>,
+    ) -> iced::Task<Message> {
+        let Some(text) = self.vim_content_text() else {
+            return iced::Task::none();
+        };
+        let text = MotionText::new(&text);
+        let cursor = self.vim_cursor();
+
+        let range = match target {
+            Target::Lines => Some(VimRange::Lines {
+                start: cursor.0,
+                end: cursor.0 + count.unwrap_or(1).max(1) - 1,
+            }),
+            Target::Object(object) => text_object_range(&text, cursor, object, count),
+            Target::Motion(Motion::WordForward(big))
+                if operator == VimOperator::Change
+                    && text.char_at(cursor).is_some_and(|c| !c.is_whitespace()) =>
+            {
+                // `cw` changes to the end of the word, like `ce`.
+                let target = MotionTarget {
+                    pos: change_word_end(&text, cursor, count, big),
+                    linewise: false,
+                    inclusive: true,
+                };
+                Some(motion_range(&text, cursor, target))
+            }
+            Target::Motion(motion) => {
+                self.vim_motion_target(&text, motion, count)
+                    .map(|mut target| {
+                        let word = matches!(motion, Motion::WordForward(_));
+                        if word
+                            && target.pos.0 > cursor.0
+                            && target.pos.1 <= text.first_nonblank(target.pos.0)
                         {
-                            code_editor.lose_focus();
+                            // `dw` on a line's last word stops at the line break.
+                            let line = target.pos.0 - 1;
+                            target.pos = (line, text.line_len(line));
+                        }
+                        motion_range(&text, cursor, target)
+                    })
+            }
+        };
+
+        match range {
+            Some(range) => self.vim_apply_operator(operator, range, 1, register),
+            None => iced::Task::none(),
+        }
+    }
+
+    /// Moves the cursor to a 1-based position, clamped to the buffer. Outside
+    /// insert mode the cursor stays on a character rather than past the end.
+    fn vim_goto_position(&mut self, target_line: usize, target_col: usize) -> iced::Task<Message> {
+        let Some(buffer) = self.vim_buffer() else {
+            return iced::Task::none();
+        };
+        let line = target_line.saturating_sub(1).min(buffer.line_count() - 1);
+        let len = buffer.line_len(line);
+        let max_col = if self.vim_mode == VimMode::Insert {
+            len
+        } else {
+            len.saturating_sub(1)
+        };
+        let col = target_col.saturating_sub(1).min(max_col);
+
+        let task = self.vim_send_editor_msg(EditorMessage::GotoPosition(line, col));
+        self.cursor_line = line + 1;
+        self.cursor_col = col + 1;
+        task
+    }
+
+    fn vim_open_line(&mut self, above: bool) -> iced::Task<Message> {
+        let Some(buffer) = self.vim_buffer() else {
+            return iced::Task::none();
+        };
+        let line = self.cursor_line - 1;
+        let current = line_text(&buffer, line);
+        let indent: String = current.chars().take_while(|c| c.is_whitespace()).collect();
+
+        let (edit, target_line) = if above {
+            let edit = TextEdit {
+                start: (line, 0),
+                end: (line, 0),
+                text: format!("{indent}\n"),
+            };
+            (edit, line)
+        } else {
+            let end = (line, buffer.line_len(line));
+            let edit = TextEdit {
+                start: end,
+                end,
+                text: format!("\n{indent}"),
+            };
+            (edit, line + 1)
+        };
+        self.apply_text_edit(&edit);
+        self.vim_set_mode(VimMode::Insert);
+        self.vim_goto_position(target_line + 1, indent.chars().count() + 1)
+    }
+
+    // --- Visual mode --- //
+
+    /// Enters a visual mode, switches between them, or leaves visual mode
+    /// when `mode` is the current one.
+    fn vim_toggle_visual(&mut self, mode: VimMode) -> iced::Task<Message> {
+        if self.vim_mode == mode {
+            self.vim_set_mode(VimMode::Normal);
+            return iced::Task::none();
+        }
+        if !self.vim_mode.is_visual() {
+            self.vim_visual_anchor = (self.cursor_line, self.cursor_col);
+        }
+        self.vim_set_mode(mode);
+        iced::Task::none()
+    }
+
+    /// Visual-mode keys that are neither motions nor operators.
+    fn vim_visual_key(&mut self, key: char, register: Option<char>) -> iced::Task<Message> {
+        let linewise = match key {
+            'v' => return self.vim_toggle_visual(VimMode::Visual),
+            'V' => return self.vim_toggle_visual(VimMode::VisualLine),
+            'o' => {
+                let anchor = self.vim_visual_anchor;
+                self.vim_visual_anchor = (self.cursor_line, self.cursor_col);
+                return self.vim_goto_position(anchor.0, anchor.1);
+            }
+            'I' | 'A' if self.vim_mode == VimMode::VisualBlock => {
+                let VimRange::Block {
+                    top,
+                    bottom,
+                    left,
+                    right,
+                } = self.vim_visual_range()
+                else {
+                    return iced::Task::none();
+                };
+                let (col, pad) = if key == 'A' {
+                    (right, true)
+                } else {
+                    (left, false)
+                };
+                self.vim_set_mode(VimMode::Normal);
+                return self.vim_start_block_insert(top, bottom, col, pad);
+            }
+            ':' => {
+                self.vim_set_mode(VimMode::Normal);
+                self.command_input.open();
+                self.command_input.input = "'<,'>".to_string();
+                self.vim_refresh_cursor_style();
+                let id = self.command_input_id.clone();
+                return iced::Task::batch([
+                    iced::widget::operation::focus(id.clone()),
+                    iced::widget::operation::move_cursor_to_end(id),
+                ]);
+            }
+            '/' | '?' => return self.vim_start_search(key, None),
+            // The uppercase operators act on whole lines.
+            'X' | 'D' => VimOperator::Delete,
+            'Y' => VimOperator::Yank,
+            'C' | 'S' | 'R' => VimOperator::Change,
+            _ => return iced::Task::none(),
+        };
+        let (start, end) = range_lines(self.vim_visual_range());
+        self.vim_set_mode(VimMode::Normal);
+        self.vim_apply_operator(linewise, VimRange::Lines { start, end }, 1, register)
+    }
+
+    /// Selects a text object typed in visual mode, switching to linewise
+    /// visual mode for objects made of whole lines.
+    fn vim_select_object(
+        &mut self,
+        object: TextObject,
+        count: Option<usize>,
+    ) -> iced::Task<Message> {
+        let Some(text) = self.vim_content_text() else {
+            return iced::Task::none();
+        };
+        let text = MotionText::new(&text);
+        let (mode, start, end) = match text_object_range(&text, self.vim_cursor(), object, count) {
+            Some(VimRange::Lines { start, end }) => (VimMode::VisualLine, (start, 0), (end, 0)),
+            Some(VimRange::Chars { start, end }) if start < end => {
+                // The selection includes its last character.
+                let end = if end.1 > 0 {
+                    (end.0, end.1 - 1)
+                } else {
+                    (end.0 - 1, text.line_len(end.0 - 1))
+                };
+                (VimMode::Visual, start, end)
+            }
+            _ => return iced::Task::none(),
+        };
+        if self.vim_mode != mode {
+            self.vim_set_mode(mode);
+        }
+        self.vim_visual_anchor = (start.0 + 1, start.1 + 1);
+        self.vim_goto_position(end.0 + 1, end.1 + 1)
+    }
+
+    fn vim_visual_range(&self) -> VimRange {
+        let anchor = (
+            self.vim_visual_anchor.0.saturating_sub(1),
+            self.vim_visual_anchor.1.saturating_sub(1),
+        );
+        let cursor = (self.cursor_line - 1, self.cursor_col - 1);
+        let (start, end) = if anchor <= cursor {
+            (anchor, cursor)
+        } else {
+            (cursor, anchor)
+        };
+        match self.vim_mode {
+            VimMode::VisualLine => VimRange::Lines {
+                start: start.0,
+                end: end.0,
+            },
+            VimMode::VisualBlock => VimRange::Block {
+                top: start.0,
+                bottom: end.0,
+                left: anchor.1.min(cursor.1),
+                right: anchor.1.max(cursor.1) + 1,
+            },
+            _ => VimRange::Chars {
+                start,
+                end: (end.0, end.1 + 1),
+            },
+        }
+    }
+
+    /// Shows the visual range as the editor's selection. The editor has a
+    /// single contiguous selection, so a block shows from its first corner
+    /// to its last.
+    fn vim_paint_selection(&mut self) {
+        let Some(buffer) = self.vim_buffer() else {
+            return;
+        };
+        let (start, end) = match self.vim_visual_range() {
+            VimRange::Chars { start, end } => (start, (end.0, end.1.min(buffer.line_len(end.0)))),
+            VimRange::Lines { start, end } => ((start, 0), (end, buffer.line_len(end))),
+            VimRange::Block {
+                top,
+                bottom,
+                left,
+                right,
+            } => (
+                (top, left.min(buffer.line_len(top))),
+                (bottom, right.min(buffer.line_len(bottom))),
+            ),
+        };
+        self.select_active_range(start, end);
+    }
+
+    // --- Operators --- //
+
+    fn vim_apply_operator(
+        &mut self,
+        operator: VimOperator,
+        range: VimRange,
+        count: usize,
+        register: Option<char>,
+    ) -> iced::Task<Message> {
+        let Some(buffer) = self.vim_buffer() else {
+            return iced::Task::none();
+        };
+        let range = clamp_range(&buffer, range);
+        let (first_line, last_line) = range_lines(range);
+
+        match operator {
+            VimOperator::Yank => {
+                let stored =
+                    self.vim_store_register(register, range_register(&buffer, range), true);
+                let (line, col) = range_start(range);
+                iced::Task::batch([stored, self.vim_goto_position(line + 1, col + 1)])
+            }
+            VimOperator::Delete | VimOperator::Change => {
+                let stored =
+                    self.vim_store_register(register, range_register(&buffer, range), false);
+                let change = operator == VimOperator::Change;
+                let (line, col) = match range {
+                    VimRange::Chars { start, end } => {
+                        self.apply_text_edit(&TextEdit {
+                            start,
+                            end,
+                            text: String::new(),
+                        });
+                        start
+                    }
+                    VimRange::Lines { start, end } if change => {
+                        // `c` on lines keeps one line, indented like the first.
+                        let indent: String = line_text(&buffer, start)
+                            .chars()
+                            .take_while(|c| c.is_whitespace())
+                            .collect();
+                        let col = indent.chars().count();
+                        self.apply_text_edit(&buffer.lines_edit(start, end + 1, &[indent]));
+                        (start, col)
+                    }
+                    VimRange::Lines { start, end } => {
+                        self.apply_text_edit(&buffer.lines_edit(start, end + 1, &[]));
+                        let line = start.min(buffer.line_count().saturating_sub(end - start + 2));
+                        let col = self
+                            .vim_buffer()
+                            .map_or(0, |buffer| first_nonblank(&line_text(&buffer, line)));
+                        (line, col)
+                    }
+                    VimRange::Block {
+                        top,
+                        bottom,
+                        left,
+                        right,
+                    } => {
+                        let lines: Vec<String> = (top..=bottom)
+                            .map(|line| block_delete(&line_text(&buffer, line), left, right))
+                            .collect();
+                        self.apply_text_edit(&buffer.lines_edit(top, bottom + 1, &lines));
+                        if change {
+                            let insert = self.vim_start_block_insert(top, bottom, left, false);
+                            return iced::Task::batch([stored, insert]);
                         }
+                        (top, left)
                     }
+                };
+                if change {
+                    self.vim_set_mode(VimMode::Insert);
                 }
+                iced::Task::batch([stored, self.vim_goto_position(line + 1, col + 1)])
             }
-            VimMode::Insert => {
-                if let Some(idx) = self.active_tab {
-                    if let Some(tab) = self.tabs.get_mut(idx) {
-                        if let TabKind::Editor {
-                            ref code_editor, ..
-                        } = tab.kind
-                        {
-                            code_editor.request_focus();
+            VimOperator::Indent | VimOperator::Outdent => {
+                let unit = self.editor_preferences.indent_unit();
+                let tab_size = self.editor_preferences.tab_size.max(1);
+                let lines: Vec<String> = (first_line..=last_line)
+                    .map(|line| {
+                        let mut text = line_text(&buffer, line);
+                        for _ in 0..count {
+                            text = if operator == VimOperator::Indent {
+                                indent_line(&text, &unit)
+                            } else {
+                                outdent_line(&text, tab_size)
+                            };
                         }
+                        text
+                    })
+                    .collect();
+                self.replace_lines(&buffer, first_line, &lines);
+                let col = first_nonblank(&lines[0]);
+                self.vim_goto_position(first_line + 1, col + 1)
+            }
+            VimOperator::Reindent => {
+                let unit = self.editor_preferences.indent_unit();
+                let above = (0..first_line)
+                    .rev()
+                    .map(|line| line_text(&buffer, line))
+                    .find(|line| !line.trim().is_empty());
+                let lines: Vec<String> = (first_line..=last_line)
+                    .map(|line| line_text(&buffer, line))
+                    .collect();
+                let lines = reindent_lines(above.as_deref(), &lines, &unit);
+                self.replace_lines(&buffer, first_line, &lines);
+                let col = first_nonblank(&lines[0]);
+                self.vim_goto_position(first_line + 1, col + 1)
+            }
+            VimOperator::Lowercase | VimOperator::Uppercase | VimOperator::ToggleCase => {
+                let convert: fn(&str) -> String = match operator {
+                    VimOperator::Lowercase => str::to_lowercase,
+                    VimOperator::Uppercase => str::to_uppercase,
+                    _ => toggle_case,
+                };
+                match range {
+                    VimRange::Chars { start, end } => {
+                        let text = convert(&buffer.text_between(start, end));
+                        self.apply_text_edit(&TextEdit { start, end, text });
+                    }
+                    VimRange::Lines { start, end } => {
+                        let lines: Vec<String> = (start..=end)
+                            .map(|line| convert(&line_text(&buffer, line)))
+                            .collect();
+                        self.replace_lines(&buffer, start, &lines);
+                    }
+                    VimRange::Block {
+                        top,
+                        bottom,
+                        left,
+                        right,
+                    } => {
+                        let lines: Vec<String> = (top..=bottom)
+                            .map(|line| {
+                                map_columns(&line_text(&buffer, line), left, right, convert)
+                            })
+                            .collect();
+                        self.replace_lines(&buffer, top, &lines);
                     }
                 }
+                let (line, col) = range_start(range);
+                self.vim_goto_position(line + 1, col + 1)
+            }
+        }
+    }
+
+    // --- Repeating --- //
+
+    /// Adds a key to the macro being recorded and to the change being typed.
+    /// Keys typed in an insert mode that no vim command started are not part
+    /// of a change.
+    fn vim_record(&mut self, input: VimInput) {
+        if self.vim_replay_depth == 0 {
+            if let Some((_, keys)) = &mut self.vim_recording {
+                keys.push(input);
+            }
+        }
+        if !self.vim_repeating && (self.vim_mode != VimMode::Insert || !self.vim_change.is_empty())
+        {
+            self.vim_change.push(input);
+        }
+    }
+
+    /// Keeps the keys of a command that changed the text for `.`. A change
+    /// that goes on into insert mode is kept once Escape ends it.
+    fn vim_change_done(&mut self) {
+        if self.vim_mode != VimMode::Insert && !self.vim_repeating && !self.vim_change.is_empty() {
+            self.vim_last_change = std::mem::take(&mut self.vim_change);
+        }
+    }
+
+    /// `.`: the last change again. A count replaces the one it was typed with.
+    fn vim_repeat_change(&mut self, count: Option<usize>) -> iced::Task<Message> {
+        let mut keys = self.vim_last_change.clone();
+        if let Some(count) = count {
+            let digits = keys
+                .iter()
+                .take_while(
+                    |input| matches!(input, VimInput::Key(VimKey::Char(ch)) if ch.is_ascii_digit()),
+                )
+                .count();
+            let count: Vec<VimInput> = count
+                .to_string()
+                .chars()
+                .map(|ch| VimInput::Key(VimKey::Char(ch)))
+                .collect();
+            keys.splice(..digits, count);
+        }
+        self.vim_repeating = true;
+        let task = self.vim_replay(&keys);
+        self.vim_repeating = false;
+        task
+    }
+
+    fn vim_play_macro(&mut self, name: char, count: usize) -> iced::Task<Message> {
+        let name = match name {
+            '@' => match self.vim_last_macro {
+                Some(name) => name,
+                None => return iced::Task::none(),
+            },
+            _ => name,
+        };
+        let Some(register) = self.vim_registers.get(name) else {
+            return iced::Task::none();
+        };
+        let keys = recording::decode(&register.text);
+        self.vim_last_macro = Some(name);
+        if self.vim_replay_depth >= MAX_REPLAY_DEPTH {
+            return iced::Task::none();
+        }
+        let tasks: Vec<_> = (0..count).map(|_| self.vim_replay(&keys)).collect();
+        iced::Task::batch(tasks)
+    }
+
+    /// Feeds recorded keys through the paths typed keys take: vim commands
+    /// outside insert mode, and editor messages inside it.
+    fn vim_replay(&mut self, inputs: &[VimInput]) -> iced::Task<Message> {
+        self.vim_replay_depth += 1;
+        let mut tasks = Vec::new();
+        for &input in inputs {
+            if !self.vim_enabled || !self.vim_context_active() {
+                break;
+            }
+            let task = match input {
+                VimInput::Escape => {
+                    self.vim_escape();
+                    iced::Task::none()
+                }
+                VimInput::Key(key) if self.vim_mode == VimMode::Insert => {
+                    self.vim_replay_insert_key(key)
+                }
+                VimInput::Key(key) => self.handle_vim_key(key),
+            };
+            tasks.push(task);
+        }
+        self.vim_replay_depth -= 1;
+        iced::Task::batch(tasks)
+    }
+
+    fn vim_replay_insert_key(&mut self, key: VimKey) -> iced::Task<Message> {
+        let event = match key {
+            VimKey::Char(ch) => EditorMessage::CharacterInput(ch),
+            VimKey::Ctrl('i') => EditorMessage::Tab,
+            VimKey::Ctrl(_) => return iced::Task::none(),
+            VimKey::Enter => EditorMessage::Enter,
+            VimKey::Backspace => EditorMessage::Backspace,
+            VimKey::Up => EditorMessage::ArrowKey(ArrowDirection::Up, false),
+            VimKey::Down => EditorMessage::ArrowKey(ArrowDirection::Down, false),
+            VimKey::Left => EditorMessage::ArrowKey(ArrowDirection::Left, false),
+            VimKey::Right => EditorMessage::ArrowKey(ArrowDirection::Right, false),
+        };
+        // Completion popups would take Enter for themselves.
+        self.autocomplete.cancel();
+        self.lsp_overlay = iced_code_editor::LspOverlayState::new();
+        self.update(Message::CodeEditorEvent(event))
+    }
+
+    /// `u` and `Ctrl-r`, stepping through the editor's own undo history.
+    fn vim_undo(&mut self, redo: bool, count: usize) -> iced::Task<Message> {
+        let message = if redo {
+            EditorMessage::Redo
+        } else {
+            EditorMessage::Undo
+        };
+        let Some(tab) = self.active_tab.and_then(|idx| self.tabs.get_mut(idx)) else {
+            return iced::Task::none();
+        };
+        let TabKind::Editor {
+            code_editor,
+            buffer,
+        } = &mut tab.kind
+        else {
+            return iced::Task::none();
+        };
+        let tasks: Vec<_> = (0..count)
+            .map(|_| code_editor.update(&message).map(Message::CodeEditorEvent))
+            .collect();
+        let content = code_editor.content();
+        if content != buffer.text() {
+            buffer.set_text(&content);
+            tab.autosave_requested_at = Some(Instant::now());
+        }
+        self.sync_cursor_from_active_editor();
+        let cursor = self.vim_goto_position(self.cursor_line, self.cursor_col);
+        iced::Task::batch(tasks).chain(cursor)
+    }
+
+    // --- Registers --- //
+
+    /// Stores yanked or deleted text, copying it to the system clipboard for
+    /// `+` and `*`.
+    fn vim_store_register(
+        &mut self,
+        name: Option<char>,
+        register: VimRegister,
+        yank: bool,
+    ) -> iced::Task<Message> {
+        let task = match name {
+            Some('+') => iced::clipboard::write(register.text.clone()),
+            Some('*') => iced::clipboard::write_primary(register.text.clone()),
+            _ => iced::Task::none(),
+        };
+        self.vim_registers.store(name, register, yank);
+        task
+    }
+
+    /// `p` and `P`. The clipboard registers are read first and put once
+    /// [`Message::VimClipboardRead`] comes back.
+    fn vim_put(&mut self, name: Option<char>, before: bool, count: usize) -> iced::Task<Message> {
+        let name = name.unwrap_or('"');
+        if name == '+' || name == '*' {
+            let paste = VimPaste {
+                register: name,
+                before,
+                count,
+            };
+            let read = if name == '+' {
+                iced::clipboard::read()
+            } else {
+                iced::clipboard::read_primary()
+            };
+            return read.map(move |text| Message::VimClipboardRead(paste, text));
+        }
+        match self.vim_registers.get(name).cloned() {
+            Some(register) => self.vim_put_register(register, before, count),
+            None => iced::Task::none(),
+        }
+    }
+
+    pub(super) fn vim_clipboard_read(
+        &mut self,
+        paste: VimPaste,
+        text: Option<String>,
+    ) -> iced::Task<Message> {
+        let Some(text) = text.filter(|text| !text.is_empty()) else {
+            return iced::Task::none();
+        };
+        if !self.vim_enabled || self.vim_mode == VimMode::Insert || !self.vim_context_active() {
+            return iced::Task::none();
+        }
+        let register = self.vim_registers.read_clipboard(paste.register, text);
+        let task = self.vim_put_register(register, paste.before, paste.count);
+        if self.vim_mode.is_visual() {
+            self.vim_paint_selection();
+        }
+        task
+    }
+
+    fn vim_put_register(
+        &mut self,
+        register: VimRegister,
+        before: bool,
+        count: usize,
+    ) -> iced::Task<Message> {
+        if self.vim_mode.is_visual() {
+            return self.vim_replace_selection(register, count);
+        }
+        let Some(buffer) = self.vim_buffer() else {
+            return iced::Task::none();
+        };
+        let (line, col) = self.vim_cursor();
+        let len = buffer.line_len(line);
+
+        match register.kind {
+            RegisterKind::Lines => {
+                let text = register.text.strip_suffix('\n').unwrap_or(&register.text);
+                let lines: Vec<String> = std::iter::repeat_n(text, count)
+                    .flat_map(|text| text.split('\n'))
+                    .map(str::to_string)
+                    .collect();
+                let at = if before { line } else { line + 1 };
+                self.apply_text_edit(&buffer.lines_edit(at, at, &lines));
+                self.vim_goto_position(at + 1, first_nonblank(&lines[0]) + 1)
+            }
+            RegisterKind::Chars => {
+                let at = if before || len == 0 {
+                    (line, col)
+                } else {
+                    (line, col + 1)
+                };
+                let text = register.text.repeat(count);
+                // The cursor ends on the last character put, unless the
+                // text spans lines.
+                let cursor = if text.contains('\n') {
+                    at
+                } else {
+                    (line, at.1 + text.chars().count().saturating_sub(1))
+                };
+                self.apply_text_edit(&TextEdit {
+                    start: at,
+                    end: at,
+                    text,
+                });
+                self.vim_goto_position(cursor.0 + 1, cursor.1 + 1)
             }
+            RegisterKind::Block => {
+                let at = if before || len == 0 { col } else { col + 1 };
+                let pieces: Vec<&str> = register.text.split('\n').collect();
+                let width = pieces.iter().map(|piece| piece.chars().count()).max();
+                let width = width.unwrap_or(0);
+                let lines: Vec<String> = pieces
+                    .iter()
+                    .enumerate()
+                    .map(|(i, piece)| {
+                        let current = if line + i < buffer.line_count() {
+                            line_text(&buffer, line + i)
+                        } else {
+                            String::new()
+                        };
+                        block_put(&current, at, &piece.repeat(count), width * count)
+                    })
+                    .collect();
+                let end = (line + lines.len()).min(buffer.line_count());
+                self.apply_text_edit(&buffer.lines_edit(line, end, &lines));
+                self.vim_goto_position(line + 1, at + 1)
+            }
+        }
+    }
+
+    /// `p` in visual mode: the selection is replaced by the register, and
+    /// goes to the unnamed register as if deleted.
+    fn vim_replace_selection(
+        &mut self,
+        register: VimRegister,
+        count: usize,
+    ) -> iced::Task<Message> {
+        let range = self.vim_visual_range();
+        self.vim_set_mode(VimMode::Normal);
+        let Some(buffer) = self.vim_buffer() else {
+            return iced::Task::none();
+        };
+        let range = clamp_range(&buffer, range);
+        let replaced = range_register(&buffer, range);
+        let text = register.text.repeat(count);
+
+        let task = match (range, register.kind) {
+            (VimRange::Lines { start, end }, _) => {
+                let lines: Vec<String> = text
+                    .strip_suffix('\n')
+                    .unwrap_or(&text)
+                    .split('\n')
+                    .map(str::to_string)
+                    .collect();
+                self.apply_text_edit(&buffer.lines_edit(start, end + 1, &lines));
+                self.vim_goto_position(start + 1, first_nonblank(&lines[0]) + 1)
+            }
+            (VimRange::Chars { start, end }, RegisterKind::Lines) => {
+                // Lines put into text go on lines of their own.
+                self.apply_text_edit(&TextEdit {
+                    start,
+                    end,
+                    text: format!("\n{text}"),
+                });
+                self.vim_goto_position(start.0 + 2, 1)
+            }
+            (VimRange::Chars { start, end }, _) => {
+                self.apply_text_edit(&TextEdit { start, end, text });
+                self.vim_goto_position(start.0 + 1, start.1 + 1)
+            }
+            (
+                VimRange::Block {
+                    top,
+                    bottom,
+                    left,
+                    right,
+                },
+                _,
+            ) => {
+                let lines: Vec<String> = (top..=bottom)
+                    .map(|line| block_delete(&line_text(&buffer, line), left, right))
+                    .collect();
+                self.apply_text_edit(&buffer.lines_edit(top, bottom + 1, &lines));
+                let _ = self.vim_goto_position(top + 1, left + 1);
+                self.vim_put_register(register, true, count)
+            }
+        };
+        self.vim_registers.store(None, replaced, false);
+        task
+    }
+
+    /// Replaces the lines from `first` on with `lines` in a single edit,
+    /// unless nothing changes.
+    fn replace_lines(&mut self, buffer: &EditorBuffer, first: usize, lines: &[String]) {
+        let unchanged = lines
+            .iter()
+            .enumerate()
+            .all(|(i, line)| *line == line_text(buffer, first + i));
+        if !unchanged {
+            self.apply_text_edit(&buffer.lines_edit(first, first + lines.len(), lines));
+        }
+    }
+
+    // --- Search, marks and jumps --- //
+
+    /// `/` and `?` open the command bar as a search prompt.
+    fn vim_start_search(&mut self, prompt: char, count: Option<usize>) -> iced::Task<Message> {
+        self.vim_search_start = Some(VimSearchStart {
+            cursor: (self.cursor_line, self.cursor_col),
+            count,
+        });
+        self.command_input.open_prompt(prompt);
+        self.vim_refresh_cursor_style();
+        iced::widget::operation::focus(self.command_input_id.clone())
+    }
+
+    /// The search typed in the prompt. An empty prompt repeats the last
+    /// pattern, in the prompt's direction.
+    fn vim_prompt_search(&self) -> Option<VimSearch> {
+        let backward = self.command_input.prompt == '?';
+        if self.command_input.input.is_empty() {
+            let last = self.vim_search.clone()?;
+            return Some(VimSearch { backward, ..last });
+        }
+        Some(VimSearch {
+            pattern: self.command_input.input.clone(),
+            backward,
+            whole_word: false,
+        })
+    }
+
+    /// Where the prompt's search goes from where it was started.
+    fn vim_prompt_target(&self, search: &VimSearch, start: VimSearchStart) -> Option<MotionTarget> {
+        let text = self.vim_content_text()?;
+        let from = (start.cursor.0 - 1, start.cursor.1 - 1);
+        search_target(search, &text, from, false, start.count)
+    }
+
+    /// Shows the match the pattern typed so far goes to.
+    pub(super) fn vim_search_preview(&mut self) -> iced::Task<Message> {
+        let Some(start) = self.vim_search_start else {
+            return iced::Task::none();
+        };
+        let found = self.vim_prompt_search().and_then(|search| {
+            let target = self.vim_prompt_target(&search, start)?;
+            Some((target, search.pattern.chars().count()))
+        });
+        match found {
+            Some((target, len)) => self.vim_show_match(target.pos, len),
+            None => self.vim_goto_position(start.cursor.0, start.cursor.1),
+        }
+    }
+
+    /// Enter in the search prompt. Without a match the prompt stays open
+    /// and says so.
+    pub(super) fn vim_search_submit(&mut self) -> iced::Task<Message> {
+        let Some(start) = self.vim_search_start else {
+            self.command_input.close();
+            return iced::Task::none();
+        };
+        let Some(search) = self.vim_prompt_search() else {
+            self.command_input
+                .show_output(vec!["No previous search pattern".to_string()]);
+            return iced::Task::none();
+        };
+        let target = self.vim_prompt_target(&search, start);
+        let len = search.pattern.chars().count();
+        let pattern = search.pattern.clone();
+        self.vim_search = Some(search);
+        let Some(target) = target else {
+            self.command_input
+                .show_output(vec![format!("Pattern not found: {pattern}")]);
+            return self.vim_goto_position(start.cursor.0, start.cursor.1);
+        };
+
+        self.vim_search_start = None;
+        self.command_input.close();
+        self.vim_refresh_cursor_style();
+        (self.cursor_line, self.cursor_col) = start.cursor;
+        self.vim_push_jump();
+        self.vim_show_match(target.pos, len)
+    }
+
+    /// Escape in the search prompt goes back to where it was started.
+    pub(super) fn vim_search_cancel(&mut self) -> iced::Task<Message> {
+        let Some(start) = self.vim_search_start.take() else {
+            return iced::Task::none();
+        };
+        let task = self.vim_goto_position(start.cursor.0, start.cursor.1);
+        if self.vim_mode.is_visual() {
+            self.vim_paint_selection();
         }
+        task
     }
 
-    pub(super) fn handle_vim_key(&mut self, _key: crate::message::VimKey) -> iced::Task<Message> {
-        iced::Task::none()
+    /// Moves to a search match and selects it in normal mode, which is as
+    /// close to vim's match highlighting as the editor gets.
+    fn vim_show_match(&mut self, pos: (usize, usize), len: usize) -> iced::Task<Message> {
+        let task = self.vim_goto_position(pos.0 + 1, pos.1 + 1);
+        if self.vim_mode == VimMode::Normal {
+            self.select_active_range(pos, (pos.0, pos.1 + len));
+        } else if self.vim_mode.is_visual() {
+            self.vim_paint_selection();
+        }
+        task
     }
 
-    pub(super) fn vim_context_active(&self) -> bool {
-        self.active_tab.is_some()
-            && !self.settings_open
-            && !self.command_palette.open
-            && !self.fuzzy_finder.open
-            && !self.file_finder_visible
-            && !self.search_visible
-            && !self.command_input.open
-            && !self.terminal_open
+    /// The active tab's file, which `a`-`z` marks belong to.
+    fn vim_path(&self) -> Option<PathBuf> {
+        let tab = self.tabs.get(self.active_tab?)?;
+        Some(tab.path.clone())
     }
 
-    fn vim_handle_char(&mut self, ch: char) -> iced::Task<Message> {
-        if ch.is_ascii_digit() && self.vim_pending.is_empty() {
-            if ch == '0' && self.vim_count.is_empty() {
-                return self.vim_send_editor_msg(EditorMessage::Home(false));
-            } else {
-                self.vim_count.push(ch);
+    fn vim_set_mark(&mut self, name: char) {
+        let Some(path) = self.vim_path() else {
+            return;
+        };
+        self.vim_marks.set(&path, name, self.vim_cursor());
+        if name.is_ascii_alphabetic() {
+            if let Err(err) = self.vim_marks.save() {
+                self.dev_log(format!("Vim: could not save marks: {err}"));
             }
+        }
+    }
+
+    /// Records the cursor in the jumplist, and as the `'` mark, before a
+    /// jump leaves it. Also called for jumps made outside vim, such as
+    /// go-to-definition.
+    pub(super) fn vim_push_jump(&mut self) {
+        let Some(path) = self.vim_path() else {
+            return;
+        };
+        let pos = self.vim_cursor();
+        self.vim_marks.set(&path, '\'', pos);
+        self.vim_jumps.push(Jump { path, pos });
+    }
+
+    fn vim_jump_back(&mut self, count: usize) -> iced::Task<Message> {
+        let Some(path) = self.vim_path() else {
             return iced::Task::none();
+        };
+        let current = Jump {
+            path,
+            pos: self.vim_cursor(),
+        };
+        match self.vim_jumps.back(current, count) {
+            Some(jump) => self.go_to_location(jump.path, jump.pos),
+            None => iced::Task::none(),
         }
+    }
 
-        if !self.vim_pending.is_empty() {
-            let pending = self.vim_pending.clone();
-            self.vim_pending.clear();
-            return self.vim_dispatch_pending(&pending, ch);
+    fn vim_jump_forward(&mut self, count: usize) -> iced::Task<Message> {
+        match self.vim_jumps.forward(count) {
+            Some(jump) => self.go_to_location(jump.path, jump.pos),
+            None => iced::Task::none(),
         }
+    }
 
-        match ch {
-            'i' => {
-                self.vim_mode = VimMode::Insert;
-                self.vim_refresh_cursor_style();
-                iced::Task::none()
-            }
-            'a' => {
-                // 'a' in vim: move right one char, then insert
-                let task =
-                    self.vim_send_editor_msg(EditorMessage::ArrowKey(ArrowDirection::Right, false));
-                self.vim_mode = VimMode::Insert;
-                self.vim_refresh_cursor_style();
+    // --- Ex commands --- //
+
+    /// Runs a `:` command. Errors, and anything a command prints, are shown
+    /// in the command bar; otherwise the bar closes.
+    pub(super) fn vim_ex_command(&mut self, input: &str) -> iced::Task<Message> {
+        self.command_input.output.clear();
+        if input.is_empty() {
+            self.command_input.close();
+            self.vim_refresh_cursor_style();
+            return iced::Task::none();
+        }
+        match ex::parse(input).and_then(|command| self.vim_run_ex(command, input)) {
+            Ok(task) => {
+                if self.command_input.output.is_empty() && self.vim_confirm.is_none() {
+                    self.command_input.close();
+                    self.vim_refresh_cursor_style();
+                }
                 task
             }
-            'A' => {
-                let task = self.vim_send_editor_msg(EditorMessage::End(false));
-                self.vim_mode = VimMode::Insert;
-                self.vim_refresh_cursor_style();
-                task
+            Err(message) => {
+                self.command_input.show_output(vec![message]);
+                iced::Task::none()
             }
-            'I' => {
-                let task = self.vim_send_editor_msg(EditorMessage::Home(false));
-                self.vim_mode = VimMode::Insert;
-                self.vim_refresh_cursor_style();
-                task
+        }
+    }
+
+    fn vim_run_ex(
+        &mut self,
+        command: ExCommand,
+        input: &str,
+    ) -> Result<iced::Task<Message>, String> {
+        let takes_range = matches!(
+            command.name.as_str(),
+            "" | "substitute" | "global" | "vglobal" | "delete" | "yank" | "print"
+        );
+        let range = match command.range {
+            Some(_) if !takes_range => return Err("No range allowed".to_string()),
+            Some(range) => Some(self.vim_ex_range(range)?),
+            None => None,
+        };
+        let current = (self.cursor_line - 1, self.cursor_line - 1);
+
+        match command.name.as_str() {
+            "" => {
+                let Some((_, line)) = range else {
+                    return Ok(iced::Task::none());
+                };
+                let buffer = self.vim_ex_buffer()?;
+                self.vim_push_jump();
+                let col = first_nonblank(&line_text(&buffer, line));
+                Ok(self.vim_goto_position(line + 1, col + 1))
             }
-            'o' => {
-                // Open new line below
-                let t1 = self.vim_send_editor_msg(EditorMessage::End(false));
-                let t2 = self.vim_send_editor_msg(EditorMessage::Enter);
-                self.vim_mode = VimMode::Insert;
-                self.vim_refresh_cursor_style();
-                iced::Task::batch([t1, t2])
-            }
-            'O' => {
-                // Open new line above
-                let t1 = self.vim_send_editor_msg(EditorMessage::Home(false));
-                let t2 = self.vim_send_editor_msg(EditorMessage::Enter);
-                let t3 =
-                    self.vim_send_editor_msg(EditorMessage::ArrowKey(ArrowDirection::Up, false));
-                self.vim_mode = VimMode::Insert;
-                self.vim_refresh_cursor_style();
-                iced::Task::batch([t1, t2, t3])
-            }
-            'h' => self.vim_repeat_motion(ArrowDirection::Left),
-            'j' => self.vim_repeat_motion(ArrowDirection::Down),
-            'k' => self.vim_repeat_motion(ArrowDirection::Up),
-            'l' => self.vim_repeat_motion(ArrowDirection::Right),
-            'w' | 'W' => self.vim_word_motion_forward(ch == 'W'),
-            'e' | 'E' => self.vim_word_motion_end(ch == 'E'),
-            'b' | 'B' => self.vim_word_motion_backward(ch == 'B'),
-            '%' => self.vim_match_pair(),
-            '^' => self.vim_move_first_nonblank(),
-            '$' => self.vim_send_editor_msg(EditorMessage::End(false)),
-            'G' => self.vim_goto_end_or_line(),
-            'x' => {
-                // Delete char under cursor
-                self.vim_count.clear();
-                self.vim_send_editor_msg(EditorMessage::Delete)
-            }
-            'H' | 'M' | 'L' => {
-                // Screen-relative motions - limited support, just use
-                // page-level navigation
-                self.vim_count.clear();
-                iced::Task::none()
+            "substitute" => self.vim_ex_substitute(range.unwrap_or(current), &command.args),
+            "global" | "vglobal" => {
+                let whole = (0, self.vim_ex_buffer()?.line_count() - 1);
+                let invert = command.name == "vglobal" || command.bang;
+                self.vim_ex_global(range.unwrap_or(whole), invert, &command.args)
             }
-            '{' => self.vim_move_paragraph_prev(),
-            '}' => self.vim_move_paragraph_next(),
-            ';' => self.vim_repeat_last_find(false),
-            ',' => self.vim_repeat_last_find(true),
-            'd' => {
-                self.vim_pending.push('d');
-                iced::Task::none()
+            "delete" | "yank" | "print" => {
+                self.vim_ex_lines(&command.name, range.unwrap_or(current), &command.args)
             }
-            'f' | 'F' | 't' | 'T' | 'g' | 'z' => {
-                self.vim_pending.push(ch);
-                iced::Task::none()
+            "edit" | "split" | "vsplit" if !command.args.is_empty() => {
+                self.vim_ex_edit(&command.args)
             }
-            _ => {
-                self.vim_count.clear();
-                iced::Task::none()
+            "split" | "vsplit" => Err("Split windows are not supported".to_string()),
+            "bnext" | "bNext" | "bprevious" => {
+                let (Some(active), len) = (self.active_tab, self.tabs.len()) else {
+                    return Ok(iced::Task::none());
+                };
+                let next = if command.name == "bnext" {
+                    (active + 1) % len
+                } else {
+                    (active + len - 1) % len
+                };
+                Ok(self.update(Message::TabSelected(next)))
             }
-        }
-    }
-
-    fn vim_dispatch_pending(&mut self, pending: &str, ch: char) -> iced::Task<Message> {
-        match pending {
-            "g" => match ch {
-                'g' => {
-                    self.vim_count.clear();
-                    self.vim_send_editor_msg(EditorMessage::CtrlHome)
-                }
-                _ => {
-                    self.vim_count.clear();
-                    iced::Task::none()
-                }
-            },
-            "z" => {
-                self.vim_count.clear();
-                iced::Task::none()
+            "bdelete" => Ok(self.update(Message::CloseActiveTab)),
+            "quit" => Ok(self.vim_ex_quit(command.bang)),
+            "wq" | "xit" | "exit" => self.vim_ex_write_quit(command.name == "wq", command.bang),
+            "set" => {
+                let shown = ex::set_options(&mut self.editor_preferences, &command.args)?;
+                self.command_input.show_output(shown);
+                Ok(iced::Task::none())
             }
-            "d" => match ch {
-                'd' => self.vim_delete_line(),
-                'w' => self.vim_delete_word(),
-                _ => {
-                    self.vim_count.clear();
-                    iced::Task::none()
+            "registers" | "display" => {
+                let names: String = command.args.split_whitespace().collect();
+                let listing = self.vim_registers.listing(&names);
+                self.command_input.show_output(listing);
+                Ok(iced::Task::none())
+            }
+            // `:w` and the rest are commands from the palette.
+            _ => match self.command_input.process_command() {
+                Some(name) if self.command_registry.find(&name).is_some() => {
+                    self.command_input.close();
+                    Ok(self.execute_palette_command(&name))
                 }
+                _ => Err(format!("Not an editor command: {input}")),
             },
-            "f" => self.vim_find_char(ch, false, false),
-            "t" => self.vim_find_char(ch, false, true),
-            "F" => self.vim_find_char(ch, true, false),
-            "T" => self.vim_find_char(ch, true, true),
-            _ => {
-                self.vim_count.clear();
-                iced::Task::none()
-            }
         }
     }
 
-    fn vim_take_count(&mut self) -> usize {
-        if self.vim_count.is_empty() {
-            1
+    fn vim_ex_buffer(&self) -> Result<EditorBuffer, String> {
+        self.vim_buffer()
+            .ok_or_else(|| "No file is open".to_string())
+    }
+
+    /// The 0-based lines a range covers in the active buffer.
+    fn vim_ex_range(&self, range: LineRange) -> Result<(usize, usize), String> {
+        let last = self.vim_ex_buffer()?.line_count() - 1;
+        let path = self.vim_path();
+        range.resolve(self.cursor_line - 1, last, |name| {
+            let path = path.as_ref()?;
+            let (mark_path, (line, _)) = self.vim_marks.get(path, name)?;
+            (&mark_path == path).then_some(line)
+        })
+    }
+
+    /// The last `/` search as a regex, for an empty `:s` or `:g` pattern.
+    fn vim_ex_last_pattern(&self) -> Result<String, String> {
+        let search = self
+            .vim_search
+            .as_ref()
+            .ok_or("No previous regular expression")?;
+        let pattern = regex::escape(&search.pattern);
+        Ok(if search.whole_word {
+            format!(r"\b{pattern}\b")
         } else {
-            let parsed = self.vim_count.parse::<usize>().unwrap_or(1);
-            self.vim_count.clear();
-            parsed.max(1)
-        }
+            pattern
+        })
     }
 
-    /// Send a message to the active tab's CodeEditor and return the resulting Task.
-    fn vim_send_editor_msg(&mut self, msg: EditorMessage) -> iced::Task<Message> {
-        if let Some(idx) = self.active_tab {
-            if let Some(tab) = self.tabs.get_mut(idx) {
-                if let TabKind::Editor {
-                    ref mut code_editor,
-                    ref mut buffer,
-                    ..
-                } = tab.kind
-                {
-                    let task = code_editor.update(&msg);
-                    buffer.set_text(&code_editor.content());
-                    self.lsp
-                        .change_document(tab.path.clone(), code_editor.content());
-                    return task.map(Message::CodeEditorEvent);
-                }
-            }
+    /// `:s`. Without arguments it repeats the last substitution; with the
+    /// `c` flag it asks about each match, see [`App::vim_confirm_next`].
+    fn vim_ex_substitute(
+        &mut self,
+        (first, last): (usize, usize),
+        args: &str,
+    ) -> Result<iced::Task<Message>, String> {
+        let mut substitute = match args {
+            "" => self
+                .vim_last_substitute
+                .clone()
+                .ok_or("No previous substitute regular expression")?,
+            _ => VimSubstitute::parse(args)?,
+        };
+        if substitute.pattern.is_empty() {
+            substitute.pattern = self.vim_ex_last_pattern()?;
         }
-        iced::Task::none()
+        let regex = substitute.regex()?;
+        self.vim_last_substitute = Some(substitute.clone());
+
+        let buffer = self.vim_ex_buffer()?;
+        let lines: Vec<String> = (first..=last)
+            .map(|line| line_text(&buffer, line))
+            .collect();
+        let (new, count) =
+            ex::substitute_lines(&lines, &regex, &substitute.replacement, substitute.global);
+        if count == 0 {
+            return Err(format!("Pattern not found: {}", substitute.pattern));
+        }
+        if substitute.confirm {
+            self.vim_confirm = Some(VimConfirm {
+                regex,
+                replacement: substitute.replacement,
+                global: substitute.global,
+                line: first,
+                last,
+                from: 0,
+            });
+            return Ok(self.vim_confirm_next());
+        }
+
+        self.apply_text_edit(&buffer.lines_edit(first, last + 1, &new));
+        let line = first + new.len() - 1;
+        Ok(self.vim_goto_position(line + 1, first_nonblank(&new[new.len() - 1]) + 1))
     }
 
-    fn vim_repeat_motion(&mut self, dir: ArrowDirection) -> iced::Task<Message> {
-        let count = self.vim_take_count();
-        let mut tasks = Vec::with_capacity(count);
-        for _ in 0..count {
-            tasks.push(self.vim_send_editor_msg(EditorMessage::ArrowKey(dir, false)));
+    /// Selects the next match of a `:s///c` and asks about it, or finishes
+    /// when there are none left.
+    fn vim_confirm_next(&mut self) -> iced::Task<Message> {
+        let (Some(buffer), Some(confirm)) = (self.vim_buffer(), self.vim_confirm.as_mut()) else {
+            return self.vim_confirm_done();
+        };
+        while confirm.line <= confirm.last.min(buffer.line_count() - 1) {
+            let text = line_text(&buffer, confirm.line);
+            let found = (confirm.from <= text.len())
+                .then(|| confirm.regex.find_at(&text, confirm.from))
+                .flatten();
+            if let Some(found) = found {
+                let col = text[..found.start()].chars().count();
+                let len = found.as_str().chars().count();
+                let line = confirm.line;
+                let question = format!("replace with {} (y/n/a/q/l)?", confirm.replacement);
+                self.command_input.show_output(vec![question]);
+                return self.vim_show_match((line, col), len);
+            }
+            confirm.line += 1;
+            confirm.from = 0;
         }
-        iced::Task::batch(tasks)
+        self.vim_confirm_done()
     }
 
-    fn vim_goto_end_or_line(&mut self) -> iced::Task<Message> {
-        let count = self.vim_take_count();
-        if count == 1 && self.vim_count.is_empty() {
-            // G with no count = end of file
-            self.vim_send_editor_msg(EditorMessage::CtrlEnd)
+    /// A key typed while `:s///c` asks: `y` replaces, `n` skips, `a`
+    /// replaces the rest, `l` replaces this one and stops, and anything
+    /// else stops.
+    pub(super) fn vim_confirm_answer(&mut self, answer: char) -> iced::Task<Message> {
+        let (Some(buffer), Some(mut confirm)) = (self.vim_buffer(), self.vim_confirm.take()) else {
+            return iced::Task::none();
+        };
+        let text = line_text(&buffer, confirm.line);
+        let Some(captures) = confirm.regex.captures_at(&text, confirm.from) else {
+            return self.vim_confirm_done();
+        };
+        let found = captures.get(0).expect("group 0 is the whole match");
+        // Step past an empty match so it is not found again.
+        let step = if found.is_empty() {
+            text[found.end()..].chars().next().map_or(1, char::len_utf8)
         } else {
-            // Ngg = go to line N (approximate via CtrlHome + N-1 ArrowDown)
-            let mut tasks = vec![self.vim_send_editor_msg(EditorMessage::CtrlHome)];
-            for _ in 0..count.saturating_sub(1) {
-                tasks.push(
-                    self.vim_send_editor_msg(EditorMessage::ArrowKey(ArrowDirection::Down, false)),
+            0
+        };
+
+        match answer {
+            'y' | 'l' => {
+                let new = ex::expand_replacement(&confirm.replacement, &captures);
+                let start = (confirm.line, text[..found.start()].chars().count());
+                let end = (start.0, start.1 + found.as_str().chars().count());
+                self.apply_text_edit(&TextEdit {
+                    start,
+                    end,
+                    text: new.clone(),
+                });
+                if answer == 'l' {
+                    return self.vim_confirm_done();
+                }
+                let breaks = new.matches('\n').count();
+                confirm.line += breaks;
+                confirm.last += breaks;
+                confirm.from = match new.rfind('\n') {
+                    Some(idx) => new.len() - idx - 1,
+                    None => found.start() + new.len(),
+                } + if new.is_empty() { step } else { 0 };
+            }
+            'n' => confirm.from = found.end() + step,
+            'a' => {
+                let mut lines: Vec<String> = (confirm.line..=confirm.last)
+                    .map(|line| line_text(&buffer, line))
+                    .collect();
+                let head = lines[0][..confirm.from].to_string();
+                lines[0].replace_range(..confirm.from, "");
+                let (mut new, _) = ex::substitute_lines(
+                    &lines,
+                    &confirm.regex,
+                    &confirm.replacement,
+                    confirm.global,
                 );
+                new[0].insert_str(0, &head);
+                self.apply_text_edit(&buffer.lines_edit(confirm.line, confirm.last + 1, &new));
+                return self.vim_confirm_done();
             }
-            iced::Task::batch(tasks)
+            _ => return self.vim_confirm_done(),
+        }
+        if !confirm.global {
+            confirm.line += 1;
+            confirm.from = 0;
         }
+        self.vim_confirm = Some(confirm);
+        self.vim_confirm_next()
     }
 
-    fn vim_apply_ctrl_motion(&mut self, ch: char) -> iced::Task<Message> {
-        match ch {
-            'f' => {
-                self.vim_count.clear();
-                self.vim_send_editor_msg(EditorMessage::PageDown)
-            }
-            'b' => {
-                self.vim_count.clear();
-                self.vim_send_editor_msg(EditorMessage::PageUp)
-            }
-            'd' => {
-                // Half page down: approximate with multiple ArrowDown
-                let half = 30;
-                let mut tasks = Vec::with_capacity(half);
-                for _ in 0..half {
-                    tasks.push(
-                        self.vim_send_editor_msg(EditorMessage::ArrowKey(
-                            ArrowDirection::Down,
-                            false,
-                        )),
-                    );
+    fn vim_confirm_done(&mut self) -> iced::Task<Message> {
+        self.vim_confirm = None;
+        self.command_input.close();
+        self.vim_refresh_cursor_style();
+        // Moving the cursor drops the selected match.
+        self.vim_goto_position(self.cursor_line, self.cursor_col)
+    }
+
+    /// `:g/pattern/command` and `:v`, for `d`, `s` and `p`. Deleted lines go
+    /// to the unnamed register together.
+    fn vim_ex_global(
+        &mut self,
+        (first, last): (usize, usize),
+        invert: bool,
+        args: &str,
+    ) -> Result<iced::Task<Message>, String> {
+        let (pattern, command) = ex::split_global(args)?;
+        let pattern = match pattern.as_str() {
+            "" => self.vim_ex_last_pattern()?,
+            _ => pattern,
+        };
+        let regex = ex::build_regex(&pattern, false)?;
+        let command = ex::parse(&command)?;
+        if command.range.is_some() {
+            return Err("Ranges are not supported after :g".to_string());
+        }
+        let action = match command.name.as_str() {
+            "" | "print" => GlobalAction::Print,
+            "delete" => GlobalAction::Delete,
+            "substitute" => {
+                let substitute = VimSubstitute::parse(&command.args)?;
+                if substitute.confirm {
+                    return Err("The c flag is not supported after :g".to_string());
                 }
-                iced::Task::batch(tasks)
-            }
-            'u' => {
-                // Half page up
-                let half = 30;
-                let mut tasks = Vec::with_capacity(half);
-                for _ in 0..half {
-                    tasks.push(
-                        self.vim_send_editor_msg(EditorMessage::ArrowKey(
-                            ArrowDirection::Up,
-                            false,
-                        )),
-                    );
+                let regex = match substitute.pattern.as_str() {
+                    "" => regex.clone(),
+                    _ => substitute.regex()?,
+                };
+                GlobalAction::Substitute {
+                    regex,
+                    replacement: substitute.replacement,
+                    global: substitute.global,
                 }
-                iced::Task::batch(tasks)
             }
-            _ => iced::Task::none(),
+            name => return Err(format!("Not supported after :g: {name}")),
+        };
+
+        let buffer = self.vim_ex_buffer()?;
+        let lines: Vec<String> = (first..=last)
+            .map(|line| line_text(&buffer, line))
+            .collect();
+        if !lines.iter().any(|line| regex.is_match(line) != invert) {
+            return Err(format!("Pattern not found: {pattern}"));
+        }
+        let result = ex::global_lines(&lines, &regex, invert, &action);
+        if !result.deleted.is_empty() {
+            let register = VimRegister {
+                text: result
+                    .deleted
+                    .iter()
+                    .map(|line| format!("{line}\n"))
+                    .collect(),
+                kind: RegisterKind::Lines,
+            };
+            self.vim_registers.store(None, register, false);
+        }
+        if result.lines != lines {
+            self.apply_text_edit(&buffer.lines_edit(first, last + 1, &result.lines));
+        }
+        if !result.printed.is_empty() {
+            self.vim_ex_print(result.printed);
         }
+        Ok(self.vim_goto_position(self.cursor_line, self.cursor_col))
     }
 
-    // --- Word motions --- //
+    /// `:d`, `:y` and `:p`. `:d` and `:y` take a register and a count, so
+    /// `:d a 3` deletes three lines from the end of the range into `a`.
+    fn vim_ex_lines(
+        &mut self,
+        name: &str,
+        (start, end): (usize, usize),
+        args: &str,
+    ) -> Result<iced::Task<Message>, String> {
+        let mut args = args.trim();
+        let mut register = None;
+        if let Some(reg) = args.chars().next() {
+            if name != "print" && !reg.is_ascii_digit() && is_register_name(reg) {
+                register = Some(reg);
+                args = args[reg.len_utf8()..].trim_start();
+            }
+        }
+        let (start, end) = match args {
+            "" => (start, end),
+            _ => {
+                let count = args
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|count| *count > 0)
+                    .ok_or_else(|| format!("Trailing characters: {args}"))?;
+                (end, end + count - 1)
+            }
+        };
+        let buffer = self.vim_ex_buffer()?;
+        let end = end.min(buffer.line_count() - 1);
+        let range = VimRange::Lines { start, end };
 
-    fn vim_content_text(&self) -> Option<String> {
-        let idx = self.active_tab?;
-        let tab = self.tabs.get(idx)?;
-        if let TabKind::Editor {
-            ref code_editor, ..
-        } = tab.kind
-        {
-            Some(code_editor.content())
-        } else {
-            None
+        match name {
+            "delete" => Ok(self.vim_apply_operator(VimOperator::Delete, range, 1, register)),
+            "yank" => Ok(self.vim_store_register(register, range_register(&buffer, range), true)),
+            _ => {
+                let lines = (start..=end).map(|line| line_text(&buffer, line)).collect();
+                self.vim_ex_print(lines);
+                let col = first_nonblank(&line_text(&buffer, end));
+                Ok(self.vim_goto_position(end + 1, col + 1))
+            }
         }
     }
 
-    fn vim_word_motion_forward(&mut self, big: bool) -> iced::Task<Message> {
-        let count = self.vim_take_count();
-        let Some(text) = self.vim_content_text() else {
-            return iced::Task::none();
-        };
-        let lines: Vec<&str> = text.split('\n').collect();
-        let mut idx = position_to_index(&lines, self.cursor_line, self.cursor_col);
-        for _ in 0..count {
-            idx = next_word_start(&text, idx, big);
+    /// Shows lines a command printed, at most a screenful of them.
+    fn vim_ex_print(&mut self, mut lines: Vec<String>) {
+        const MAX_LINES: usize = 50;
+        if lines.len() > MAX_LINES {
+            let more = lines.len() - MAX_LINES;
+            lines.truncate(MAX_LINES);
+            lines.push(format!("-- {more} more lines --"));
         }
-        let (target_line, target_col) = index_to_position(&lines, idx);
-        self.vim_goto_position(target_line, target_col)
+        self.command_input.show_output(lines);
     }
 
-    fn vim_word_motion_end(&mut self, big: bool) -> iced::Task<Message> {
-        let count = self.vim_take_count();
-        let Some(text) = self.vim_content_text() else {
-            return iced::Task::none();
+    /// `:wq` saves the file as `:w` would and quits once it is written; `:x`
+    /// only saves it if it changed. Other unsaved tabs are confirmed as for
+    /// `:q`, unless `!` is given.
+    fn vim_ex_write_quit(
+        &mut self,
+        always: bool,
+        force: bool,
+    ) -> Result<iced::Task<Message>, String> {
+        let tab = self.active_tab.and_then(|idx| self.tabs.get(idx));
+        let Some((path, modified)) = tab.and_then(|tab| match &tab.kind {
+            TabKind::Editor { code_editor, .. } => {
+                Some((tab.path.clone(), code_editor.is_modified()))
+            }
+            TabKind::Preview { .. } => None,
+        }) else {
+            return Err("No file is open".to_string());
         };
-        let lines: Vec<&str> = text.split('\n').collect();
-        let mut idx = position_to_index(&lines, self.cursor_line, self.cursor_col);
-        for _ in 0..count {
-            idx = next_word_end(&text, idx, big);
+        if always || modified {
+            if path == std::path::Path::new("untitled") {
+                return Err("No file name".to_string());
+            }
+            self.command_input.close();
+            self.quit_after_save = Some((path, force));
+            return Ok(self.update(Message::SaveFile));
         }
-        let (target_line, target_col) = index_to_position(&lines, idx);
-        self.vim_goto_position(target_line, target_col)
+        Ok(self.vim_ex_quit(force))
     }
 
-    fn vim_word_motion_backward(&mut self, big: bool) -> iced::Task<Message> {
-        let count = self.vim_take_count();
-        let Some(text) = self.vim_content_text() else {
-            return iced::Task::none();
-        };
-        let lines: Vec<&str> = text.split('\n').collect();
-        let mut idx = position_to_index(&lines, self.cursor_line, self.cursor_col);
-        for _ in 0..count {
-            idx = prev_word_start(&text, idx, big);
+    /// `:q` asks before discarding unsaved changes; `:q!` doesn't.
+    fn vim_ex_quit(&mut self, force: bool) -> iced::Task<Message> {
+        self.command_input.close();
+        if force {
+            iced::exit()
+        } else {
+            self.request_quit()
         }
-        let (target_line, target_col) = index_to_position(&lines, idx);
-        self.vim_goto_position(target_line, target_col)
     }
 
-    fn vim_match_pair(&mut self) -> iced::Task<Message> {
-        self.vim_count.clear();
-        let Some(text) = self.vim_content_text() else {
-            return iced::Task::none();
-        };
-        let lines: Vec<&str> = text.split('\n').collect();
-        let idx = position_to_index(&lines, self.cursor_line, self.cursor_col);
-        if let Some(target) = match_pair_index(&text, idx) {
-            let (l, c) = index_to_position(&lines, target);
-            self.vim_goto_position(l, c)
-        } else {
-            iced::Task::none()
+    /// `:e path`. There are no split windows, so `:sp path` and `:vs path`
+    /// open the file in a tab too.
+    fn vim_ex_edit(&mut self, typed: &str) -> Result<iced::Task<Message>, String> {
+        let path = ex::resolve_path(&self.vim_ex_dir(), typed);
+        if !path.is_file() {
+            return Err(format!("No such file: {typed}"));
         }
+        Ok(self.update(Message::FileClicked(path)))
     }
 
-    fn vim_move_first_nonblank(&mut self) -> iced::Task<Message> {
-        self.vim_count.clear();
-        let Some(text) = self.vim_content_text() else {
-            return iced::Task::none();
-        };
-        let lines: Vec<&str> = text.split('\n').collect();
-        let line_idx = self
-            .cursor_line
-            .saturating_sub(1)
-            .min(lines.len().saturating_sub(1));
-        if let Some(line) = lines.get(line_idx) {
-            let col = line
-                .chars()
-                .position(|c| !c.is_whitespace())
-                .map(|i| i + 1)
-                .unwrap_or(1);
-            self.vim_goto_position(self.cursor_line, col)
-        } else {
-            iced::Task::none()
+    /// What `:e` paths are relative to: the open folder, else the current
+    /// file's folder.
+    fn vim_ex_dir(&self) -> PathBuf {
+        if let Some(tree) = &self.file_tree {
+            return tree.root.clone();
         }
+        self.vim_path()
+            .and_then(|path| path.parent().map(std::path::Path::to_path_buf))
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| std::env::current_dir().ok())
+            .unwrap_or_default()
     }
 
-    fn vim_move_paragraph_next(&mut self) -> iced::Task<Message> {
-        self.vim_count.clear();
-        let Some(text) = self.vim_content_text() else {
+    /// Tab in the command bar completes the path after `:e`, `:sp` or `:vs`,
+    /// listing the candidates when there are several.
+    pub(super) fn vim_ex_complete(&mut self) -> iced::Task<Message> {
+        if !self.command_input.open || self.command_input.is_search() || self.vim_confirm.is_some()
+        {
             return iced::Task::none();
-        };
-        let lines: Vec<&str> = text.split('\n').collect();
-        let mut i = self.cursor_line;
-        while i < lines.len() && !lines[i.saturating_sub(1)].trim().is_empty() {
-            i += 1;
         }
-        while i < lines.len() && lines[i.saturating_sub(1)].trim().is_empty() {
-            i += 1;
+        let Some((name, typed)) = self.command_input.input.split_once(' ') else {
+            return iced::Task::none();
+        };
+        let edits = ex::parse(name).is_ok_and(|command| {
+            command.range.is_none() && matches!(command.name.as_str(), "edit" | "split" | "vsplit")
+        });
+        if !edits {
+            return iced::Task::none();
         }
-        self.vim_goto_position(i.min(lines.len()).max(1), 1)
+        let (completed, candidates) = ex::complete_path(&self.vim_ex_dir(), typed.trim_start());
+        self.command_input.input = format!("{name} {completed}");
+        self.command_input.output = candidates;
+        iced::widget::operation::move_cursor_to_end(self.command_input_id.clone())
     }
 
-    fn vim_move_paragraph_prev(&mut self) -> iced::Task<Message> {
-        self.vim_count.clear();
-        let Some(text) = self.vim_content_text() else {
+    // --- Block insert --- //
+
+    fn vim_start_block_insert(
+        &mut self,
+        top: usize,
+        bottom: usize,
+        col: usize,
+        pad: bool,
+    ) -> iced::Task<Message> {
+        let Some(buffer) = self.vim_buffer() else {
             return iced::Task::none();
         };
-        let lines: Vec<&str> = text.split('\n').collect();
-        let mut i = self.cursor_line.saturating_sub(1);
-        while i > 0 && lines[i.saturating_sub(1)].trim().is_empty() {
-            i = i.saturating_sub(1);
-        }
-        while i > 0 && !lines[i.saturating_sub(1)].trim().is_empty() {
-            i = i.saturating_sub(1);
+        let mut line_before = line_text(&buffer, top);
+        let missing = col.saturating_sub(line_before.chars().count());
+        if pad && missing > 0 {
+            let end = (top, buffer.line_len(top));
+            self.apply_text_edit(&TextEdit {
+                start: end,
+                end,
+                text: " ".repeat(missing),
+            });
+            line_before.push_str(&" ".repeat(missing));
         }
-        self.vim_goto_position(i.max(1), 1)
-    }
 
-    /// Move cursor to an absolute position using CtrlHome + arrow keys.
-    fn vim_goto_position(&mut self, target_line: usize, target_col: usize) -> iced::Task<Message> {
-        let mut tasks = vec![self.vim_send_editor_msg(EditorMessage::CtrlHome)];
-        let line_moves = target_line.saturating_sub(1);
-        for _ in 0..line_moves {
-            tasks.push(
-                self.vim_send_editor_msg(EditorMessage::ArrowKey(ArrowDirection::Down, false)),
-            );
-        }
-        // Use Home to ensure we're at column 1, then move to the target column
-        tasks.push(self.vim_send_editor_msg(EditorMessage::Home(false)));
-        let col_moves = target_col.saturating_sub(1);
-        for _ in 0..col_moves {
-            tasks.push(
-                self.vim_send_editor_msg(EditorMessage::ArrowKey(ArrowDirection::Right, false)),
-            );
-        }
-        self.cursor_line = target_line;
-        self.cursor_col = target_col;
-        iced::Task::batch(tasks)
+        self.vim_block_insert = Some(VimBlockInsert {
+            top,
+            bottom,
+            col: col.min(line_before.chars().count()),
+            pad,
+            line_before,
+        });
+        self.vim_set_mode(VimMode::Insert);
+        self.vim_goto_position(top + 1, col + 1)
     }
 
-    // --- Find char motions --- //
-
-    fn vim_find_char(&mut self, ch: char, backward: bool, till: bool) -> iced::Task<Message> {
-        self.vim_last_find = Some(VimFindState {
-            kind: match (backward, till) {
-                (false, false) => VimFindKind::ForwardTo,
-                (false, true) => VimFindKind::ForwardTill,
-                (true, false) => VimFindKind::BackwardTo,
-                (true, true) => VimFindKind::BackwardTill,
-            },
-            needle: ch,
-        });
-        let count = self.vim_take_count();
-        let Some(text) = self.vim_content_text() else {
-            return iced::Task::none();
+    /// Copies what was typed on the block's first line to its other lines.
+    fn vim_finish_block_insert(&mut self, insert: VimBlockInsert) {
+        let Some(buffer) = self.vim_buffer() else {
+            return;
         };
-        let lines: Vec<&str> = text.split('\n').collect();
-        let line_idx = self
-            .cursor_line
-            .saturating_sub(1)
-            .min(lines.len().saturating_sub(1));
-        let Some(line) = lines.get(line_idx) else {
-            return iced::Task::none();
+        let after = line_text(&buffer, insert.top);
+        let Some(inserted) = inserted_text(&insert.line_before, &after, insert.col) else {
+            return;
         };
-        let chars: Vec<char> = line.chars().collect();
-        let cur = self.cursor_col.saturating_sub(1).min(chars.len());
-
-        let mut result_col = None;
-        if !backward {
-            let mut found_count = 0;
-            for (pos, &c) in chars.iter().enumerate().skip(cur.saturating_add(1)) {
-                if c == ch {
-                    found_count += 1;
-                    if found_count == count {
-                        result_col = Some(if till { pos.saturating_sub(1) } else { pos });
-                        break;
-                    }
-                }
-            }
-        } else {
-            let mut found_count = 0;
-            for i in (0..cur).rev() {
-                if chars[i] == ch {
-                    found_count += 1;
-                    if found_count == count {
-                        result_col = Some(if till { (i + 1).min(chars.len()) } else { i });
-                        break;
-                    }
+        let lines: Vec<String> = (insert.top + 1..=insert.bottom)
+            .map(|line| {
+                let text = line_text(&buffer, line);
+                let len = text.chars().count();
+                if len < insert.col && !insert.pad {
+                    return text;
                 }
-            }
-        }
-
-        if let Some(col) = result_col {
-            self.vim_goto_position(self.cursor_line, col + 1)
-        } else {
-            iced::Task::none()
-        }
+                let mut chars: Vec<char> = text.chars().collect();
+                chars.resize(len.max(insert.col), ' ');
+                chars.splice(insert.col..insert.col, inserted.chars());
+                chars.into_iter().collect()
+            })
+            .collect();
+        self.replace_lines(&buffer, insert.top + 1, &lines);
     }
+}
 
-    fn vim_repeat_last_find(&mut self, reverse: bool) -> iced::Task<Message> {
-        if let Some(last) = self.vim_last_find {
-            let (backward, till) = if reverse {
-                match last.kind {
-                    VimFindKind::ForwardTo => (true, false),
-                    VimFindKind::ForwardTill => (true, true),
-                    VimFindKind::BackwardTo => (false, false),
-                    VimFindKind::BackwardTill => (false, true),
-                }
+/// Where `search`, repeated `count` times, goes from `from`; `reverse`
+/// for `N`.
+fn search_target(
+    search: &VimSearch,
+    text: &str,
+    from: (usize, usize),
+    reverse: bool,
+    count: Option<usize>,
+) -> Option<MotionTarget> {
+    let forward = search.backward == reverse;
+    let found = search.next_match(text, byte_offset(text, from), forward, count.unwrap_or(1))?;
+    Some(MotionTarget {
+        pos: byte_position(text, found),
+        linewise: false,
+        inclusive: false,
+    })
+}
+
+/// A line's text without its line break.
+fn line_text(buffer: &EditorBuffer, line: usize) -> String {
+    buffer.line(line).trim_end_matches(['\n', '\r']).to_string()
+}
+
+fn first_nonblank(line: &str) -> usize {
+    line.chars().take_while(|c| c.is_whitespace()).count()
+}
+
+/// Keeps a range inside the buffer. A charwise range ending past the end of
+/// its line takes the line break with it.
+fn clamp_range(buffer: &EditorBuffer, range: VimRange) -> VimRange {
+    let last = buffer.line_count() - 1;
+    match range {
+        VimRange::Chars { start, end } => {
+            let end = if end.1 > buffer.line_len(end.0) && end.0 < last {
+                (end.0 + 1, 0)
             } else {
-                match last.kind {
-                    VimFindKind::ForwardTo => (false, false),
-                    VimFindKind::ForwardTill => (false, true),
-                    VimFindKind::BackwardTo => (true, false),
-                    VimFindKind::BackwardTill => (true, true),
-                }
+                end
             };
-            self.vim_find_char(last.needle, backward, till)
-        } else {
-            iced::Task::none()
+            VimRange::Chars { start, end }
         }
+        VimRange::Lines { start, end } => VimRange::Lines {
+            start: start.min(last),
+            end: end.min(last),
+        },
+        VimRange::Block {
+            top,
+            bottom,
+            left,
+            right,
+        } => VimRange::Block {
+            top: top.min(last),
+            bottom: bottom.min(last),
+            left,
+            right,
+        },
     }
+}
 
-    // --- Delete operations --- //
+/// The text from the cursor to where a motion lands. An exclusive motion
+/// that ends at the start of a later line stops at the end of the line
+/// before, and covers whole lines if it also began at or before the first
+/// non-blank, so `d}` deletes a paragraph's lines.
+fn motion_range(text: &MotionText, cursor: (usize, usize), target: MotionTarget) -> VimRange {
+    let (start, end) = if target.pos < cursor {
+        (target.pos, cursor)
+    } else {
+        (cursor, target.pos)
+    };
+    if target.linewise {
+        return VimRange::Lines {
+            start: start.0,
+            end: end.0,
+        };
+    }
+    if target.inclusive {
+        return VimRange::Chars {
+            start,
+            end: (end.0, end.1 + 1),
+        };
+    }
+    if end.1 == 0 && end.0 > start.0 {
+        if start.1 <= text.first_nonblank(start.0) {
+            return VimRange::Lines {
+                start: start.0,
+                end: end.0 - 1,
+            };
+        }
+        let end = (end.0 - 1, text.line_len(end.0 - 1));
+        return VimRange::Chars { start, end };
+    }
+    VimRange::Chars { start, end }
+}
 
-    fn vim_delete_line(&mut self) -> iced::Task<Message> {
-        self.vim_count.clear();
-        // Select entire current line and delete it
-        let t1 = self.vim_send_editor_msg(EditorMessage::Home(false));
-        // Select to end of line
-        let t2 = self.vim_send_editor_msg(EditorMessage::End(true));
-        // Delete the selection
-        let t3 = self.vim_send_editor_msg(EditorMessage::Backspace);
-        // Delete the remaining newline
-        let t4 = self.vim_send_editor_msg(EditorMessage::Backspace);
-        iced::Task::batch([t1, t2, t3, t4])
+fn range_start(range: VimRange) -> (usize, usize) {
+    match range {
+        VimRange::Chars { start, .. } => start,
+        VimRange::Lines { start, .. } => (start, 0),
+        VimRange::Block { top, left, .. } => (top, left),
     }
+}
 
-    fn vim_delete_word(&mut self) -> iced::Task<Message> {
-        self.vim_count.clear();
-        // Approximate: select word forward with shift+right arrows then delete
-        let Some(text) = self.vim_content_text() else {
-            return iced::Task::none();
-        };
-        let lines: Vec<&str> = text.split('\n').collect();
-        let idx = position_to_index(&lines, self.cursor_line, self.cursor_col);
-        let end = next_word_start(&text, idx, false);
-        let chars_to_select = end.saturating_sub(idx);
+/// First and last line a range touches.
+fn range_lines(range: VimRange) -> (usize, usize) {
+    match range {
+        VimRange::Chars { start, end } if end.0 > start.0 && end.1 == 0 => (start.0, end.0 - 1),
+        VimRange::Chars { start, end } => (start.0, end.0),
+        VimRange::Lines { start, end } => (start, end),
+        VimRange::Block { top, bottom, .. } => (top, bottom),
+    }
+}
 
-        let mut tasks = Vec::with_capacity(chars_to_select + 1);
-        for _ in 0..chars_to_select {
-            tasks.push(
-                self.vim_send_editor_msg(EditorMessage::ArrowKey(ArrowDirection::Right, true)),
-            );
-        }
-        tasks.push(self.vim_send_editor_msg(EditorMessage::Backspace));
-        iced::Task::batch(tasks)
+fn range_register(buffer: &EditorBuffer, range: VimRange) -> VimRegister {
+    match range {
+        VimRange::Chars { start, end } => VimRegister {
+            text: buffer.text_between(start, end),
+            kind: RegisterKind::Chars,
+        },
+        VimRange::Lines { start, end } => VimRegister {
+            text: (start..=end)
+                .map(|line| line_text(buffer, line) + "\n")
+                .collect(),
+            kind: RegisterKind::Lines,
+        },
+        VimRange::Block {
+            top,
+            bottom,
+            left,
+            right,
+        } => VimRegister {
+            text: (top..=bottom)
+                .map(|line| {
+                    line_text(buffer, line)
+                        .chars()
+                        .skip(left)
+                        .take(right - left)
+                        .collect::<String>()
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+            kind: RegisterKind::Block,
+        },
     }
 }
 
-// --- Helper functions (preserved from original) --- //
+/// Applies `f` to columns `left..right` of `line`.
+fn map_columns(line: &str, left: usize, right: usize, f: impl Fn(&str) -> String) -> String {
+    let chars: Vec<char> = line.chars().collect();
+    let left = left.min(chars.len());
+    let right = right.clamp(left, chars.len());
+    let middle: String = chars[left..right].iter().collect();
+    let head: String = chars[..left].iter().collect();
+    let tail: String = chars[right..].iter().collect();
+    format!("{head}{}{tail}", f(&middle))
+}
 
-fn is_word_char(ch: char) -> bool {
-    ch.is_alphanumeric() || ch == '_'
+fn block_delete(line: &str, left: usize, right: usize) -> String {
+    map_columns(line, left, right, |_| String::new())
 }
 
-fn position_to_index(lines: &[&str], line_1: usize, col_1: usize) -> usize {
-    let mut idx = 0usize;
-    let line_idx = line_1.saturating_sub(1).min(lines.len().saturating_sub(1));
-    for line in &lines[..line_idx] {
-        idx += line.chars().count() + 1;
+/// Puts one line of a block at column `col`, padding the line out to `col`
+/// and the piece out to `width` when text follows it.
+fn block_put(line: &str, col: usize, piece: &str, width: usize) -> String {
+    let mut chars: Vec<char> = line.chars().collect();
+    if chars.len() < col {
+        chars.resize(col, ' ');
     }
-    let line_len = lines.get(line_idx).map(|l| l.chars().count()).unwrap_or(0);
-    idx + col_1.saturating_sub(1).min(line_len)
+    let mut inserted: Vec<char> = piece.chars().collect();
+    if chars.len() > col {
+        inserted.resize(width.max(inserted.len()), ' ');
+    }
+    chars.splice(col..col, inserted);
+    chars.into_iter().collect()
 }
 
-fn index_to_position(lines: &[&str], mut idx: usize) -> (usize, usize) {
-    for (i, line) in lines.iter().enumerate() {
-        let len = line.chars().count();
-        if idx <= len {
-            return (i + 1, idx + 1);
-        }
-        idx = idx.saturating_sub(len + 1);
+fn toggle_case(text: &str) -> String {
+    text.chars()
+        .flat_map(|c| {
+            if c.is_uppercase() {
+                c.to_lowercase().collect::<Vec<_>>()
+            } else {
+                c.to_uppercase().collect()
+            }
+        })
+        .collect()
+}
+
+/// Blank lines are left alone, as in vim.
+fn indent_line(line: &str, unit: &str) -> String {
+    if line.trim().is_empty() {
+        line.to_string()
+    } else {
+        format!("{unit}{line}")
     }
-    let last = lines.len().max(1);
-    let col = lines.last().map(|l| l.chars().count() + 1).unwrap_or(1);
-    (last, col)
 }
 
-fn next_word_start(text: &str, idx: usize, big: bool) -> usize {
-    let chars: Vec<char> = text.chars().collect();
-    let mut i = idx.min(chars.len());
-    while i < chars.len()
-        && if big {
-            !chars[i].is_whitespace()
-        } else {
-            is_word_char(chars[i])
-        }
-    {
-        i += 1;
+/// Removes one level of indentation: a tab, or up to `tab_size` spaces.
+fn outdent_line(line: &str, tab_size: usize) -> String {
+    if let Some(rest) = line.strip_prefix('\t') {
+        return rest.to_string();
     }
-    while i < chars.len() && chars[i].is_whitespace() {
-        i += 1;
+    let spaces = line
+        .chars()
+        .take(tab_size)
+        .take_while(|&c| c == ' ')
+        .count();
+    line[spaces..].to_string()
+}
+
+/// Indents `lines` by the brackets that open and close around them,
+/// starting from `above`, the nearest non-blank line before them. A line
+/// starting with a closing bracket lines up with the line that opened it.
+fn reindent_lines(above: Option<&str>, lines: &[String], unit: &str) -> Vec<String> {
+    let depth_change = |line: &str| -> isize {
+        line.chars()
+            .map(|c| match c {
+                '(' | '[' | '{' => 1,
+                ')' | ']' | '}' => -1,
+                _ => 0,
+            })
+            .sum()
+    };
+    let base: String = above
+        .unwrap_or("")
+        .chars()
+        .take_while(|c| c.is_whitespace())
+        .collect();
+    let mut depth = above.map_or(0, |line| {
+        let trimmed = line.trim_end();
+        isize::from(depth_change(line) > 0 || trimmed.ends_with(':'))
+    });
+
+    lines
+        .iter()
+        .map(|line| {
+            let trimmed = line.trim_start();
+            if trimmed.is_empty() {
+                return String::new();
+            }
+            let closes = trimmed.starts_with([')', ']', '}']);
+            let level = (depth - isize::from(closes)).max(0) as usize;
+            depth += depth_change(trimmed);
+            format!("{base}{}{trimmed}", unit.repeat(level))
+        })
+        .collect()
+}
+
+/// The text typed at `col` that turned `before` into `after`, if that is
+/// all that changed.
+fn inserted_text(before: &str, after: &str, col: usize) -> Option<String> {
+    let before: Vec<char> = before.chars().collect();
+    let after: Vec<char> = after.chars().collect();
+    let added = after.len().checked_sub(before.len()).filter(|&n| n > 0)?;
+    let col = col.min(before.len());
+    if after[..col] != before[..col] || after[col + added..] != before[col..] {
+        return None;
     }
-    while i < chars.len() && !chars[i].is_whitespace() && !big && !is_word_char(chars[i]) {
-        i += 1;
+    Some(after[col..col + added].iter().collect())
+}
+
+/// How much of the insert-mode escape sequence has been typed once `ch`
+/// follows the first `typed` characters. `typed` may come from a longer
+/// sequence the preferences have since replaced.
+fn escape_progress(sequence: &[char], typed: usize, ch: char) -> usize {
+    if sequence.get(typed) == Some(&ch) {
+        typed + 1
+    } else {
+        usize::from(sequence.first() == Some(&ch))
     }
-    i
 }
 
-fn next_word_end(text: &str, idx: usize, big: bool) -> usize {
-    let chars: Vec<char> = text.chars().collect();
-    let mut i = idx.min(chars.len());
-    while i < chars.len() && chars[i].is_whitespace() {
-        i += 1;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_columns_are_edited_per_line() {
+        assert_eq!(block_delete("abcdef", 1, 3), "adef");
+        assert_eq!(block_delete("ab", 3, 5), "ab");
+        assert_eq!(map_columns("abcdef", 2, 4, toggle_case), "abCDef");
+        assert_eq!(block_put("abcd", 1, "x", 3), "ax  bcd");
+        assert_eq!(block_put("a", 3, "xy", 3), "a  xy");
     }
-    while i < chars.len()
-        && if big {
-            !chars[i].is_whitespace()
-        } else {
-            is_word_char(chars[i])
-        }
-    {
-        i += 1;
+
+    #[test]
+    fn escape_progress_survives_a_shorter_sequence() {
+        let jk: Vec<char> = "jk".chars().collect();
+        assert_eq!(escape_progress(&jk, 0, 'j'), 1);
+        assert_eq!(escape_progress(&jk, 1, 'k'), 2);
+        assert_eq!(escape_progress(&jk, 1, 'j'), 1);
+        assert_eq!(escape_progress(&jk, 1, 'x'), 0);
+        // Two characters of `jkl` were typed before `vim_escape` became `jk`.
+        assert_eq!(escape_progress(&jk, 2, 'l'), 0);
+        assert_eq!(escape_progress(&jk, 2, 'j'), 1);
     }
-    i.saturating_sub(1)
-}
 
-fn prev_word_start(text: &str, idx: usize, big: bool) -> usize {
-    let chars: Vec<char> = text.chars().collect();
-    if chars.is_empty() {
-        return 0;
+    #[test]
+    fn outdent_removes_one_level() {
+        assert_eq!(outdent_line("\t\tx", 4), "\tx");
+        assert_eq!(outdent_line("      x", 4), "  x");
+        assert_eq!(outdent_line("  x", 4), "x");
+        assert_eq!(indent_line("", "    "), "");
     }
-    let mut i = idx.saturating_sub(1).min(chars.len().saturating_sub(1));
-    while i > 0 && chars[i].is_whitespace() {
-        i -= 1;
+
+    #[test]
+    fn reindent_follows_the_brackets() {
+        let lines: Vec<String> = ["let x = [", "1,", "  ];", "", "  }"]
+            .iter()
+            .map(|line| line.to_string())
+            .collect();
+        assert_eq!(
+            reindent_lines(Some("  fn f() {"), &lines, "  "),
+            ["    let x = [", "      1,", "    ];", "", "  }"]
+        );
+        assert_eq!(reindent_lines(None, &lines[1..2], "  "), ["1,"]);
     }
-    while i > 0
-        && if big {
-            !chars[i - 1].is_whitespace()
-        } else {
-            is_word_char(chars[i - 1])
-        }
-    {
-        i -= 1;
+
+    #[test]
+    fn inserted_text_finds_what_was_typed_at_the_column() {
+        assert_eq!(
+            inserted_text("ab cd", "ab // cd", 3).as_deref(),
+            Some("// ")
+        );
+        assert_eq!(inserted_text("ab cd", "ab cd", 3), None);
+        assert_eq!(inserted_text("ab cd", "xab cd", 3), None);
     }
-    i
-}
 
-fn match_pair_index(text: &str, idx: usize) -> Option<usize> {
-    let chars: Vec<char> = text.chars().collect();
-    if chars.is_empty() {
-        return None;
+    #[test]
+    fn registers_keep_the_shape_of_the_range() {
+        let buffer = EditorBuffer::from_text("one\ntwo\nthree");
+        let lines = range_register(&buffer, VimRange::Lines { start: 0, end: 1 });
+        assert_eq!(lines.text, "one\ntwo\n");
+        assert_eq!(lines.kind, RegisterKind::Lines);
+        let block = VimRange::Block {
+            top: 0,
+            bottom: 2,
+            left: 1,
+            right: 3,
+        };
+        assert_eq!(range_register(&buffer, block).text, "ne\nwo\nhr");
+        let chars = clamp_range(
+            &buffer,
+            VimRange::Chars {
+                start: (0, 2),
+                end: (0, 4),
+            },
+        );
+        assert_eq!(range_register(&buffer, chars).text, "e\n");
     }
-    let i = idx.min(chars.len().saturating_sub(1));
-    let ch = chars[i];
-    let (open, close, forward) = match ch {
-        '(' => ('(', ')', true),
-        '[' => ('[', ']', true),
-        '{' => ('{', '}', true),
-        ')' => ('(', ')', false),
-        ']' => ('[', ']', false),
-        '}' => ('{', '}', false),
-        _ => return None,
-    };
-    let mut depth = 0i32;
-    if forward {
-        for (j, c) in chars.iter().enumerate().skip(i) {
-            if *c == open {
-                depth += 1;
-            } else if *c == close {
-                depth -= 1;
-                if depth == 0 {
-                    return Some(j);
-                }
-            }
+
+    fn modified(app: &App, idx: usize) -> bool {
+        matches!(&app.tabs[idx].kind, TabKind::Editor { code_editor, .. } if code_editor.is_modified())
+    }
+
+    #[test]
+    fn write_quit_saves_before_quitting() {
+        let other = PathBuf::from("/tmp/other.txt");
+        let file = PathBuf::from("/tmp/file.txt");
+        let mut app = App::default();
+        app.editor_preferences.autosave_enabled = false;
+        app.open_test_tab(other.clone(), "other");
+        if let TabKind::Editor { code_editor, .. } = &mut app.tabs[0].kind {
+            let _ = code_editor.update(&EditorMessage::Paste("edited ".to_string()));
         }
-    } else {
-        for j in (0..=i).rev() {
-            let c = chars[j];
-            if c == close {
-                depth += 1;
-            } else if c == open {
-                depth -= 1;
-                if depth == 0 {
-                    return Some(j);
-                }
-            }
+        app.open_test_tab(file.clone(), "file");
+
+        // `:wq` saves even an unchanged file, then asks about the other tab
+        // once the save is done.
+        let _ = app.vim_ex_command("wq");
+        assert_eq!(app.quit_after_save, Some((file.clone(), false)));
+        assert_eq!(app.pending_quit, None);
+        let _ = app.update(Message::FileSaved(Ok(())));
+        assert_eq!(app.quit_after_save, None);
+        assert_eq!(app.pending_quit, Some(vec!["other.txt".to_string()]));
+
+        // `:x` only saves a changed file.
+        app.pending_quit = None;
+        let _ = app.vim_ex_command("x");
+        assert_eq!(app.quit_after_save, None);
+        assert_eq!(app.pending_quit, Some(vec!["other.txt".to_string()]));
+
+        // A failed save cancels the quit.
+        app.pending_quit = None;
+        app.active_tab = Some(0);
+        let _ = app.vim_ex_command("x");
+        assert_eq!(app.quit_after_save, Some((other.clone(), false)));
+        let _ = app.update(Message::FileSaved(Err("disk full".to_string())));
+        assert_eq!(app.quit_after_save, None);
+        assert_eq!(app.pending_quit, None);
+        assert!(modified(&app, 0));
+
+        app.tabs.push(Tab {
+            path: PathBuf::from("untitled"),
+            name: "untitled".into(),
+            kind: TabKind::Preview {
+                md_items: Vec::new(),
+            },
+            autosave_requested_at: None,
+            autosave_in_flight: false,
+        });
+        app.active_tab = Some(2);
+        let _ = app.vim_ex_command("wq");
+        assert_eq!(app.command_input.output, ["No file is open"]);
+    }
+
+    #[test]
+    fn quit_asks_about_unsaved_changes_unless_forced() {
+        let mut app = App::default();
+        app.editor_preferences.autosave_enabled = false;
+        app.open_test_tab("/tmp/notes.txt", "notes");
+        if let TabKind::Editor { code_editor, .. } = &mut app.tabs[0].kind {
+            let _ = code_editor.update(&EditorMessage::Paste("edited ".to_string()));
         }
+
+        let _ = app.vim_ex_command("q!");
+        assert!(app.command_input.output.is_empty());
+        assert_eq!(app.pending_quit, None);
+
+        let _ = app.vim_ex_command("q");
+        assert_eq!(app.pending_quit, Some(vec!["notes.txt".to_string()]));
     }
-    None
 }
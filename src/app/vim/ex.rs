@@ -0,0 +1,657 @@
+//! The `:` command language: an optional line range (`%`, `.,$`, `'<,'>`,
+//! `12`, `.+3`), a command name abbreviated as in vim (`s` for
+//! `substitute`), `!`, and arguments. The work `:s`, `:g` and `:set` do on
+//! lines and preferences happens here; the editor applies the result.
+
+use crate::config::preferences::EditorPreferences;
+use regex::{Captures, Regex, RegexBuilder};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LineBase {
+    /// A 1-based line number.
+    Number(usize),
+    Current,
+    Last,
+    Mark(char),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Address {
+    base: LineBase,
+    offset: isize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineRange {
+    start: Address,
+    end: Address,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExCommand {
+    pub range: Option<LineRange>,
+    /// The full name, such as `substitute` for `s`. Names vim does not
+    /// handle itself are kept as typed, and a bare range (`:12`) has none.
+    pub name: String,
+    pub bang: bool,
+    pub args: String,
+}
+
+/// Commands by full name, with the length of their shortest abbreviation.
+const COMMANDS: &[(&str, usize)] = &[
+    ("substitute", 1),
+    ("global", 1),
+    ("vglobal", 1),
+    ("delete", 1),
+    ("yank", 1),
+    ("print", 1),
+    ("edit", 1),
+    ("split", 2),
+    ("vsplit", 2),
+    ("bnext", 2),
+    ("bNext", 2),
+    ("bprevious", 2),
+    ("bdelete", 2),
+    ("set", 2),
+    ("registers", 3),
+    ("display", 2),
+    ("quit", 1),
+    ("xit", 1),
+    ("exit", 3),
+];
+
+pub fn parse(input: &str) -> Result<ExCommand, String> {
+    let input = input.trim_start_matches([' ', ':']);
+    let (range, rest) = parse_range(input)?;
+    let rest = rest.trim_start();
+    let name_len = rest
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(rest.len());
+    let (name, rest) = rest.split_at(name_len);
+    let name = COMMANDS
+        .iter()
+        .find(|(full, min)| name.len() >= *min && full.starts_with(name))
+        .map_or(name, |(full, _)| full);
+    let (bang, rest) = match rest.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, rest),
+    };
+    if name.is_empty() && (bang || !rest.trim().is_empty()) {
+        return Err(format!("Not an editor command: {input}"));
+    }
+    Ok(ExCommand {
+        range,
+        name: name.to_string(),
+        bang,
+        args: rest.trim_start().to_string(),
+    })
+}
+
+fn parse_range(input: &str) -> Result<(Option<LineRange>, &str), String> {
+    if let Some(rest) = input.strip_prefix('%') {
+        let range = LineRange {
+            start: Address {
+                base: LineBase::Number(1),
+                offset: 0,
+            },
+            end: Address {
+                base: LineBase::Last,
+                offset: 0,
+            },
+        };
+        return Ok((Some(range), rest));
+    }
+    let (Some(start), rest) = parse_address(input)? else {
+        return Ok((None, input));
+    };
+    let Some(rest) = rest.strip_prefix([',', ';']) else {
+        return Ok((Some(LineRange { start, end: start }), rest));
+    };
+    // `1,` ends at the cursor line, as in vim.
+    let (end, rest) = parse_address(rest)?;
+    let end = end.unwrap_or(Address {
+        base: LineBase::Current,
+        offset: 0,
+    });
+    Ok((Some(LineRange { start, end }), rest))
+}
+
+fn parse_address(input: &str) -> Result<(Option<Address>, &str), String> {
+    let digits = |text: &str| {
+        text.find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(text.len())
+    };
+    let (base, mut rest) = match input.chars().next() {
+        Some('.') => (Some(LineBase::Current), &input[1..]),
+        Some('$') => (Some(LineBase::Last), &input[1..]),
+        Some('\'') => {
+            let name = input[1..].chars().next().ok_or("Invalid range")?;
+            (Some(LineBase::Mark(name)), &input[1 + name.len_utf8()..])
+        }
+        Some(c) if c.is_ascii_digit() => {
+            let end = digits(input);
+            let line = input[..end].parse().map_err(|_| "Invalid range")?;
+            (Some(LineBase::Number(line)), &input[end..])
+        }
+        _ => (None, input),
+    };
+
+    let mut offset = 0isize;
+    let mut has_offset = false;
+    while let Some(sign) = rest.chars().next().filter(|c| *c == '+' || *c == '-') {
+        rest = &rest[1..];
+        let end = digits(rest);
+        let step: isize = match end {
+            0 => 1,
+            _ => rest[..end].parse().map_err(|_| "Invalid range")?,
+        };
+        rest = &rest[end..];
+        offset += if sign == '+' { step } else { -step };
+        has_offset = true;
+    }
+    if base.is_none() && !has_offset {
+        return Ok((None, input));
+    }
+    let base = base.unwrap_or(LineBase::Current);
+    Ok((Some(Address { base, offset }), rest))
+}
+
+impl LineRange {
+    /// The 0-based first and last line, in order. Lines past either end
+    /// of the buffer are clamped; `mark` gives the line a mark is on.
+    pub fn resolve(
+        &self,
+        cursor: usize,
+        last: usize,
+        mark: impl Fn(char) -> Option<usize>,
+    ) -> Result<(usize, usize), String> {
+        let line = |address: Address| -> Result<usize, String> {
+            let base = match address.base {
+                LineBase::Number(line) => line as isize - 1,
+                LineBase::Current => cursor as isize,
+                LineBase::Last => last as isize,
+                LineBase::Mark(name) => mark(name).ok_or("Mark not set")? as isize,
+            };
+            Ok((base + address.offset).clamp(0, last as isize) as usize)
+        };
+        let (start, end) = (line(self.start)?, line(self.end)?);
+        Ok((start.min(end), start.max(end)))
+    }
+}
+
+/// `:s/pattern/replacement/flags`.
+#[derive(Debug, Clone)]
+pub struct VimSubstitute {
+    pub pattern: String,
+    pub replacement: String,
+    /// `g`: every match on a line, not just the first.
+    pub global: bool,
+    /// `c`: ask before each replacement.
+    pub confirm: bool,
+    /// `i` or `I`; otherwise case matters.
+    pub ignore_case: bool,
+}
+
+impl VimSubstitute {
+    pub fn parse(args: &str) -> Result<Self, String> {
+        let mut chars = args.chars();
+        let delimiter = chars.next().ok_or("Missing pattern")?;
+        if delimiter.is_alphanumeric() || matches!(delimiter, '\\' | '"' | '|' | ' ') {
+            return Err("Patterns can't be delimited by letters".to_string());
+        }
+        let rest = chars.as_str();
+        let (pattern, rest) = split_delimited(rest, delimiter);
+        let (replacement, flags) = split_delimited(rest.unwrap_or(""), delimiter);
+
+        let mut substitute = VimSubstitute {
+            pattern,
+            replacement,
+            global: false,
+            confirm: false,
+            ignore_case: false,
+        };
+        for flag in flags.unwrap_or("").trim_end().chars() {
+            match flag {
+                'g' => substitute.global = true,
+                'c' => substitute.confirm = true,
+                'i' => substitute.ignore_case = true,
+                'I' => substitute.ignore_case = false,
+                _ => return Err(format!("Trailing characters: {flag}")),
+            }
+        }
+        Ok(substitute)
+    }
+
+    pub fn regex(&self) -> Result<Regex, String> {
+        build_regex(&self.pattern, self.ignore_case)
+    }
+}
+
+/// Splits `text` at the first `delimiter` not escaped with `\`, dropping
+/// the `\` of an escaped delimiter. `None` for the rest if there is no
+/// delimiter.
+fn split_delimited(text: &str, delimiter: char) -> (String, Option<&str>) {
+    let mut part = String::new();
+    let mut chars = text.char_indices();
+    while let Some((idx, ch)) = chars.next() {
+        if ch == delimiter {
+            return (part, Some(&text[idx + ch.len_utf8()..]));
+        }
+        if ch == '\\' {
+            match chars.next() {
+                Some((_, next)) if next == delimiter => part.push(next),
+                Some((_, next)) => {
+                    part.push('\\');
+                    part.push(next);
+                }
+                None => part.push('\\'),
+            }
+        } else {
+            part.push(ch);
+        }
+    }
+    (part, None)
+}
+
+/// Compiles a pattern in Rust regex syntax, also taking vim's `\<` and
+/// `\>` word boundaries.
+pub fn build_regex(pattern: &str, ignore_case: bool) -> Result<Regex, String> {
+    let pattern = pattern.replace("\\<", "\\b").replace("\\>", "\\b");
+    RegexBuilder::new(&pattern)
+        .case_insensitive(ignore_case)
+        .build()
+        .map_err(|err| {
+            let message = err.to_string();
+            let reason = message.lines().last().unwrap_or_default();
+            format!("Invalid pattern: {}", reason.trim_start_matches("error: "))
+        })
+}
+
+/// The text a `:s` replacement stands for: `&` and `\0` are the match,
+/// `\1`-`\9` its groups, and `\r` or `\n` a line break.
+pub fn expand_replacement(replacement: &str, captures: &Captures) -> String {
+    let mut out = String::new();
+    let mut chars = replacement.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '&' => out.push_str(&captures[0]),
+            '\\' => match chars.next() {
+                Some(digit @ '0'..='9') => {
+                    let group = captures.get(digit as usize - '0' as usize);
+                    out.push_str(group.map_or("", |group| group.as_str()));
+                }
+                Some('n' | 'r') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some(other) => out.push(other),
+                None => out.push('\\'),
+            },
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+/// Runs a substitution over `lines`. Returns the new lines, which differ in
+/// number when the replacement breaks lines, and how many matches were
+/// replaced.
+pub fn substitute_lines(
+    lines: &[String],
+    regex: &Regex,
+    replacement: &str,
+    global: bool,
+) -> (Vec<String>, usize) {
+    let mut count = 0;
+    let limit = if global { 0 } else { 1 };
+    let text = lines
+        .iter()
+        .map(|line| {
+            regex
+                .replacen(line, limit, |captures: &Captures| {
+                    count += 1;
+                    expand_replacement(replacement, captures)
+                })
+                .into_owned()
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    (text.split('\n').map(str::to_string).collect(), count)
+}
+
+/// What `:g` does on each line that matches.
+pub enum GlobalAction {
+    Print,
+    Delete,
+    Substitute {
+        regex: Regex,
+        replacement: String,
+        global: bool,
+    },
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct GlobalResult {
+    pub lines: Vec<String>,
+    pub printed: Vec<String>,
+    pub deleted: Vec<String>,
+}
+
+/// Splits `:g/pattern/command` into the pattern and the command.
+pub fn split_global(args: &str) -> Result<(String, String), String> {
+    let mut chars = args.chars();
+    let delimiter = chars.next().ok_or("Missing pattern")?;
+    if delimiter.is_alphanumeric() || matches!(delimiter, '\\' | '"' | '|' | ' ') {
+        return Err("Patterns can't be delimited by letters".to_string());
+    }
+    let (pattern, command) = split_delimited(chars.as_str(), delimiter);
+    Ok((pattern, command.unwrap_or("").trim().to_string()))
+}
+
+/// Runs `action` on the `lines` that match `regex`, or that do not when
+/// `invert` is set (`:v`, `:g!`).
+pub fn global_lines(
+    lines: &[String],
+    regex: &Regex,
+    invert: bool,
+    action: &GlobalAction,
+) -> GlobalResult {
+    let mut result = GlobalResult::default();
+    for line in lines {
+        if regex.is_match(line) == invert {
+            result.lines.push(line.clone());
+            continue;
+        }
+        match action {
+            GlobalAction::Print => {
+                result.printed.push(line.clone());
+                result.lines.push(line.clone());
+            }
+            GlobalAction::Delete => result.deleted.push(line.clone()),
+            GlobalAction::Substitute {
+                regex,
+                replacement,
+                global,
+            } => {
+                let (new, _) =
+                    substitute_lines(std::slice::from_ref(line), regex, replacement, *global);
+                result.lines.extend(new);
+            }
+        }
+    }
+    result
+}
+
+/// A path typed after `:e`, relative to `base` unless absolute or under
+/// `~`.
+pub fn resolve_path(base: &Path, typed: &str) -> PathBuf {
+    if let Some(rest) = typed.strip_prefix("~/") {
+        if let Ok(home) = std::env::var("HOME") {
+            return PathBuf::from(home).join(rest);
+        }
+    }
+    base.join(typed)
+}
+
+/// Tab completion for the path typed after `:e`: the typed text extended as
+/// far as the matching entries agree, and the entries when more than one
+/// matches. Directories end in `/`.
+pub fn complete_path(base: &Path, typed: &str) -> (String, Vec<String>) {
+    let (dir, prefix) = match typed.rfind('/') {
+        Some(idx) => typed.split_at(idx + 1),
+        None => ("", typed),
+    };
+    let Ok(entries) = fs::read_dir(resolve_path(base, dir)) else {
+        return (typed.to_string(), Vec::new());
+    };
+    let mut names: Vec<String> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let mut name = entry.file_name().into_string().ok()?;
+            let hidden = name.starts_with('.') && !prefix.starts_with('.');
+            if hidden || !name.starts_with(prefix) {
+                return None;
+            }
+            if entry.path().is_dir() {
+                name.push('/');
+            }
+            Some(name)
+        })
+        .collect();
+    names.sort();
+
+    let Some(first) = names.first() else {
+        return (typed.to_string(), Vec::new());
+    };
+    let common = names.iter().fold(first.as_str(), |common, name| {
+        let len = common
+            .char_indices()
+            .zip(name.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(common.len().min(name.len()), |((idx, _), _)| idx);
+        &common[..len]
+    });
+    let completed = format!("{dir}{common}");
+    if names.len() == 1 {
+        names.clear();
+    }
+    (completed, names)
+}
+
+/// `:set` options: full name, short name, and whether it is on/off rather
+/// than a number.
+const OPTIONS: &[(&str, &str, bool)] = &[
+    ("tabstop", "ts", false),
+    ("shiftwidth", "sw", false),
+    ("softtabstop", "sts", false),
+    ("expandtab", "et", true),
+    ("numberwidth", "nuw", false),
+    ("autosave", "as", true),
+];
+
+fn find_option(name: &str) -> Option<(&'static str, bool)> {
+    OPTIONS
+        .iter()
+        .find(|(full, short, _)| name == *full || name == *short)
+        .map(|&(full, _, boolean)| (full, boolean))
+}
+
+fn show_option(prefs: &EditorPreferences, name: &str) -> String {
+    let flag = |on: bool| {
+        if on {
+            format!("  {name}")
+        } else {
+            format!("no{name}")
+        }
+    };
+    match name {
+        "expandtab" => flag(prefs.use_spaces),
+        "autosave" => flag(prefs.autosave_enabled),
+        "numberwidth" => format!("  {name}={}", prefs.line_number_width),
+        _ => format!("  {name}={}", prefs.tab_size),
+    }
+}
+
+fn set_flag(prefs: &mut EditorPreferences, name: &str, on: Option<bool>) {
+    let flag = match name {
+        "expandtab" => &mut prefs.use_spaces,
+        _ => &mut prefs.autosave_enabled,
+    };
+    *flag = on.unwrap_or(!*flag);
+}
+
+/// Applies `:set` arguments such as `ts=2 noet`, with the same limits as the
+/// settings panel. Returns what to show for queries like `ts?`, or every
+/// option for a bare `:set` or `:set all`.
+pub fn set_options(prefs: &mut EditorPreferences, args: &str) -> Result<Vec<String>, String> {
+    let mut shown = Vec::new();
+    if args.trim().is_empty() || args.trim() == "all" {
+        for (name, _, _) in OPTIONS {
+            shown.push(show_option(prefs, name));
+        }
+        return Ok(shown);
+    }
+
+    for arg in args.split_whitespace() {
+        let unknown = || format!("Unknown option: {arg}");
+        if let Some((name, value)) = arg.split_once(['=', ':']) {
+            let (name, boolean) = find_option(name).ok_or_else(unknown)?;
+            let number: f32 = value
+                .parse()
+                .ok()
+                .filter(|_| !boolean)
+                .ok_or_else(|| format!("Invalid argument: {arg}"))?;
+            match name {
+                "numberwidth" => prefs.line_number_width = number.clamp(20.0, 120.0),
+                _ => prefs.tab_size = (number as usize).clamp(1, 16),
+            }
+        } else if let Some(name) = arg.strip_suffix('?') {
+            let (name, _) = find_option(name).ok_or_else(unknown)?;
+            shown.push(show_option(prefs, name));
+        } else if let Some((name, true)) = find_option(arg) {
+            set_flag(prefs, name, Some(true));
+        } else if let Some((name, false)) = find_option(arg) {
+            shown.push(show_option(prefs, name));
+        } else {
+            let (toggle, name) = match arg.strip_suffix('!') {
+                Some(name) => (true, name),
+                None => match arg.strip_prefix("inv") {
+                    Some(name) => (true, name),
+                    None => (false, arg.strip_prefix("no").ok_or_else(unknown)?),
+                },
+            };
+            match find_option(name) {
+                Some((name, true)) => set_flag(prefs, name, (!toggle).then_some(false)),
+                Some(_) => return Err(format!("Invalid argument: {arg}")),
+                None => return Err(unknown()),
+            }
+        }
+    }
+    Ok(shown)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.split('\n').map(str::to_string).collect()
+    }
+
+    #[test]
+    fn ranges_resolve_against_the_buffer() {
+        let resolve = |input: &str| {
+            let range = parse(input).unwrap().range.unwrap();
+            range.resolve(4, 9, |mark| (mark == 'a').then_some(2))
+        };
+        assert_eq!(resolve("%d"), Ok((0, 9)));
+        assert_eq!(resolve(".,$s/a/b/"), Ok((4, 9)));
+        assert_eq!(resolve("'a,.+2y"), Ok((2, 6)));
+        assert_eq!(resolve("7,3"), Ok((2, 6)));
+        assert_eq!(resolve("-"), Ok((3, 3)));
+        assert_eq!(resolve("99"), Ok((9, 9)));
+        assert_eq!(resolve("'b"), Err("Mark not set".to_string()));
+    }
+
+    #[test]
+    fn names_are_abbreviated_as_in_vim() {
+        let command = parse("'<,'>s/x/y/g").unwrap();
+        assert_eq!(command.name, "substitute");
+        assert_eq!(command.args, "/x/y/g");
+        assert_eq!(parse("se ts=2").unwrap().name, "set");
+        assert_eq!(parse("vs").unwrap().name, "vsplit");
+        assert!(parse("bd!").unwrap().bang);
+        assert_eq!(parse("wq").unwrap().name, "wq");
+        assert_eq!(parse("q!").unwrap().name, "quit");
+        assert_eq!(parse("x").unwrap().name, "xit");
+        assert_eq!(parse("exi").unwrap().name, "exit");
+        assert_eq!(parse("e").unwrap().name, "edit");
+        assert_eq!(parse("12").unwrap().name, "");
+        assert!(parse("12!").is_err());
+    }
+
+    #[test]
+    fn substitute_expands_groups() {
+        let err = VimSubstitute::parse("/a/b/gx").unwrap_err();
+        assert_eq!(err, "Trailing characters: x");
+
+        let substitute = VimSubstitute::parse(r"#(\w+)=(\d+)#\2=\1;#g").unwrap();
+        assert!(substitute.global && !substitute.confirm);
+        let regex = substitute.regex().unwrap();
+        let (new, count) = substitute_lines(
+            &lines("a=1 b=2\nc=3"),
+            &regex,
+            &substitute.replacement,
+            true,
+        );
+        assert_eq!((new, count), (lines("1=a; 2=b;\n3=c;"), 3));
+
+        let split = VimSubstitute::parse(r"/, /\r/").unwrap();
+        let (new, _) = substitute_lines(
+            &lines("x, y, z"),
+            &split.regex().unwrap(),
+            &split.replacement,
+            false,
+        );
+        assert_eq!(new, lines("x\ny, z"));
+        assert!(build_regex("(", false)
+            .unwrap_err()
+            .starts_with("Invalid pattern"));
+    }
+
+    #[test]
+    fn global_acts_on_matching_lines() {
+        let (pattern, command) = split_global(r"/^\s*\/\//d").unwrap();
+        assert_eq!((pattern.as_str(), command.as_str()), (r"^\s*//", "d"));
+        let regex = build_regex(&pattern, false).unwrap();
+        let text = lines("a\n// b\nc\n  // d");
+
+        let deleted = global_lines(&text, &regex, false, &GlobalAction::Delete);
+        assert_eq!(deleted.lines, lines("a\nc"));
+        assert_eq!(deleted.deleted, lines("// b\n  // d"));
+
+        let kept = global_lines(&text, &regex, true, &GlobalAction::Print);
+        assert_eq!(kept.printed, lines("a\nc"));
+        assert_eq!(kept.lines, text);
+    }
+
+    #[test]
+    fn set_maps_onto_preferences() {
+        let mut prefs = EditorPreferences::default();
+        assert_eq!(set_options(&mut prefs, "ts=2 noet"), Ok(Vec::new()));
+        assert_eq!((prefs.tab_size, prefs.use_spaces), (2, false));
+        assert_eq!(
+            set_options(&mut prefs, "sw? et! nuw=500"),
+            Ok(vec!["  shiftwidth=2".to_string()])
+        );
+        assert!(prefs.use_spaces);
+        assert_eq!(prefs.line_number_width, 120.0);
+        assert_eq!(
+            set_options(&mut prefs, "foo"),
+            Err("Unknown option: foo".to_string())
+        );
+        assert_eq!(
+            set_options(&mut prefs, "et=1"),
+            Err("Invalid argument: et=1".to_string())
+        );
+    }
+
+    #[test]
+    fn paths_complete_to_the_common_prefix() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/main.rs"), "").unwrap();
+        fs::write(dir.path().join("src/mod_a.rs"), "").unwrap();
+        fs::write(dir.path().join("src/mod_b.rs"), "").unwrap();
+
+        assert_eq!(complete_path(dir.path(), "s"), ("src/".to_string(), vec![]));
+        assert_eq!(
+            complete_path(dir.path(), "src/mo"),
+            (
+                "src/mod_".to_string(),
+                vec!["mod_a.rs".to_string(), "mod_b.rs".to_string()]
+            )
+        );
+        assert_eq!(complete_path(dir.path(), "x"), ("x".to_string(), vec![]));
+    }
+}
@@ -0,0 +1,564 @@
+//! Parses the keys typed in normal and visual mode into commands: a motion,
+//! `[count] operator [count] motion`, `[count] operator text-object`, or a
+//! doubled operator (`dd`, `gUU`, `>>`) for whole lines. Any of them can be
+//! preceded by `"x` to name a register.
+
+use super::registers::is_register_name;
+use super::VimFindKind;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VimOperator {
+    Delete,
+    Yank,
+    Change,
+    Indent,
+    Outdent,
+    Reindent,
+    Lowercase,
+    Uppercase,
+    ToggleCase,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    /// `+`: first non-blank of a following line.
+    NextLine,
+    /// `-`: first non-blank of a preceding line.
+    PrevLine,
+    /// `true` for WORD motions (`W`, `E`, `B`).
+    WordForward(bool),
+    WordEnd(bool),
+    WordBackward(bool),
+    /// `G`: the line given by the count, or the last one.
+    GotoLine,
+    /// `gg`: the line given by the count, or the first one.
+    FirstLine,
+    ParagraphForward,
+    ParagraphBackward,
+    MatchPair,
+    Find(VimFindKind, char),
+    /// `;` and `,`: the last `f`/`t`/`F`/`T` again, `reverse` for `,`.
+    RepeatFind {
+        reverse: bool,
+    },
+    /// `'x` (to the first non-blank of the mark's line) or `` `x `` (`exact`).
+    Mark {
+        name: char,
+        exact: bool,
+    },
+    /// `n` and `N`: the last search again, `reverse` for `N`.
+    SearchNext {
+        reverse: bool,
+    },
+    /// `*` and `#`: search for the word under the cursor.
+    SearchWord {
+        backward: bool,
+    },
+}
+
+impl Motion {
+    /// Whether the motion adds to the jumplist, like vim's jump motions.
+    pub fn is_jump(self) -> bool {
+        matches!(
+            self,
+            Motion::GotoLine
+                | Motion::FirstLine
+                | Motion::ParagraphForward
+                | Motion::ParagraphBackward
+                | Motion::MatchPair
+                | Motion::Mark { .. }
+                | Motion::SearchNext { .. }
+                | Motion::SearchWord { .. }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextObjectKind {
+    Word,
+    BigWord,
+    /// A bracket pair, by its opening and closing character.
+    Pair(char, char),
+    Quote(char),
+    Tag,
+    Paragraph,
+}
+
+/// `i` (inner) or `a` followed by an object, as in `iw` or `a(`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextObject {
+    pub inner: bool,
+    pub kind: TextObjectKind,
+}
+
+/// What an operator typed in normal mode acts on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// The operator was doubled: whole lines from the cursor down.
+    Lines,
+    Motion(Motion),
+    Object(TextObject),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Move(Motion),
+    Operate(VimOperator, Target),
+    /// An operator typed in visual mode, applied to the selection.
+    OperateVisual(VimOperator),
+    /// A text object typed in visual mode, which becomes the selection.
+    Select(TextObject),
+    /// `q{register}`: start recording a macro.
+    Record(char),
+    /// `@{register}`, where `@@` plays the last macro again.
+    Replay(char),
+    /// `m{mark}`: remember the cursor position.
+    SetMark(char),
+    /// Any other key, left to the caller.
+    Key(char),
+}
+
+impl Action {
+    /// Whether `.` repeats the command. In visual mode `o` and the other
+    /// selection keys change nothing.
+    pub fn changes_text(self, visual: bool) -> bool {
+        match self {
+            Action::Operate(operator, _) | Action::OperateVisual(operator) => {
+                operator != VimOperator::Yank
+            }
+            Action::Key(key) if visual => "IAXDCSRpP".contains(key),
+            Action::Key(key) => "iaAIoOpP~".contains(key),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VimCommand {
+    pub count: Option<usize>,
+    /// The register named with `"x`.
+    pub register: Option<char>,
+    pub action: Action,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parse {
+    /// The keys are the start of a command; wait for more.
+    Pending,
+    Invalid,
+    Done(VimCommand),
+}
+
+/// Parses `keys`, everything typed since the last complete command.
+pub fn parse(keys: &str, visual: bool) -> Parse {
+    let mut keys = keys.chars().peekable();
+    let mut count = take_count(&mut keys);
+    let mut register = None;
+    if keys.next_if_eq(&'"').is_some() {
+        match keys.next() {
+            Some(name) if is_register_name(name) => register = Some(name),
+            Some(_) => return Parse::Invalid,
+            None => return Parse::Pending,
+        }
+        count = multiply(count, take_count(&mut keys));
+    }
+    let Some(key) = keys.next() else {
+        return Parse::Pending;
+    };
+    let done = |action| {
+        Parse::Done(VimCommand {
+            count,
+            register,
+            action,
+        })
+    };
+
+    if key == 'q' || key == '@' || key == 'm' {
+        let Some(name) = keys.next() else {
+            return Parse::Pending;
+        };
+        return match key {
+            'q' if name.is_ascii_alphanumeric() || name == '"' => done(Action::Record(name)),
+            '@' if is_register_name(name) || name == '@' => done(Action::Replay(name)),
+            'm' if is_mark_name(name) => done(Action::SetMark(mark_name(name))),
+            _ => Parse::Invalid,
+        };
+    }
+
+    let operator = match key {
+        'g' => match keys.peek() {
+            None => return Parse::Pending,
+            Some('u' | 'U' | '~') => operator_for(&format!("g{}", keys.next().unwrap())),
+            Some(_) => None,
+        },
+        _ => operator_for(&key.to_string()),
+    };
+    if let Some((operator, double)) = operator {
+        if visual {
+            return done(Action::OperateVisual(operator));
+        }
+        return parse_operator_target(operator, double, count, register, &mut keys);
+    }
+
+    if visual {
+        let operator = match key {
+            'x' => Some(VimOperator::Delete),
+            's' => Some(VimOperator::Change),
+            '~' => Some(VimOperator::ToggleCase),
+            'u' => Some(VimOperator::Lowercase),
+            'U' => Some(VimOperator::Uppercase),
+            _ => None,
+        };
+        if let Some(operator) = operator {
+            return done(Action::OperateVisual(operator));
+        }
+        if key == 'i' || key == 'a' {
+            return match parse_text_object(key == 'i', keys.next()) {
+                Ok(object) => done(Action::Select(object)),
+                Err(parse) => parse,
+            };
+        }
+    } else {
+        // Shorthands for an operator and motion.
+        let shorthand = match key {
+            'x' => Some((VimOperator::Delete, Target::Motion(Motion::Right))),
+            'X' => Some((VimOperator::Delete, Target::Motion(Motion::Left))),
+            'D' => Some((VimOperator::Delete, Target::Motion(Motion::LineEnd))),
+            'C' => Some((VimOperator::Change, Target::Motion(Motion::LineEnd))),
+            's' => Some((VimOperator::Change, Target::Motion(Motion::Right))),
+            'S' => Some((VimOperator::Change, Target::Lines)),
+            'Y' => Some((VimOperator::Yank, Target::Lines)),
+            _ => None,
+        };
+        if let Some((operator, target)) = shorthand {
+            return done(Action::Operate(operator, target));
+        }
+    }
+
+    match parse_motion(key, &mut keys) {
+        Ok(Some(motion)) => done(Action::Move(motion)),
+        Ok(None) => done(Action::Key(key)),
+        Err(parse) => parse,
+    }
+}
+
+/// Whether the pending keys are an operator waiting for what to act on,
+/// which the cursor shows.
+pub fn operator_pending(keys: &str) -> bool {
+    let mut keys = keys.chars().peekable();
+    take_count(&mut keys);
+    if keys.next_if_eq(&'"').is_some() {
+        keys.next();
+        take_count(&mut keys);
+    }
+    match keys.next() {
+        Some('g') => matches!(keys.next(), Some('u' | 'U' | '~')),
+        Some(key) => operator_for(&key.to_string()).is_some(),
+        None => false,
+    }
+}
+
+/// The count typed before the pending keys, as `Ctrl` commands use it.
+pub fn leading_count(keys: &str) -> Option<usize> {
+    take_count(&mut keys.chars().peekable())
+}
+
+fn take_count(keys: &mut std::iter::Peekable<impl Iterator<Item = char>>) -> Option<usize> {
+    let mut digits = String::new();
+    while let Some(&ch) = keys.peek() {
+        if !ch.is_ascii_digit() || (ch == '0' && digits.is_empty()) {
+            break;
+        }
+        digits.push(ch);
+        keys.next();
+    }
+    digits.parse().ok()
+}
+
+/// Counts typed in two places, as in `2d3w`, multiply.
+fn multiply(first: Option<usize>, second: Option<usize>) -> Option<usize> {
+    match (first, second) {
+        (None, None) => None,
+        (a, b) => Some(a.unwrap_or(1) * b.unwrap_or(1)),
+    }
+}
+
+/// The operator `keys` name, and the key that completes it when doubled.
+fn operator_for(keys: &str) -> Option<(VimOperator, char)> {
+    let operator = match keys {
+        "d" => VimOperator::Delete,
+        "y" => VimOperator::Yank,
+        "c" => VimOperator::Change,
+        ">" => VimOperator::Indent,
+        "<" => VimOperator::Outdent,
+        "=" => VimOperator::Reindent,
+        "gu" => VimOperator::Lowercase,
+        "gU" => VimOperator::Uppercase,
+        "g~" => VimOperator::ToggleCase,
+        _ => return None,
+    };
+    Some((operator, keys.chars().last()?))
+}
+
+fn parse_operator_target(
+    operator: VimOperator,
+    double: char,
+    count: Option<usize>,
+    register: Option<char>,
+    keys: &mut std::iter::Peekable<impl Iterator<Item = char>>,
+) -> Parse {
+    let count = multiply(count, take_count(keys));
+    let done = |target| {
+        Parse::Done(VimCommand {
+            count,
+            register,
+            action: Action::Operate(operator, target),
+        })
+    };
+
+    let Some(key) = keys.next() else {
+        return Parse::Pending;
+    };
+    if key == double {
+        return done(Target::Lines);
+    }
+    let g_operator = matches!(
+        operator,
+        VimOperator::Lowercase | VimOperator::Uppercase | VimOperator::ToggleCase
+    );
+    if key == 'g' && g_operator && keys.peek() == Some(&double) {
+        return done(Target::Lines);
+    }
+    if key == 'i' || key == 'a' {
+        return match parse_text_object(key == 'i', keys.next()) {
+            Ok(object) => done(Target::Object(object)),
+            Err(parse) => parse,
+        };
+    }
+    match parse_motion(key, keys) {
+        Ok(Some(motion)) => done(Target::Motion(motion)),
+        Ok(None) => Parse::Invalid,
+        Err(parse) => parse,
+    }
+}
+
+/// `Ok(None)` if `key` is not a motion; `Err` if it needs more keys or the
+/// ones after it make no sense.
+fn parse_motion(key: char, keys: &mut impl Iterator<Item = char>) -> Result<Option<Motion>, Parse> {
+    let motion = match key {
+        'h' => Motion::Left,
+        'l' | ' ' => Motion::Right,
+        'j' => Motion::Down,
+        'k' => Motion::Up,
+        '0' => Motion::LineStart,
+        '^' => Motion::FirstNonBlank,
+        '$' => Motion::LineEnd,
+        '+' => Motion::NextLine,
+        '-' => Motion::PrevLine,
+        'w' | 'W' => Motion::WordForward(key == 'W'),
+        'e' | 'E' => Motion::WordEnd(key == 'E'),
+        'b' | 'B' => Motion::WordBackward(key == 'B'),
+        'G' => Motion::GotoLine,
+        '}' => Motion::ParagraphForward,
+        '{' => Motion::ParagraphBackward,
+        '%' => Motion::MatchPair,
+        ';' => Motion::RepeatFind { reverse: false },
+        ',' => Motion::RepeatFind { reverse: true },
+        'f' | 't' | 'F' | 'T' => {
+            let kind = match key {
+                'f' => VimFindKind::ForwardTo,
+                't' => VimFindKind::ForwardTill,
+                'F' => VimFindKind::BackwardTo,
+                _ => VimFindKind::BackwardTill,
+            };
+            let needle = keys.next().ok_or(Parse::Pending)?;
+            Motion::Find(kind, needle)
+        }
+        'n' | 'N' => Motion::SearchNext {
+            reverse: key == 'N',
+        },
+        '*' | '#' => Motion::SearchWord {
+            backward: key == '#',
+        },
+        '\'' | '`' => match keys.next() {
+            Some(name) if is_mark_name(name) => Motion::Mark {
+                name: mark_name(name),
+                exact: key == '`',
+            },
+            Some(_) => return Err(Parse::Invalid),
+            None => return Err(Parse::Pending),
+        },
+        'g' => match keys.next() {
+            Some('g') => Motion::FirstLine,
+            Some(_) => return Err(Parse::Invalid),
+            None => return Err(Parse::Pending),
+        },
+        _ => return Ok(None),
+    };
+    Ok(Some(motion))
+}
+
+/// Marks are letters, plus `'` and `` ` `` for the place before the last
+/// jump.
+fn is_mark_name(name: char) -> bool {
+    name.is_ascii_alphabetic() || matches!(name, '\'' | '`' | '<' | '>')
+}
+
+/// `` ` `` names the same mark as `'`.
+fn mark_name(name: char) -> char {
+    if name == '`' {
+        '\''
+    } else {
+        name
+    }
+}
+
+fn parse_text_object(inner: bool, key: Option<char>) -> Result<TextObject, Parse> {
+    let kind = match key.ok_or(Parse::Pending)? {
+        'w' => TextObjectKind::Word,
+        'W' => TextObjectKind::BigWord,
+        '(' | ')' | 'b' => TextObjectKind::Pair('(', ')'),
+        '[' | ']' => TextObjectKind::Pair('[', ']'),
+        '{' | '}' | 'B' => TextObjectKind::Pair('{', '}'),
+        '<' | '>' => TextObjectKind::Pair('<', '>'),
+        quote @ ('"' | '\'' | '`') => TextObjectKind::Quote(quote),
+        't' => TextObjectKind::Tag,
+        'p' => TextObjectKind::Paragraph,
+        _ => return Err(Parse::Invalid),
+    };
+    Ok(TextObject { inner, kind })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(keys: &str) -> VimCommand {
+        match parse(keys, false) {
+            Parse::Done(command) => command,
+            other => panic!("{keys:?} parsed as {other:?}"),
+        }
+    }
+
+    #[test]
+    fn counts_multiply_across_operator_and_motion() {
+        let parsed = command("2d3w");
+        assert_eq!(parsed.count, Some(6));
+        assert_eq!(
+            parsed.action,
+            Action::Operate(
+                VimOperator::Delete,
+                Target::Motion(Motion::WordForward(false))
+            )
+        );
+        assert_eq!(command("d$").count, None);
+        assert_eq!(command("10j").count, Some(10));
+    }
+
+    #[test]
+    fn doubled_operators_act_on_lines() {
+        for keys in ["dd", "3yy", ">>", "gUU", "gUgU", "guu", "=="] {
+            assert!(
+                matches!(command(keys).action, Action::Operate(_, Target::Lines)),
+                "{keys}"
+            );
+        }
+        assert_eq!(
+            command("dgg").action,
+            Action::Operate(VimOperator::Delete, Target::Motion(Motion::FirstLine))
+        );
+    }
+
+    #[test]
+    fn text_objects_and_pending_keys() {
+        assert_eq!(
+            command("ci\"").action,
+            Action::Operate(
+                VimOperator::Change,
+                Target::Object(TextObject {
+                    inner: true,
+                    kind: TextObjectKind::Quote('"'),
+                })
+            )
+        );
+        for keys in ["d", "2d", "ci", "g", "gu", "df", "y2"] {
+            assert_eq!(parse(keys, false), Parse::Pending, "{keys}");
+        }
+        for keys in ["d", "2\"ay3", "gu", "ci"] {
+            assert!(operator_pending(keys), "{keys}");
+        }
+        for keys in ["", "2", "g", "\"a", "f"] {
+            assert!(!operator_pending(keys), "{keys}");
+        }
+        assert_eq!(parse("dq", false), Parse::Invalid);
+        assert_eq!(
+            parse("i", false),
+            Parse::Done(VimCommand {
+                count: None,
+                register: None,
+                action: Action::Key('i'),
+            })
+        );
+        assert!(matches!(
+            parse("ap", true),
+            Parse::Done(VimCommand {
+                action: Action::Select(_),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn registers_are_named_before_the_command() {
+        let parsed = command("2\"a3p");
+        assert_eq!((parsed.count, parsed.register), (Some(6), Some('a')));
+        assert_eq!(parsed.action, Action::Key('p'));
+        assert_eq!(command("\"_dd").register, Some('_'));
+        assert_eq!(parse("\"", false), Parse::Pending);
+        assert_eq!(parse("\"a", false), Parse::Pending);
+        assert_eq!(parse("\"!", false), Parse::Invalid);
+    }
+
+    #[test]
+    fn macros_name_a_register() {
+        assert_eq!(command("qa").action, Action::Record('a'));
+        let parsed = command("3@@");
+        assert_eq!(
+            (parsed.count, parsed.action),
+            (Some(3), Action::Replay('@'))
+        );
+        assert_eq!(parse("q", false), Parse::Pending);
+        assert_eq!(parse("q_", false), Parse::Invalid);
+        assert!(Action::Key('o').changes_text(false));
+        assert!(!Action::Key('o').changes_text(true));
+        assert!(!command("yy").action.changes_text(false));
+    }
+
+    #[test]
+    fn marks_and_searches_are_jumps() {
+        assert_eq!(command("ma").action, Action::SetMark('a'));
+        assert_eq!(command("m`").action, Action::SetMark('\''));
+        assert_eq!(
+            command("d`a").action,
+            Action::Operate(
+                VimOperator::Delete,
+                Target::Motion(Motion::Mark {
+                    name: 'a',
+                    exact: true,
+                })
+            )
+        );
+        assert_eq!(parse("'", false), Parse::Pending);
+        assert_eq!(parse("m1", false), Parse::Invalid);
+        assert!(Motion::SearchNext { reverse: true }.is_jump());
+        assert!(matches!(command("#").action, Action::Move(m) if m.is_jump()));
+        assert!(!Motion::WordForward(false).is_jump());
+    }
+}
@@ -0,0 +1,102 @@
+//! The jumplist `Ctrl-o` and `Ctrl-i` walk. Long moves (`G`, `%`, marks,
+//! searches) and jumps to other files record the place they left, so a jump
+//! can be undone across tabs. Places in a tab that closes are forgotten.
+
+use std::path::{Path, PathBuf};
+
+/// How many places the list keeps, as in vim.
+const MAX_JUMPS: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Jump {
+    pub path: PathBuf,
+    /// 0-based `(line, column)`.
+    pub pos: (usize, usize),
+}
+
+#[derive(Debug, Default)]
+pub struct JumpList {
+    jumps: Vec<Jump>,
+    /// Where `Ctrl-o` and `Ctrl-i` have walked to; `jumps.len()` when not
+    /// walking.
+    index: usize,
+}
+
+impl JumpList {
+    /// Adds the place a jump left. An older entry for the same line is
+    /// dropped, so walking back visits each line once.
+    pub fn push(&mut self, jump: Jump) {
+        self.jumps
+            .retain(|old| old.path != jump.path || old.pos.0 != jump.pos.0);
+        self.jumps.push(jump);
+        if self.jumps.len() > MAX_JUMPS {
+            self.jumps.remove(0);
+        }
+        self.index = self.jumps.len();
+    }
+
+    /// `Ctrl-o`: `count` places back. Leaving the end of the list records
+    /// `current` first, so `Ctrl-i` can return to it.
+    pub fn back(&mut self, current: Jump, count: usize) -> Option<Jump> {
+        if self.index >= self.jumps.len() {
+            self.push(current);
+            self.index = self.jumps.len() - 1;
+        }
+        self.index = self.index.checked_sub(count.max(1))?;
+        self.jumps.get(self.index).cloned()
+    }
+
+    /// `Ctrl-i`: `count` places forward again.
+    pub fn forward(&mut self, count: usize) -> Option<Jump> {
+        let index = self.index + count.max(1);
+        let jump = self.jumps.get(index)?.clone();
+        self.index = index;
+        Some(jump)
+    }
+
+    /// Drops the places in a closed tab.
+    pub fn forget(&mut self, path: &Path) {
+        let before = self.jumps[..self.index.min(self.jumps.len())]
+            .iter()
+            .filter(|jump| jump.path == path)
+            .count();
+        self.jumps.retain(|jump| jump.path != path);
+        self.index -= before.min(self.index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jump(path: &str, line: usize) -> Jump {
+        Jump {
+            path: PathBuf::from(path),
+            pos: (line, 0),
+        }
+    }
+
+    #[test]
+    fn back_and_forward_walk_the_list() {
+        let mut jumps = JumpList::default();
+        jumps.push(jump("a.rs", 1));
+        jumps.push(jump("b.rs", 5));
+        jumps.push(jump("a.rs", 1));
+
+        assert_eq!(jumps.back(jump("b.rs", 9), 1), Some(jump("a.rs", 1)));
+        assert_eq!(jumps.back(jump("a.rs", 1), 1), Some(jump("b.rs", 5)));
+        assert_eq!(jumps.back(jump("b.rs", 5), 1), None);
+        assert_eq!(jumps.forward(2), Some(jump("b.rs", 9)));
+        assert_eq!(jumps.forward(1), None);
+    }
+
+    #[test]
+    fn closed_tabs_are_forgotten() {
+        let mut jumps = JumpList::default();
+        jumps.push(jump("a.rs", 1));
+        jumps.push(jump("b.rs", 2));
+        jumps.push(jump("a.rs", 3));
+        jumps.forget(Path::new("b.rs"));
+        assert_eq!(jumps.back(jump("a.rs", 4), 2), Some(jump("a.rs", 1)));
+    }
+}
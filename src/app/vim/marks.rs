@@ -0,0 +1,131 @@
+//! Marks set with `m`. `a`-`z` belong to one file and `A`-`Z` to the whole
+//! editor; both are saved to `marks.lua` in the config dir so they outlive
+//! the session. `'` is the place before the last jump, for `''`, and is not
+//! saved.
+
+use crate::config::lua_config;
+use crate::config::theme_manager::get_config_dir;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Default, PartialEq)]
+pub struct VimMarks {
+    local: HashMap<PathBuf, BTreeMap<char, (usize, usize)>>,
+    global: BTreeMap<char, (PathBuf, (usize, usize))>,
+}
+
+fn get_marks_path() -> PathBuf {
+    get_config_dir().join("marks.lua")
+}
+
+impl VimMarks {
+    /// Loads `marks.lua`; a missing file means no marks.
+    pub fn load() -> Result<Self, String> {
+        match fs::read_to_string(get_marks_path()) {
+            Ok(content) => Self::from_lua(&content),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let path = get_marks_path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, self.to_lua())
+    }
+
+    /// Sets mark `name` at the 0-based `pos` in `path`.
+    pub fn set(&mut self, path: &Path, name: char, pos: (usize, usize)) {
+        if name.is_ascii_uppercase() {
+            self.global.insert(name, (path.to_path_buf(), pos));
+        } else {
+            self.local
+                .entry(path.to_path_buf())
+                .or_default()
+                .insert(name, pos);
+        }
+    }
+
+    /// The file and position of mark `name`, seen from `path`.
+    pub fn get(&self, path: &Path, name: char) -> Option<(PathBuf, (usize, usize))> {
+        if name.is_ascii_uppercase() {
+            return self.global.get(&name).cloned();
+        }
+        let pos = self.local.get(path)?.get(&name)?;
+        Some((path.to_path_buf(), *pos))
+    }
+
+    /// The letter marks of saved files, one table per mark.
+    fn to_lua(&self) -> String {
+        let local = self
+            .local
+            .iter()
+            .flat_map(|(path, marks)| marks.iter().map(move |(&name, &pos)| (name, path, pos)));
+        let global = self
+            .global
+            .iter()
+            .map(|(&name, (path, pos))| (name, path, *pos));
+        let mut lines: Vec<String> = local
+            .chain(global)
+            .filter(|(name, path, _)| name.is_ascii_alphabetic() && path.is_absolute())
+            .map(|(name, path, (line, col))| {
+                format!(
+                    "  {{ name = {}, file = {}, line = {}, col = {} }},",
+                    lua_config::lua_string(&name.to_string()),
+                    lua_config::lua_string(&path.to_string_lossy()),
+                    line + 1,
+                    col + 1
+                )
+            })
+            .collect();
+        lines.sort();
+        format!(
+            "-- Vim marks, rewritten whenever one is set.\nreturn {{\n marks = {{\n{}\n }},\n}}\n",
+            lines.join("\n")
+        )
+    }
+
+    fn from_lua(content: &str) -> Result<Self, String> {
+        let table = lua_config::eval("marks.lua", content)?;
+        let mut marks = Self::default();
+        for mark in table.table_list("marks")?.unwrap_or_default() {
+            let name = mark.string("name")?.and_then(|name| name.chars().next());
+            let file = mark.string("file")?;
+            let line = mark.integer("line")?.unwrap_or(1).max(1) as usize;
+            let col = mark.integer("col")?.unwrap_or(1).max(1) as usize;
+            if let (Some(name), Some(file)) = (name, file) {
+                marks.set(Path::new(&file), name, (line - 1, col - 1));
+            }
+        }
+        Ok(marks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marks_round_trip_through_lua() {
+        let mut marks = VimMarks::default();
+        marks.set(Path::new("/src/a.rs"), 'a', (4, 2));
+        marks.set(Path::new("/src/a.rs"), 'Q', (0, 0));
+        marks.set(Path::new("/src/b.rs"), '\'', (7, 0));
+        marks.set(Path::new("untitled"), 'b', (1, 1));
+
+        let loaded = VimMarks::from_lua(&marks.to_lua()).unwrap();
+        assert_eq!(
+            loaded.get(Path::new("/src/a.rs"), 'a'),
+            Some((PathBuf::from("/src/a.rs"), (4, 2)))
+        );
+        assert_eq!(
+            loaded.get(Path::new("/src/b.rs"), 'Q'),
+            Some((PathBuf::from("/src/a.rs"), (0, 0)))
+        );
+        assert_eq!(loaded.get(Path::new("/src/b.rs"), '\''), None);
+        assert_eq!(loaded.get(Path::new("untitled"), 'b'), None);
+        assert_eq!(loaded.get(Path::new("/src/b.rs"), 'a'), None);
+    }
+}
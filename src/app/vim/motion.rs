@@ -0,0 +1,765 @@
+//! Where motions take the cursor and which text a text object covers.
+//! Positions are 0-based `(line, column)`; the word and bracket helpers work
+//! on char indices into the whole text.
+
+use super::grammar::{Motion, TextObject, TextObjectKind};
+use super::{VimFindKind, VimRange};
+
+/// The buffer as motions see it.
+pub struct MotionText<'a> {
+    text: &'a str,
+    lines: Vec<&'a str>,
+}
+
+/// Where a motion lands, and how an operator treats the text up to there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MotionTarget {
+    pub pos: (usize, usize),
+    /// Operators act on whole lines (`j`, `G`, ...).
+    pub linewise: bool,
+    /// The character at `pos` is part of the operated text (`e`, `$`, `f`).
+    pub inclusive: bool,
+}
+
+impl<'a> MotionText<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            lines: text.split('\n').collect(),
+        }
+    }
+
+    pub fn as_str(&self) -> &'a str {
+        self.text
+    }
+
+    pub fn last_line(&self) -> usize {
+        self.lines.len() - 1
+    }
+
+    fn line(&self, line: usize) -> &str {
+        self.lines.get(line).copied().unwrap_or("")
+    }
+
+    pub fn line_len(&self, line: usize) -> usize {
+        self.line(line).chars().count()
+    }
+
+    fn index(&self, (line, col): (usize, usize)) -> usize {
+        position_to_index(&self.lines, line + 1, col + 1)
+    }
+
+    fn position(&self, idx: usize) -> (usize, usize) {
+        let (line, col) = index_to_position(&self.lines, idx);
+        (line - 1, col - 1)
+    }
+
+    pub fn first_nonblank(&self, line: usize) -> usize {
+        self.line(line)
+            .chars()
+            .take_while(|c| c.is_whitespace())
+            .count()
+    }
+
+    pub fn char_at(&self, (line, col): (usize, usize)) -> Option<char> {
+        self.line(line).chars().nth(col)
+    }
+
+    fn is_blank(&self, line: usize) -> bool {
+        self.line(line).trim().is_empty()
+    }
+}
+
+/// Where `motion`, repeated `count` times, takes the cursor. `None` if it
+/// cannot move, like `f` without a match or `j` on the last line.
+///
+/// `Motion::RepeatFind` must be resolved to a `Motion::Find` first; marks
+/// and searches need editor state and are left to the caller.
+pub fn motion_target(
+    text: &MotionText,
+    cursor: (usize, usize),
+    motion: Motion,
+    count: Option<usize>,
+) -> Option<MotionTarget> {
+    let n = count.unwrap_or(1).max(1);
+    let (line, col) = cursor;
+    let last = text.last_line();
+    let exclusive = |pos| MotionTarget {
+        pos,
+        linewise: false,
+        inclusive: false,
+    };
+    let inclusive = |pos| MotionTarget {
+        pos,
+        linewise: false,
+        inclusive: true,
+    };
+    let linewise = |pos| MotionTarget {
+        pos,
+        linewise: true,
+        inclusive: false,
+    };
+
+    let target = match motion {
+        Motion::Left if col > 0 => exclusive((line, col.saturating_sub(n))),
+        Motion::Right if col < text.line_len(line) => {
+            exclusive((line, (col + n).min(text.line_len(line))))
+        }
+        Motion::Up if line > 0 => linewise((line.saturating_sub(n), col)),
+        Motion::Down if line < last => linewise(((line + n).min(last), col)),
+        Motion::Left | Motion::Right | Motion::Up | Motion::Down => return None,
+        Motion::LineStart => exclusive((line, 0)),
+        Motion::FirstNonBlank => exclusive((line, text.first_nonblank(line))),
+        Motion::LineEnd => {
+            let line = (line + n - 1).min(last);
+            exclusive((line, text.line_len(line)))
+        }
+        Motion::NextLine if line < last => {
+            let line = (line + n).min(last);
+            linewise((line, text.first_nonblank(line)))
+        }
+        Motion::PrevLine if line > 0 => {
+            let line = line.saturating_sub(n);
+            linewise((line, text.first_nonblank(line)))
+        }
+        Motion::NextLine | Motion::PrevLine => return None,
+        Motion::WordForward(big) => {
+            let mut idx = text.index(cursor);
+            for _ in 0..n {
+                idx = next_word_start(text.text, idx, big);
+            }
+            exclusive(text.position(idx))
+        }
+        Motion::WordEnd(big) => {
+            let mut idx = text.index(cursor);
+            for _ in 0..n {
+                idx = next_word_end(text.text, idx + 1, big);
+            }
+            inclusive(text.position(idx))
+        }
+        Motion::WordBackward(big) => {
+            let mut idx = text.index(cursor);
+            for _ in 0..n {
+                idx = prev_word_start(text.text, idx, big);
+            }
+            exclusive(text.position(idx))
+        }
+        Motion::GotoLine | Motion::FirstLine => {
+            let line = match count {
+                Some(n) => n.saturating_sub(1).min(last),
+                None if motion == Motion::GotoLine => last,
+                None => 0,
+            };
+            linewise((line, text.first_nonblank(line)))
+        }
+        Motion::ParagraphForward => {
+            let mut line = line;
+            for _ in 0..n {
+                while line < last && text.is_blank(line) {
+                    line += 1;
+                }
+                while line < last && !text.is_blank(line) {
+                    line += 1;
+                }
+            }
+            if line == last && !text.is_blank(line) {
+                exclusive((line, text.line_len(line)))
+            } else {
+                exclusive((line, 0))
+            }
+        }
+        Motion::ParagraphBackward => {
+            let mut line = line;
+            for _ in 0..n {
+                while line > 0 && text.is_blank(line) {
+                    line -= 1;
+                }
+                while line > 0 && !text.is_blank(line) {
+                    line -= 1;
+                }
+            }
+            exclusive((line, 0))
+        }
+        Motion::MatchPair => {
+            // Like vim, use the first bracket at or after the cursor.
+            let offset = text
+                .line(line)
+                .chars()
+                .skip(col)
+                .position(|c| "()[]{}".contains(c))?;
+            let idx = text.index((line, col + offset));
+            inclusive(text.position(match_pair_index(text.text, idx)?))
+        }
+        Motion::Find(kind, needle) => {
+            let chars: Vec<char> = text.line(line).chars().collect();
+            let col = find_in_line(&chars, col, kind, needle, n)?;
+            match kind {
+                VimFindKind::ForwardTo | VimFindKind::ForwardTill => inclusive((line, col)),
+                VimFindKind::BackwardTo | VimFindKind::BackwardTill => exclusive((line, col)),
+            }
+        }
+        Motion::RepeatFind { .. }
+        | Motion::Mark { .. }
+        | Motion::SearchNext { .. }
+        | Motion::SearchWord { .. } => return None,
+    };
+    Some(target)
+}
+
+/// The end of the word under the cursor and of `count - 1` words after it,
+/// which is what `cw` changes.
+pub fn change_word_end(
+    text: &MotionText,
+    cursor: (usize, usize),
+    count: Option<usize>,
+    big: bool,
+) -> (usize, usize) {
+    let mut idx = next_word_end(text.text, text.index(cursor), big);
+    for _ in 1..count.unwrap_or(1).max(1) {
+        idx = next_word_end(text.text, idx + 1, big);
+    }
+    text.position(idx)
+}
+
+/// Column of the `count`th `needle` from `col` in the direction of `kind`.
+fn find_in_line(
+    chars: &[char],
+    col: usize,
+    kind: VimFindKind,
+    needle: char,
+    count: usize,
+) -> Option<usize> {
+    match kind {
+        VimFindKind::ForwardTo | VimFindKind::ForwardTill => {
+            let found = chars
+                .iter()
+                .enumerate()
+                .skip(col + 1)
+                .filter(|(_, &c)| c == needle)
+                .nth(count - 1)?
+                .0;
+            Some(if kind == VimFindKind::ForwardTill {
+                found - 1
+            } else {
+                found
+            })
+        }
+        VimFindKind::BackwardTo | VimFindKind::BackwardTill => {
+            let found = chars[..col.min(chars.len())]
+                .iter()
+                .enumerate()
+                .rev()
+                .filter(|(_, &c)| c == needle)
+                .nth(count - 1)?
+                .0;
+            Some(if kind == VimFindKind::BackwardTill {
+                found + 1
+            } else {
+                found
+            })
+        }
+    }
+}
+
+/// The text `object` covers around the cursor, or `None` if there is no
+/// such object there.
+pub fn text_object_range(
+    text: &MotionText,
+    cursor: (usize, usize),
+    object: TextObject,
+    count: Option<usize>,
+) -> Option<VimRange> {
+    let n = count.unwrap_or(1).max(1);
+    match object.kind {
+        TextObjectKind::Word | TextObjectKind::BigWord => {
+            let chars: Vec<char> = text.line(cursor.0).chars().collect();
+            let big = object.kind == TextObjectKind::BigWord;
+            let (start, end) = word_object(&chars, cursor.1, object.inner, big, n)?;
+            Some(VimRange::Chars {
+                start: (cursor.0, start),
+                end: (cursor.0, end),
+            })
+        }
+        TextObjectKind::Pair(open, close) => {
+            let chars: Vec<char> = text.text.chars().collect();
+            let idx = text.index(cursor);
+            let mut open_idx = enclosing_open(&chars, idx, open, close)?;
+            for _ in 1..n {
+                open_idx = open_before(&chars, open_idx, open, close)?;
+            }
+            let close_idx = match_pair_index(text.text, open_idx)?;
+            if !object.inner {
+                return Some(VimRange::Chars {
+                    start: text.position(open_idx),
+                    end: text.position(close_idx + 1),
+                });
+            }
+
+            // A block whose brackets sit on their own lines is operated on
+            // line by line, keeping the bracket lines.
+            let (open_line, close_line) = (text.position(open_idx).0, text.position(close_idx).0);
+            let close_alone = text.position(close_idx).1 == text.first_nonblank(close_line);
+            if chars.get(open_idx + 1) == Some(&'\n') && close_alone && close_line > open_line + 1 {
+                return Some(VimRange::Lines {
+                    start: open_line + 1,
+                    end: close_line - 1,
+                });
+            }
+            Some(VimRange::Chars {
+                start: text.position(open_idx + 1),
+                end: text.position(close_idx),
+            })
+        }
+        TextObjectKind::Quote(quote) => {
+            let chars: Vec<char> = text.line(cursor.0).chars().collect();
+            let (start, end) = quote_object(&chars, cursor.1, quote, object.inner)?;
+            Some(VimRange::Chars {
+                start: (cursor.0, start),
+                end: (cursor.0, end),
+            })
+        }
+        TextObjectKind::Tag => {
+            let chars: Vec<char> = text.text.chars().collect();
+            let idx = text.index(cursor);
+            let mut pairs: Vec<TagPair> = tag_pairs(&chars)
+                .into_iter()
+                .filter(|pair| pair.open_start <= idx && idx < pair.close_end)
+                .collect();
+            // Innermost first.
+            pairs.sort_by_key(|pair| std::cmp::Reverse(pair.open_start));
+            let pair = pairs.get(n - 1)?;
+            let (start, end) = if object.inner {
+                (pair.open_end, pair.close_start)
+            } else {
+                (pair.open_start, pair.close_end)
+            };
+            Some(VimRange::Chars {
+                start: text.position(start),
+                end: text.position(end),
+            })
+        }
+        TextObjectKind::Paragraph => {
+            let (start, end) = paragraph_object(text, cursor.0, object.inner, n);
+            Some(VimRange::Lines { start, end })
+        }
+    }
+}
+
+fn char_class(ch: char, big: bool) -> u8 {
+    if ch.is_whitespace() {
+        0
+    } else if big || is_word_char(ch) {
+        1
+    } else {
+        2
+    }
+}
+
+/// Columns `start..end` of `iw`/`aw` on a line. Runs of whitespace count as
+/// words for `iw`, as in vim.
+fn word_object(
+    chars: &[char],
+    col: usize,
+    inner: bool,
+    big: bool,
+    count: usize,
+) -> Option<(usize, usize)> {
+    if chars.is_empty() {
+        return None;
+    }
+    let col = col.min(chars.len() - 1);
+    let class = |i: usize| char_class(chars[i], big);
+    let run_end = |from: usize| {
+        let mut i = from;
+        while i < chars.len() && class(i) == class(from) {
+            i += 1;
+        }
+        i
+    };
+    let mut start = col;
+    while start > 0 && class(start - 1) == class(col) {
+        start -= 1;
+    }
+
+    let mut end = col;
+    if inner {
+        for _ in 0..count {
+            if end >= chars.len() {
+                break;
+            }
+            end = run_end(end);
+        }
+        return Some((start, end));
+    }
+
+    let on_space = class(col) == 0;
+    for _ in 0..count {
+        if end >= chars.len() {
+            break;
+        }
+        // A word plus the space after it, or space plus the word after it.
+        end = run_end(end);
+        if end < chars.len() && (on_space || class(end) == 0) {
+            end = run_end(end);
+        }
+    }
+    if !on_space && chars.get(end - 1).is_some_and(|c| !c.is_whitespace()) {
+        // No trailing space, so take the space before the word instead.
+        while start > 0 && chars[start - 1].is_whitespace() {
+            start -= 1;
+        }
+    }
+    Some((start, end))
+}
+
+/// Index of the bracket that opens the pair around `idx`; a bracket at
+/// `idx` itself counts.
+fn enclosing_open(chars: &[char], idx: usize, open: char, close: char) -> Option<usize> {
+    let idx = idx.min(chars.len().checked_sub(1)?);
+    if chars[idx] == open {
+        return Some(idx);
+    }
+    open_before(
+        chars,
+        if chars[idx] == close { idx } else { idx + 1 },
+        open,
+        close,
+    )
+}
+
+/// Index of the last `open` before `idx` that is not closed before `idx`.
+fn open_before(chars: &[char], idx: usize, open: char, close: char) -> Option<usize> {
+    let mut depth = 0usize;
+    for j in (0..idx).rev() {
+        if chars[j] == close {
+            depth += 1;
+        } else if chars[j] == open {
+            if depth == 0 {
+                return Some(j);
+            }
+            depth -= 1;
+        }
+    }
+    None
+}
+
+/// Columns `start..end` of `i"`/`a"` on a line: the quoted string around
+/// the cursor, or else the next one after it.
+fn quote_object(chars: &[char], col: usize, quote: char, inner: bool) -> Option<(usize, usize)> {
+    let quotes: Vec<usize> = chars
+        .iter()
+        .enumerate()
+        .filter(|&(i, &c)| c == quote && (i == 0 || chars[i - 1] != '\\'))
+        .map(|(i, _)| i)
+        .collect();
+    let (open, close) = quotes
+        .chunks_exact(2)
+        .map(|pair| (pair[0], pair[1]))
+        .find(|&(_, close)| col <= close)?;
+
+    if inner {
+        return Some((open + 1, close));
+    }
+    let mut end = close + 1;
+    while end < chars.len() && chars[end].is_whitespace() {
+        end += 1;
+    }
+    let mut start = open;
+    if end == close + 1 {
+        while start > 0 && chars[start - 1].is_whitespace() {
+            start -= 1;
+        }
+    }
+    Some((start, end))
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TagPair {
+    open_start: usize,
+    open_end: usize,
+    close_start: usize,
+    close_end: usize,
+}
+
+/// Matching `<tag ...>` / `</tag>` pairs by char index. Self-closing tags,
+/// comments and declarations are skipped.
+fn tag_pairs(chars: &[char]) -> Vec<TagPair> {
+    let mut pairs = Vec::new();
+    let mut open: Vec<(String, usize, usize)> = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] != '<' {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        let Some(end) = chars[i..].iter().position(|&c| c == '>').map(|p| i + p + 1) else {
+            break;
+        };
+        i = end;
+
+        let closing = chars.get(start + 1) == Some(&'/');
+        let name: String = chars[start + 1 + usize::from(closing)..end - 1]
+            .iter()
+            .take_while(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | ':' | '.'))
+            .collect();
+        if name.is_empty() || chars[end - 2] == '/' {
+            continue;
+        }
+        if !closing {
+            open.push((name, start, end));
+        } else if let Some(at) = open.iter().rposition(|(open_name, ..)| *open_name == name) {
+            let (_, open_start, open_end) = open[at];
+            open.truncate(at);
+            pairs.push(TagPair {
+                open_start,
+                open_end,
+                close_start: start,
+                close_end: end,
+            });
+        }
+    }
+    pairs
+}
+
+/// Lines `start..=end` of `ip`/`ap`. A paragraph is a run of non-blank
+/// lines, or of blank ones; `ap` adds the blank lines after it, or before it
+/// if there are none after.
+fn paragraph_object(text: &MotionText, line: usize, inner: bool, count: usize) -> (usize, usize) {
+    let last = text.last_line();
+    let blank = text.is_blank(line);
+    let mut start = line;
+    while start > 0 && text.is_blank(start - 1) == blank {
+        start -= 1;
+    }
+    let run_end = |from: usize| {
+        let mut end = from;
+        while end < last && text.is_blank(end + 1) == text.is_blank(from) {
+            end += 1;
+        }
+        end
+    };
+
+    let runs = if inner { count } else { count * 2 };
+    let mut end = run_end(line);
+    for _ in 1..runs {
+        if end == last {
+            break;
+        }
+        end = run_end(end + 1);
+    }
+    if !inner && !blank && end == run_end(line) {
+        // The paragraph ends the file: take the blank lines before it.
+        while start > 0 && text.is_blank(start - 1) {
+            start -= 1;
+        }
+    }
+    (start, end)
+}
+
+pub fn is_word_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+fn position_to_index(lines: &[&str], line_1: usize, col_1: usize) -> usize {
+    let mut idx = 0usize;
+    let line_idx = line_1.saturating_sub(1).min(lines.len().saturating_sub(1));
+    for line in &lines[..line_idx] {
+        idx += line.chars().count() + 1;
+    }
+    let line_len = lines.get(line_idx).map(|l| l.chars().count()).unwrap_or(0);
+    idx + col_1.saturating_sub(1).min(line_len)
+}
+
+fn index_to_position(lines: &[&str], mut idx: usize) -> (usize, usize) {
+    for (i, line) in lines.iter().enumerate() {
+        let len = line.chars().count();
+        if idx <= len {
+            return (i + 1, idx + 1);
+        }
+        idx = idx.saturating_sub(len + 1);
+    }
+    let last = lines.len().max(1);
+    let col = lines.last().map(|l| l.chars().count() + 1).unwrap_or(1);
+    (last, col)
+}
+
+pub fn next_word_start(text: &str, idx: usize, big: bool) -> usize {
+    let chars: Vec<char> = text.chars().collect();
+    let mut i = idx.min(chars.len());
+    if let Some(&ch) = chars.get(i) {
+        let class = char_class(ch, big);
+        while i < chars.len() && class != 0 && char_class(chars[i], big) == class {
+            i += 1;
+        }
+    }
+    while i < chars.len() && chars[i].is_whitespace() {
+        i += 1;
+    }
+    i
+}
+
+fn next_word_end(text: &str, idx: usize, big: bool) -> usize {
+    let chars: Vec<char> = text.chars().collect();
+    let mut i = idx.min(chars.len());
+    while i < chars.len() && chars[i].is_whitespace() {
+        i += 1;
+    }
+    if let Some(&ch) = chars.get(i) {
+        let class = char_class(ch, big);
+        while i < chars.len() && char_class(chars[i], big) == class {
+            i += 1;
+        }
+    }
+    i.saturating_sub(1)
+}
+
+fn prev_word_start(text: &str, idx: usize, big: bool) -> usize {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.is_empty() {
+        return 0;
+    }
+    let mut i = idx.saturating_sub(1).min(chars.len().saturating_sub(1));
+    while i > 0 && chars[i].is_whitespace() {
+        i -= 1;
+    }
+    let class = char_class(chars[i], big);
+    while i > 0 && char_class(chars[i - 1], big) == class {
+        i -= 1;
+    }
+    i
+}
+
+fn match_pair_index(text: &str, idx: usize) -> Option<usize> {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.is_empty() {
+        return None;
+    }
+    let i = idx.min(chars.len().saturating_sub(1));
+    let ch = chars[i];
+    let (open, close, forward) = match ch {
+        '(' => ('(', ')', true),
+        '[' => ('[', ']', true),
+        '{' => ('{', '}', true),
+        ')' => ('(', ')', false),
+        ']' => ('[', ']', false),
+        '}' => ('{', '}', false),
+        _ => return None,
+    };
+    let mut depth = 0i32;
+    if forward {
+        for (j, c) in chars.iter().enumerate().skip(i) {
+            if *c == open {
+                depth += 1;
+            } else if *c == close {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(j);
+                }
+            }
+        }
+    } else {
+        for j in (0..=i).rev() {
+            let c = chars[j];
+            if c == close {
+                depth += 1;
+            } else if c == open {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(j);
+                }
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(
+        text: &str,
+        cursor: (usize, usize),
+        inner: bool,
+        kind: TextObjectKind,
+    ) -> Option<VimRange> {
+        text_object_range(
+            &MotionText::new(text),
+            cursor,
+            TextObject { inner, kind },
+            None,
+        )
+    }
+
+    #[test]
+    fn word_motions_stop_at_punctuation() {
+        let text = MotionText::new("foo.bar baz\n  next");
+        let target = |motion, count| motion_target(&text, (0, 0), motion, count).map(|t| t.pos);
+        assert_eq!(target(Motion::WordForward(false), None), Some((0, 3)));
+        assert_eq!(target(Motion::WordForward(true), None), Some((0, 8)));
+        assert_eq!(target(Motion::WordForward(false), Some(4)), Some((1, 2)));
+        assert_eq!(target(Motion::WordEnd(false), Some(2)), Some((0, 3)));
+        assert_eq!(
+            target(Motion::Find(VimFindKind::ForwardTill, 'z'), None),
+            Some((0, 9))
+        );
+        assert_eq!(target(Motion::Up, None), None);
+    }
+
+    #[test]
+    fn pair_and_quote_objects() {
+        let text = "f(a, (b), \"c d\")";
+        let chars = |start, end| {
+            Some(VimRange::Chars {
+                start: (0, start),
+                end: (0, end),
+            })
+        };
+        let parens = TextObjectKind::Pair('(', ')');
+        assert_eq!(object(text, (0, 3), true, parens), chars(2, 15));
+        assert_eq!(object(text, (0, 6), false, parens), chars(5, 8));
+        assert_eq!(
+            object(text, (0, 12), true, TextObjectKind::Quote('"')),
+            chars(11, 14)
+        );
+        assert_eq!(object(text, (0, 0), true, parens), None);
+    }
+
+    #[test]
+    fn word_tag_and_paragraph_objects() {
+        let chars = |start, end| {
+            Some(VimRange::Chars {
+                start: (0, start),
+                end: (0, end),
+            })
+        };
+        assert_eq!(
+            object("ab cd ef", (0, 4), true, TextObjectKind::Word),
+            chars(3, 5)
+        );
+        assert_eq!(
+            object("ab cd ef", (0, 4), false, TextObjectKind::Word),
+            chars(3, 6)
+        );
+        assert_eq!(
+            object("<a><b>x</b></a>", (0, 6), true, TextObjectKind::Tag),
+            chars(6, 7)
+        );
+        assert_eq!(
+            object("<a><b>x</b></a>", (0, 6), false, TextObjectKind::Tag),
+            chars(3, 11)
+        );
+
+        let text = "one\ntwo\n\nthree";
+        let lines = |start, end| Some(VimRange::Lines { start, end });
+        assert_eq!(
+            object(text, (1, 0), true, TextObjectKind::Paragraph),
+            lines(0, 1)
+        );
+        assert_eq!(
+            object(text, (1, 0), false, TextObjectKind::Paragraph),
+            lines(0, 2)
+        );
+    }
+}
@@ -0,0 +1,115 @@
+//! Keys recorded for `.` and for macros. Macros live in registers as text,
+//! written in vim's key notation (`<Esc>`, `<CR>`, `<C-v>`) so `:registers`
+//! shows them and `"ap` puts them like any other text.
+
+use crate::message::VimKey;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VimInput {
+    Key(VimKey),
+    Escape,
+}
+
+/// Writes `inputs` in key notation.
+pub fn encode(inputs: &[VimInput]) -> String {
+    let mut text = String::new();
+    for input in inputs {
+        match input {
+            VimInput::Escape => text.push_str("<Esc>"),
+            VimInput::Key(VimKey::Char('<')) => text.push_str("<lt>"),
+            VimInput::Key(VimKey::Char(ch)) => text.push(*ch),
+            VimInput::Key(VimKey::Ctrl('i')) => text.push_str("<Tab>"),
+            VimInput::Key(VimKey::Ctrl(ch)) => text.push_str(&format!("<C-{ch}>")),
+            VimInput::Key(VimKey::Enter) => text.push_str("<CR>"),
+            VimInput::Key(VimKey::Backspace) => text.push_str("<BS>"),
+            VimInput::Key(VimKey::Up) => text.push_str("<Up>"),
+            VimInput::Key(VimKey::Down) => text.push_str("<Down>"),
+            VimInput::Key(VimKey::Left) => text.push_str("<Left>"),
+            VimInput::Key(VimKey::Right) => text.push_str("<Right>"),
+        }
+    }
+    text
+}
+
+/// Reads key notation back. A `<` that starts no known key is a plain `<`,
+/// and line breaks and tabs in the text are `<CR>` and `<Tab>`.
+pub fn decode(text: &str) -> Vec<VimInput> {
+    let mut inputs = Vec::new();
+    let mut rest = text;
+    while let Some(ch) = rest.chars().next() {
+        if let Some((input, len)) = named_key(rest) {
+            inputs.push(input);
+            rest = &rest[len..];
+            continue;
+        }
+        inputs.push(match ch {
+            '\n' => VimInput::Key(VimKey::Enter),
+            '\t' => VimInput::Key(VimKey::Ctrl('i')),
+            '\u{1b}' => VimInput::Escape,
+            _ => VimInput::Key(VimKey::Char(ch)),
+        });
+        rest = &rest[ch.len_utf8()..];
+    }
+    inputs
+}
+
+/// The `<...>` key at the start of `text`, and its length in bytes.
+fn named_key(text: &str) -> Option<(VimInput, usize)> {
+    let end = text.strip_prefix('<')?.find('>')? + 2;
+    let name = &text[1..end - 1];
+    let key = match name.to_ascii_lowercase().as_str() {
+        "esc" => return Some((VimInput::Escape, end)),
+        "lt" => VimKey::Char('<'),
+        "cr" | "enter" | "return" => VimKey::Enter,
+        "bs" => VimKey::Backspace,
+        "tab" => VimKey::Ctrl('i'),
+        "space" => VimKey::Char(' '),
+        "up" => VimKey::Up,
+        "down" => VimKey::Down,
+        "left" => VimKey::Left,
+        "right" => VimKey::Right,
+        _ => {
+            let ctrl = name
+                .strip_prefix("C-")
+                .or_else(|| name.strip_prefix("c-"))?;
+            let mut chars = ctrl.chars();
+            let ch = chars.next().filter(|_| chars.next().is_none())?;
+            VimKey::Ctrl(ch.to_ascii_lowercase())
+        }
+    };
+    Some((VimInput::Key(key), end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_notation_round_trips() {
+        let inputs = vec![
+            VimInput::Key(VimKey::Char('c')),
+            VimInput::Key(VimKey::Char('w')),
+            VimInput::Key(VimKey::Char('<')),
+            VimInput::Key(VimKey::Enter),
+            VimInput::Escape,
+            VimInput::Key(VimKey::Ctrl('v')),
+            VimInput::Key(VimKey::Down),
+        ];
+        let text = encode(&inputs);
+        assert_eq!(text, "cw<lt><CR><Esc><C-v><Down>");
+        assert_eq!(decode(&text), inputs);
+    }
+
+    #[test]
+    fn plain_text_decodes_as_keys() {
+        assert_eq!(
+            decode("a<b\n"),
+            vec![
+                VimInput::Key(VimKey::Char('a')),
+                VimInput::Key(VimKey::Char('<')),
+                VimInput::Key(VimKey::Char('b')),
+                VimInput::Key(VimKey::Enter),
+            ]
+        );
+    }
+}
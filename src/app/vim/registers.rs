@@ -0,0 +1,234 @@
+//! Where yanked and deleted text goes: the unnamed register `"`, `0` for the
+//! last yank, `1`-`9` for the last deletes of whole lines (newest first),
+//! `a`-`z` (appended to through `A`-`Z`), the black hole `_`, and `+`/`*`
+//! for the system clipboard.
+
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RegisterKind {
+    #[default]
+    Chars,
+    Lines,
+    Block,
+}
+
+/// Yanked or deleted text, with the shape it was taken in.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VimRegister {
+    pub text: String,
+    pub kind: RegisterKind,
+}
+
+impl VimRegister {
+    /// Adds `other` to the end, as `"Ay` does. Appending lines to text, or
+    /// text to lines, gives lines.
+    fn append(&mut self, other: VimRegister) {
+        if self.text.is_empty() {
+            *self = other;
+            return;
+        }
+        if self.kind == RegisterKind::Lines || other.kind == RegisterKind::Lines {
+            if !self.text.ends_with('\n') {
+                self.text.push('\n');
+            }
+            self.text.push_str(&other.text);
+            if !self.text.ends_with('\n') {
+                self.text.push('\n');
+            }
+            self.kind = RegisterKind::Lines;
+        } else {
+            self.text.push_str(&other.text);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct VimRegisters {
+    unnamed: VimRegister,
+    numbered: [VimRegister; 10],
+    /// `a`-`z`, and the last text written to `+` and `*`.
+    named: BTreeMap<char, VimRegister>,
+}
+
+/// Whether `name` can follow `"` in a command.
+pub fn is_register_name(name: char) -> bool {
+    name.is_ascii_alphanumeric() || matches!(name, '"' | '_' | '+' | '*')
+}
+
+impl VimRegisters {
+    /// What a put from `name` inserts; `None` if the register is empty.
+    pub fn get(&self, name: char) -> Option<&VimRegister> {
+        let register = match name {
+            '"' => Some(&self.unnamed),
+            '0'..='9' => self.numbered.get(name.to_digit(10)? as usize),
+            _ => self.named.get(&name.to_ascii_lowercase()),
+        }?;
+        (!register.text.is_empty()).then_some(register)
+    }
+
+    /// Stores what `y` (`yank`) or `d`/`c` took. Without a register name
+    /// the text also goes to `0` or shifts into `1`-`9`; whatever the name,
+    /// the unnamed register ends up holding it, except for `_`.
+    pub fn store(&mut self, name: Option<char>, register: VimRegister, yank: bool) {
+        let register = match name {
+            Some('_') => return,
+            None | Some('"') => {
+                if yank {
+                    self.numbered[0] = register.clone();
+                } else if register.kind == RegisterKind::Lines || register.text.contains('\n') {
+                    self.numbered[1..].rotate_right(1);
+                    self.numbered[1] = register.clone();
+                }
+                register
+            }
+            Some(digit @ '0'..='9') => {
+                self.numbered[digit as usize - '0' as usize] = register.clone();
+                register
+            }
+            Some(upper @ 'A'..='Z') => {
+                let named = self.named.entry(upper.to_ascii_lowercase()).or_default();
+                named.append(register);
+                named.clone()
+            }
+            Some(name) => {
+                self.named.insert(name, register.clone());
+                register
+            }
+        };
+        self.unnamed = register;
+    }
+
+    /// Stores a recorded macro in `name`, appending for `A`-`Z`. Unlike
+    /// [`VimRegisters::store`], the unnamed register is left alone.
+    pub fn record(&mut self, name: char, keys: String) {
+        let register = VimRegister {
+            text: keys,
+            kind: RegisterKind::Chars,
+        };
+        match name {
+            '"' => self.unnamed = register,
+            '0'..='9' => self.numbered[name as usize - '0' as usize] = register,
+            'A'..='Z' => self
+                .named
+                .entry(name.to_ascii_lowercase())
+                .or_default()
+                .append(register),
+            _ => {
+                self.named.insert(name, register);
+            }
+        }
+    }
+
+    /// Text read back from the clipboard. If it is what vim last put there
+    /// it keeps its shape; otherwise it is lines when it ends in a line
+    /// break.
+    pub fn read_clipboard(&self, name: char, text: String) -> VimRegister {
+        if let Some(register) = self.named.get(&name).filter(|r| r.text == text) {
+            return register.clone();
+        }
+        let kind = if text.ends_with('\n') {
+            RegisterKind::Lines
+        } else {
+            RegisterKind::Chars
+        };
+        VimRegister { text, kind }
+    }
+
+    /// The `:registers` table, limited to the registers named in `names`
+    /// when it is not empty.
+    pub fn listing(&self, names: &str) -> Vec<String> {
+        let wanted = |name: char| names.is_empty() || names.contains(name);
+        let numbered = ('0'..='9').zip(self.numbered.iter());
+        let registers = std::iter::once(('"', &self.unnamed))
+            .chain(numbered)
+            .chain(self.named.iter().map(|(&name, register)| (name, register)));
+
+        let mut lines = vec!["Type Name Content".to_string()];
+        for (name, register) in registers {
+            if register.text.is_empty() || !wanted(name) {
+                continue;
+            }
+            let kind = match register.kind {
+                RegisterKind::Chars => 'c',
+                RegisterKind::Lines => 'l',
+                RegisterKind::Block => 'b',
+            };
+            let content: String = register
+                .text
+                .replace('\n', "^J")
+                .replace('\t', "^I")
+                .chars()
+                .take(80)
+                .collect();
+            lines.push(format!("  {kind}  \"{name}   {content}"));
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chars(text: &str) -> VimRegister {
+        VimRegister {
+            text: text.to_string(),
+            kind: RegisterKind::Chars,
+        }
+    }
+
+    fn lines(text: &str) -> VimRegister {
+        VimRegister {
+            text: text.to_string(),
+            kind: RegisterKind::Lines,
+        }
+    }
+
+    #[test]
+    fn yanks_and_deletes_fill_the_numbered_registers() {
+        let mut registers = VimRegisters::default();
+        registers.store(None, chars("word"), true);
+        registers.store(None, lines("first\n"), false);
+        registers.store(None, lines("second\n"), false);
+        registers.store(None, chars("x"), false);
+
+        assert_eq!(registers.get('0'), Some(&chars("word")));
+        assert_eq!(registers.get('1'), Some(&lines("second\n")));
+        assert_eq!(registers.get('2'), Some(&lines("first\n")));
+        assert_eq!(registers.get('"'), Some(&chars("x")));
+        assert_eq!(registers.get('3'), None);
+    }
+
+    #[test]
+    fn named_registers_append_and_black_hole_drops() {
+        let mut registers = VimRegisters::default();
+        registers.store(Some('a'), chars("one"), true);
+        registers.store(Some('A'), chars(" two"), true);
+        assert_eq!(registers.get('a'), Some(&chars("one two")));
+        registers.store(Some('A'), lines("three\n"), true);
+        assert_eq!(registers.get('a'), Some(&lines("one two\nthree\n")));
+
+        registers.store(Some('_'), chars("gone"), false);
+        assert_eq!(registers.get('"'), Some(&lines("one two\nthree\n")));
+        assert_eq!(registers.get('0'), None);
+    }
+
+    #[test]
+    fn clipboard_text_keeps_its_shape_when_unchanged() {
+        let mut registers = VimRegisters::default();
+        registers.store(Some('+'), chars("line\n"), true);
+        assert_eq!(
+            registers.read_clipboard('+', "line\n".to_string()).kind,
+            RegisterKind::Chars
+        );
+        assert_eq!(
+            registers.read_clipboard('+', "other\n".to_string()).kind,
+            RegisterKind::Lines
+        );
+        assert_eq!(
+            registers.listing("+"),
+            ["Type Name Content", "  c  \"+   line^J"]
+        );
+    }
+}
@@ -0,0 +1,143 @@
+//! `/`, `?`, `*`, `#`, `n` and `N`. Matching is the find panel's
+//! [`FindReplace`] with vim's smartcase: a pattern with a capital letter is
+//! case-sensitive. Matches are byte offsets into the whole text.
+
+use super::motion::is_word_char;
+use crate::features::find_replace::FindReplace;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VimSearch {
+    pub pattern: String,
+    /// Started with `?` or `#`, so `n` goes up.
+    pub backward: bool,
+    /// Started with `*` or `#`: only whole words match, in the same case.
+    pub whole_word: bool,
+}
+
+impl VimSearch {
+    pub fn matches(&self, text: &str) -> Vec<usize> {
+        let mut find = FindReplace {
+            find_text: self.pattern.clone(),
+            case_sensitive: self.whole_word || self.pattern.chars().any(char::is_uppercase),
+            ..FindReplace::default()
+        };
+        let end = |start: usize| start + self.pattern.len();
+        find.find_matches(text)
+            .into_iter()
+            // Lowercasing can shift offsets in non-ASCII text.
+            .filter(|&start| text.is_char_boundary(start) && text.is_char_boundary(end(start)))
+            .filter(|&start| {
+                !self.whole_word
+                    || !(text[..start].chars().next_back().is_some_and(is_word_char)
+                        || text[end(start)..].chars().next().is_some_and(is_word_char))
+            })
+            .collect()
+    }
+
+    /// The `count`th match after `from`, or before it when `forward` is
+    /// false, wrapping around the ends of the text.
+    pub fn next_match(
+        &self,
+        text: &str,
+        from: usize,
+        forward: bool,
+        count: usize,
+    ) -> Option<usize> {
+        let matches = self.matches(text);
+        let mut pos = from;
+        for _ in 0..count.max(1) {
+            pos = if forward {
+                matches
+                    .iter()
+                    .find(|&&start| start > pos)
+                    .or(matches.first())
+            } else {
+                matches
+                    .iter()
+                    .rev()
+                    .find(|&&start| start < pos)
+                    .or(matches.last())
+            }
+            .copied()?;
+        }
+        Some(pos)
+    }
+}
+
+/// Byte offset of the 0-based `(line, column)` position.
+pub fn byte_offset(text: &str, (line, col): (usize, usize)) -> usize {
+    let line_start: usize = text.split_inclusive('\n').take(line).map(str::len).sum();
+    let line_text = text[line_start..].split('\n').next().unwrap_or("");
+    line_start
+        + line_text
+            .char_indices()
+            .nth(col)
+            .map_or(line_text.len(), |(idx, _)| idx)
+}
+
+/// The 0-based `(line, column)` position of a byte offset.
+pub fn byte_position(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset];
+    let line = before.matches('\n').count();
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+    (line, before[line_start..].chars().count())
+}
+
+/// The word `*` searches for and the column it starts at: the word under
+/// the cursor, or the next one on the line.
+pub fn word_at(line: &str, col: usize) -> Option<(usize, String)> {
+    let chars: Vec<char> = line.chars().collect();
+    let start = (col..chars.len()).find(|&idx| is_word_char(chars[idx]))?;
+    let start = (0..start)
+        .rev()
+        .take_while(|&idx| is_word_char(chars[idx]))
+        .last()
+        .unwrap_or(start);
+    let word = chars[start..]
+        .iter()
+        .take_while(|&&ch| is_word_char(ch))
+        .collect();
+    Some((start, word))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(pattern: &str, whole_word: bool) -> VimSearch {
+        VimSearch {
+            pattern: pattern.to_string(),
+            backward: false,
+            whole_word,
+        }
+    }
+
+    #[test]
+    fn smartcase_and_whole_words() {
+        let text = "foo Foo food foo_bar";
+        assert_eq!(search("foo", false).matches(text), [0, 4, 8, 13]);
+        assert_eq!(search("Foo", false).matches(text), [4]);
+        assert_eq!(search("foo", true).matches(text), [0]);
+    }
+
+    #[test]
+    fn next_match_wraps_around() {
+        let text = "a\nb a\na";
+        let a = search("a", false);
+        assert_eq!(a.next_match(text, 0, true, 1), Some(4));
+        assert_eq!(a.next_match(text, 4, true, 2), Some(0));
+        assert_eq!(a.next_match(text, 0, false, 1), Some(6));
+        assert_eq!(search("z", false).next_match(text, 0, true, 1), None);
+    }
+
+    #[test]
+    fn positions_and_words() {
+        let text = "héllo\nwörld";
+        assert_eq!(byte_offset(text, (1, 2)), 10);
+        assert_eq!(byte_position(text, 10), (1, 2));
+        assert_eq!(byte_offset(text, (0, 99)), 6);
+        assert_eq!(word_at("  let x_1 = y", 7), Some((6, "x_1".to_string())));
+        assert_eq!(word_at("  let x", 0), Some((2, "let".to_string())));
+        assert_eq!(word_at("x  ", 1), None);
+    }
+}
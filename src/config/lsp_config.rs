@@ -0,0 +1,370 @@
+//! `lsp.lua`: which language server runs for which files.
+//!
+//! Entries are keyed by LSP language id. An entry for a built-in language is
+//! merged over it field by field, so pinning a command keeps its root
+//! markers and file patterns; new languages need a `command` and
+//! `file_patterns`.
+//!
+//! ```lua
+//! return {
+//!     rust = { command = { "/opt/rust-analyzer/bin/rust-analyzer" } },
+//!     c = {
+//!         command = { "clangd", "--background-index" },
+//!         root_markers = { "compile_commands.json", ".git" },
+//!         file_patterns = { "*.c", "*.h" },
+//!     },
+//!     zig = { command = { "zls" }, root_markers = { "build.zig" }, file_patterns = { "*.zig" } },
+//!     python = {
+//!         settings = { python = { analysis = { typeCheckingMode = "strict" } } },
+//!     },
+//!     lua = { enabled = false },
+//! }
+//! ```
+
+use super::lua_config::{self, ConfigTable};
+use super::theme_manager::get_config_dir;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How to run the language server for one language.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LanguageServer {
+    /// The LSP language id documents are opened with, e.g. `rust`.
+    pub language: String,
+    /// Names the server in logs and the status bar. Languages whose servers
+    /// share a name share one process per workspace root, started with the
+    /// settings of whichever language opened first.
+    pub name: String,
+    /// The program and its arguments.
+    pub command: Vec<String>,
+    /// Files that mark a workspace root, most telling first: the root is the
+    /// nearest folder holding the first marker found, then the next.
+    pub root_markers: Vec<String>,
+    /// File name patterns such as `*.rs`, where `*` and `?` are wildcards.
+    pub file_patterns: Vec<String>,
+    /// Sent as `initializationOptions` in `initialize`.
+    pub initialization_options: Option<Value>,
+    /// Sent with `workspace/didChangeConfiguration` and used to answer
+    /// `workspace/configuration`.
+    pub settings: Option<Value>,
+}
+
+impl LanguageServer {
+    fn builtin(
+        language: &str,
+        name: &str,
+        command: &[&str],
+        root_markers: &[&str],
+        file_patterns: &[&str],
+    ) -> Self {
+        let strings = |items: &[&str]| items.iter().map(|item| item.to_string()).collect();
+        Self {
+            language: language.to_string(),
+            name: name.to_string(),
+            command: strings(command),
+            root_markers: strings(root_markers),
+            file_patterns: strings(file_patterns),
+            initialization_options: None,
+            settings: None,
+        }
+    }
+
+    pub fn handles(&self, path: &Path) -> bool {
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            return false;
+        };
+        self.file_patterns
+            .iter()
+            .any(|pattern| pattern_matches(pattern, name))
+    }
+}
+
+/// The language servers from `lsp.lua` over the built-in ones.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LspRegistry {
+    /// By language id.
+    languages: BTreeMap<String, LanguageServer>,
+}
+
+impl Default for LspRegistry {
+    fn default() -> Self {
+        let languages = [
+            LanguageServer::builtin(
+                "rust",
+                "rust-analyzer",
+                &["rust-analyzer"],
+                &["Cargo.lock", "Cargo.toml", "rust-project.json", ".git"],
+                &["*.rs"],
+            ),
+            LanguageServer::builtin(
+                "python",
+                "pyright",
+                &["pyright-langserver", "--stdio"],
+                &[
+                    "pyproject.toml",
+                    "setup.py",
+                    "setup.cfg",
+                    "requirements.txt",
+                    "pyrightconfig.json",
+                    ".git",
+                ],
+                &["*.py"],
+            ),
+            LanguageServer::builtin(
+                "javascript",
+                "typescript-language-server",
+                &["typescript-language-server", "--stdio"],
+                &["tsconfig.json", "jsconfig.json", "package.json", ".git"],
+                &["*.js", "*.jsx"],
+            ),
+            LanguageServer::builtin(
+                "typescript",
+                "typescript-language-server",
+                &["typescript-language-server", "--stdio"],
+                &["tsconfig.json", "jsconfig.json", "package.json", ".git"],
+                &["*.ts", "*.tsx"],
+            ),
+            LanguageServer::builtin(
+                "lua",
+                "lua-language-server",
+                &["lua-language-server"],
+                &[".luarc.json", ".luarc.jsonc", ".git"],
+                &["*.lua"],
+            ),
+            LanguageServer::builtin(
+                "go",
+                "gopls",
+                &["gopls"],
+                &["go.work", "go.mod", ".git"],
+                &["*.go"],
+            ),
+        ];
+        Self {
+            languages: languages
+                .into_iter()
+                .map(|server| (server.language.clone(), server))
+                .collect(),
+        }
+    }
+}
+
+impl LspRegistry {
+    /// The server for a file, going by its file name.
+    pub fn for_path(&self, path: &Path) -> Option<&LanguageServer> {
+        self.languages.values().find(|server| server.handles(path))
+    }
+
+    /// The first language's entry for the server called `name`.
+    pub fn server(&self, name: &str) -> Option<&LanguageServer> {
+        self.languages.values().find(|server| server.name == name)
+    }
+
+    /// One entry per server, for listing them.
+    pub fn servers(&self) -> Vec<&LanguageServer> {
+        let mut servers: Vec<&LanguageServer> = Vec::new();
+        for server in self.languages.values() {
+            if !servers.iter().any(|known| known.name == server.name) {
+                servers.push(server);
+            }
+        }
+        servers
+    }
+}
+
+pub fn get_lsp_config_path() -> PathBuf {
+    get_config_dir().join("lsp.lua")
+}
+
+/// Loads `lsp.lua`; a missing file gives the built-in servers.
+pub fn load() -> Result<LspRegistry, String> {
+    match fs::read_to_string(get_lsp_config_path()) {
+        Ok(content) => from_lua(&content),
+        Err(_) => Ok(LspRegistry::default()),
+    }
+}
+
+fn from_lua(content: &str) -> Result<LspRegistry, String> {
+    let table = lua_config::eval("lsp.lua", content)?;
+    let mut registry = LspRegistry::default();
+    for (language, entry) in table.tables()? {
+        if entry.boolean("enabled")? == Some(false) {
+            registry.languages.remove(&language);
+            continue;
+        }
+        let server = match registry.languages.remove(&language) {
+            Some(builtin) => merge(builtin, &entry)?,
+            None => new_language(&language, &entry)?,
+        };
+        registry.languages.insert(language, server);
+    }
+    Ok(registry)
+}
+
+fn merge(mut server: LanguageServer, entry: &ConfigTable) -> Result<LanguageServer, String> {
+    if let Some(command) = entry.string_list("command")? {
+        if command.is_empty() {
+            return Err(format!(
+                "lsp.lua: `{}.command` must not be empty",
+                server.language
+            ));
+        }
+        server.command = command;
+    }
+    if let Some(name) = entry.string("name")? {
+        server.name = name;
+    }
+    if let Some(markers) = entry.string_list("root_markers")? {
+        server.root_markers = markers;
+    }
+    if let Some(patterns) = entry.string_list("file_patterns")? {
+        server.file_patterns = patterns;
+    }
+    if let Some(options) = entry.json("initialization_options")? {
+        server.initialization_options = Some(options);
+    }
+    if let Some(settings) = entry.json("settings")? {
+        server.settings = Some(settings);
+    }
+    Ok(server)
+}
+
+fn new_language(language: &str, entry: &ConfigTable) -> Result<LanguageServer, String> {
+    let empty = LanguageServer::builtin(language, "", &[], &[], &[]);
+    let mut server = merge(empty, entry)?;
+    let Some(program) = server.command.first() else {
+        return Err(format!("lsp.lua: `{language}` needs a `command`"));
+    };
+    if server.file_patterns.is_empty() {
+        return Err(format!("lsp.lua: `{language}` needs `file_patterns`"));
+    }
+    if server.name.is_empty() {
+        server.name = Path::new(program)
+            .file_name()
+            .map_or_else(|| program.clone(), |name| name.to_string_lossy().into());
+    }
+    Ok(server)
+}
+
+/// Matches a file name against a pattern where `*` stands for any run of
+/// characters and `?` for one.
+fn pattern_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    // Backtracks to the last `*`, letting it take one more character.
+    let (mut p, mut n) = (0, 0);
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// The workspace root for a file in `dir`: the nearest folder holding the
+/// first of `markers` that any folder above it holds.
+pub fn find_root(dir: &Path, markers: &[String]) -> Option<PathBuf> {
+    markers.iter().find_map(|marker| {
+        dir.ancestors()
+            .find(|folder| folder.join(marker).exists())
+            .map(Path::to_path_buf)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns_match_file_names() {
+        assert!(pattern_matches("*.rs", "main.rs"));
+        assert!(!pattern_matches("*.rs", "main.rsx"));
+        assert!(pattern_matches("Makefile", "Makefile"));
+        assert!(pattern_matches("*.test.*", "app.test.ts"));
+        assert!(pattern_matches("?.c", "a.c"));
+        assert!(!pattern_matches("?.c", "ab.c"));
+        assert!(pattern_matches("*", ""));
+    }
+
+    #[test]
+    fn lsp_lua_merges_over_the_builtin_servers() {
+        let registry = from_lua(
+            r#"return {
+                rust = { command = { "/opt/ra/rust-analyzer" }, settings = { ["rust-analyzer"] = { cargo = { features = "all" } } } },
+                zig = { command = { "/usr/bin/zls" }, root_markers = { "build.zig" }, file_patterns = { "*.zig" } },
+                lua = { enabled = false },
+            }"#,
+        )
+        .unwrap();
+
+        let rust = registry.for_path(Path::new("/src/main.rs")).unwrap();
+        assert_eq!(rust.name, "rust-analyzer");
+        assert_eq!(rust.command, ["/opt/ra/rust-analyzer"]);
+        assert_eq!(rust.root_markers[0], "Cargo.lock");
+        assert_eq!(
+            rust.settings,
+            Some(serde_json::json!({ "rust-analyzer": { "cargo": { "features": "all" } } }))
+        );
+
+        let zig = registry.for_path(Path::new("build.zig")).unwrap();
+        assert_eq!((zig.language.as_str(), zig.name.as_str()), ("zig", "zls"));
+        assert!(registry.for_path(Path::new("init.lua")).is_none());
+        assert_eq!(
+            registry
+                .servers()
+                .iter()
+                .filter(|server| server.name == "typescript-language-server")
+                .count(),
+            1
+        );
+
+        assert_eq!(
+            from_lua("return { zig = { file_patterns = { '*.zig' } } }").unwrap_err(),
+            "lsp.lua: `zig` needs a `command`"
+        );
+    }
+
+    #[test]
+    fn roots_follow_marker_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let member = dir.path().join("crates").join("core");
+        fs::create_dir_all(member.join("src")).unwrap();
+        fs::write(dir.path().join("Cargo.lock"), "").unwrap();
+        fs::write(dir.path().join("Cargo.toml"), "").unwrap();
+        fs::write(member.join("Cargo.toml"), "").unwrap();
+
+        let markers = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>()
+        };
+        let src = member.join("src");
+        assert_eq!(
+            find_root(&src, &markers(&["Cargo.lock", "Cargo.toml"])).as_deref(),
+            Some(dir.path())
+        );
+        assert_eq!(
+            find_root(&src, &markers(&["Cargo.toml"])).as_deref(),
+            Some(member.as_path())
+        );
+        assert_eq!(find_root(&src, &markers(&["go.mod"])), None);
+    }
+}
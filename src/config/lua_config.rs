@@ -0,0 +1,654 @@
+//! Config files (`preferences.lua`, `wakatime.lua`, `theme.lua`, `lsp.lua`) are
+//! real Lua: each is evaluated in its own interpreter and must return a table,
+//! so users can read environment variables, branch on the host and so on.
+//!
+//! Saving patches the assignments in the existing file instead of
+//! regenerating it, which keeps comments, layout and any computed values the
+//! editor did not change.
+
+use mlua::{Lua, Table, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// The table returned by a config file.
+pub struct ConfigTable {
+    table: Table,
+    file: String,
+    // Keeps the state `table` lives in alive.
+    _lua: Lua,
+}
+
+/// Runs `source` and returns the table it evaluates to. `file` names the
+/// config in error messages, e.g. `preferences.lua:3: unexpected symbol`.
+pub fn eval(file: &str, source: &str) -> Result<ConfigTable, String> {
+    let lua = Lua::new();
+    let value: Value = lua
+        .load(source)
+        .set_name(format!("={file}"))
+        .eval()
+        .map_err(|err| describe(&err))?;
+
+    match value {
+        Value::Table(table) => Ok(ConfigTable {
+            table,
+            file: file.to_string(),
+            _lua: lua,
+        }),
+        other => Err(format!(
+            "{file} must return a table, got {}",
+            other.type_name()
+        )),
+    }
+}
+
+/// First line of a Lua error without mlua's `runtime error: ` style prefix.
+fn describe(err: &mlua::Error) -> String {
+    let message = err.to_string();
+    let first = message.lines().next().unwrap_or_default();
+    first
+        .strip_prefix("runtime error: ")
+        .or_else(|| first.strip_prefix("syntax error: "))
+        .unwrap_or(first)
+        .to_string()
+}
+
+impl ConfigTable {
+    fn get(&self, key: &str) -> Result<Value, String> {
+        self.table
+            .get(key)
+            .map_err(|err| format!("{}: reading `{key}`: {}", self.file, describe(&err)))
+    }
+
+    fn type_error(&self, key: &str, expected: &str, value: &Value) -> String {
+        format!(
+            "{}: `{key}` must be {expected}, got {}",
+            self.file,
+            value.type_name()
+        )
+    }
+
+    pub fn string(&self, key: &str) -> Result<Option<String>, String> {
+        match self.get(key)? {
+            Value::Nil => Ok(None),
+            Value::String(s) => Ok(Some(s.to_string_lossy())),
+            other => Err(self.type_error(key, "a string", &other)),
+        }
+    }
+
+    pub fn boolean(&self, key: &str) -> Result<Option<bool>, String> {
+        match self.get(key)? {
+            Value::Nil => Ok(None),
+            Value::Boolean(b) => Ok(Some(b)),
+            other => Err(self.type_error(key, "a boolean", &other)),
+        }
+    }
+
+    pub fn integer(&self, key: &str) -> Result<Option<i64>, String> {
+        match self.get(key)? {
+            Value::Nil => Ok(None),
+            Value::Integer(i) => Ok(Some(i)),
+            Value::Number(n) if n.fract() == 0.0 => Ok(Some(n as i64)),
+            Value::Number(n) => Err(format!(
+                "{}: `{key}` must be a whole number, got {n}",
+                self.file
+            )),
+            other => Err(self.type_error(key, "an integer", &other)),
+        }
+    }
+
+    pub fn number(&self, key: &str) -> Result<Option<f64>, String> {
+        match self.get(key)? {
+            Value::Nil => Ok(None),
+            Value::Integer(i) => Ok(Some(i as f64)),
+            Value::Number(n) => Ok(Some(n)),
+            other => Err(self.type_error(key, "a number", &other)),
+        }
+    }
+
+    /// A list of strings such as `{ "a", "b" }`.
+    pub fn string_list(&self, key: &str) -> Result<Option<Vec<String>>, String> {
+        let table = match self.get(key)? {
+            Value::Nil => return Ok(None),
+            Value::Table(table) => table,
+            other => return Err(self.type_error(key, "a list of strings", &other)),
+        };
+
+        let mut items = Vec::new();
+        for (index, value) in table.sequence_values::<Value>().enumerate() {
+            match value.map_err(|err| describe(&err))? {
+                Value::String(s) => items.push(s.to_string_lossy()),
+                other => {
+                    return Err(format!(
+                        "{}: `{key}[{}]` must be a string, got {}",
+                        self.file,
+                        index + 1,
+                        other.type_name()
+                    ))
+                }
+            }
+        }
+        Ok(Some(items))
+    }
+
+    /// A table of strings keyed by name, such as `{ rust = "rustfmt" }`.
+    pub fn string_map(&self, key: &str) -> Result<Option<BTreeMap<String, String>>, String> {
+        let table = match self.get(key)? {
+            Value::Nil => return Ok(None),
+            Value::Table(table) => table,
+            other => return Err(self.type_error(key, "a table of strings", &other)),
+        };
+
+        let mut items = BTreeMap::new();
+        for pair in table.pairs::<Value, Value>() {
+            let (name, value) = pair.map_err(|err| describe(&err))?;
+            let Value::String(name) = name else {
+                return Err(format!(
+                    "{}: `{key}` keys must be strings, got {}",
+                    self.file,
+                    name.type_name()
+                ));
+            };
+            let name = name.to_string_lossy();
+            match value {
+                Value::String(s) => {
+                    items.insert(name, s.to_string_lossy());
+                }
+                other => {
+                    return Err(format!(
+                        "{}: `{key}.{name}` must be a string, got {}",
+                        self.file,
+                        other.type_name()
+                    ))
+                }
+            }
+        }
+        Ok(Some(items))
+    }
+
+    /// A list of tables such as `{ { line = 1 }, { line = 2 } }`.
+    pub fn table_list(&self, key: &str) -> Result<Option<Vec<ConfigTable>>, String> {
+        let table = match self.get(key)? {
+            Value::Nil => return Ok(None),
+            Value::Table(table) => table,
+            other => return Err(self.type_error(key, "a list of tables", &other)),
+        };
+
+        let mut items = Vec::new();
+        for (index, value) in table.sequence_values::<Value>().enumerate() {
+            match value.map_err(|err| describe(&err))? {
+                Value::Table(table) => items.push(ConfigTable {
+                    table,
+                    file: self.file.clone(),
+                    _lua: self._lua.clone(),
+                }),
+                other => {
+                    return Err(format!(
+                        "{}: `{key}[{}]` must be a table, got {}",
+                        self.file,
+                        index + 1,
+                        other.type_name()
+                    ))
+                }
+            }
+        }
+        Ok(Some(items))
+    }
+
+    /// The table's own fields, each of which must be a table, sorted by key.
+    /// For configs keyed by name, such as `{ rust = { ... } }`.
+    pub fn tables(&self) -> Result<Vec<(String, ConfigTable)>, String> {
+        let mut items = Vec::new();
+        for pair in self.table.pairs::<Value, Value>() {
+            let (name, value) = pair.map_err(|err| describe(&err))?;
+            let Value::String(name) = name else {
+                return Err(format!(
+                    "{}: keys must be strings, got {}",
+                    self.file,
+                    name.type_name()
+                ));
+            };
+            let name = name.to_string_lossy();
+            match value {
+                Value::Table(table) => items.push((
+                    name,
+                    ConfigTable {
+                        table,
+                        file: self.file.clone(),
+                        _lua: self._lua.clone(),
+                    },
+                )),
+                other => {
+                    return Err(format!(
+                        "{}: `{name}` must be a table, got {}",
+                        self.file,
+                        other.type_name()
+                    ))
+                }
+            }
+        }
+        items.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(items)
+    }
+
+    /// Any plain value as JSON, for options passed through to other
+    /// programs. Tables with only the keys `1..n` become arrays and other
+    /// tables objects.
+    pub fn json(&self, key: &str) -> Result<Option<serde_json::Value>, String> {
+        match self.get(key)? {
+            Value::Nil => Ok(None),
+            value => to_json(value, key)
+                .map(Some)
+                .map_err(|err| format!("{}: {err}", self.file)),
+        }
+    }
+}
+
+fn to_json(value: Value, key: &str) -> Result<serde_json::Value, String> {
+    use serde_json::Value as Json;
+    Ok(match value {
+        Value::Nil => Json::Null,
+        Value::Boolean(b) => Json::Bool(b),
+        Value::Integer(i) => Json::from(i),
+        Value::Number(n) => serde_json::Number::from_f64(n)
+            .map(Json::Number)
+            .ok_or_else(|| format!("`{key}` must be a finite number, got {n}"))?,
+        Value::String(s) => Json::String(s.to_string_lossy()),
+        Value::Table(table) => {
+            let len = table.raw_len();
+            let pairs = table
+                .pairs::<Value, Value>()
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|err| describe(&err))?;
+            let index = |name: &Value| match name {
+                Value::Integer(i) if (1..=len as i64).contains(i) => Some(*i as usize),
+                _ => None,
+            };
+            if len > 0 && pairs.len() == len && pairs.iter().all(|(name, _)| index(name).is_some())
+            {
+                let mut items = vec![Json::Null; len];
+                for (name, value) in pairs {
+                    let Some(i) = index(&name) else { continue };
+                    items[i - 1] = to_json(value, &format!("{key}[{i}]"))?;
+                }
+                Json::Array(items)
+            } else {
+                let mut object = serde_json::Map::new();
+                for (name, value) in pairs {
+                    let name = match name {
+                        Value::String(name) => name.to_string_lossy(),
+                        Value::Integer(i) => i.to_string(),
+                        other => {
+                            return Err(format!(
+                                "`{key}` keys must be strings, got {}",
+                                other.type_name()
+                            ))
+                        }
+                    };
+                    let value = to_json(value, &format!("{key}.{name}"))?;
+                    object.insert(name, value);
+                }
+                Json::Object(object)
+            }
+        }
+        other => return Err(format!("`{key}` can't hold a {}", other.type_name())),
+    })
+}
+
+/// Formats a string as a double-quoted Lua literal.
+pub fn lua_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for ch in value.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\{:03}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+pub fn lua_string_list(values: &[String]) -> String {
+    if values.is_empty() {
+        return "{}".to_string();
+    }
+    let items: Vec<String> = values.iter().map(|value| lua_string(value)).collect();
+    format!("{{ {} }}", items.join(", "))
+}
+
+/// A table key: the plain name, or `["key"]` when it isn't a valid one.
+pub fn lua_key(key: &str) -> String {
+    let plain = key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if plain {
+        key.to_string()
+    } else {
+        format!("[{}]", lua_string(key))
+    }
+}
+
+/// Formats a table of strings such as `{ rust = "rustfmt" }`.
+pub fn lua_string_map(values: &BTreeMap<String, String>) -> String {
+    if values.is_empty() {
+        return "{}".to_string();
+    }
+    let items: Vec<String> = values
+        .iter()
+        .map(|(key, value)| format!("{} = {}", lua_key(key), lua_string(value)))
+        .collect();
+    format!("{{ {} }}", items.join(", "))
+}
+
+/// Saves `fields` (key, Lua literal) to the config file at `path`.
+///
+/// A new file is written from `template`. An existing one is evaluated with
+/// `current` and patched with [`patch_source`]; if it no longer evaluates, it
+/// is left untouched and the error returned, since rewriting it could lose
+/// whatever the user was in the middle of writing.
+pub fn save_fields(
+    path: &Path,
+    fields: &[(&str, String)],
+    current: impl FnOnce(&str) -> Result<Vec<(&'static str, String)>, String>,
+    template: impl FnOnce() -> String,
+) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let content = match fs::read_to_string(path) {
+        Ok(existing) => current(&existing)
+            .and_then(|previous| patch_source(&existing, fields, &previous))
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?,
+        Err(_) => template(),
+    };
+
+    let mut file = fs::File::create(path)?;
+    file.write_all(content.as_bytes())
+}
+
+/// Writes `fields` (key, Lua literal) into an existing config source.
+///
+/// `previous` holds the same fields as the file evaluated to before; keys
+/// whose literal is unchanged are left alone so computed values like
+/// `os.getenv(...)` survive a save. Changed keys have their value replaced
+/// on the `key = ...` line, keeping any trailing comment, or are added at the
+/// end of the returned table.
+pub fn patch_source(
+    source: &str,
+    fields: &[(&str, String)],
+    previous: &[(&str, String)],
+) -> Result<String, String> {
+    let mut out = source.to_string();
+
+    for (key, literal) in fields {
+        let unchanged = previous
+            .iter()
+            .any(|(prev_key, prev_literal)| prev_key == key && prev_literal == literal);
+        if unchanged {
+            continue;
+        }
+
+        out = match find_assignment(&out, key) {
+            Some(range) => format!("{}{literal}{}", &out[..range.start], &out[range.end..]),
+            None => insert_field(&out, key, literal)
+                .ok_or_else(|| format!("no `{key} = ...` line or table to add it to"))?,
+        };
+    }
+
+    Ok(out)
+}
+
+/// Byte range of the value in the first line that starts with `key =`.
+fn find_assignment(source: &str, key: &str) -> Option<std::ops::Range<usize>> {
+    let mut offset = 0;
+    for line in source.split_inclusive('\n') {
+        let indent = line.len() - line.trim_start().len();
+        if let Some(rest) = line[indent..].strip_prefix(key) {
+            let after_key = rest.trim_start();
+            if after_key.starts_with('=') && !after_key.starts_with("==") {
+                let eq = offset + line.len() - after_key.len();
+                let value_start = eq + 1 + whitespace_len(&source[eq + 1..]);
+                return Some(value_start..value_end(source, value_start));
+            }
+        }
+        offset += line.len();
+    }
+    None
+}
+
+fn whitespace_len(text: &str) -> usize {
+    text.len() - text.trim_start().len()
+}
+
+/// End of the expression starting at `start`: the first `,`, `;`, newline,
+/// comment or closing bracket outside of strings and nested brackets.
+fn value_end(source: &str, start: usize) -> usize {
+    let bytes = source.as_bytes();
+    let mut depth = 0usize;
+    let mut i = start;
+    let mut end = start;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            quote @ (b'"' | b'\'') => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote && bytes[i] != b'\n' {
+                    if bytes[i] == b'\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                i += 1;
+                end = i.min(bytes.len());
+                continue;
+            }
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                if depth == 0 {
+                    return end;
+                }
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            b'\n' | b',' | b';' if depth == 0 => return end,
+            b'(' | b'{' | b'[' => depth += 1,
+            b')' | b'}' | b']' => {
+                if depth == 0 {
+                    return end;
+                }
+                depth -= 1;
+            }
+            b if b.is_ascii_whitespace() => {
+                i += 1;
+                continue;
+            }
+            _ => {}
+        }
+        i += 1;
+        end = i;
+    }
+
+    end
+}
+
+/// Adds `key = literal,` before the last line that closes a table.
+fn insert_field(source: &str, key: &str, literal: &str) -> Option<String> {
+    let mut lines: Vec<&str> = source.split_inclusive('\n').collect();
+    let close = lines
+        .iter()
+        .rposition(|line| line.trim_start().starts_with('}'))?;
+
+    let previous = lines[..close]
+        .iter()
+        .rposition(|line| !code_part(line).trim().is_empty());
+    let indent = previous
+        .map(|idx| {
+            let line = lines[idx];
+            &line[..line.len() - line.trim_start().len()]
+        })
+        .filter(|indent| !indent.is_empty())
+        .unwrap_or("    ");
+
+    // The entry before the new one needs a separator.
+    let mut fixed_previous = None;
+    if let Some(idx) = previous {
+        let line = lines[idx];
+        let code = code_part(line).trim_end();
+        if !code.ends_with([',', ';', '{']) {
+            fixed_previous = Some((idx, format!("{code},{}", &line[code.len()..])));
+        }
+    }
+
+    let entry = format!("{indent}{key} = {literal},\n");
+    let mut out = String::with_capacity(source.len() + entry.len() + 1);
+    for (idx, line) in lines.drain(..).enumerate() {
+        if idx == close {
+            out.push_str(&entry);
+        }
+        match &fixed_previous {
+            Some((fixed, text)) if *fixed == idx => out.push_str(text),
+            _ => out.push_str(line),
+        }
+    }
+    Some(out)
+}
+
+/// The part of a line before a `--` comment that is outside any string.
+fn code_part(line: &str) -> &str {
+    let bytes = line.as_bytes();
+    let mut quote = None;
+    let mut i = 0;
+    while i < bytes.len() {
+        match (quote, bytes[i]) {
+            (Some(_), b'\\') => i += 1,
+            (Some(q), b) if b == q => quote = None,
+            (None, b'"' | b'\'') => quote = Some(bytes[i]),
+            (None, b'-') if bytes.get(i + 1) == Some(&b'-') => return &line[..i],
+            _ => {}
+        }
+        i += 1;
+    }
+    line.trim_end_matches(['\n', '\r'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eval_reports_type_errors() {
+        let table = eval(
+            "preferences.lua",
+            "local t = { tab_size = 2 * 2, use_spaces = 'yes' } return t",
+        )
+        .unwrap();
+        assert_eq!(table.integer("tab_size").unwrap(), Some(4));
+        assert_eq!(
+            table.boolean("use_spaces").unwrap_err(),
+            "preferences.lua: `use_spaces` must be a boolean, got string"
+        );
+        assert_eq!(table.string("missing").unwrap(), None);
+
+        let err = eval("preferences.lua", "return {\n  tab_size = = 4\n}")
+            .err()
+            .unwrap();
+        assert!(err.starts_with("preferences.lua:2:"), "{err}");
+    }
+
+    #[test]
+    fn patch_keeps_comments_and_unchanged_values() {
+        let source = "-- my prefs\nreturn {\n    tab_size = 4, -- spaces\n    api_key = os.getenv(\"KEY\"),\n    list = {\n        \"a\",\n    },\n    last = true -- no comma\n}\n";
+        let previous = [
+            ("tab_size", "4".to_string()),
+            ("api_key", "\"secret\"".to_string()),
+            ("list", "{ \"a\" }".to_string()),
+        ];
+        let fields = [
+            ("tab_size", "2".to_string()),
+            ("api_key", "\"secret\"".to_string()),
+            ("list", "{ \"b\" }".to_string()),
+            ("new_key", "false".to_string()),
+        ];
+
+        let patched = patch_source(source, &fields, &previous).unwrap();
+        assert_eq!(
+            patched,
+            "-- my prefs\nreturn {\n    tab_size = 2, -- spaces\n    api_key = os.getenv(\"KEY\"),\n    list = { \"b\" },\n    last = true, -- no comma\n    new_key = false,\n}\n"
+        );
+    }
+
+    #[test]
+    fn lua_string_escapes() {
+        assert_eq!(lua_string("a\"b\\c\n"), "\"a\\\"b\\\\c\\n\"");
+        let table = eval(
+            "t.lua",
+            &format!("return {{ s = {} }}", lua_string("x\"\\\n\u{1}y")),
+        )
+        .unwrap();
+        assert_eq!(table.string("s").unwrap().unwrap(), "x\"\\\n\u{1}y");
+    }
+
+    #[test]
+    fn string_maps_round_trip() {
+        let map = BTreeMap::from([
+            ("rust".to_string(), "rustfmt".to_string()),
+            ("c++".to_string(), "clang-format".to_string()),
+        ]);
+        let literal = lua_string_map(&map);
+        assert_eq!(
+            literal,
+            "{ [\"c++\"] = \"clang-format\", rust = \"rustfmt\" }"
+        );
+        let table = eval("t.lua", &format!("return {{ m = {literal} }}")).unwrap();
+        assert_eq!(table.string_map("m").unwrap(), Some(map));
+        assert_eq!(
+            eval("t.lua", "return { m = { rust = 1 } }")
+                .unwrap()
+                .string_map("m")
+                .unwrap_err(),
+            "t.lua: `m.rust` must be a string, got integer"
+        );
+    }
+
+    #[test]
+    fn tables_convert_to_json() {
+        let table = eval(
+            "lsp.lua",
+            "return { rust = { settings = { features = { 'a', 'b' }, check = { enable = true }, n = 1.5 } }, go = {} }",
+        )
+        .unwrap();
+        let tables = table.tables().unwrap();
+        assert_eq!(tables.len(), 2);
+        assert_eq!(tables[0].0, "go");
+        assert_eq!(tables[0].1.json("settings").unwrap(), None);
+        assert_eq!(
+            tables[1].1.json("settings").unwrap(),
+            Some(
+                serde_json::json!({ "features": ["a", "b"], "check": { "enable": true }, "n": 1.5 })
+            )
+        );
+        assert_eq!(
+            eval("lsp.lua", "return { rust = 1 }")
+                .unwrap()
+                .tables()
+                .err()
+                .unwrap(),
+            "lsp.lua: `rust` must be a table, got integer"
+        );
+        assert_eq!(
+            eval("lsp.lua", "return { s = { f = print } }")
+                .unwrap()
+                .json("s")
+                .unwrap_err(),
+            "lsp.lua: `s.f` can't hold a function"
+        );
+    }
+}
@@ -1,2 +1,5 @@
+pub mod lsp_config;
+pub mod lua_config;
 pub mod preferences;
 pub mod theme_manager;
+pub mod watcher;
@@ -1,7 +1,9 @@
+use super::lua_config;
 use super::theme_manager::{get_config_dir, load_theme, ThemeColors};
+use crate::features::{format, statusline};
+use std::collections::BTreeMap;
 use std::fs;
-use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone)]
 pub struct EditorPreferences {
@@ -14,8 +16,25 @@ pub struct EditorPreferences {
     pub window_height: f32,
     /// Width of the line-number gutter in logical pixels (default 40).
     pub line_number_width: f32,
+    /// Modal vim editing.
+    pub vim_mode: bool,
+    /// Keys that leave vim's insert mode like Escape when typed quickly one
+    /// after the other, such as `jk`. Empty turns this off.
+    pub vim_escape: String,
     /// Enable developer mode with debug logging
     pub developer_mode: bool,
+    /// Plugin folder names under `plugins/` that should not be loaded.
+    pub disabled_plugins: Vec<String>,
+    /// Status bar segment ids shown on the left and right, in order.
+    pub statusline_left: Vec<String>,
+    pub statusline_right: Vec<String>,
+    /// Languages (`rust`, `python`, ...) formatted before each save.
+    pub format_on_save: Vec<String>,
+    /// External formatter command per language, used when the language
+    /// server can't format. `{file}` stands for the file's path.
+    pub formatters: BTreeMap<String, String>,
+    /// Type and parameter name hints from the language server.
+    pub inlay_hints: bool,
 }
 
 impl Default for EditorPreferences {
@@ -29,7 +48,15 @@ impl Default for EditorPreferences {
             window_width: 1200.0,
             window_height: 800.0,
             line_number_width: 40.0,
+            vim_mode: false,
+            vim_escape: String::new(),
             developer_mode: false,
+            disabled_plugins: Vec::new(),
+            statusline_left: statusline::DEFAULT_LEFT.map(String::from).to_vec(),
+            statusline_right: statusline::DEFAULT_RIGHT.map(String::from).to_vec(),
+            format_on_save: Vec::new(),
+            formatters: format::default_formatters(),
+            inlay_hints: true,
         }
     }
 }
@@ -42,6 +69,10 @@ impl EditorPreferences {
             "\t".to_string()
         }
     }
+
+    pub fn formats_on_save(&self, language: &str) -> bool {
+        self.format_on_save.iter().any(|name| name == language)
+    }
 }
 
 pub fn get_preferences_path() -> PathBuf {
@@ -52,14 +83,19 @@ pub fn get_themes_dir() -> PathBuf {
     get_config_dir().join("themes")
 }
 
-pub fn load_preferences() -> EditorPreferences {
+/// Loads `preferences.lua`, creating it with defaults if it does not exist.
+/// A file that fails to evaluate is reported rather than replaced.
+pub fn load_preferences() -> Result<EditorPreferences, String> {
     let primary = get_preferences_path();
     let legacy = legacy_preferences_path();
 
-    let primary_prefs = read_preferences_from(&primary);
-    let legacy_prefs = legacy.as_ref().and_then(|path| read_preferences_from(path));
+    let primary_prefs = read_preferences_from(&primary).transpose()?;
+    let legacy_prefs = legacy
+        .as_ref()
+        .and_then(read_preferences_from)
+        .transpose()?;
 
-    match (primary_prefs, legacy_prefs) {
+    let prefs = match (primary_prefs, legacy_prefs) {
         (Some(prefs), None) => prefs,
         (None, Some(prefs)) => {
             let _ = save_preferences_to_path(&prefs, &primary);
@@ -78,7 +114,8 @@ pub fn load_preferences() -> EditorPreferences {
             let _ = save_preferences_to_path(&prefs, &primary);
             prefs
         }
-    }
+    };
+    Ok(prefs)
 }
 
 fn legacy_preferences_path() -> Option<PathBuf> {
@@ -91,63 +128,101 @@ fn legacy_preferences_path() -> Option<PathBuf> {
     )
 }
 
-fn parse_preferences(content: &str) -> EditorPreferences {
+fn parse_preferences(content: &str) -> Result<EditorPreferences, String> {
+    let table = lua_config::eval("preferences.lua", content)?;
     let mut prefs = EditorPreferences::default();
-    for line in content.lines() {
-        let line = line.trim();
-        if line.is_empty() || line.starts_with("--") || line == "return {" || line == "}" {
-            continue;
-        }
-        if let Some((key, value)) = line.split_once('=') {
-            let key = key.trim();
-            let value = value
-                .trim()
-                .trim_end_matches(',')
-                .trim_matches('"')
-                .trim_matches('\'');
-            match key {
-                "tab_size" => {
-                    if let Ok(size) = value.parse::<usize>() {
-                        prefs.tab_size = size.max(1).min(16);
-                    }
-                }
-                "use_spaces" => {
-                    prefs.use_spaces = value == "true";
-                }
-                "autosave_enabled" => {
-                    prefs.autosave_enabled = value == "true";
-                }
-                "autosave_interval_ms" => {
-                    if let Ok(interval) = value.parse::<u64>() {
-                        prefs.autosave_interval_ms = interval.clamp(30, 1000);
-                    }
-                }
-                "theme_name" => {
-                    prefs.theme_name = value.to_string();
-                }
-                "window_width" => {
-                    if let Ok(width) = value.parse::<f32>() {
-                        prefs.window_width = width.max(640.0).min(10000.0);
-                    }
-                }
-                "window_height" => {
-                    if let Ok(height) = value.parse::<f32>() {
-                        prefs.window_height = height.max(480.0).min(10000.0);
-                    }
-                }
-                "line_number_width" => {
-                    if let Ok(w) = value.parse::<f32>() {
-                        prefs.line_number_width = w.max(20.0).min(120.0);
-                    }
-                }
-                "developer_mode" => {
-                    prefs.developer_mode = value == "true";
-                }
-                _ => {}
-            }
-        }
+
+    if let Some(size) = table.integer("tab_size")? {
+        prefs.tab_size = size.clamp(1, 16) as usize;
+    }
+    if let Some(use_spaces) = table.boolean("use_spaces")? {
+        prefs.use_spaces = use_spaces;
+    }
+    if let Some(enabled) = table.boolean("autosave_enabled")? {
+        prefs.autosave_enabled = enabled;
+    }
+    if let Some(interval) = table.integer("autosave_interval_ms")? {
+        prefs.autosave_interval_ms = interval.clamp(30, 1000) as u64;
+    }
+    if let Some(name) = table.string("theme_name")? {
+        prefs.theme_name = name;
+    }
+    if let Some(width) = table.number("window_width")? {
+        prefs.window_width = (width as f32).clamp(640.0, 10000.0);
     }
-    prefs
+    if let Some(height) = table.number("window_height")? {
+        prefs.window_height = (height as f32).clamp(480.0, 10000.0);
+    }
+    if let Some(width) = table.number("line_number_width")? {
+        prefs.line_number_width = (width as f32).clamp(20.0, 120.0);
+    }
+    if let Some(enabled) = table.boolean("vim_mode")? {
+        prefs.vim_mode = enabled;
+    }
+    if let Some(keys) = table.string("vim_escape")? {
+        prefs.vim_escape = keys.chars().filter(|c| !c.is_whitespace()).collect();
+    }
+    if let Some(enabled) = table.boolean("developer_mode")? {
+        prefs.developer_mode = enabled;
+    }
+    if let Some(disabled) = table.string_list("disabled_plugins")? {
+        prefs.disabled_plugins = disabled;
+    }
+    if let Some(left) = table.string_list("statusline_left")? {
+        prefs.statusline_left = left;
+    }
+    if let Some(right) = table.string_list("statusline_right")? {
+        prefs.statusline_right = right;
+    }
+    if let Some(languages) = table.string_list("format_on_save")? {
+        prefs.format_on_save = languages;
+    }
+    if let Some(formatters) = table.string_map("formatters")? {
+        prefs.formatters = formatters;
+    }
+    if let Some(enabled) = table.boolean("inlay_hints")? {
+        prefs.inlay_hints = enabled;
+    }
+
+    Ok(prefs)
+}
+
+/// Each saved preference as a Lua literal, in file order.
+fn preference_fields(prefs: &EditorPreferences) -> Vec<(&'static str, String)> {
+    vec![
+        ("tab_size", prefs.tab_size.to_string()),
+        ("use_spaces", prefs.use_spaces.to_string()),
+        ("autosave_enabled", prefs.autosave_enabled.to_string()),
+        (
+            "autosave_interval_ms",
+            prefs.autosave_interval_ms.to_string(),
+        ),
+        ("theme_name", lua_config::lua_string(&prefs.theme_name)),
+        ("window_width", prefs.window_width.to_string()),
+        ("window_height", prefs.window_height.to_string()),
+        ("line_number_width", prefs.line_number_width.to_string()),
+        ("vim_mode", prefs.vim_mode.to_string()),
+        ("vim_escape", lua_config::lua_string(&prefs.vim_escape)),
+        ("developer_mode", prefs.developer_mode.to_string()),
+        (
+            "disabled_plugins",
+            lua_config::lua_string_list(&prefs.disabled_plugins),
+        ),
+        (
+            "statusline_left",
+            lua_config::lua_string_list(&prefs.statusline_left),
+        ),
+        (
+            "statusline_right",
+            lua_config::lua_string_list(&prefs.statusline_right),
+        ),
+        (
+            "format_on_save",
+            lua_config::lua_string_list(&prefs.format_on_save),
+        ),
+        ("formatters", lua_config::lua_string_map(&prefs.formatters)),
+        ("inlay_hints", prefs.inlay_hints.to_string()),
+    ]
 }
 
 pub fn save_preferences(prefs: &EditorPreferences) -> Result<(), std::io::Error> {
@@ -182,10 +257,10 @@ pub fn load_theme_by_name(name: &str) -> ThemeColors {
         }
     }
 
-    load_theme()
+    load_theme().unwrap_or_default()
 }
 
-fn read_preferences_from(path: &PathBuf) -> Option<EditorPreferences> {
+fn read_preferences_from(path: &PathBuf) -> Option<Result<EditorPreferences, String>> {
     let content = fs::read_to_string(path).ok()?;
     Some(parse_preferences(&content))
 }
@@ -205,14 +280,18 @@ fn legacy_is_newer_than_primary(legacy: Option<&PathBuf>, primary: &PathBuf) ->
     }
 }
 
-fn save_preferences_to_path(
-    prefs: &EditorPreferences,
-    path: &PathBuf,
-) -> Result<(), std::io::Error> {
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)?;
-    }
-    let content = format!(
+fn save_preferences_to_path(prefs: &EditorPreferences, path: &Path) -> Result<(), std::io::Error> {
+    lua_config::save_fields(
+        path,
+        &preference_fields(prefs),
+        |existing| parse_preferences(existing).map(|current| preference_fields(&current)),
+        || preferences_template(prefs),
+    )
+}
+
+/// A fresh, commented `preferences.lua`.
+fn preferences_template(prefs: &EditorPreferences) -> String {
+    format!(
         r#"-- Pinel Editor Preferences
 -- Edit these values to customize your editor
 
@@ -222,26 +301,67 @@ return {{
     autosave_enabled = {},
     -- Autosave interval in milliseconds (30–1000)
     autosave_interval_ms = {},
-    theme_name = "{}",
+    theme_name = {},
     window_width = {},
     window_height = {},
     -- Width of the line-number gutter in logical pixels (20–120)
     line_number_width = {},
+    -- Modal vim editing
+    vim_mode = {},
+    -- Keys that leave vim's insert mode when typed quickly, e.g. "jk"; "" for none
+    vim_escape = {},
     -- Enable developer mode with debug logging (WARNING: Logs may contain sensitive data)
     developer_mode = {},
+    -- Plugin folders in ~/.config/pinel/plugins that should not be loaded
+    disabled_plugins = {},
+    -- Status bar segments, in order. Built in: file, git_branch, problems,
+    -- diagnostic, lsp, line_ending, encoding, language, cursor. Segments added with
+    -- pinel.statusline.add can be listed here too.
+    statusline_left = {},
+    statusline_right = {},
+    -- Languages to format before saving, e.g. {{ "rust", "python" }}
+    format_on_save = {},
+    -- Formatter commands that read the file on stdin and print it formatted,
+    -- used when the language server can't format. {{file}} is the file's path.
+    formatters = {},
+    -- Type and parameter name hints from the language server
+    inlay_hints = {},
 }}
 "#,
         prefs.tab_size,
         prefs.use_spaces,
         prefs.autosave_enabled,
         prefs.autosave_interval_ms,
-        prefs.theme_name,
+        lua_config::lua_string(&prefs.theme_name),
         prefs.window_width,
         prefs.window_height,
         prefs.line_number_width,
+        prefs.vim_mode,
+        lua_config::lua_string(&prefs.vim_escape),
         prefs.developer_mode,
-    );
-    let mut file = fs::File::create(path)?;
-    file.write_all(content.as_bytes())?;
-    Ok(())
+        lua_config::lua_string_list(&prefs.disabled_plugins),
+        lua_config::lua_string_list(&prefs.statusline_left),
+        lua_config::lua_string_list(&prefs.statusline_right),
+        lua_config::lua_string_list(&prefs.format_on_save),
+        formatters_literal(&prefs.formatters),
+        prefs.inlay_hints,
+    )
+}
+
+/// The `formatters` table with one language per line.
+fn formatters_literal(formatters: &BTreeMap<String, String>) -> String {
+    if formatters.is_empty() {
+        return "{}".to_string();
+    }
+    let entries: String = formatters
+        .iter()
+        .map(|(language, command)| {
+            format!(
+                "        {} = {},\n",
+                lua_config::lua_key(language),
+                lua_config::lua_string(command)
+            )
+        })
+        .collect();
+    format!("{{\n{entries}    }}")
 }
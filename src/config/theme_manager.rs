@@ -1,5 +1,5 @@
+use super::lua_config;
 use std::fs;
-use std::io::Write;
 use std::path::PathBuf;
 
 #[derive(Debug, Clone)]
@@ -66,121 +66,51 @@ impl Default for ThemeColors {
 }
 
 impl ThemeColors {
-    pub fn to_lua(&self) -> String {
-        format!(
-            r#"-- Pinel Theme Configuration
--- Edit these hex color values to customize your theme
-
-return {{
-    rosewater = "{}",
-    flamingo = "{}",
-    pink = "{}",
-    mauve = "{}",
-    red = "{}",
-    maroon = "{}",
-    peach = "{}",
-    yellow = "{}",
-    green = "{}",
-    teal = "{}",
-    sky = "{}",
-    sapphire = "{}",
-    blue = "{}",
-    lavender = "{}",
-    text = "{}",
-    subtext1 = "{}",
-    subtext0 = "{}",
-    overlay2 = "{}",
-    overlay1 = "{}",
-    overlay0 = "{}",
-    surface2 = "{}",
-    surface1 = "{}",
-    surface0 = "{}",
-    base = "{}",
-    mantle = "{}",
-    crust = "{}",
-}}
-"#,
-            self.rosewater,
-            self.flamingo,
-            self.pink,
-            self.mauve,
-            self.red,
-            self.maroon,
-            self.peach,
-            self.yellow,
-            self.green,
-            self.teal,
-            self.sky,
-            self.sapphire,
-            self.blue,
-            self.lavender,
-            self.text,
-            self.subtext1,
-            self.subtext0,
-            self.overlay2,
-            self.overlay1,
-            self.overlay0,
-            self.surface2,
-            self.surface1,
-            self.surface0,
-            self.base,
-            self.mantle,
-            self.crust,
-        )
-    }
-
     pub fn from_lua(content: &str) -> Result<Self, String> {
+        let table = lua_config::eval("theme.lua", content)?;
         let mut theme = Self::default();
 
-        for line in content.lines() {
-            let line = line.trim();
-            if line.is_empty() || line.starts_with("--") || line == "return {" || line == "}" {
-                continue;
-            }
-
-            if let Some((key, value)) = line.split_once('=') {
-                let key = key.trim();
-                let value = value
-                    .trim()
-                    .trim_end_matches(',')
-                    .trim_matches('"')
-                    .trim_matches('\'')
-                    .to_string();
-
-                match key {
-                    "rosewater" => theme.rosewater = value,
-                    "flamingo" => theme.flamingo = value,
-                    "pink" => theme.pink = value,
-                    "mauve" => theme.mauve = value,
-                    "red" => theme.red = value,
-                    "maroon" => theme.maroon = value,
-                    "peach" => theme.peach = value,
-                    "yellow" => theme.yellow = value,
-                    "green" => theme.green = value,
-                    "teal" => theme.teal = value,
-                    "sky" => theme.sky = value,
-                    "sapphire" => theme.sapphire = value,
-                    "blue" => theme.blue = value,
-                    "lavender" => theme.lavender = value,
-                    "text" => theme.text = value,
-                    "subtext1" => theme.subtext1 = value,
-                    "subtext0" => theme.subtext0 = value,
-                    "overlay2" => theme.overlay2 = value,
-                    "overlay1" => theme.overlay1 = value,
-                    "overlay0" => theme.overlay0 = value,
-                    "surface2" => theme.surface2 = value,
-                    "surface1" => theme.surface1 = value,
-                    "surface0" => theme.surface0 = value,
-                    "base" => theme.base = value,
-                    "mantle" => theme.mantle = value,
-                    "crust" => theme.crust = value,
-                    _ => {}
-                }
+        for (name, slot) in theme.colors_mut() {
+            if let Some(value) = table.string(name)? {
+                crate::theme::parse_hex_color(&value)
+                    .map_err(|err| format!("theme.lua: `{name}`: {err}"))?;
+                *slot = value;
             }
         }
 
         Ok(theme)
     }
+
+    fn colors_mut(&mut self) -> [(&'static str, &mut String); 26] {
+        [
+            ("rosewater", &mut self.rosewater),
+            ("flamingo", &mut self.flamingo),
+            ("pink", &mut self.pink),
+            ("mauve", &mut self.mauve),
+            ("red", &mut self.red),
+            ("maroon", &mut self.maroon),
+            ("peach", &mut self.peach),
+            ("yellow", &mut self.yellow),
+            ("green", &mut self.green),
+            ("teal", &mut self.teal),
+            ("sky", &mut self.sky),
+            ("sapphire", &mut self.sapphire),
+            ("blue", &mut self.blue),
+            ("lavender", &mut self.lavender),
+            ("text", &mut self.text),
+            ("subtext1", &mut self.subtext1),
+            ("subtext0", &mut self.subtext0),
+            ("overlay2", &mut self.overlay2),
+            ("overlay1", &mut self.overlay1),
+            ("overlay0", &mut self.overlay0),
+            ("surface2", &mut self.surface2),
+            ("surface1", &mut self.surface1),
+            ("surface0", &mut self.surface0),
+            ("base", &mut self.base),
+            ("mantle", &mut self.mantle),
+            ("crust", &mut self.crust),
+        ]
+    }
 }
 
 pub fn get_config_dir() -> PathBuf {
@@ -192,21 +122,10 @@ pub fn get_theme_path() -> PathBuf {
     get_config_dir().join("theme.lua")
 }
 
-pub fn load_theme() -> ThemeColors {
-    let path = get_theme_path();
-    if let Ok(content) = fs::read_to_string(&path) {
-        ThemeColors::from_lua(&content).unwrap_or_default()
-    } else {
-        ThemeColors::default()
-    }
-}
-
-pub fn save_theme(theme: &ThemeColors) -> Result<(), std::io::Error> {
-    let path = get_theme_path();
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)?;
+/// Loads `theme.lua`; a missing file gives the default palette.
+pub fn load_theme() -> Result<ThemeColors, String> {
+    match fs::read_to_string(get_theme_path()) {
+        Ok(content) => ThemeColors::from_lua(&content),
+        Err(_) => Ok(ThemeColors::default()),
     }
-    let mut file = fs::File::create(path)?;
-    file.write_all(theme.to_lua().as_bytes())?;
-    Ok(())
 }
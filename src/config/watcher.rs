@@ -0,0 +1,88 @@
+//! Change detection for the Lua config files so edits apply without a restart.
+//!
+//! The config dir holds a handful of small files, so this polls modification
+//! times from a subscription tick instead of pulling in a platform watcher.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::lsp_config::get_lsp_config_path;
+use super::theme_manager::{get_config_dir, get_theme_path};
+
+/// Which parts of the config changed since the last poll.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ConfigChanges {
+    /// `init.lua` or a plugin file changed; the scripts need to re-run.
+    pub scripts: bool,
+    /// `theme.lua` changed.
+    pub theme: bool,
+    /// `lsp.lua` changed.
+    pub lsp: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct ConfigWatcher {
+    scripts: Vec<(PathBuf, Option<SystemTime>)>,
+    theme: Option<SystemTime>,
+    lsp: Option<SystemTime>,
+}
+
+impl ConfigWatcher {
+    /// Records the current state of the config files as the baseline.
+    pub fn new() -> Self {
+        Self {
+            scripts: script_stamps(),
+            theme: modified(&get_theme_path()),
+            lsp: modified(&get_lsp_config_path()),
+        }
+    }
+
+    pub fn poll(&mut self) -> ConfigChanges {
+        let scripts = script_stamps();
+        let theme = modified(&get_theme_path());
+        let lsp = modified(&get_lsp_config_path());
+
+        let changes = ConfigChanges {
+            scripts: scripts != self.scripts,
+            theme: theme != self.theme,
+            lsp: lsp != self.lsp,
+        };
+
+        self.scripts = scripts;
+        self.theme = theme;
+        self.lsp = lsp;
+        changes
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// `init.lua` plus every `.lua` file under `plugins/`, sorted by path.
+fn script_stamps() -> Vec<(PathBuf, Option<SystemTime>)> {
+    let config_dir = get_config_dir();
+    let init = config_dir.join("init.lua");
+
+    let mut stamps = vec![(init.clone(), modified(&init))];
+    collect_lua_files(&config_dir.join("plugins"), &mut stamps);
+    stamps.sort_by(|a, b| a.0.cmp(&b.0));
+    stamps
+}
+
+fn collect_lua_files(dir: &Path, out: &mut Vec<(PathBuf, Option<SystemTime>)>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_lua_files(&path, out);
+        } else if path.extension().is_some_and(|ext| ext == "lua") {
+            let stamp = modified(&path);
+            out.push((path, stamp));
+        }
+    }
+}
@@ -3,22 +3,44 @@
 
 pub struct CommandInput {
     pub open: bool,
+    /// `:` for commands, or `/` and `?` while searching in vim.
+    pub prompt: char,
     pub input: String,
+    /// Lines a command printed, shown above the input until the next key.
+    pub output: Vec<String>,
 }
 
 impl Default for CommandInput {
     fn default() -> Self {
         Self {
             open: false,
+            prompt: ':',
             input: String::new(),
+            output: Vec::new(),
         }
     }
 }
 
 impl CommandInput {
     pub fn open(&mut self) {
+        self.open_prompt(':');
+    }
+
+    pub fn open_prompt(&mut self, prompt: char) {
         self.open = true;
+        self.prompt = prompt;
+        self.input.clear();
+        self.output.clear();
+    }
+
+    pub fn is_search(&self) -> bool {
+        self.prompt != ':'
+    }
+
+    /// Keeps the bar open with `lines` above an empty input.
+    pub fn show_output(&mut self, lines: Vec<String>) {
         self.input.clear();
+        self.output = lines;
     }
 
     pub fn close(&mut self) {
@@ -43,10 +65,10 @@ impl CommandInput {
         match cmd {
             "w" | "write" => Some("Save File".to_string()),
             "q" | "quit" => Some("Quit".to_string()),
-            "wq" => Some("Save and Quit".to_string()),
             "e" | "edit" => Some("Open File".to_string()),
             "new" => Some("New File".to_string()),
-            _ => None,
+            // Anything else is looked up by name, so registered commands work too.
+            _ => Some(cmd.to_string()),
         }
     }
 }
@@ -1,93 +1,252 @@
-/// Command Palette - VS Code-style overlay command palette (Cmd+Shift+P)
-/// Ported from pinel's command_palette.rs, adapted for iced.
+//! Command Palette - VS Code-style overlay command palette (Cmd+Shift+P)
+//! Ported from pinel's command_palette.rs, adapted for iced.
+
+use crate::features::lsp::LocationKind;
+use crate::message::Message;
+
+/// What running a palette command does.
+#[derive(Clone, Debug)]
+pub enum CommandAction {
+    /// Dispatches an app message; every built-in command is one of these.
+    Message(Message),
+    /// Calls a Lua function registered with `pinel.command.register`.
+    Lua(usize),
+}
 
 #[derive(Clone, Debug)]
 pub struct Command {
     pub name: String,
     pub description: String,
+    pub action: CommandAction,
+    /// Only offered while the active tab can be rendered as markdown.
+    pub markdown_only: bool,
 }
 
-pub struct CommandPalette {
-    pub open: bool,
-    pub input: String,
+impl Command {
+    fn builtin(name: &str, description: &str, message: Message) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            action: CommandAction::Message(message),
+            markdown_only: false,
+        }
+    }
+}
+
+/// Every command the palette and the `:` command input can run, by name.
+pub struct CommandRegistry {
     commands: Vec<Command>,
-    pub filtered_commands: Vec<Command>,
+    builtin_count: usize,
 }
 
-impl Default for CommandPalette {
+impl Default for CommandRegistry {
     fn default() -> Self {
-        let commands = Self::commands_for(false);
-        let filtered = commands.clone();
+        let commands = vec![
+            Command::builtin(
+                "Theme",
+                "Open theme settings",
+                Message::OpenSettingsSection("theme".to_string()),
+            ),
+            Command::builtin("Settings", "Open editor settings", Message::ToggleSettings),
+            Command::builtin(
+                "Open File",
+                "Open an existing file",
+                Message::OpenFileDialog,
+            ),
+            Command::builtin(
+                "Open Folder",
+                "Open a folder in file tree",
+                Message::OpenFolderDialog,
+            ),
+            Command::builtin("Save File", "Save the current file", Message::SaveFile),
+            Command::builtin("Quit", "Exit the editor", Message::Quit),
+            Command::builtin("New File", "Create a new file", Message::NewFile),
+            Command::builtin(
+                "Save As",
+                "Save the current file with a new name",
+                Message::SaveAs,
+            ),
+            Command::builtin(
+                "Toggle Terminal",
+                "Toggle embedded terminal panel",
+                Message::ToggleTerminal,
+            ),
+            Command::builtin(
+                "Lua Console",
+                "Evaluate Lua against the running editor",
+                Message::ToggleDeveloperPanel,
+            ),
+            Command::builtin(
+                "Find and Replace",
+                "Search and replace text in editor",
+                Message::ToggleFindReplace,
+            ),
+            Command::builtin(
+                "Toggle Sidebar",
+                "Show or hide the file tree",
+                Message::ToggleSidebar,
+            ),
+            Command::builtin("Close Tab", "Close the active tab", Message::CloseActiveTab),
+            Command::builtin(
+                "Toggle Vim Mode",
+                "Switch modal vim editing on or off",
+                Message::ToggleVimMode,
+            ),
+            Command::builtin(
+                "Problems",
+                "Show diagnostics from every file",
+                Message::ToggleProblems,
+            ),
+            Command::builtin(
+                "Next Problem",
+                "Go to the next diagnostic",
+                Message::NextProblem,
+            ),
+            Command::builtin(
+                "Previous Problem",
+                "Go to the previous diagnostic",
+                Message::PreviousProblem,
+            ),
+            Command::builtin(
+                "Go to Definition",
+                "Jump to where the symbol is defined",
+                Message::FindLocations(LocationKind::Definition),
+            ),
+            Command::builtin(
+                "Go to Type Definition",
+                "Jump to the definition of the symbol's type",
+                Message::FindLocations(LocationKind::TypeDefinition),
+            ),
+            Command::builtin(
+                "Go to Implementation",
+                "Jump to the implementations of the symbol",
+                Message::FindLocations(LocationKind::Implementation),
+            ),
+            Command::builtin(
+                "Find All References",
+                "List every use of the symbol",
+                Message::FindLocations(LocationKind::References),
+            ),
+            Command::builtin(
+                "Rename Symbol",
+                "Rename the symbol across the workspace",
+                Message::RenameSymbol,
+            ),
+            Command::builtin(
+                "Code Actions",
+                "Show quick fixes and refactorings at the cursor",
+                Message::ShowCodeActions,
+            ),
+            Command::builtin(
+                "Format Document",
+                "Format the file with its language server or formatter",
+                Message::FormatDocument,
+            ),
+            Command::builtin(
+                "Go to Symbol in Workspace",
+                "Search symbols across the workspace",
+                Message::ShowWorkspaceSymbols,
+            ),
+            Command::builtin(
+                "Toggle Outline",
+                "Show or hide the sidebar outline",
+                Message::ToggleOutline,
+            ),
+            Command::builtin(
+                "Restart Language Servers",
+                "Restart every running language server",
+                Message::RestartLsp,
+            ),
+            Command::builtin(
+                "Language Servers",
+                "Inspect language server status and JSON-RPC traffic",
+                Message::ToggleLspInspector,
+            ),
+            Command::builtin(
+                "Toggle Fullscreen",
+                "Maximize the editor window",
+                Message::ToggleFullscreen(iced::window::Mode::Fullscreen),
+            ),
+            Command {
+                markdown_only: true,
+                ..Command::builtin(
+                    "Render Markdown",
+                    "Open a live markdown preview beside the editor",
+                    Message::PreviewMarkdown,
+                )
+            },
+        ];
+        let builtin_count = commands.len();
 
         Self {
-            open: false,
-            input: String::new(),
             commands,
-            filtered_commands: filtered,
+            builtin_count,
         }
     }
 }
 
-impl CommandPalette {
-    fn commands_for(include_markdown_render: bool) -> Vec<Command> {
-        let mut commands = vec![
-            Command {
-                name: "Theme".to_string(),
-                description: "Open theme settings".to_string(),
-            },
-            Command {
-                name: "Settings".to_string(),
-                description: "Open editor settings".to_string(),
-            },
-            Command {
-                name: "Open File".to_string(),
-                description: "Open an existing file".to_string(),
-            },
-            Command {
-                name: "Open Folder".to_string(),
-                description: "Open a folder in file tree".to_string(),
-            },
-            Command {
-                name: "Save File".to_string(),
-                description: "Save the current file".to_string(),
-            },
-            Command {
-                name: "Quit".to_string(),
-                description: "Exit the editor".to_string(),
-            },
-            Command {
-                name: "New File".to_string(),
-                description: "Create a new file".to_string(),
-            },
-            Command {
-                name: "Save As".to_string(),
-                description: "Save the current file with a new name".to_string(),
-            },
-            Command {
-                name: "Toggle Terminal".to_string(),
-                description: "Toggle embedded terminal panel".to_string(),
-            },
-            Command {
-                name: "Find and Replace".to_string(),
-                description: "Search and replace text in editor".to_string(),
-            },
-        ];
+impl CommandRegistry {
+    /// Adds a scripted command, replacing an earlier one with the same name.
+    /// Built-in commands cannot be shadowed.
+    pub fn register(&mut self, command: Command) -> Result<(), String> {
+        if self.commands[..self.builtin_count]
+            .iter()
+            .any(|existing| existing.name == command.name)
+        {
+            return Err(format!("'{}' is a built-in command", command.name));
+        }
 
-        if include_markdown_render {
-            commands.push(Command {
-                name: "Render Markdown".to_string(),
-                description: "Open a live markdown preview beside the editor".to_string(),
-            });
+        match self.commands[self.builtin_count..]
+            .iter_mut()
+            .find(|existing| existing.name == command.name)
+        {
+            Some(existing) => *existing = command,
+            None => self.commands.push(command),
         }
+        Ok(())
+    }
+
+    /// Removes every scripted command, keeping the built-ins.
+    pub fn clear_user_commands(&mut self) {
+        self.commands.truncate(self.builtin_count);
+    }
+
+    /// Finds a command by exact name, falling back to a case-insensitive match.
+    pub fn find(&self, name: &str) -> Option<&Command> {
+        self.commands
+            .iter()
+            .find(|command| command.name == name)
+            .or_else(|| {
+                self.commands
+                    .iter()
+                    .find(|command| command.name.eq_ignore_ascii_case(name))
+            })
+    }
 
-        commands
+    /// Commands the palette should offer right now.
+    pub fn available(&self, include_markdown_render: bool) -> Vec<Command> {
+        self.commands
+            .iter()
+            .filter(|command| include_markdown_render || !command.markdown_only)
+            .cloned()
+            .collect()
     }
+}
+
+#[derive(Default)]
+pub struct CommandPalette {
+    pub open: bool,
+    pub input: String,
+    commands: Vec<Command>,
+    pub filtered_commands: Vec<Command>,
+}
 
-    pub fn toggle(&mut self, include_markdown_render: bool) {
+impl CommandPalette {
+    pub fn toggle(&mut self, commands: Vec<Command>) {
         self.open = !self.open; // literally toggles it, ! changes on to off and vice versa
         if self.open {
             self.input.clear();
-            self.commands = Self::commands_for(include_markdown_render);
+            self.commands = commands;
             self.filtered_commands = self.commands.clone();
         }
     }
@@ -98,8 +257,8 @@ impl CommandPalette {
         self.filtered_commands.clear();
     }
 
-    pub fn filter_commands(&mut self, include_markdown_render: bool) {
-        self.commands = Self::commands_for(include_markdown_render);
+    pub fn filter_commands(&mut self, commands: Vec<Command>) {
+        self.commands = commands;
         let input_lower = self.input.to_lowercase();
 
         if input_lower.is_empty() {
@@ -114,3 +273,72 @@ impl CommandPalette {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_command(name: &str, callback: usize) -> Command {
+        Command {
+            name: name.to_string(),
+            description: String::new(),
+            action: CommandAction::Lua(callback),
+            markdown_only: false,
+        }
+    }
+
+    #[test]
+    fn builtin_names_cannot_be_registered() {
+        let mut registry = CommandRegistry::default();
+        let builtins = registry.commands.len();
+        assert_eq!(
+            registry.register(user_command("Save File", 1)),
+            Err("'Save File' is a built-in command".to_string())
+        );
+        assert_eq!(registry.commands.len(), builtins);
+    }
+
+    #[test]
+    fn registering_again_replaces_the_user_command() {
+        let mut registry = CommandRegistry::default();
+        let builtins = registry.commands.len();
+        registry.register(user_command("Sort Lines", 1)).unwrap();
+        registry.register(user_command("Sort Lines", 2)).unwrap();
+
+        assert_eq!(registry.commands.len(), builtins + 1);
+        assert!(matches!(
+            registry.find("Sort Lines").unwrap().action,
+            CommandAction::Lua(2)
+        ));
+    }
+
+    #[test]
+    fn find_falls_back_to_any_case() {
+        let mut registry = CommandRegistry::default();
+        registry.register(user_command("sort lines", 1)).unwrap();
+        registry.register(user_command("Sort Lines", 2)).unwrap();
+
+        assert_eq!(registry.find("save file").unwrap().name, "Save File");
+        assert!(matches!(
+            registry.find("Sort Lines").unwrap().action,
+            CommandAction::Lua(2)
+        ));
+        assert!(matches!(
+            registry.find("SORT LINES").unwrap().action,
+            CommandAction::Lua(1)
+        ));
+        assert!(registry.find("No Such Command").is_none());
+    }
+
+    #[test]
+    fn clearing_user_commands_keeps_the_builtins() {
+        let mut registry = CommandRegistry::default();
+        let builtins = registry.commands.len();
+        registry.register(user_command("Sort Lines", 1)).unwrap();
+
+        registry.clear_user_commands();
+        assert_eq!(registry.commands.len(), builtins);
+        assert!(registry.find("Sort Lines").is_none());
+        assert!(registry.find("Quit").is_some());
+    }
+}
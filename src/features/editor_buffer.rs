@@ -40,3 +40,178 @@ impl EditorBuffer {
         self.rope.line(line_idx).to_string()
     }
 }
+
+/// A replacement of the text between two 0-based `(line, column)` positions.
+///
+/// Columns count characters, matching `CodeEditor::cursor_position`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+    pub text: String,
+}
+
+impl TextEdit {
+    /// Position just past the inserted text once the edit is applied.
+    pub fn new_end(&self) -> (usize, usize) {
+        match self.text.rfind('\n') {
+            Some(last_newline) => (
+                self.start.0 + self.text.matches('\n').count(),
+                self.text[last_newline + 1..].chars().count(),
+            ),
+            None => (self.start.0, self.start.1 + self.text.chars().count()),
+        }
+    }
+
+    /// Maps a position from before the edit to where it ends up afterwards.
+    ///
+    /// Positions inside the replaced range collapse to the end of the new text.
+    pub fn map_position(&self, pos: (usize, usize)) -> (usize, usize) {
+        if pos < self.start {
+            return pos;
+        }
+
+        let new_end = self.new_end();
+        if pos < self.end {
+            new_end
+        } else if pos.0 == self.end.0 {
+            (new_end.0, new_end.1 + pos.1 - self.end.1)
+        } else {
+            (pos.0 + new_end.0 - self.end.0, pos.1)
+        }
+    }
+}
+
+impl EditorBuffer {
+    /// Number of characters on a line, excluding the line break.
+    pub fn line_len(&self, line_idx: usize) -> usize {
+        if line_idx >= self.rope.len_lines() {
+            return 0;
+        }
+        let line = self.rope.line(line_idx);
+        let mut len = line.len_chars();
+        if len > 0 && line.char(len - 1) == '\n' {
+            len -= 1;
+            if len > 0 && line.char(len - 1) == '\r' {
+                len -= 1;
+            }
+        }
+        len
+    }
+
+    /// Converts a `(line, column)` position to a char index, clamping to the buffer.
+    pub fn position_to_char(&self, (line, col): (usize, usize)) -> usize {
+        if line >= self.rope.len_lines() {
+            return self.rope.len_chars();
+        }
+        self.rope.line_to_char(line) + col.min(self.line_len(line))
+    }
+
+    /// The text between two `(line, column)` positions.
+    pub fn text_between(&self, start: (usize, usize), end: (usize, usize)) -> String {
+        let start = self.position_to_char(start);
+        let end = self.position_to_char(end).max(start);
+        self.rope.slice(start..end).to_string()
+    }
+
+    pub fn apply(&mut self, edit: &TextEdit) {
+        let start = self.position_to_char(edit.start);
+        let end = self.position_to_char(edit.end).max(start);
+        self.rope.remove(start..end);
+        self.rope.insert(start, &edit.text);
+    }
+
+    /// Builds the edit that replaces lines `start..end` (0-based, end exclusive)
+    /// with `lines`. `start == end` inserts before `start`.
+    pub fn lines_edit(&self, start: usize, end: usize, lines: &[String]) -> TextEdit {
+        let count = self.line_count();
+        let start = start.min(count);
+        let end = end.clamp(start, count);
+        let joined = lines.join("\n");
+
+        if end < count {
+            let text = if lines.is_empty() {
+                String::new()
+            } else {
+                format!("{joined}\n")
+            };
+            return TextEdit {
+                start: (start, 0),
+                end: (end, 0),
+                text,
+            };
+        }
+
+        // The range touches the end of the buffer, so the line break that
+        // separates it from the lines before has to move instead.
+        let last = count - 1;
+        let buffer_end = (last, self.line_len(last));
+        if start == count {
+            TextEdit {
+                start: buffer_end,
+                end: buffer_end,
+                text: if lines.is_empty() {
+                    String::new()
+                } else {
+                    format!("\n{joined}")
+                },
+            }
+        } else if lines.is_empty() && start > 0 {
+            TextEdit {
+                start: (start - 1, self.line_len(start - 1)),
+                end: buffer_end,
+                text: String::new(),
+            }
+        } else {
+            TextEdit {
+                start: (start, 0),
+                end: buffer_end,
+                text: joined,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn apply_lines(text: &str, start: usize, end: usize, new: &[&str]) -> String {
+        let mut buffer = EditorBuffer::from_text(text);
+        let edit = buffer.lines_edit(start, end, &lines(new));
+        buffer.apply(&edit);
+        buffer.text()
+    }
+
+    #[test]
+    fn lines_edit_replaces_inner_and_trailing_lines() {
+        assert_eq!(apply_lines("a\nb\nc", 1, 2, &["x", "y"]), "a\nx\ny\nc");
+        assert_eq!(apply_lines("a\nb\nc", 1, 3, &["x"]), "a\nx");
+        assert_eq!(apply_lines("a\nb\nc", 0, 3, &[]), "");
+    }
+
+    #[test]
+    fn lines_edit_deletes_and_inserts() {
+        assert_eq!(apply_lines("a\nb\nc", 1, 3, &[]), "a");
+        assert_eq!(apply_lines("a\nb\nc", 0, 1, &[]), "b\nc");
+        assert_eq!(apply_lines("a\nb", 1, 1, &["x"]), "a\nx\nb");
+        assert_eq!(apply_lines("a\nb", 2, 2, &["x"]), "a\nb\nx");
+    }
+
+    #[test]
+    fn map_position_shifts_positions_after_the_edit() {
+        let edit = TextEdit {
+            start: (0, 1),
+            end: (1, 1),
+            text: "xy\nz".to_string(),
+        };
+        assert_eq!(edit.map_position((0, 0)), (0, 0));
+        assert_eq!(edit.map_position((0, 3)), (1, 1));
+        assert_eq!(edit.map_position((1, 4)), (1, 4));
+        assert_eq!(edit.map_position((2, 2)), (2, 2));
+    }
+}
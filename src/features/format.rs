@@ -0,0 +1,286 @@
+//! Document formatting: external formatter commands and turning a formatted
+//! text into the few edits that produce it, so the cursor and undo history
+//! outside the changed lines are left alone.
+
+use crate::features::editor_buffer::{EditorBuffer, TextEdit};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+
+/// Stands for the file's path in a formatter command.
+pub const FILE_PLACEHOLDER: &str = "{file}";
+
+/// Middle sections with more line pairs than this are replaced whole
+/// instead of being diffed line by line.
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// The language a file is formatted as, named like LSP language ids. This
+/// is the key of the `format_on_save` and `formatters` preferences.
+pub fn language_id(path: &Path) -> Option<&'static str> {
+    let id = match path.extension()?.to_str()? {
+        "rs" => "rust",
+        "py" | "pyi" => "python",
+        "js" | "mjs" | "cjs" => "javascript",
+        "jsx" => "javascriptreact",
+        "ts" | "mts" | "cts" => "typescript",
+        "tsx" => "typescriptreact",
+        "json" => "json",
+        "css" => "css",
+        "scss" => "scss",
+        "html" | "htm" => "html",
+        "md" | "markdown" => "markdown",
+        "yaml" | "yml" => "yaml",
+        "lua" => "lua",
+        "go" => "go",
+        "toml" => "toml",
+        "c" | "h" => "c",
+        "cpp" | "cc" | "cxx" | "hpp" => "cpp",
+        _ => return None,
+    };
+    Some(id)
+}
+
+/// The formatters used when `preferences.lua` doesn't list any. Each reads
+/// the document on stdin and writes the formatted text to stdout.
+pub fn default_formatters() -> BTreeMap<String, String> {
+    let prettier = "prettier --stdin-filepath {file}";
+    [
+        ("rust", "rustfmt --edition 2021"),
+        ("python", "black --quiet -"),
+        ("go", "gofmt"),
+        ("javascript", prettier),
+        ("javascriptreact", prettier),
+        ("typescript", prettier),
+        ("typescriptreact", prettier),
+        ("json", prettier),
+        ("css", prettier),
+        ("scss", prettier),
+        ("html", prettier),
+        ("markdown", prettier),
+        ("yaml", prettier),
+    ]
+    .into_iter()
+    .map(|(language, command)| (language.to_string(), command.to_string()))
+    .collect()
+}
+
+/// Splits a formatter command on whitespace and fills in the file path.
+pub fn command_args(command: &str, path: &Path) -> Vec<String> {
+    let file = path.display().to_string();
+    command
+        .split_whitespace()
+        .map(|arg| arg.replace(FILE_PLACEHOLDER, &file))
+        .collect()
+}
+
+/// Pipes `text` through `command` in the file's directory and returns what
+/// it printed. A non-zero exit is an error carrying the first line of stderr.
+pub async fn run_external(command: String, path: &Path, text: String) -> Result<String, String> {
+    let args = command_args(&command, path);
+    let (program, args) = args.split_first().ok_or("The formatter command is empty")?;
+    let mut process = tokio::process::Command::new(program);
+    process
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    if let Some(dir) = path.parent().filter(|dir| dir.is_dir()) {
+        process.current_dir(dir);
+    }
+    let mut child = process
+        .spawn()
+        .map_err(|err| format!("Couldn't run {program}: {err}"))?;
+
+    let mut stdin = child.stdin.take().ok_or("The formatter has no stdin")?;
+    let writer = tokio::spawn(async move {
+        let _ = stdin.write_all(text.as_bytes()).await;
+    });
+    let output = child
+        .wait_with_output()
+        .await
+        .map_err(|err| format!("{program} failed: {err}"))?;
+    let _ = writer.await;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .map_or_else(|| output.status.to_string(), String::from);
+        return Err(format!("{program}: {reason}"));
+    }
+    String::from_utf8(output.stdout).map_err(|_| format!("{program} printed invalid UTF-8"))
+}
+
+/// The edits that turn `old` into `new`, one per run of changed lines, in
+/// document order and all relative to `old`. A single changed line only
+/// has its changed middle replaced.
+pub fn minimal_edits(old: &str, new: &str) -> Vec<TextEdit> {
+    let old_lines: Vec<&str> = old.split('\n').collect();
+    let new_lines: Vec<&str> = new.split('\n').collect();
+    let buffer = EditorBuffer::from_text(old);
+
+    changed_runs(&old_lines, &new_lines)
+        .into_iter()
+        .map(|(old_range, new_range)| {
+            if old_range.len() == 1 && new_range.len() == 1 {
+                return line_edit(
+                    old_range.start,
+                    old_lines[old_range.start],
+                    new_lines[new_range.start],
+                );
+            }
+            let lines: Vec<String> = new_lines[new_range].iter().map(|l| l.to_string()).collect();
+            buffer.lines_edit(old_range.start, old_range.end, &lines)
+        })
+        .collect()
+}
+
+type Run = (std::ops::Range<usize>, std::ops::Range<usize>);
+
+/// The line ranges of `old` and `new` that differ, from a longest common
+/// subsequence of the lines between their shared start and end.
+fn changed_runs(old: &[&str], new: &[&str]) -> Vec<Run> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let max_suffix = old.len().min(new.len()) - prefix;
+    let suffix = old
+        .iter()
+        .rev()
+        .zip(new.iter().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+    if old_mid.is_empty() && new_mid.is_empty() {
+        return Vec::new();
+    }
+    if old_mid.is_empty()
+        || new_mid.is_empty()
+        || old_mid.len().saturating_mul(new_mid.len()) > MAX_DIFF_CELLS
+    {
+        return vec![(
+            prefix..prefix + old_mid.len(),
+            prefix..prefix + new_mid.len(),
+        )];
+    }
+
+    // lengths[i][j]: the longest common subsequence of old_mid[i..] and new_mid[j..].
+    let (n, m) = (old_mid.len(), new_mid.len());
+    let mut lengths = vec![0u32; (n + 1) * (m + 1)];
+    let at = |i: usize, j: usize| i * (m + 1) + j;
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[at(i, j)] = if old_mid[i] == new_mid[j] {
+                lengths[at(i + 1, j + 1)] + 1
+            } else {
+                lengths[at(i + 1, j)].max(lengths[at(i, j + 1)])
+            };
+        }
+    }
+
+    let mut runs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    let (mut run_i, mut run_j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && old_mid[i] == new_mid[j] {
+            if (run_i, run_j) != (i, j) {
+                runs.push((prefix + run_i..prefix + i, prefix + run_j..prefix + j));
+            }
+            i += 1;
+            j += 1;
+            (run_i, run_j) = (i, j);
+        } else if j < m && (i == n || lengths[at(i, j + 1)] >= lengths[at(i + 1, j)]) {
+            j += 1;
+        } else {
+            i += 1;
+        }
+    }
+    if (run_i, run_j) != (n, m) {
+        runs.push((prefix + run_i..prefix + n, prefix + run_j..prefix + m));
+    }
+    runs
+}
+
+/// Replaces only the part of line `line` that differs.
+fn line_edit(line: usize, old: &str, new: &str) -> TextEdit {
+    let old: Vec<char> = old.chars().collect();
+    let new: Vec<char> = new.chars().collect();
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    TextEdit {
+        start: (line, prefix),
+        end: (line, old.len() - suffix),
+        text: new[prefix..new.len() - suffix].iter().collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(old: &str, edits: &[TextEdit]) -> String {
+        let mut buffer = EditorBuffer::from_text(old);
+        for edit in edits.iter().rev() {
+            buffer.apply(edit);
+        }
+        buffer.text()
+    }
+
+    #[test]
+    fn edits_only_touch_changed_lines() {
+        let old = "fn a() {\nlet x=1;\n}\n\nfn b() {}\n";
+        let new = "fn a() {\n    let x = 1;\n}\n\nfn b() {}\n";
+        let edits = minimal_edits(old, new);
+        assert_eq!(
+            edits,
+            [TextEdit {
+                start: (1, 0),
+                end: (1, 6),
+                text: "    let x = ".to_string(),
+            }]
+        );
+        assert_eq!(apply(old, &edits), new);
+        assert!(minimal_edits(new, new).is_empty());
+    }
+
+    #[test]
+    fn edits_rebuild_the_formatted_text() {
+        let cases = [
+            ("a\nb\nc", "a\nc"),
+            ("a\nc", "a\nb\nb2\nc"),
+            ("a\nb\nc\nd\ne", "x\nb\nd\ne\nf"),
+            ("a\nb", "a\nb\n"),
+            ("a\nb\n", "a\nb"),
+            ("one\ntwo\n", ""),
+            ("", "one\n"),
+            ("x\na\ny\na\nz", "a\nq\na"),
+        ];
+        for (old, new) in cases {
+            assert_eq!(
+                apply(old, &minimal_edits(old, new)),
+                new,
+                "{old:?} -> {new:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn commands_fill_in_the_file() {
+        let path = Path::new("/w/src/main.ts");
+        assert_eq!(
+            command_args("prettier  --stdin-filepath {file}", path),
+            ["prettier", "--stdin-filepath", "/w/src/main.ts"]
+        );
+        assert_eq!(language_id(path), Some("typescript"));
+        assert_eq!(language_id(Path::new("Makefile")), None);
+    }
+}
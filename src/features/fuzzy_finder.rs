@@ -1,6 +1,10 @@
+use crate::features::symbols::Symbol;
 use std::fs;
 use std::path::{Path, PathBuf};
 
+/// Most symbols listed in `@` mode.
+const MAX_SYMBOL_RESULTS: usize = 200;
+
 /// A single file entry produced by scanning a directory.
 #[derive(Debug, Clone)]
 pub struct FileEntry {
@@ -8,14 +12,24 @@ pub struct FileEntry {
     pub display_name: String,
 }
 
+/// What Enter picked: a file, or a symbol in `@` mode.
+pub enum FinderItem {
+    File(PathBuf),
+    Symbol(Symbol),
+}
+
 pub struct FuzzyFinder {
     pub open: bool,
     pub input: String,
     pub current_folder: Option<PathBuf>,
     all_files: Vec<FileEntry>,
     pub filtered_files: Vec<FileEntry>,
+    /// Matches for the `@` query, best first.
+    pub symbol_results: Vec<Symbol>,
     pub selected_index: usize,
     pub preview_cache: Option<(PathBuf, String)>, // Cached preview: (path that was loaded, file content string)
+    /// The file line the preview starts at, 0-based.
+    pub preview_first_line: usize,
     pub input_id: iced::widget::Id,
 }
 
@@ -27,8 +41,10 @@ impl Default for FuzzyFinder {
             current_folder: None,
             all_files: Vec::new(),
             filtered_files: Vec::new(),
+            symbol_results: Vec::new(),
             selected_index: 0,
             preview_cache: None,
+            preview_first_line: 0,
             input_id: iced::widget::Id::unique(),
         }
     }
@@ -62,6 +78,7 @@ impl FuzzyFinder {
         self.open = false;
         self.input.clear();
         self.filtered_files = self.all_files.clone();
+        self.symbol_results.clear();
         self.selected_index = 0;
         self.preview_cache = None;
     }
@@ -74,8 +91,60 @@ impl FuzzyFinder {
         self.selected_index = 0;
     }
 
+    pub fn files(&self) -> Vec<PathBuf> {
+        self.all_files.iter().map(|f| f.path.clone()).collect()
+    }
+
+    /// The query after a leading `@`, which searches symbols instead of files.
+    pub fn symbol_query(&self) -> Option<&str> {
+        self.input.strip_prefix('@').map(str::trim)
+    }
+
+    /// Lists the symbols matching the `@` query, adding to those already
+    /// found when `append` is set (one answer per language server).
+    pub fn show_symbols(&mut self, symbols: &[Symbol], append: bool) {
+        let Some(query) = self.symbol_query() else {
+            return;
+        };
+        let query = query.to_lowercase();
+        let mut scored: Vec<(Symbol, i32)> = symbols
+            .iter()
+            .filter_map(|symbol| {
+                let score = fuzzy_match(&symbol.name.to_lowercase(), &query);
+                (score > 0).then(|| (symbol.clone(), score))
+            })
+            .collect();
+        if append {
+            scored.extend(self.symbol_results.drain(..).map(|symbol| {
+                let score = fuzzy_match(&symbol.name.to_lowercase(), &query);
+                (symbol, score)
+            }));
+        }
+        // Shorter names first among equal matches, so exact names lead.
+        scored.sort_by(|(a, a_score), (b, b_score)| {
+            b_score
+                .cmp(a_score)
+                .then_with(|| a.name.len().cmp(&b.name.len()))
+                .then_with(|| (&a.path, a.start).cmp(&(&b.path, b.start)))
+        });
+        scored.dedup_by(|(a, _), (b, _)| a.path == b.path && a.start == b.start);
+        scored.truncate(MAX_SYMBOL_RESULTS);
+        self.symbol_results = scored.into_iter().map(|(symbol, _)| symbol).collect();
+        if !append {
+            self.selected_index = 0;
+        }
+        self.selected_index = self
+            .selected_index
+            .min(self.symbol_results.len().saturating_sub(1));
+        self.update_preview();
+    }
+
     pub fn filter(&mut self) {
-        if self.input.is_empty() {
+        if self.symbol_query().is_some() {
+            // The app fills in symbol results as they arrive.
+            self.filtered_files.clear();
+            self.symbol_results.clear();
+        } else if self.input.is_empty() {
             self.filtered_files = self.all_files.clone();
         } else {
             let input_lower = self.input.to_lowercase();
@@ -106,7 +175,11 @@ impl FuzzyFinder {
     }
 
     pub fn navigate(&mut self, delta: i32) {
-        let count = self.filtered_files.len();
+        let count = if self.symbol_query().is_some() {
+            self.symbol_results.len()
+        } else {
+            self.filtered_files.len()
+        };
         if count == 0 {
             return;
         }
@@ -115,36 +188,65 @@ impl FuzzyFinder {
         self.update_preview();
     }
 
-    pub fn select(&mut self) -> Option<PathBuf> {
-        let path = self
-            .filtered_files
-            .get(self.selected_index)
-            .map(|f| f.path.clone());
+    pub fn select(&mut self) -> Option<FinderItem> {
+        let item = if self.symbol_query().is_some() {
+            self.symbol_results
+                .get(self.selected_index)
+                .cloned()
+                .map(FinderItem::Symbol)
+        } else {
+            self.filtered_files
+                .get(self.selected_index)
+                .map(|f| FinderItem::File(f.path.clone()))
+        };
         self.close();
-        path
+        item
+    }
+
+    fn selected_path(&self) -> Option<&Path> {
+        if self.symbol_query().is_some() {
+            self.symbol_results
+                .get(self.selected_index)
+                .map(|s| s.path.as_path())
+        } else {
+            self.filtered_files
+                .get(self.selected_index)
+                .map(|f| f.path.as_path())
+        }
     }
 
     pub fn update_preview(&mut self) {
-        let Some(entry) = self.filtered_files.get(self.selected_index) else {
+        let Some(path) = self.selected_path().map(Path::to_path_buf) else {
             self.preview_cache = None;
             return;
         };
+        // Symbols are previewed from a few lines above their definition.
+        let first_line = self
+            .symbol_results
+            .get(self.selected_index)
+            .filter(|_| self.symbol_query().is_some())
+            .map_or(0, |symbol| symbol.start.0.saturating_sub(5));
         if let Some((cached_path, _)) = &self.preview_cache {
-            if cached_path == &entry.path {
+            if cached_path == &path && self.preview_first_line == first_line {
                 return;
             }
         }
         // Read first ~200 lines for preview (no need to load huge files)
-        let content = fs::read_to_string(&entry.path)
+        let content = fs::read_to_string(&path)
             .unwrap_or_else(|_| String::from("[binary or unreadable file]"));
-        let truncated: String = content.lines().take(200).collect::<Vec<_>>().join("\n");
-        self.preview_cache = Some((entry.path.clone(), truncated));
+        let truncated: String = content
+            .lines()
+            .skip(first_line)
+            .take(200)
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.preview_cache = Some((path, truncated));
+        self.preview_first_line = first_line;
     }
 
     pub fn selected_extension(&self) -> &str {
-        self.filtered_files
-            .get(self.selected_index)
-            .and_then(|f| f.path.extension())
+        self.selected_path()
+            .and_then(|path| path.extension())
             .and_then(|e| e.to_str())
             .unwrap_or("")
     }
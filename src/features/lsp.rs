@@ -1,25 +1,315 @@
-//! LSP support using iced-code-editor's lsp-process feature.
+//! LSP support for the editor.
 //!
-//! Provides hover documentation, auto-completion, and go-to-definition
-//! for supported language servers.
+//! Provides hover documentation, auto-completion, navigation, rename, code
+//! actions, formatting, symbols, signature help and inlay hints for supported
+//! language servers. Which server handles which files comes from `lsp.lua`
+//! (see [`crate::config::lsp_config`]). One server runs per language server
+//! and workspace root; every open document of that language shares it.
 
-use iced_code_editor::{LspClient, LspEvent, LspProcessClient};
+mod document;
+mod server;
+mod traffic;
+
+use crate::config::lsp_config::{self, LanguageServer, LspRegistry};
+use crate::features::editor_buffer::{EditorBuffer, TextEdit};
+use crate::features::symbols::Symbol;
+use iced_code_editor::LspClient;
+use serde_json::Value;
+use server::LspServer;
 use std::path::{Path, PathBuf};
-use std::sync::mpsc;
+use std::sync::Arc;
+
+pub use traffic::TrafficEntry;
+
+/// Where a language server is in its lifecycle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LspServerState {
+    /// Spawned, waiting for the `initialize` response.
+    Starting,
+    Ready,
+    /// The process exited or failed to initialize, with the reason.
+    Crashed(String),
+    /// Stopped from the Language Servers panel.
+    Stopped,
+}
+
+/// What the language servers report back to the editor.
+#[derive(Debug, Clone)]
+pub enum LspEvent {
+    Hover {
+        text: String,
+    },
+    Completion {
+        items: Vec<String>,
+    },
+    Locations {
+        kind: LocationKind,
+        locations: Vec<LspLocation>,
+    },
+    Rename(WorkspaceChange),
+    /// The actions available at `line` (0-based) of `path`.
+    CodeActions {
+        path: PathBuf,
+        line: usize,
+        actions: Vec<CodeAction>,
+    },
+    /// The edits that format `path`, relative to the text it had when
+    /// formatting was requested.
+    Formatted {
+        path: PathBuf,
+        edits: Result<Vec<TextEdit>, String>,
+    },
+    /// The outline of `path`.
+    DocumentSymbols {
+        path: PathBuf,
+        symbols: Vec<Symbol>,
+    },
+    /// One server's matches for a `workspace/symbol` query.
+    WorkspaceSymbols {
+        query: String,
+        symbols: Vec<Symbol>,
+    },
+    /// The signature of the call around the cursor in `path`; `None` once
+    /// the cursor has left it.
+    SignatureHelp {
+        path: PathBuf,
+        help: Option<SignatureHelp>,
+    },
+    InlayHints {
+        path: PathBuf,
+        hints: Vec<InlayHint>,
+    },
+    /// The server's hints changed, e.g. after indexing, and should be asked
+    /// for again.
+    InlayHintsRefresh,
+    /// An edit the server asked to apply through `workspace/applyEdit`.
+    ApplyEdit(WorkspaceChange),
+    /// A request the user started was refused.
+    Failed(String),
+    Diagnostics(DiagnosticUpdate),
+    Log {
+        server_key: String,
+        message: String,
+    },
+    State {
+        server_key: String,
+        state: LspServerState,
+    },
+}
+
+/// The navigation requests that answer with locations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocationKind {
+    Definition,
+    TypeDefinition,
+    Implementation,
+    References,
+}
+
+impl LocationKind {
+    fn method(self) -> &'static str {
+        match self {
+            LocationKind::Definition => "textDocument/definition",
+            LocationKind::TypeDefinition => "textDocument/typeDefinition",
+            LocationKind::Implementation => "textDocument/implementation",
+            LocationKind::References => "textDocument/references",
+        }
+    }
+
+    /// The server capability that announces support for the request.
+    fn provider(self) -> &'static str {
+        match self {
+            LocationKind::Definition => "definitionProvider",
+            LocationKind::TypeDefinition => "typeDefinitionProvider",
+            LocationKind::Implementation => "implementationProvider",
+            LocationKind::References => "referencesProvider",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            LocationKind::Definition => "Definitions",
+            LocationKind::TypeDefinition => "Type Definitions",
+            LocationKind::Implementation => "Implementations",
+            LocationKind::References => "References",
+        }
+    }
+}
+
+/// A range in a file. Positions are 0-based `(line, column)` and columns
+/// count characters, like [`TextEdit`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LspLocation {
+    pub path: PathBuf,
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+}
+
+/// The active signature of a `textDocument/signatureHelp` answer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureHelp {
+    pub label: String,
+    /// The active parameter as a range of characters in `label`.
+    pub active_parameter: Option<std::ops::Range<usize>>,
+    pub documentation: Option<String>,
+    /// Which overload `label` is (0-based) and how many there are.
+    pub overload: (usize, usize),
+}
+
+/// A type or parameter name hint from `textDocument/inlayHint`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InlayHint {
+    /// 0-based `(line, column)`, columns counting characters.
+    pub position: (usize, usize),
+    pub label: String,
+    pub kind: Option<lsp_types::InlayHintKind>,
+}
+
+/// The hints of one line as text to show after its end, since the editor
+/// can't make room for them inside it. Type hints name the binding they
+/// belong to (`x: i32`) and parameter hints the argument they label
+/// (`count: 5`).
+pub fn end_of_line_hints(line: &str, hints: &[InlayHint]) -> String {
+    let chars: Vec<char> = line.chars().collect();
+    let is_word = |c: &char| c.is_alphanumeric() || *c == '_';
+    hints
+        .iter()
+        .map(|hint| {
+            let column = hint.position.1.min(chars.len());
+            let label = hint.label.trim();
+            match hint.kind {
+                Some(lsp_types::InlayHintKind::TYPE) => {
+                    let start = chars[..column]
+                        .iter()
+                        .rposition(|c| !is_word(c))
+                        .map_or(0, |idx| idx + 1);
+                    let name: String = chars[start..column].iter().collect();
+                    format!("{name}{label}")
+                }
+                Some(lsp_types::InlayHintKind::PARAMETER) => {
+                    let mut depth = 0usize;
+                    let argument: String = chars[column..]
+                        .iter()
+                        .take_while(|c| match c {
+                            '(' | '[' | '{' => {
+                                depth += 1;
+                                true
+                            }
+                            ')' | ']' | '}' if depth == 0 => false,
+                            ')' | ']' | '}' => {
+                                depth -= 1;
+                                true
+                            }
+                            ',' => depth > 0,
+                            _ => true,
+                        })
+                        .take(24)
+                        .collect();
+                    format!("{label} {}", argument.trim())
+                        .trim_end()
+                        .to_string()
+                }
+                _ => label.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("  ")
+}
+
+/// The edits a `WorkspaceEdit` makes to one file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChange {
+    pub path: PathBuf,
+    pub edits: Vec<TextEdit>,
+}
+
+impl FileChange {
+    /// Applies the edits, which all refer to the text before any of them, and
+    /// returns the lines (0-based) they start on afterwards.
+    pub fn apply(&self, buffer: &mut EditorBuffer) -> Vec<usize> {
+        let mut edits: Vec<&TextEdit> = self.edits.iter().collect();
+        edits.sort_by_key(|edit| std::cmp::Reverse(edit.start));
+        let mut lines = Vec::new();
+        for edit in edits {
+            buffer.apply(edit);
+            let added = edit.new_end().0 as isize - edit.end.0 as isize;
+            for line in &mut lines {
+                *line = (*line as isize + added) as usize;
+            }
+            lines.push(edit.start.0);
+        }
+        lines.reverse();
+        lines.dedup();
+        lines
+    }
+}
+
+/// A `WorkspaceEdit` with positions in character columns.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorkspaceChange {
+    pub files: Vec<FileChange>,
+    /// File create, rename and delete operations, which aren't supported.
+    pub skipped_operations: usize,
+}
+
+impl WorkspaceChange {
+    pub fn edit_count(&self) -> usize {
+        self.files.iter().map(|file| file.edits.len()).sum()
+    }
+}
+
+/// A code action or bare command offered by the server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeAction {
+    pub title: String,
+    /// E.g. `quickfix` or `refactor.rewrite`.
+    pub kind: Option<String>,
+    pub preferred: bool,
+    pub edit: Option<WorkspaceChange>,
+    /// The `Command` to run after the edit, passed back unchanged.
+    pub command: Option<Value>,
+}
+
+/// A running server as the Language Servers panel shows it.
+#[derive(Debug, Clone)]
+pub struct ServerStatus {
+    pub server_key: String,
+    pub root_uri: String,
+    pub pid: Option<u32>,
+    pub state: LspServerState,
+    pub capabilities: Value,
+    pub stderr: Vec<String>,
+}
+
+impl ServerStatus {
+    /// The names of the capabilities the server announced.
+    pub fn capability_names(&self) -> Vec<&str> {
+        let Some(capabilities) = self.capabilities.as_object() else {
+            return Vec::new();
+        };
+        capabilities
+            .iter()
+            .filter(|(_, value)| !matches!(value, Value::Null | Value::Bool(false)))
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+}
 
 pub struct LspManager {
-    sender: mpsc::Sender<LspEvent>,
-    receiver: Option<mpsc::Receiver<LspEvent>>,
+    servers: Vec<LspServer>,
     workspace_root: Option<PathBuf>,
+    registry: LspRegistry,
+    /// Whether servers record their JSON-RPC traffic.
+    recording: bool,
 }
 
 impl LspManager {
-    pub fn new() -> Self {
-        let (sender, receiver) = mpsc::channel();
+    pub fn new(registry: LspRegistry) -> Self {
         Self {
-            sender,
-            receiver: Some(receiver),
+            servers: Vec::new(),
             workspace_root: None,
+            registry,
+            recording: false,
         }
     }
 
@@ -27,67 +317,358 @@ impl LspManager {
         self.workspace_root = Some(root);
     }
 
-    /// Creates a new LSP process client for the given server key.
-    /// The client should be attached to a CodeEditor via `editor.attach_lsp()`.
-    pub fn create_client(
-        &self,
-        server_key: &str,
-        root_hint: Option<&Path>,
-    ) -> Result<Box<dyn LspClient>, String> {
-        let root_uri = self.root_uri(root_hint);
-        let client = LspProcessClient::new_with_server(&root_uri, self.sender.clone(), server_key)
-            .map_err(|e| format!("Failed to start LSP server '{}': {}", server_key, e))?;
-        Ok(Box::new(client))
+    pub fn registry(&self) -> &LspRegistry {
+        &self.registry
     }
 
-    pub fn drain_events(&mut self) -> Vec<LspEvent> {
-        let mut events = Vec::new();
-        if let Some(ref receiver) = self.receiver {
-            loop {
-                match receiver.try_recv() {
-                    Ok(event) => events.push(event),
-                    Err(mpsc::TryRecvError::Empty) => break,
-                    Err(mpsc::TryRecvError::Disconnected) => {
-                        self.receiver = None;
-                        break;
-                    }
-                }
+    /// Replaces the server list. Running servers pick up their new entries
+    /// when they restart.
+    pub fn set_registry(&mut self, registry: LspRegistry) {
+        self.registry = registry;
+    }
+
+    /// The configured server for a file, if any.
+    pub fn language_for_path(&self, path: &Path) -> Option<&LanguageServer> {
+        self.registry.for_path(path)
+    }
+
+    /// Returns a client for `language`'s server in the workspace around
+    /// `path`, starting it first if none runs yet and restarting it if it
+    /// crashed. Attach the client with `editor.attach_lsp()`.
+    pub fn client(
+        &mut self,
+        language: &LanguageServer,
+        path: &Path,
+    ) -> Result<Box<dyn LspClient>, String> {
+        let root_uri = self.root_uri(path, &language.root_markers);
+        if let Some(server) = self
+            .servers
+            .iter_mut()
+            .find(|server| server.server_key == language.name && server.root_uri == root_uri)
+        {
+            if matches!(
+                server.state,
+                LspServerState::Crashed(_) | LspServerState::Stopped
+            ) {
+                server.restart()?;
             }
+            return Ok(server.client());
         }
-        events
+
+        let server = LspServer::start(language.clone(), &root_uri)?;
+        server.set_recording(self.recording);
+        let client = server.client();
+        self.servers.push(server);
+        Ok(client)
     }
 
-    fn root_uri(&self, root_hint: Option<&Path>) -> String {
-        let cwd = std::env::current_dir().ok();
-        let root_dir = root_hint
-            .and_then(|path| {
-                if path.is_dir() {
-                    Some(path.to_path_buf())
-                } else {
-                    path.parent().map(PathBuf::from)
+    /// A short status for the server behind a document: `starting`, the
+    /// current progress, `crashed` or `stopped`. `None` when it is idle and
+    /// ready or when no server handles it.
+    pub fn status(&self, server_key: &str, path: &Path) -> Option<String> {
+        let server = self.server_for(server_key, path)?;
+        match &server.state {
+            LspServerState::Starting => Some("starting".to_string()),
+            LspServerState::Ready => server.progress(),
+            LspServerState::Crashed(_) => Some("crashed".to_string()),
+            LspServerState::Stopped => Some("stopped".to_string()),
+        }
+    }
+
+    /// Restarts every server with its current `lsp.lua` entry. Open
+    /// documents are reopened on the new processes, so editors stay
+    /// attached.
+    pub fn restart_all(&mut self) -> Vec<String> {
+        let registry = &self.registry;
+        self.servers
+            .iter_mut()
+            .filter_map(|server| {
+                if let Some(language) = registry.server(&server.server_key) {
+                    server.configure(language.clone());
                 }
+                server.restart().err()
             })
-            .map(|hint_dir| {
-                if let Some(ref cwd) = cwd {
-                    if hint_dir.starts_with(cwd) {
-                        return cwd.clone();
-                    }
-                }
-                hint_dir
+            .collect()
+    }
+
+    /// Stops every server.
+    pub fn shutdown(&mut self) {
+        self.servers.clear();
+    }
+
+    /// Every server started so far, in start order.
+    pub fn server_statuses(&self) -> Vec<ServerStatus> {
+        self.servers
+            .iter()
+            .map(|server| ServerStatus {
+                server_key: server.server_key.clone(),
+                root_uri: server.root_uri.clone(),
+                pid: server.pid(),
+                state: server.state.clone(),
+                capabilities: server.capabilities.clone(),
+                stderr: server.stderr_tail(),
             })
-            .or(cwd)
-            .or_else(|| self.workspace_root.clone());
+            .collect()
+    }
+
+    /// Starts or stops recording JSON-RPC traffic on every server, and on
+    /// servers started later. Stopping drops what was recorded.
+    pub fn set_recording(&mut self, recording: bool) {
+        self.recording = recording;
+        for server in &self.servers {
+            server.set_recording(recording);
+        }
+    }
+
+    /// The messages recorded for the `index`th server.
+    pub fn traffic(&self, index: usize) -> Vec<Arc<TrafficEntry>> {
+        self.servers
+            .get(index)
+            .map(LspServer::traffic)
+            .unwrap_or_default()
+    }
+
+    pub fn clear_traffic(&self, index: usize) {
+        if let Some(server) = self.servers.get(index) {
+            server.clear_traffic();
+        }
+    }
 
-        match root_dir {
-            Some(dir) => path_to_file_uri(&dir),
-            None => "file:///".to_string(),
+    /// Restarts the `index`th server with its current `lsp.lua` entry.
+    pub fn restart_server(&mut self, index: usize) -> Result<(), String> {
+        let server = self
+            .servers
+            .get_mut(index)
+            .ok_or_else(|| "That language server is gone".to_string())?;
+        if let Some(language) = self.registry.server(&server.server_key) {
+            server.configure(language.clone());
         }
+        server.restart()
+    }
+
+    /// Stops the `index`th server. It starts again on restart or when a
+    /// file it handles is opened.
+    pub fn stop_server(&mut self, index: usize) -> Option<String> {
+        let server = self.servers.get_mut(index)?;
+        server.shut_down();
+        Some(server.server_key.clone())
+    }
+
+    /// Asks for the definitions, references, etc. of the symbol at the
+    /// 0-based `position` of `path`.
+    pub fn request_locations(
+        &self,
+        server_key: &str,
+        path: &Path,
+        kind: LocationKind,
+        position: (usize, usize),
+    ) -> Result<(), String> {
+        let feature = kind.label().to_lowercase();
+        let server = self.ready_server(server_key, path, kind.provider(), &feature)?;
+        server.request_locations(path, kind, position);
+        Ok(())
+    }
+
+    pub fn rename(
+        &self,
+        server_key: &str,
+        path: &Path,
+        position: (usize, usize),
+        new_name: &str,
+    ) -> Result<(), String> {
+        let server = self.ready_server(server_key, path, "renameProvider", "rename")?;
+        server.rename(path, position, new_name);
+        Ok(())
+    }
+
+    /// Asks for the code actions over `start..end`, passing along the
+    /// diagnostics it overlaps.
+    pub fn code_actions(
+        &self,
+        server_key: &str,
+        path: &Path,
+        start: (usize, usize),
+        end: (usize, usize),
+    ) -> Result<(), String> {
+        let server = self.ready_server(server_key, path, "codeActionProvider", "code actions")?;
+        server.code_actions(path, start, end);
+        Ok(())
+    }
+
+    /// Asks for the edits that format the whole of `path`.
+    pub fn format(
+        &self,
+        server_key: &str,
+        path: &Path,
+        tab_size: usize,
+        insert_spaces: bool,
+    ) -> Result<(), String> {
+        let server =
+            self.ready_server(server_key, path, "documentFormattingProvider", "formatting")?;
+        server.format(path, tab_size, insert_spaces);
+        Ok(())
+    }
+
+    /// Asks for the signature around `position`. `trigger` is the character
+    /// just typed, if it is one of the server's trigger characters, and
+    /// `retrigger` is set while the popup is already showing.
+    pub fn signature_help(
+        &self,
+        server_key: &str,
+        path: &Path,
+        position: (usize, usize),
+        trigger: Option<char>,
+        retrigger: bool,
+    ) -> Result<(), String> {
+        let server =
+            self.ready_server(server_key, path, "signatureHelpProvider", "signature help")?;
+        server.signature_help(path, position, trigger, retrigger);
+        Ok(())
+    }
+
+    /// The characters that open the signature popup and those that update
+    /// it while it shows, from the server's capabilities.
+    pub fn signature_triggers(&self, server_key: &str, path: &Path) -> (Vec<char>, Vec<char>) {
+        let Ok(server) =
+            self.ready_server(server_key, path, "signatureHelpProvider", "signature help")
+        else {
+            return (Vec::new(), Vec::new());
+        };
+        let characters = |field: &str| -> Vec<char> {
+            server
+                .capabilities
+                .pointer(&format!("/signatureHelpProvider/{field}"))
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(|c| c.as_str()?.chars().next())
+                .collect()
+        };
+        (
+            characters("triggerCharacters"),
+            characters("retriggerCharacters"),
+        )
+    }
+
+    /// Asks for the hints of the first `line_count` lines of `path`.
+    pub fn inlay_hints(
+        &self,
+        server_key: &str,
+        path: &Path,
+        line_count: usize,
+    ) -> Result<(), String> {
+        let server = self.ready_server(server_key, path, "inlayHintProvider", "inlay hints")?;
+        server.inlay_hints(path, line_count);
+        Ok(())
+    }
+
+    pub fn document_symbols(&self, server_key: &str, path: &Path) -> Result<(), String> {
+        let server = self.ready_server(
+            server_key,
+            path,
+            "documentSymbolProvider",
+            "document symbols",
+        )?;
+        server.document_symbols(path);
+        Ok(())
+    }
+
+    /// Sends `query` to every running server that can search the workspace
+    /// for symbols, and returns how many were asked.
+    pub fn workspace_symbols(&self, query: &str) -> usize {
+        let servers: Vec<&LspServer> = self
+            .servers
+            .iter()
+            .filter(|server| {
+                server.state == LspServerState::Ready
+                    && !matches!(
+                        server.capabilities.get("workspaceSymbolProvider"),
+                        None | Some(Value::Null | Value::Bool(false))
+                    )
+            })
+            .collect();
+        for server in &servers {
+            server.workspace_symbols(query);
+        }
+        servers.len()
+    }
+
+    /// Runs a code action's `Command` on the server, if it offers it.
+    pub fn execute_command(
+        &self,
+        server_key: &str,
+        path: &Path,
+        command: &Value,
+    ) -> Result<(), String> {
+        let server = self.ready_server(server_key, path, "executeCommandProvider", "commands")?;
+        let name = command.get("command").and_then(Value::as_str).unwrap_or("");
+        let offered = server
+            .capabilities
+            .pointer("/executeCommandProvider/commands")
+            .and_then(Value::as_array)
+            .is_some_and(|commands| commands.iter().any(|c| c.as_str() == Some(name)));
+        if !offered {
+            return Err(format!("{server_key} can't run the command \"{name}\""));
+        }
+        server.execute_command(command);
+        Ok(())
+    }
+
+    /// The running server for `path`, if it announced `provider`.
+    fn ready_server(
+        &self,
+        server_key: &str,
+        path: &Path,
+        provider: &str,
+        feature: &str,
+    ) -> Result<&LspServer, String> {
+        let server = self
+            .server_for(server_key, path)
+            .ok_or_else(|| format!("{server_key} isn't running for this file"))?;
+        match &server.state {
+            LspServerState::Starting => Err(format!("{server_key} is still starting")),
+            LspServerState::Crashed(_) | LspServerState::Stopped => {
+                Err(format!("{server_key} has stopped"))
+            }
+            LspServerState::Ready => match server.capabilities.get(provider) {
+                None | Some(Value::Null | Value::Bool(false)) => {
+                    Err(format!("{server_key} doesn't support {feature}"))
+                }
+                Some(_) => Ok(server),
+            },
+        }
+    }
+
+    pub fn drain_events(&mut self) -> Vec<LspEvent> {
+        self.servers.iter_mut().flat_map(LspServer::poll).collect()
+    }
+
+    /// The server called `server_key` that has `path` open.
+    fn server_for(&self, server_key: &str, path: &Path) -> Option<&LspServer> {
+        self.servers
+            .iter()
+            .find(|server| server.server_key == server_key && server.has_document(path))
+    }
+
+    /// Where a server for `path` runs: the folder its root markers point
+    /// at, else the open folder when it holds the file, else the file's own
+    /// folder.
+    fn root_uri(&self, path: &Path, root_markers: &[String]) -> String {
+        let dir = path.parent().unwrap_or(path);
+        let root = lsp_config::find_root(dir, root_markers)
+            .or_else(|| {
+                self.workspace_root
+                    .clone()
+                    .filter(|root| dir.starts_with(root))
+            })
+            .unwrap_or_else(|| dir.to_path_buf());
+        path_to_file_uri(&root)
     }
 }
 
 impl Default for LspManager {
     fn default() -> Self {
-        Self::new()
+        Self::new(LspRegistry::default())
     }
 }
 
@@ -109,8 +690,15 @@ fn path_to_file_uri(path: &Path) -> String {
 /// # Fields
 ///
 /// - `line` (`usize`) - The line that the user requires a diagnostic for.
+/// - `column` (`usize`) - The column the diagnostic starts at.
+/// - `end_line` (`usize`) - The line the diagnostic ends on.
+/// - `end_column` (`usize`) - The column just past the end of the diagnostic.
 /// - `severity` (`lsp_types`) - What severity is the error/warning in the users code.
 /// - `message` (`String`) - The message that the LSP provides for inline diagnostic visuals.
+/// - `source` (`Option<String>`) - What produced it, e.g. `rustc` or `clippy`.
+/// - `code` (`Option<String>`) - The lint or error code, e.g. `E0308`.
+///
+/// Lines and columns are 1-based, like the cursor position in the status bar.
 ///
 /// # Examples
 ///
@@ -119,15 +707,25 @@ fn path_to_file_uri(path: &Path) -> String {
 ///
 /// let s = InlineDiagnostic {
 ///     line: value,
+///     column: value,
+///     end_line: value,
+///     end_column: value,
 ///     severity: value,
 ///     message: value,
+///     source: value,
+///     code: value,
 /// };
 /// ```
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct InlineDiagnostic {
     pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
     pub severity: lsp_types::DiagnosticSeverity,
     pub message: String,
+    pub source: Option<String>,
+    pub code: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -135,3 +733,53 @@ pub struct DiagnosticUpdate {
     pub path: PathBuf,
     pub diagnostics: Vec<InlineDiagnostic>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_changes_apply_from_the_end() {
+        let edit = |start, end, text: &str| TextEdit {
+            start,
+            end,
+            text: text.to_string(),
+        };
+        let change = FileChange {
+            path: PathBuf::from("/src/lib.rs"),
+            edits: vec![
+                edit((0, 3), (0, 4), "total"),
+                edit((1, 0), (1, 0), "// sum\n"),
+                edit((1, 4), (1, 5), "total"),
+                edit((2, 0), (2, 1), "total"),
+            ],
+        };
+        let mut buffer = EditorBuffer::from_text("fn a(x: u8) {\nlet x = 1;\nx\n}");
+        let lines = change.apply(&mut buffer);
+        assert_eq!(
+            buffer.text(),
+            "fn total(x: u8) {\n// sum\nlet total = 1;\ntotal\n}"
+        );
+        assert_eq!(lines, [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn end_of_line_hints_show_their_code() {
+        let hint = |column, label: &str, kind| InlayHint {
+            position: (0, column),
+            label: label.to_string(),
+            kind: Some(kind),
+        };
+        let line = "let total = add(count, f(a, b));";
+        let hints = [
+            hint(9, ": u32", lsp_types::InlayHintKind::TYPE),
+            hint(16, "left:", lsp_types::InlayHintKind::PARAMETER),
+            hint(23, "right:", lsp_types::InlayHintKind::PARAMETER),
+        ];
+        assert_eq!(
+            end_of_line_hints(line, &hints),
+            "total: u32  left: count  right: f(a, b)"
+        );
+        assert_eq!(end_of_line_hints(line, &[]), "");
+    }
+}
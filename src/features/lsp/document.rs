@@ -0,0 +1,193 @@
+//! Shadow copies of the documents a language server has open.
+//!
+//! The editor reports positions as character columns while LSP counts UTF-16
+//! code units, so each open document keeps its text around for conversion.
+
+use iced_code_editor::{LspPosition, LspTextChange};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone)]
+pub struct ShadowDocument {
+    pub language_id: String,
+    pub version: i32,
+    lines: Vec<String>,
+}
+
+impl ShadowDocument {
+    pub fn new(language_id: &str, version: i32, text: &str) -> Self {
+        Self {
+            language_id: language_id.to_string(),
+            version,
+            lines: text.split('\n').map(String::from).collect(),
+        }
+    }
+
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    /// Converts a character column to the UTF-16 column the server expects.
+    pub fn utf16_position(&self, position: LspPosition) -> LspPosition {
+        let line = self
+            .lines
+            .get(position.line as usize)
+            .map_or("", String::as_str);
+        let character = line
+            .chars()
+            .take(position.character as usize)
+            .map(|c| c.len_utf16() as u32)
+            .sum();
+        LspPosition {
+            line: position.line,
+            character,
+        }
+    }
+
+    /// Converts a UTF-16 column from the server back to a character column.
+    pub fn char_position(&self, position: LspPosition) -> LspPosition {
+        let line = self
+            .lines
+            .get(position.line as usize)
+            .map_or("", String::as_str);
+        let mut units = 0;
+        let character = line
+            .chars()
+            .take_while(|c| {
+                units += c.len_utf16() as u32;
+                units <= position.character
+            })
+            .count() as u32;
+        LspPosition {
+            line: position.line,
+            character,
+        }
+    }
+
+    /// Applies an edit given in character columns. Out-of-range edits are
+    /// ignored, like the editor's own shadow text.
+    pub fn apply(&mut self, change: &LspTextChange) {
+        let start_line = change.range.start.line as usize;
+        let end_line = change.range.end.line as usize;
+        if start_line > end_line || end_line >= self.lines.len() {
+            return;
+        }
+
+        let start = byte_index(&self.lines[start_line], change.range.start.character);
+        let end = byte_index(&self.lines[end_line], change.range.end.character);
+        let prefix = self.lines[start_line][..start].to_string();
+        let suffix = self.lines[end_line][end..].to_string();
+
+        let mut replacement: Vec<String> = change.text.split('\n').map(String::from).collect();
+        if let Some(first) = replacement.first_mut() {
+            first.insert_str(0, &prefix);
+        }
+        if let Some(last) = replacement.last_mut() {
+            last.push_str(&suffix);
+        }
+        self.lines.splice(start_line..=end_line, replacement);
+    }
+}
+
+type OpenDocument<'a> = Box<dyn Fn(&Path) -> Option<ShadowDocument> + 'a>;
+
+/// Converts server positions anywhere in the workspace to character columns:
+/// from the open document when there is one, or else from the file on disk,
+/// read once per lookup. Positions in unreadable files are left unchanged.
+pub struct ColumnMap<'a> {
+    open: OpenDocument<'a>,
+    files: HashMap<PathBuf, Option<ShadowDocument>>,
+}
+
+impl<'a> ColumnMap<'a> {
+    pub fn new(open: impl Fn(&Path) -> Option<ShadowDocument> + 'a) -> Self {
+        Self {
+            open: Box::new(open),
+            files: HashMap::new(),
+        }
+    }
+
+    /// The 0-based `(line, column)` of a server position in `path`.
+    pub fn position(&mut self, path: &Path, position: lsp_types::Position) -> (usize, usize) {
+        let position = LspPosition {
+            line: position.line,
+            character: position.character,
+        };
+        let open = &self.open;
+        let document = self.files.entry(path.to_path_buf()).or_insert_with(|| {
+            open(path).or_else(|| {
+                std::fs::read_to_string(path)
+                    .ok()
+                    .map(|text| ShadowDocument::new("", 0, &text))
+            })
+        });
+        let position = document
+            .as_ref()
+            .map_or(position, |doc| doc.char_position(position));
+        (position.line as usize, position.character as usize)
+    }
+}
+
+fn byte_index(line: &str, character: u32) -> usize {
+    line.char_indices()
+        .nth(character as usize)
+        .map_or(line.len(), |(idx, _)| idx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iced_code_editor::LspRange;
+
+    fn change(start: (u32, u32), end: (u32, u32), text: &str) -> LspTextChange {
+        LspTextChange {
+            range: LspRange {
+                start: LspPosition {
+                    line: start.0,
+                    character: start.1,
+                },
+                end: LspPosition {
+                    line: end.0,
+                    character: end.1,
+                },
+            },
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn edits_keep_trailing_lines() {
+        let mut doc = ShadowDocument::new("rust", 1, "fn a() {}\n");
+        doc.apply(&change((1, 0), (1, 0), "fn b() {}"));
+        assert_eq!(doc.text(), "fn a() {}\nfn b() {}");
+
+        doc.apply(&change((0, 3), (1, 3), "x() {}\nfn "));
+        assert_eq!(doc.text(), "fn x() {}\nfn b() {}");
+
+        doc.apply(&change((0, 9), (1, 9), ""));
+        assert_eq!(doc.text(), "fn x() {}");
+
+        doc.apply(&change((4, 0), (4, 0), "ignored"));
+        assert_eq!(doc.text(), "fn x() {}");
+    }
+
+    #[test]
+    fn positions_count_utf16_units() {
+        let doc = ShadowDocument::new("rust", 1, "let é = \"🦀\";");
+        let at = |character| {
+            doc.utf16_position(LspPosition { line: 0, character })
+                .character
+        };
+        assert_eq!(at(5), 5);
+        assert_eq!(at(10), 11);
+        assert_eq!(at(99), 13);
+
+        let back = |character| {
+            doc.char_position(LspPosition { line: 0, character })
+                .character
+        };
+        assert_eq!(back(11), 10);
+        assert_eq!(back(13), 12);
+        assert_eq!(back(40), 12);
+    }
+}
@@ -0,0 +1,1782 @@
+//! One running language server and the JSON-RPC plumbing around it.
+//!
+//! Every editor showing a document the server handles gets a [`SharedClient`]
+//! pointing at the same [`Shared`] state, so opening, switching and closing
+//! tabs only sends `didOpen`/`didClose` instead of respawning the process.
+//! The document shadows live in that state too, which lets a restarted
+//! process be handed every open document again.
+
+use super::document::{ColumnMap, ShadowDocument};
+use super::traffic::{Traffic, TrafficEntry};
+use super::{
+    CodeAction, DiagnosticUpdate, FileChange, InlayHint, InlineDiagnostic, LocationKind, LspEvent,
+    LspLocation, LspServerState, SignatureHelp, WorkspaceChange,
+};
+use crate::config::lsp_config::LanguageServer;
+use crate::features::editor_buffer::TextEdit;
+use crate::features::symbols::Symbol;
+use iced_code_editor::{LspClient, LspDocument, LspPosition, LspTextChange};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long a stopped server gets to exit by itself before it is killed.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// What the reader threads report back to [`LspServer::poll`].
+enum Incoming {
+    Event(LspEvent),
+    Initialized(Value),
+    Progress(ProgressUpdate),
+    Failed(String),
+    Exited,
+}
+
+#[derive(Debug, PartialEq)]
+struct ProgressUpdate {
+    token: String,
+    title: Option<String>,
+    detail: String,
+    done: bool,
+}
+
+#[derive(Debug, Clone)]
+enum RequestKind {
+    Initialize,
+    Hover,
+    Completion,
+    Locations(LocationKind),
+    Rename,
+    /// Tagged with the file and the 0-based line the range starts on.
+    CodeActions {
+        path: PathBuf,
+        line: usize,
+    },
+    ExecuteCommand,
+    Formatting(PathBuf),
+    DocumentSymbols(PathBuf),
+    WorkspaceSymbols(String),
+    SignatureHelp(PathBuf),
+    InlayHints(PathBuf),
+}
+
+/// Messages are held back until the server answers `initialize`.
+struct Outgoing {
+    sender: mpsc::Sender<Vec<u8>>,
+    ready: bool,
+    queued: Vec<Vec<u8>>,
+}
+
+struct Shared {
+    server_key: String,
+    outgoing: Mutex<Outgoing>,
+    next_id: AtomicU64,
+    pending: Mutex<HashMap<u64, RequestKind>>,
+    documents: Mutex<HashMap<String, ShadowDocument>>,
+    /// The last published diagnostics per document, as sent, so code action
+    /// requests can hand them back.
+    diagnostics: Mutex<HashMap<String, Value>>,
+    /// The `settings` from `lsp.lua`, or null.
+    settings: Mutex<Value>,
+    traffic: Mutex<Traffic>,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Editors open documents under an unescaped `file://` uri.
+fn document_uri(path: &Path) -> String {
+    format!("file://{}", path.display())
+}
+
+fn lsp_position((line, character): (usize, usize)) -> LspPosition {
+    LspPosition {
+        line: line as u32,
+        character: character as u32,
+    }
+}
+
+fn frame(value: &Value) -> Vec<u8> {
+    let body = value.to_string();
+    format!("Content-Length: {}\r\n\r\n{}", body.len(), body).into_bytes()
+}
+
+impl Shared {
+    /// Frames a message for the server, noting it in the traffic log.
+    fn frame(&self, value: &Value) -> Vec<u8> {
+        lock(&self.traffic).record(true, value, Instant::now());
+        frame(value)
+    }
+
+    fn send(&self, value: Value) {
+        let mut outgoing = lock(&self.outgoing);
+        if outgoing.ready {
+            let _ = outgoing.sender.send(self.frame(&value));
+        } else {
+            outgoing.queued.push(self.frame(&value));
+        }
+    }
+
+    fn notify(&self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    fn request(&self, kind: RequestKind, method: &str, params: Value) {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        lock(&self.pending).insert(id, kind);
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+    }
+
+    fn respond(&self, id: Value, result: Value) {
+        let message = json!({ "jsonrpc": "2.0", "id": id, "result": result });
+        let _ = lock(&self.outgoing).sender.send(self.frame(&message));
+    }
+
+    /// Sends `initialized` and the settings, followed by everything queued
+    /// while starting.
+    fn mark_ready(&self) {
+        let mut outgoing = lock(&self.outgoing);
+        let initialized = json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} });
+        let _ = outgoing.sender.send(self.frame(&initialized));
+        let settings = lock(&self.settings).clone();
+        if !settings.is_null() {
+            let configuration = json!({
+                "jsonrpc": "2.0",
+                "method": "workspace/didChangeConfiguration",
+                "params": { "settings": settings },
+            });
+            let _ = outgoing.sender.send(self.frame(&configuration));
+        }
+        for message in std::mem::take(&mut outgoing.queued) {
+            let _ = outgoing.sender.send(message);
+        }
+        outgoing.ready = true;
+    }
+
+    fn position_request(
+        &self,
+        kind: RequestKind,
+        method: &str,
+        uri: &str,
+        position: LspPosition,
+        extra: Value,
+    ) {
+        let Some(position) = lock(&self.documents)
+            .get(uri)
+            .map(|doc| doc.utf16_position(position))
+        else {
+            return;
+        };
+        let mut params = json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": position.line, "character": position.character },
+        });
+        if let (Some(params), Value::Object(extra)) = (params.as_object_mut(), extra) {
+            params.extend(extra);
+        }
+        self.request(kind, method, params);
+    }
+
+    fn code_actions(&self, path: &Path, start: (usize, usize), end: (usize, usize)) {
+        let uri = document_uri(path);
+        let Some((start, end)) = lock(&self.documents).get(&uri).map(|doc| {
+            (
+                doc.utf16_position(lsp_position(start)),
+                doc.utf16_position(lsp_position(end)),
+            )
+        }) else {
+            return;
+        };
+        let (from, to) = ((start.line, start.character), (end.line, end.character));
+        let overlaps = |diagnostic: &&Value| {
+            diagnostic
+                .get("range")
+                .and_then(|range| serde_json::from_value::<lsp_types::Range>(range.clone()).ok())
+                .is_some_and(|range| {
+                    (range.start.line, range.start.character) <= to
+                        && from <= (range.end.line, range.end.character)
+                })
+        };
+        let diagnostics: Vec<Value> = lock(&self.diagnostics)
+            .get(&uri)
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter(overlaps)
+            .cloned()
+            .collect();
+        self.request(
+            RequestKind::CodeActions {
+                path: path.to_path_buf(),
+                line: start.line as usize,
+            },
+            "textDocument/codeAction",
+            json!({
+                "textDocument": { "uri": uri },
+                "range": {
+                    "start": { "line": start.line, "character": start.character },
+                    "end": { "line": end.line, "character": end.character },
+                },
+                "context": { "diagnostics": diagnostics, "triggerKind": 1 },
+            }),
+        );
+    }
+
+    fn column_map(&self) -> ColumnMap<'_> {
+        ColumnMap::new(|path| lock(&self.documents).get(&document_uri(path)).cloned())
+    }
+}
+
+/// The [`LspClient`] handed to each editor. All of them forward to the one
+/// process that serves their language and workspace.
+pub struct SharedClient {
+    shared: Arc<Shared>,
+}
+
+impl LspClient for SharedClient {
+    fn did_open(&mut self, document: &LspDocument, text: &str) {
+        lock(&self.shared.documents).insert(
+            document.uri.clone(),
+            ShadowDocument::new(&document.language_id, document.version, text),
+        );
+        self.shared.notify(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": {
+                    "uri": document.uri,
+                    "languageId": document.language_id,
+                    "version": document.version,
+                    "text": text,
+                }
+            }),
+        );
+    }
+
+    fn did_change(&mut self, document: &LspDocument, changes: &[LspTextChange]) {
+        let content_changes: Vec<Value> = {
+            let mut documents = lock(&self.shared.documents);
+            let Some(doc) = documents.get_mut(&document.uri) else {
+                return;
+            };
+            doc.version = document.version;
+            changes
+                .iter()
+                .map(|change| {
+                    let start = doc.utf16_position(change.range.start);
+                    let end = doc.utf16_position(change.range.end);
+                    doc.apply(change);
+                    json!({
+                        "range": {
+                            "start": { "line": start.line, "character": start.character },
+                            "end": { "line": end.line, "character": end.character },
+                        },
+                        "text": change.text,
+                    })
+                })
+                .collect()
+        };
+        if content_changes.is_empty() {
+            return;
+        }
+        self.shared.notify(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": document.uri, "version": document.version },
+                "contentChanges": content_changes,
+            }),
+        );
+    }
+
+    fn did_save(&mut self, document: &LspDocument, text: &str) {
+        self.shared.notify(
+            "textDocument/didSave",
+            json!({ "textDocument": { "uri": document.uri }, "text": text }),
+        );
+    }
+
+    fn did_close(&mut self, document: &LspDocument) {
+        lock(&self.shared.documents).remove(&document.uri);
+        self.shared.notify(
+            "textDocument/didClose",
+            json!({ "textDocument": { "uri": document.uri } }),
+        );
+    }
+
+    fn request_hover(&mut self, document: &LspDocument, position: LspPosition) {
+        self.shared.position_request(
+            RequestKind::Hover,
+            "textDocument/hover",
+            &document.uri,
+            position,
+            Value::Null,
+        );
+    }
+
+    fn request_completion(&mut self, document: &LspDocument, position: LspPosition) {
+        self.shared.position_request(
+            RequestKind::Completion,
+            "textDocument/completion",
+            &document.uri,
+            position,
+            json!({ "context": { "triggerKind": 1 } }),
+        );
+    }
+
+    fn request_definition(&mut self, document: &LspDocument, position: LspPosition) {
+        self.shared.position_request(
+            RequestKind::Locations(LocationKind::Definition),
+            LocationKind::Definition.method(),
+            &document.uri,
+            position,
+            Value::Null,
+        );
+    }
+}
+
+/// A language server process for one (server key, workspace root) pair.
+pub struct LspServer {
+    pub server_key: String,
+    pub root_uri: String,
+    /// The `lsp.lua` entry it was started from.
+    language: LanguageServer,
+    pub state: LspServerState,
+    /// The `capabilities` object from the `initialize` response.
+    pub capabilities: Value,
+    /// Unfinished work-done progress as (token, title, detail).
+    progress: Vec<(String, String, String)>,
+    shared: Arc<Shared>,
+    child: Option<Child>,
+    incoming: mpsc::Receiver<Incoming>,
+}
+
+impl LspServer {
+    pub fn start(language: LanguageServer, root_uri: &str) -> Result<Self, String> {
+        let (sender, _) = mpsc::channel();
+        let shared = Arc::new(Shared {
+            server_key: language.name.clone(),
+            outgoing: Mutex::new(Outgoing {
+                sender,
+                ready: false,
+                queued: Vec::new(),
+            }),
+            next_id: AtomicU64::new(1),
+            pending: Mutex::new(HashMap::new()),
+            documents: Mutex::new(HashMap::new()),
+            diagnostics: Mutex::new(HashMap::new()),
+            settings: Mutex::new(language.settings.clone().unwrap_or_default()),
+            traffic: Mutex::new(Traffic::default()),
+        });
+        let (child, incoming) = spawn(&shared, &language, root_uri)?;
+        Ok(Self {
+            server_key: language.name.clone(),
+            root_uri: root_uri.to_string(),
+            language,
+            state: LspServerState::Starting,
+            capabilities: Value::Null,
+            progress: Vec::new(),
+            shared,
+            child: Some(child),
+            incoming,
+        })
+    }
+
+    /// Replaces the process, reopening every document the old one had.
+    pub fn restart(&mut self) -> Result<(), String> {
+        self.stop();
+        self.progress.clear();
+        self.capabilities = Value::Null;
+        match spawn(&self.shared, &self.language, &self.root_uri) {
+            Ok((child, incoming)) => {
+                self.child = Some(child);
+                self.incoming = incoming;
+                self.state = LspServerState::Starting;
+                Ok(())
+            }
+            Err(err) => {
+                self.state = LspServerState::Crashed(err.clone());
+                Err(err)
+            }
+        }
+    }
+
+    /// Takes a changed `lsp.lua` entry for the next restart.
+    pub fn configure(&mut self, language: LanguageServer) {
+        *lock(&self.shared.settings) = language.settings.clone().unwrap_or_default();
+        self.language = language;
+    }
+
+    pub fn has_document(&self, path: &Path) -> bool {
+        lock(&self.shared.documents).contains_key(&document_uri(path))
+    }
+
+    pub fn client(&self) -> Box<dyn LspClient> {
+        Box::new(SharedClient {
+            shared: self.shared.clone(),
+        })
+    }
+
+    pub fn request_locations(&self, path: &Path, kind: LocationKind, position: (usize, usize)) {
+        let extra = match kind {
+            LocationKind::References => json!({ "context": { "includeDeclaration": true } }),
+            _ => Value::Null,
+        };
+        self.shared.position_request(
+            RequestKind::Locations(kind),
+            kind.method(),
+            &document_uri(path),
+            lsp_position(position),
+            extra,
+        );
+    }
+
+    pub fn rename(&self, path: &Path, position: (usize, usize), new_name: &str) {
+        self.shared.position_request(
+            RequestKind::Rename,
+            "textDocument/rename",
+            &document_uri(path),
+            lsp_position(position),
+            json!({ "newName": new_name }),
+        );
+    }
+
+    pub fn code_actions(&self, path: &Path, start: (usize, usize), end: (usize, usize)) {
+        self.shared.code_actions(path, start, end);
+    }
+
+    pub fn execute_command(&self, command: &Value) {
+        self.shared.request(
+            RequestKind::ExecuteCommand,
+            "workspace/executeCommand",
+            json!({
+                "command": command.get("command"),
+                "arguments": command.get("arguments").cloned().unwrap_or(json!([])),
+            }),
+        );
+    }
+
+    pub fn format(&self, path: &Path, tab_size: usize, insert_spaces: bool) {
+        self.shared.request(
+            RequestKind::Formatting(path.to_path_buf()),
+            "textDocument/formatting",
+            json!({
+                "textDocument": { "uri": document_uri(path) },
+                "options": { "tabSize": tab_size, "insertSpaces": insert_spaces },
+            }),
+        );
+    }
+
+    pub fn signature_help(
+        &self,
+        path: &Path,
+        position: (usize, usize),
+        trigger: Option<char>,
+        retrigger: bool,
+    ) {
+        // Trigger kinds: 1 invoked, 2 trigger character, 3 content change.
+        let context = match trigger {
+            Some(c) => {
+                json!({ "triggerKind": 2, "triggerCharacter": c.to_string(), "isRetrigger": retrigger })
+            }
+            None => {
+                json!({ "triggerKind": if retrigger { 3 } else { 1 }, "isRetrigger": retrigger })
+            }
+        };
+        self.shared.position_request(
+            RequestKind::SignatureHelp(path.to_path_buf()),
+            "textDocument/signatureHelp",
+            &document_uri(path),
+            lsp_position(position),
+            json!({ "context": context }),
+        );
+    }
+
+    pub fn inlay_hints(&self, path: &Path, line_count: usize) {
+        self.shared.request(
+            RequestKind::InlayHints(path.to_path_buf()),
+            "textDocument/inlayHint",
+            json!({
+                "textDocument": { "uri": document_uri(path) },
+                "range": {
+                    "start": { "line": 0, "character": 0 },
+                    "end": { "line": line_count, "character": 0 },
+                },
+            }),
+        );
+    }
+
+    pub fn document_symbols(&self, path: &Path) {
+        self.shared.request(
+            RequestKind::DocumentSymbols(path.to_path_buf()),
+            "textDocument/documentSymbol",
+            json!({ "textDocument": { "uri": document_uri(path) } }),
+        );
+    }
+
+    pub fn workspace_symbols(&self, query: &str) {
+        self.shared.request(
+            RequestKind::WorkspaceSymbols(query.to_string()),
+            "workspace/symbol",
+            json!({ "query": query }),
+        );
+    }
+
+    /// The most recent work-done progress that hasn't finished yet.
+    pub fn progress(&self) -> Option<String> {
+        self.progress
+            .last()
+            .map(|(_, title, detail)| format!("{title} {detail}").trim().to_string())
+    }
+
+    /// The process id, while it runs.
+    pub fn pid(&self) -> Option<u32> {
+        self.child.as_ref().map(Child::id)
+    }
+
+    /// The last lines the server wrote to stderr, oldest first.
+    pub fn stderr_tail(&self) -> Vec<String> {
+        lock(&self.shared.traffic).stderr()
+    }
+
+    /// The recorded JSON-RPC messages, oldest first.
+    pub fn traffic(&self) -> Vec<Arc<TrafficEntry>> {
+        lock(&self.shared.traffic).messages()
+    }
+
+    pub fn set_recording(&self, recording: bool) {
+        lock(&self.shared.traffic).set_recording(recording);
+    }
+
+    pub fn clear_traffic(&self) {
+        lock(&self.shared.traffic).clear();
+    }
+
+    /// Stops the process until the next restart, keeping its documents so
+    /// a restart reopens them.
+    pub fn shut_down(&mut self) {
+        self.stop();
+        self.progress.clear();
+        self.state = LspServerState::Stopped;
+    }
+
+    /// Collects what the server sent since the last call and tracks its
+    /// lifecycle.
+    pub fn poll(&mut self) -> Vec<LspEvent> {
+        let mut events = Vec::new();
+        while let Ok(incoming) = self.incoming.try_recv() {
+            match incoming {
+                Incoming::Event(event) => events.push(event),
+                Incoming::Initialized(capabilities) => {
+                    self.capabilities = capabilities;
+                    self.set_state(LspServerState::Ready, &mut events);
+                }
+                Incoming::Progress(update) => {
+                    let index = self.progress.iter().position(|(t, ..)| *t == update.token);
+                    let previous = index.map(|index| self.progress.remove(index));
+                    if !update.done {
+                        let title = update
+                            .title
+                            .or(previous.map(|(_, title, _)| title))
+                            .unwrap_or_default();
+                        self.progress.push((update.token, title, update.detail));
+                    }
+                }
+                Incoming::Failed(message) => {
+                    self.stop();
+                    self.set_state(LspServerState::Crashed(message), &mut events);
+                }
+                // A process we stopped ourselves isn't a crash.
+                Incoming::Exited if self.child.is_none() => {}
+                Incoming::Exited => {
+                    let status = self
+                        .child
+                        .take()
+                        .and_then(|mut child| child.wait().ok())
+                        .map_or_else(|| "exited".to_string(), |status| status.to_string());
+                    self.progress.clear();
+                    self.set_state(LspServerState::Crashed(status), &mut events);
+                }
+            }
+        }
+        events
+    }
+
+    fn set_state(&mut self, state: LspServerState, events: &mut Vec<LspEvent>) {
+        if self.state == state {
+            return;
+        }
+        self.state = state.clone();
+        events.push(LspEvent::State {
+            server_key: self.server_key.clone(),
+            state,
+        });
+    }
+
+    /// Asks the process to exit and kills it if it hasn't within
+    /// [`SHUTDOWN_TIMEOUT`]. The wait happens on a thread of its own.
+    fn stop(&mut self) {
+        let Some(mut child) = self.child.take() else {
+            return;
+        };
+        {
+            let mut outgoing = lock(&self.shared.outgoing);
+            outgoing.ready = false;
+            let shutdown =
+                json!({ "jsonrpc": "2.0", "id": 0, "method": "shutdown", "params": null });
+            let exit = json!({ "jsonrpc": "2.0", "method": "exit", "params": null });
+            let _ = outgoing.sender.send(self.shared.frame(&shutdown));
+            let _ = outgoing.sender.send(self.shared.frame(&exit));
+        }
+        thread::spawn(move || {
+            let started = Instant::now();
+            while started.elapsed() < SHUTDOWN_TIMEOUT {
+                if !matches!(child.try_wait(), Ok(None)) {
+                    return;
+                }
+                thread::sleep(Duration::from_millis(20));
+            }
+            let _ = child.kill();
+            let _ = child.wait();
+        });
+    }
+}
+
+impl Drop for LspServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Spawns the server process with its reader, writer and stderr threads and
+/// sends `initialize`. Documents already in `shared` are queued for reopening.
+fn spawn(
+    shared: &Arc<Shared>,
+    language: &LanguageServer,
+    root_uri: &str,
+) -> Result<(Child, mpsc::Receiver<Incoming>), String> {
+    let server_key = shared.server_key.clone();
+    let (program, args) = resolve_command(language)?;
+
+    let mut child = Command::new(&program)
+        .args(&args)
+        .current_dir(root_path(root_uri))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to start LSP server '{}': {}", server_key, e))?;
+    let (Some(mut stdin), Some(stdout), Some(stderr)) =
+        (child.stdin.take(), child.stdout.take(), child.stderr.take())
+    else {
+        let _ = child.kill();
+        return Err(format!("LSP server '{server_key}' has no stdio"));
+    };
+
+    let (writer, outbox) = mpsc::channel::<Vec<u8>>();
+    thread::spawn(move || {
+        for bytes in outbox {
+            if stdin.write_all(&bytes).and_then(|_| stdin.flush()).is_err() {
+                break;
+            }
+        }
+    });
+
+    let id = shared.next_id.fetch_add(1, Ordering::Relaxed);
+    {
+        let documents = lock(&shared.documents);
+        let mut outgoing = lock(&shared.outgoing);
+        outgoing.sender = writer;
+        outgoing.ready = false;
+        outgoing.queued = documents
+            .iter()
+            .map(|(uri, doc)| {
+                shared.frame(&json!({
+                    "jsonrpc": "2.0",
+                    "method": "textDocument/didOpen",
+                    "params": {
+                        "textDocument": {
+                            "uri": uri,
+                            "languageId": doc.language_id,
+                            "version": doc.version,
+                            "text": doc.text(),
+                        }
+                    }
+                }))
+            })
+            .collect();
+        let mut pending = lock(&shared.pending);
+        pending.clear();
+        pending.insert(id, RequestKind::Initialize);
+        let initialize = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "initialize",
+            "params": initialize_params(root_uri, language.initialization_options.as_ref()),
+        });
+        let _ = outgoing.sender.send(shared.frame(&initialize));
+    }
+
+    let (events, incoming) = mpsc::channel();
+    let stderr_events = events.clone();
+    let stderr_key = server_key.clone();
+    let stderr_shared = shared.clone();
+    thread::spawn(move || {
+        for line in BufReader::new(stderr).lines() {
+            let Ok(line) = line else { break };
+            let line = line.trim();
+            if !line.is_empty() {
+                lock(&stderr_shared.traffic).push_stderr(line);
+                let _ = stderr_events.send(Incoming::Event(LspEvent::Log {
+                    server_key: stderr_key.clone(),
+                    message: line.to_string(),
+                }));
+            }
+        }
+    });
+
+    let shared = shared.clone();
+    thread::spawn(move || {
+        let mut reader = BufReader::new(stdout);
+        while let Some(message) = read_message(&mut reader) {
+            lock(&shared.traffic).record(false, &message, Instant::now());
+            let Some(incoming) = dispatch(&shared, message) else {
+                continue;
+            };
+            if events.send(incoming).is_err() {
+                return;
+            }
+        }
+        let _ = events.send(Incoming::Exited);
+    });
+
+    Ok((child, incoming))
+}
+
+/// The program and arguments to run. The built-in commands keep the editor
+/// crate's lookup, which also honours variables such as `RUST_ANALYZER` and
+/// finds rustup's and Go's install folders; other commands run as written.
+fn resolve_command(language: &LanguageServer) -> Result<(String, Vec<String>), String> {
+    if language.name == "rust-analyzer" {
+        iced_code_editor::ensure_rust_analyzer_config();
+    }
+    if let Some(config) = iced_code_editor::lsp_server_config(&language.name)
+        .filter(|config| config.default_command.iter().eq(language.command.iter()))
+    {
+        let command = iced_code_editor::resolve_lsp_command(config)?;
+        return Ok((command.program, command.args));
+    }
+    let (program, args) = language
+        .command
+        .split_first()
+        .ok_or_else(|| format!("{} has no command", language.name))?;
+    Ok((program.clone(), args.to_vec()))
+}
+
+/// The folder a `file://` root uri names, for the server's working
+/// directory.
+fn root_path(root_uri: &str) -> PathBuf {
+    PathBuf::from(root_uri.strip_prefix("file://").unwrap_or(root_uri))
+}
+
+fn initialize_params(root_uri: &str, initialization_options: Option<&Value>) -> Value {
+    let name = root_uri
+        .rsplit('/')
+        .find(|part| !part.is_empty())
+        .unwrap_or("workspace");
+    json!({
+        "processId": std::process::id(),
+        "rootUri": root_uri,
+        "initializationOptions": initialization_options,
+        "workspaceFolders": [{ "uri": root_uri, "name": name }],
+        "capabilities": {
+            "general": { "positionEncodings": ["utf-16"] },
+            "textDocument": {
+                "synchronization": { "didSave": true },
+                "hover": { "contentFormat": ["markdown", "plaintext"] },
+                "completion": { "completionItem": { "snippetSupport": false } },
+                "definition": { "linkSupport": true },
+                "typeDefinition": { "linkSupport": true },
+                "implementation": { "linkSupport": true },
+                "references": {},
+                "rename": { "prepareSupport": false },
+                "codeAction": {
+                    "codeActionLiteralSupport": {
+                        "codeActionKind": {
+                            "valueSet": [
+                                "",
+                                "quickfix",
+                                "refactor",
+                                "refactor.extract",
+                                "refactor.inline",
+                                "refactor.rewrite",
+                                "source",
+                                "source.organizeImports",
+                            ]
+                        }
+                    },
+                    "isPreferredSupport": true,
+                    "disabledSupport": true,
+                },
+                "formatting": {},
+                "documentSymbol": { "hierarchicalDocumentSymbolSupport": true },
+                "signatureHelp": {
+                    "signatureInformation": {
+                        "documentationFormat": ["plaintext", "markdown"],
+                        "parameterInformation": { "labelOffsetSupport": true },
+                        "activeParameterSupport": true,
+                    },
+                    "contextSupport": true,
+                },
+                "inlayHint": {},
+                "publishDiagnostics": { "relatedInformation": false },
+            },
+            "window": { "workDoneProgress": true },
+            "workspace": {
+                "workspaceFolders": true,
+                "configuration": true,
+                "applyEdit": true,
+                "workspaceEdit": { "documentChanges": true },
+                "executeCommand": {},
+                "symbol": {},
+                "inlayHint": { "refreshSupport": true },
+            },
+        },
+    })
+}
+
+/// Answers `workspace/configuration` from the `lsp.lua` settings: each item's
+/// dotted `section` is looked up in them, and an item without one gets them
+/// all.
+fn configuration(settings: &Value, params: &Value) -> Value {
+    let items = params
+        .get("items")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    items
+        .iter()
+        .map(|item| match item.get("section").and_then(Value::as_str) {
+            Some(section) => section
+                .split('.')
+                .try_fold(settings, |value, key| value.get(key))
+                .cloned()
+                .unwrap_or(Value::Null),
+            None => settings.clone(),
+        })
+        .collect()
+}
+
+/// Reads one `Content-Length` framed message, or `None` once the stream ends.
+fn read_message(reader: &mut impl BufRead) -> Option<Value> {
+    loop {
+        let mut length = None;
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line).ok()? == 0 {
+                return None;
+            }
+            let line = line.trim();
+            if line.is_empty() {
+                break;
+            }
+            if let Some(value) = line.strip_prefix("Content-Length:") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+        let Some(length) = length else { continue };
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).ok()?;
+        if let Ok(value) = serde_json::from_slice(&body) {
+            return Some(value);
+        }
+    }
+}
+
+/// Handles one message on the reader thread. Server requests are answered
+/// here so a busy UI never stalls the server.
+fn dispatch(shared: &Shared, mut message: Value) -> Option<Incoming> {
+    let log = |message: String| {
+        Some(Incoming::Event(LspEvent::Log {
+            server_key: shared.server_key.clone(),
+            message,
+        }))
+    };
+    let method = message
+        .get("method")
+        .and_then(Value::as_str)
+        .map(String::from);
+    let mut params = message
+        .get_mut("params")
+        .map(Value::take)
+        .unwrap_or(Value::Null);
+
+    match (message.get("id").cloned(), method) {
+        (Some(id), Some(method)) => {
+            let (result, incoming) = match method.as_str() {
+                "workspace/configuration" => {
+                    (configuration(&lock(&shared.settings), &params), None)
+                }
+                // Applied on the UI thread; the server is told it was so it
+                // isn't left waiting.
+                "workspace/applyEdit" => {
+                    let change = params
+                        .get_mut("edit")
+                        .map(Value::take)
+                        .and_then(|edit| serde_json::from_value(edit).ok())
+                        .map(|edit| workspace_change(edit, &mut shared.column_map()));
+                    (
+                        json!({ "applied": change.is_some() }),
+                        change.map(|change| Incoming::Event(LspEvent::ApplyEdit(change))),
+                    )
+                }
+                "workspace/inlayHint/refresh" => (
+                    Value::Null,
+                    Some(Incoming::Event(LspEvent::InlayHintsRefresh)),
+                ),
+                _ => (Value::Null, None),
+            };
+            shared.respond(id, result);
+            incoming
+        }
+        (Some(id), None) => {
+            let kind = id
+                .as_u64()
+                .and_then(|id| lock(&shared.pending).remove(&id))?;
+            if let Some(error) = message.get("error") {
+                let error = error
+                    .get("message")
+                    .and_then(Value::as_str)
+                    .unwrap_or("unknown error")
+                    .to_string();
+                return match kind {
+                    RequestKind::Initialize => Some(Incoming::Failed(error)),
+                    RequestKind::Locations(_)
+                    | RequestKind::Rename
+                    | RequestKind::ExecuteCommand => Some(Incoming::Event(LspEvent::Failed(error))),
+                    RequestKind::Formatting(path) => Some(Incoming::Event(LspEvent::Formatted {
+                        path,
+                        edits: Err(error),
+                    })),
+                    _ => log(format!("{kind:?} request failed: {error}")),
+                };
+            }
+            let result = message
+                .get_mut("result")
+                .map(Value::take)
+                .unwrap_or(Value::Null);
+            let event = match kind {
+                RequestKind::Initialize => {
+                    shared.mark_ready();
+                    let capabilities = result.get("capabilities").cloned().unwrap_or_default();
+                    return Some(Incoming::Initialized(capabilities));
+                }
+                RequestKind::Hover => LspEvent::Hover {
+                    text: hover_text(&result),
+                },
+                RequestKind::Completion => LspEvent::Completion {
+                    items: completion_labels(&result),
+                },
+                RequestKind::Locations(kind) => LspEvent::Locations {
+                    kind,
+                    locations: locations(result, &mut shared.column_map()),
+                },
+                RequestKind::Rename => {
+                    match serde_json::from_value::<Option<lsp_types::WorkspaceEdit>>(result) {
+                        Ok(Some(edit)) => {
+                            LspEvent::Rename(workspace_change(edit, &mut shared.column_map()))
+                        }
+                        _ => LspEvent::Failed("Nothing to rename here".to_string()),
+                    }
+                }
+                RequestKind::CodeActions { path, line } => LspEvent::CodeActions {
+                    path,
+                    line,
+                    actions: code_actions(result, &mut shared.column_map()),
+                },
+                RequestKind::ExecuteCommand => return None,
+                RequestKind::DocumentSymbols(path) => LspEvent::DocumentSymbols {
+                    symbols: document_symbols(result, &path, &mut shared.column_map()),
+                    path,
+                },
+                RequestKind::WorkspaceSymbols(query) => LspEvent::WorkspaceSymbols {
+                    query,
+                    symbols: workspace_symbols(result, &mut shared.column_map()),
+                },
+                RequestKind::Formatting(path) => LspEvent::Formatted {
+                    edits: Ok(text_edits(result, &path, &mut shared.column_map())),
+                    path,
+                },
+                RequestKind::SignatureHelp(path) => LspEvent::SignatureHelp {
+                    path,
+                    help: signature_help(result),
+                },
+                RequestKind::InlayHints(path) => LspEvent::InlayHints {
+                    hints: inlay_hints(result, &path, &mut shared.column_map()),
+                    path,
+                },
+            };
+            Some(Incoming::Event(event))
+        }
+        (None, Some(method)) => match method.as_str() {
+            "$/progress" => Some(Incoming::Progress(progress_update(&params))),
+            "textDocument/publishDiagnostics" => {
+                let raw = params.get("diagnostics").cloned().unwrap_or_default();
+                let params = serde_json::from_value(params).ok()?;
+                let update = {
+                    let documents = lock(&shared.documents);
+                    diagnostic_update(params, |uri| documents.get(uri))?
+                };
+                lock(&shared.diagnostics).insert(document_uri(&update.path), raw);
+                Some(Incoming::Event(LspEvent::Diagnostics(update)))
+            }
+            "window/logMessage" | "window/showMessage" => {
+                let text = params.get("message").and_then(Value::as_str)?;
+                log(text.to_string())
+            }
+            _ => None,
+        },
+        (None, None) => None,
+    }
+}
+
+fn hover_text(result: &Value) -> String {
+    fn contents(value: &Value) -> String {
+        match value {
+            Value::String(text) => text.clone(),
+            Value::Array(items) => items
+                .iter()
+                .map(contents)
+                .filter(|text| !text.is_empty())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Value::Object(map) => map
+                .get("value")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            _ => String::new(),
+        }
+    }
+    result.get("contents").map(contents).unwrap_or_default()
+}
+
+fn completion_labels(result: &Value) -> Vec<String> {
+    let items = result
+        .as_array()
+        .or_else(|| result.get("items").and_then(Value::as_array));
+    items
+        .into_iter()
+        .flatten()
+        .filter_map(|item| item.get("label").and_then(Value::as_str))
+        .map(String::from)
+        .collect()
+}
+
+fn uri_path(uri: &lsp_types::Uri) -> Option<PathBuf> {
+    url::Url::parse(uri.as_str()).ok()?.to_file_path().ok()
+}
+
+/// Definition, implementation and reference results, which share a shape.
+fn locations(result: Value, columns: &mut ColumnMap) -> Vec<LspLocation> {
+    use lsp_types::GotoDefinitionResponse;
+    let found: Vec<(lsp_types::Uri, lsp_types::Range)> =
+        match serde_json::from_value::<Option<GotoDefinitionResponse>>(result) {
+            Ok(Some(GotoDefinitionResponse::Scalar(location))) => {
+                vec![(location.uri, location.range)]
+            }
+            Ok(Some(GotoDefinitionResponse::Array(locations))) => locations
+                .into_iter()
+                .map(|location| (location.uri, location.range))
+                .collect(),
+            Ok(Some(GotoDefinitionResponse::Link(links))) => links
+                .into_iter()
+                .map(|link| (link.target_uri, link.target_selection_range))
+                .collect(),
+            _ => Vec::new(),
+        };
+    found
+        .into_iter()
+        .filter_map(|(uri, range)| {
+            let path = uri_path(&uri)?;
+            Some(LspLocation {
+                start: columns.position(&path, range.start),
+                end: columns.position(&path, range.end),
+                path,
+            })
+        })
+        .collect()
+}
+
+/// Collects the text edits of a `WorkspaceEdit` per file. `documentChanges`
+/// wins over `changes` when a server sends both, as the spec asks.
+fn workspace_change(edit: lsp_types::WorkspaceEdit, columns: &mut ColumnMap) -> WorkspaceChange {
+    use lsp_types::{DocumentChangeOperation, DocumentChanges, OneOf};
+    let mut change = WorkspaceChange::default();
+    let document_edit = |edit: lsp_types::TextDocumentEdit| {
+        let edits = edit
+            .edits
+            .into_iter()
+            .map(|edit| match edit {
+                OneOf::Left(edit) => edit,
+                OneOf::Right(annotated) => annotated.text_edit,
+            })
+            .collect::<Vec<_>>();
+        (edit.text_document.uri, edits)
+    };
+    let edits: Vec<(lsp_types::Uri, Vec<lsp_types::TextEdit>)> = match edit.document_changes {
+        Some(DocumentChanges::Edits(edits)) => edits.into_iter().map(document_edit).collect(),
+        Some(DocumentChanges::Operations(operations)) => operations
+            .into_iter()
+            .filter_map(|operation| match operation {
+                DocumentChangeOperation::Edit(edit) => Some(document_edit(edit)),
+                DocumentChangeOperation::Op(_) => {
+                    change.skipped_operations += 1;
+                    None
+                }
+            })
+            .collect(),
+        None => edit.changes.into_iter().flatten().collect(),
+    };
+
+    for (uri, edits) in edits {
+        let Some(path) = uri_path(&uri) else { continue };
+        let edits: Vec<TextEdit> = edits
+            .into_iter()
+            .map(|edit| TextEdit {
+                start: columns.position(&path, edit.range.start),
+                end: columns.position(&path, edit.range.end),
+                text: edit.new_text,
+            })
+            .collect();
+        match change.files.iter_mut().find(|file| file.path == path) {
+            Some(file) => file.edits.extend(edits),
+            None => change.files.push(FileChange { path, edits }),
+        }
+    }
+    change.files.sort_by(|a, b| a.path.cmp(&b.path));
+    change
+}
+
+/// The edits of a `textDocument/formatting` answer; `null` means none.
+fn text_edits(result: Value, path: &Path, columns: &mut ColumnMap) -> Vec<TextEdit> {
+    serde_json::from_value::<Option<Vec<lsp_types::TextEdit>>>(result)
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|edit| TextEdit {
+            start: columns.position(path, edit.range.start),
+            end: columns.position(path, edit.range.end),
+            text: edit.new_text,
+        })
+        .collect()
+}
+
+/// Flattens a `textDocument/documentSymbol` answer in document order.
+/// Flat answers are nested by which ranges contain which.
+fn document_symbols(result: Value, path: &Path, columns: &mut ColumnMap) -> Vec<Symbol> {
+    use lsp_types::DocumentSymbolResponse;
+    fn push_nested(
+        items: Vec<lsp_types::DocumentSymbol>,
+        depth: usize,
+        path: &Path,
+        columns: &mut ColumnMap,
+        out: &mut Vec<Symbol>,
+    ) {
+        for item in items {
+            out.push(Symbol {
+                name: item.name,
+                kind: item.kind,
+                detail: item.detail.filter(|detail| !detail.is_empty()),
+                path: path.to_path_buf(),
+                start: columns.position(path, item.selection_range.start),
+                end: columns.position(path, item.range.end),
+                depth,
+            });
+            if let Some(children) = item.children {
+                push_nested(children, depth + 1, path, columns, out);
+            }
+        }
+    }
+
+    let mut symbols = Vec::new();
+    match serde_json::from_value::<Option<DocumentSymbolResponse>>(result) {
+        Ok(Some(DocumentSymbolResponse::Nested(items))) => {
+            push_nested(items, 0, path, columns, &mut symbols);
+            symbols.sort_by_key(|symbol| symbol.start);
+        }
+        Ok(Some(DocumentSymbolResponse::Flat(items))) => {
+            let mut items: Vec<_> = items
+                .into_iter()
+                .map(|item| {
+                    let range = item.location.range;
+                    let symbol = Symbol {
+                        name: item.name,
+                        kind: item.kind,
+                        detail: item.container_name,
+                        path: path.to_path_buf(),
+                        start: columns.position(path, range.start),
+                        end: columns.position(path, range.end),
+                        depth: 0,
+                    };
+                    (range, symbol)
+                })
+                .collect();
+            let key = |p: lsp_types::Position| (p.line, p.character);
+            items.sort_by_key(|(range, _)| (key(range.start), std::cmp::Reverse(key(range.end))));
+            let mut enclosing: Vec<lsp_types::Position> = Vec::new();
+            for (range, mut symbol) in items {
+                while enclosing
+                    .last()
+                    .is_some_and(|end| key(*end) <= key(range.start))
+                {
+                    enclosing.pop();
+                }
+                symbol.depth = enclosing.len();
+                enclosing.push(range.end);
+                symbols.push(symbol);
+            }
+        }
+        _ => {}
+    }
+    symbols
+}
+
+/// The active signature of a `textDocument/signatureHelp` answer, with the
+/// active parameter located in its label.
+fn signature_help(result: Value) -> Option<SignatureHelp> {
+    use lsp_types::{Documentation, ParameterLabel};
+    let help = serde_json::from_value::<Option<lsp_types::SignatureHelp>>(result).ok()??;
+    let count = help.signatures.len();
+    let index = (help.active_signature.unwrap_or(0) as usize).min(count.checked_sub(1)?);
+    let signature = help.signatures.into_iter().nth(index)?;
+    let active = signature
+        .active_parameter
+        .or(help.active_parameter)
+        .unwrap_or(0) as usize;
+    let label = signature.label;
+    let active_parameter = signature
+        .parameters
+        .and_then(|parameters| parameters.into_iter().nth(active))
+        .and_then(|parameter| match parameter.label {
+            // UTF-16 offsets into the label.
+            ParameterLabel::LabelOffsets([start, end]) => {
+                let column = |offset: u32| {
+                    let mut units = 0;
+                    label
+                        .chars()
+                        .take_while(|c| {
+                            units += c.len_utf16() as u32;
+                            units <= offset
+                        })
+                        .count()
+                };
+                Some(column(start)..column(end))
+            }
+            // Searched for after the opening parenthesis, so a parameter
+            // named like the function isn't found in its name.
+            ParameterLabel::Simple(name) => {
+                let from = label.find('(').unwrap_or(0);
+                let byte = label[from..].find(&name)? + from;
+                let start = label[..byte].chars().count();
+                Some(start..start + name.chars().count())
+            }
+        });
+    let documentation = signature
+        .documentation
+        .map(|documentation| match documentation {
+            Documentation::String(text) => text,
+            Documentation::MarkupContent(markup) => markup.value,
+        })
+        .map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty());
+    Some(SignatureHelp {
+        label,
+        active_parameter,
+        documentation,
+        overload: (index, count),
+    })
+}
+
+/// A `textDocument/inlayHint` answer, in document order.
+fn inlay_hints(result: Value, path: &Path, columns: &mut ColumnMap) -> Vec<InlayHint> {
+    use lsp_types::InlayHintLabel;
+    let mut hints: Vec<InlayHint> =
+        serde_json::from_value::<Option<Vec<lsp_types::InlayHint>>>(result)
+            .ok()
+            .flatten()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|hint| InlayHint {
+                position: columns.position(path, hint.position),
+                label: match hint.label {
+                    InlayHintLabel::String(label) => label,
+                    InlayHintLabel::LabelParts(parts) => {
+                        parts.into_iter().map(|part| part.value).collect()
+                    }
+                },
+                kind: hint.kind,
+            })
+            .collect();
+    hints.sort_by_key(|hint| hint.position);
+    hints
+}
+
+/// A `workspace/symbol` answer, with each symbol's container as its detail.
+/// Symbols given without a range point at the start of their file.
+fn workspace_symbols(result: Value, columns: &mut ColumnMap) -> Vec<Symbol> {
+    use lsp_types::{OneOf, WorkspaceSymbolResponse};
+    let mut symbol = |name: String,
+                      kind: lsp_types::SymbolKind,
+                      container: Option<String>,
+                      uri: lsp_types::Uri,
+                      range: Option<lsp_types::Range>| {
+        let path = uri_path(&uri)?;
+        let (start, end) = match range {
+            Some(range) => (
+                columns.position(&path, range.start),
+                columns.position(&path, range.end),
+            ),
+            None => ((0, 0), (0, 0)),
+        };
+        Some(Symbol {
+            name,
+            kind,
+            detail: container.filter(|container| !container.is_empty()),
+            path,
+            start,
+            end,
+            depth: 0,
+        })
+    };
+    match serde_json::from_value::<Option<WorkspaceSymbolResponse>>(result) {
+        Ok(Some(WorkspaceSymbolResponse::Flat(items))) => items
+            .into_iter()
+            .filter_map(|item| {
+                let location = item.location;
+                symbol(
+                    item.name,
+                    item.kind,
+                    item.container_name,
+                    location.uri,
+                    Some(location.range),
+                )
+            })
+            .collect(),
+        Ok(Some(WorkspaceSymbolResponse::Nested(items))) => items
+            .into_iter()
+            .filter_map(|item| {
+                let (uri, range) = match item.location {
+                    OneOf::Left(location) => (location.uri, Some(location.range)),
+                    OneOf::Right(location) => (location.uri, None),
+                };
+                symbol(item.name, item.kind, item.container_name, uri, range)
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Actions the server marked disabled are left out.
+fn code_actions(result: Value, columns: &mut ColumnMap) -> Vec<CodeAction> {
+    use lsp_types::CodeActionOrCommand;
+    let Ok(Some(items)) = serde_json::from_value::<Option<lsp_types::CodeActionResponse>>(result)
+    else {
+        return Vec::new();
+    };
+    items
+        .into_iter()
+        .filter_map(|item| match item {
+            CodeActionOrCommand::Command(command) => Some(CodeAction {
+                title: command.title.clone(),
+                kind: None,
+                preferred: false,
+                edit: None,
+                command: serde_json::to_value(command).ok(),
+            }),
+            CodeActionOrCommand::CodeAction(action) if action.disabled.is_none() => {
+                Some(CodeAction {
+                    title: action.title,
+                    kind: action.kind.map(|kind| kind.as_str().to_string()),
+                    preferred: action.is_preferred.unwrap_or(false),
+                    edit: action.edit.map(|edit| workspace_change(edit, columns)),
+                    command: action
+                        .command
+                        .and_then(|command| serde_json::to_value(command).ok()),
+                })
+            }
+            CodeActionOrCommand::CodeAction(_) => None,
+        })
+        .collect()
+}
+
+/// Converts published diagnostics to 1-based character positions, using the
+/// open document's text to undo the UTF-16 columns when there is one.
+fn diagnostic_update<'a>(
+    params: lsp_types::PublishDiagnosticsParams,
+    document: impl Fn(&str) -> Option<&'a ShadowDocument>,
+) -> Option<DiagnosticUpdate> {
+    let path = uri_path(&params.uri)?;
+    let document = document(&document_uri(&path));
+    let position = |position: lsp_types::Position| {
+        let position = LspPosition {
+            line: position.line,
+            character: position.character,
+        };
+        let position = document.map_or(position, |doc| doc.char_position(position));
+        (position.line as usize + 1, position.character as usize + 1)
+    };
+
+    let diagnostics = params
+        .diagnostics
+        .into_iter()
+        .map(|diagnostic| {
+            let (line, column) = position(diagnostic.range.start);
+            let (end_line, end_column) = position(diagnostic.range.end);
+            InlineDiagnostic {
+                line,
+                column,
+                end_line,
+                end_column,
+                severity: diagnostic
+                    .severity
+                    .unwrap_or(lsp_types::DiagnosticSeverity::ERROR),
+                message: diagnostic.message,
+                source: diagnostic.source,
+                code: diagnostic.code.map(|code| match code {
+                    lsp_types::NumberOrString::Number(code) => code.to_string(),
+                    lsp_types::NumberOrString::String(code) => code,
+                }),
+            }
+        })
+        .collect();
+    Some(DiagnosticUpdate { path, diagnostics })
+}
+
+fn progress_update(params: &Value) -> ProgressUpdate {
+    let token = match params.get("token") {
+        Some(Value::String(token)) => token.clone(),
+        Some(token) => token.to_string(),
+        None => String::new(),
+    };
+    let value = params.get("value").unwrap_or(&Value::Null);
+    let text = |key| value.get(key).and_then(Value::as_str).map(String::from);
+
+    let mut detail = text("message").unwrap_or_default();
+    if let Some(percentage) = value.get("percentage").and_then(Value::as_u64) {
+        detail = format!("{detail} {percentage}%").trim().to_string();
+    }
+    ProgressUpdate {
+        token,
+        title: text("title"),
+        detail,
+        done: value.get("kind").and_then(Value::as_str) == Some("end"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_framed_messages() {
+        let body = r#"{"jsonrpc":"2.0","method":"x"}"#;
+        let input = format!(
+            "Content-Length: {}\r\nContent-Type: utf-8\r\n\r\n{body}Content-Length: 2\r\n\r\n{{}}",
+            body.len()
+        );
+        let mut reader = BufReader::new(input.as_bytes());
+        assert_eq!(read_message(&mut reader).unwrap()["method"], "x");
+        assert_eq!(read_message(&mut reader).unwrap(), json!({}));
+        assert!(read_message(&mut reader).is_none());
+    }
+
+    #[test]
+    fn parses_responses() {
+        assert_eq!(
+            hover_text(&json!({ "contents": { "kind": "markdown", "value": "fn a()" } })),
+            "fn a()"
+        );
+        assert_eq!(
+            hover_text(&json!({ "contents": ["a", { "language": "rust", "value": "b" }] })),
+            "a\nb"
+        );
+        assert_eq!(hover_text(&Value::Null), "");
+
+        assert_eq!(
+            completion_labels(&json!([{ "label": "push" }])),
+            vec!["push"]
+        );
+        assert_eq!(
+            completion_labels(&json!({ "isIncomplete": false, "items": [{ "label": "len" }] })),
+            vec!["len"]
+        );
+
+        let mut columns = ColumnMap::new(|_| None);
+        let range =
+            json!({ "start": { "line": 3, "character": 4 }, "end": { "line": 3, "character": 8 } });
+        let found = locations(
+            json!([{ "uri": "file:///a%20b.rs", "range": range }]),
+            &mut columns,
+        );
+        assert_eq!(
+            found,
+            [LspLocation {
+                path: PathBuf::from("/a b.rs"),
+                start: (3, 4),
+                end: (3, 8),
+            }]
+        );
+        let link = json!([{
+            "targetUri": "file:///c.rs",
+            "targetRange": range,
+            "targetSelectionRange": range,
+        }]);
+        assert_eq!(
+            locations(link, &mut columns)[0].path,
+            PathBuf::from("/c.rs")
+        );
+        assert!(locations(Value::Null, &mut columns).is_empty());
+    }
+
+    fn open_document(path: &Path) -> Option<ShadowDocument> {
+        (path == Path::new("/src/lib.rs")).then(|| ShadowDocument::new("rust", 1, "let 🦀 = x;"))
+    }
+
+    #[test]
+    fn workspace_edits_use_document_columns() {
+        let edit = |line, from, to, text| {
+            json!({
+                "range": {
+                    "start": { "line": line, "character": from },
+                    "end": { "line": line, "character": to },
+                },
+                "newText": text,
+            })
+        };
+        let document_changes = json!({
+            "documentChanges": [
+                { "textDocument": { "uri": "file:///src/lib.rs", "version": 1 }, "edits": [edit(0, 9, 10, "y")] },
+                { "kind": "rename", "oldUri": "file:///src/a.rs", "newUri": "file:///src/b.rs" },
+                { "textDocument": { "uri": "file:///src/main.rs", "version": null }, "edits": [edit(2, 0, 1, "z")] },
+            ],
+            "changes": { "file:///src/ignored.rs": [edit(0, 0, 0, "x")] },
+        });
+        let change = workspace_change(
+            serde_json::from_value(document_changes).unwrap(),
+            &mut ColumnMap::new(open_document),
+        );
+        assert_eq!(change.skipped_operations, 1);
+        assert_eq!(change.edit_count(), 2);
+        assert_eq!(change.files[0].path, PathBuf::from("/src/lib.rs"));
+        assert_eq!(
+            change.files[0].edits,
+            [TextEdit {
+                start: (0, 8),
+                end: (0, 9),
+                text: "y".to_string(),
+            }]
+        );
+        assert_eq!(change.files[1].path, PathBuf::from("/src/main.rs"));
+
+        let changes = json!({ "changes": { "file:///src/lib.rs": [edit(0, 4, 6, "crab")] } });
+        let change = workspace_change(
+            serde_json::from_value(changes).unwrap(),
+            &mut ColumnMap::new(open_document),
+        );
+        assert_eq!(change.files[0].edits[0].end, (0, 5));
+
+        let mut columns = ColumnMap::new(open_document);
+        let formatted = text_edits(
+            json!([edit(0, 6, 6, " ")]),
+            Path::new("/src/lib.rs"),
+            &mut columns,
+        );
+        assert_eq!(formatted[0].start, (0, 5));
+        assert!(text_edits(Value::Null, Path::new("/src/lib.rs"), &mut columns).is_empty());
+    }
+
+    #[test]
+    fn parses_code_actions() {
+        let result = json!([
+            { "title": "Run test", "command": "rust-analyzer.runSingle", "arguments": [1] },
+            {
+                "title": "Import `HashMap`",
+                "kind": "quickfix",
+                "isPreferred": true,
+                "edit": { "changes": { "file:///src/lib.rs": [] } },
+            },
+            { "title": "Extract into function", "kind": "refactor.extract", "disabled": { "reason": "no selection" } },
+        ]);
+        let actions = code_actions(result, &mut ColumnMap::new(|_| None));
+        assert_eq!(actions.len(), 2);
+        assert_eq!(actions[0].title, "Run test");
+        assert_eq!(
+            actions[0].command.as_ref().unwrap()["command"],
+            "rust-analyzer.runSingle"
+        );
+        assert_eq!(actions[1].kind.as_deref(), Some("quickfix"));
+        assert!(actions[1].preferred);
+        assert_eq!(
+            actions[1].edit.as_ref().unwrap().files[0].path,
+            PathBuf::from("/src/lib.rs")
+        );
+        assert!(code_actions(Value::Null, &mut ColumnMap::new(|_| None)).is_empty());
+    }
+
+    #[test]
+    fn parses_symbols() {
+        let range = |line, from, to| json!({ "start": { "line": line, "character": from }, "end": { "line": line, "character": to } });
+        let nested = json!([{
+            "name": "Crab", "kind": 23, "range": range(0, 0, 11), "selectionRange": range(0, 4, 10),
+            "children": [{ "name": "x", "kind": 8, "detail": "", "range": range(0, 9, 10), "selectionRange": range(0, 9, 10) }],
+        }]);
+        let mut columns = ColumnMap::new(open_document);
+        let path = Path::new("/src/lib.rs");
+        let symbols = document_symbols(nested, path, &mut columns);
+        assert_eq!(symbols.len(), 2);
+        assert_eq!((symbols[0].start, symbols[0].end), ((0, 4), (0, 10)));
+        assert_eq!((symbols[1].name.as_str(), symbols[1].depth), ("x", 1));
+        assert_eq!(symbols[1].detail, None);
+
+        let location =
+            |from, to| json!({ "uri": "file:///src/lib.rs", "range": range(0, from, to) });
+        let flat = json!([
+            { "name": "x", "kind": 8, "location": location(9, 10), "containerName": "Crab" },
+            { "name": "Crab", "kind": 23, "location": location(0, 11) },
+        ]);
+        let symbols = document_symbols(flat.clone(), path, &mut columns);
+        assert_eq!(symbols[0].name, "Crab");
+        assert_eq!((symbols[1].start, symbols[1].depth), ((0, 8), 1));
+
+        let symbols = workspace_symbols(flat, &mut columns);
+        assert_eq!(symbols[0].detail.as_deref(), Some("Crab"));
+        assert_eq!(symbols[1].path, PathBuf::from("/src/lib.rs"));
+        assert!(workspace_symbols(Value::Null, &mut columns).is_empty());
+    }
+
+    #[test]
+    fn parses_signature_help() {
+        let help = signature_help(json!({
+            "signatures": [
+                { "label": "fn f(a: u8)", "parameters": [{ "label": "a: u8" }] },
+                {
+                    "label": "fn f(🦀: u8, b: u8)",
+                    "documentation": { "kind": "markdown", "value": "Adds.\n" },
+                    "parameters": [{ "label": [5, 11] }, { "label": [13, 18] }],
+                },
+            ],
+            "activeSignature": 1,
+            "activeParameter": 1,
+        }))
+        .unwrap();
+        assert_eq!(help.active_parameter, Some(12..17));
+        assert_eq!(help.documentation.as_deref(), Some("Adds."));
+        assert_eq!(help.overload, (1, 2));
+
+        let help = signature_help(json!({
+            "signatures": [{ "label": "fn a(a: u8)", "parameters": [{ "label": "a: u8" }] }],
+        }))
+        .unwrap();
+        assert_eq!(help.active_parameter, Some(5..10));
+        assert!(signature_help(json!({ "signatures": [] })).is_none());
+        assert!(signature_help(Value::Null).is_none());
+    }
+
+    #[test]
+    fn parses_inlay_hints() {
+        let mut columns = ColumnMap::new(open_document);
+        let path = Path::new("/src/lib.rs");
+        let hints = inlay_hints(
+            json!([
+                { "position": { "line": 0, "character": 8 }, "label": ": u8", "kind": 1 },
+                { "position": { "line": 0, "character": 6 }, "label": [{ "value": ": " }, { "value": "Crab" }], "kind": 1 },
+            ]),
+            path,
+            &mut columns,
+        );
+        assert_eq!(hints[0].position, (0, 5));
+        assert_eq!(hints[0].label, ": Crab");
+        assert_eq!(hints[1].position, (0, 7));
+        assert!(inlay_hints(Value::Null, path, &mut columns).is_empty());
+    }
+
+    #[test]
+    fn answers_configuration_from_settings() {
+        let settings = json!({ "python": { "analysis": { "typeCheckingMode": "strict" } } });
+        let params = json!({ "items": [
+            { "section": "python.analysis" },
+            { "section": "python.missing" },
+            { "scopeUri": "file:///src" },
+        ] });
+        assert_eq!(
+            configuration(&settings, &params),
+            json!([{ "typeCheckingMode": "strict" }, null, settings])
+        );
+        assert_eq!(configuration(&Value::Null, &json!({})), json!([]));
+    }
+
+    #[test]
+    fn diagnostics_use_document_columns() {
+        let params = serde_json::from_value(json!({
+            "uri": "file:///tmp/my%20crate/lib.rs",
+            "diagnostics": [{
+                "range": { "start": { "line": 0, "character": 3 }, "end": { "line": 0, "character": 5 } },
+                "severity": 2,
+                "source": "rustc",
+                "code": "unused_variables",
+                "message": "unused variable",
+            }, {
+                "range": { "start": { "line": 1, "character": 0 }, "end": { "line": 1, "character": 1 } },
+                "code": 308,
+                "message": "mismatched types",
+            }],
+        }))
+        .unwrap();
+        let doc = ShadowDocument::new("rust", 1, "🦀 x\ny");
+        let update = diagnostic_update(params, |uri| {
+            (uri == "file:///tmp/my crate/lib.rs").then_some(&doc)
+        })
+        .unwrap();
+
+        assert_eq!(
+            update.path,
+            std::path::PathBuf::from("/tmp/my crate/lib.rs")
+        );
+        let warning = &update.diagnostics[0];
+        assert_eq!((warning.line, warning.column), (1, 3));
+        assert_eq!((warning.end_line, warning.end_column), (1, 4));
+        assert_eq!(warning.severity, lsp_types::DiagnosticSeverity::WARNING);
+        assert_eq!(warning.source.as_deref(), Some("rustc"));
+        assert_eq!(warning.code.as_deref(), Some("unused_variables"));
+
+        let error = &update.diagnostics[1];
+        assert_eq!((error.line, error.column), (2, 1));
+        assert_eq!(error.severity, lsp_types::DiagnosticSeverity::ERROR);
+        assert_eq!(error.code.as_deref(), Some("308"));
+    }
+
+    #[test]
+    fn progress_updates() {
+        let report = json!({ "token": 7, "value": { "kind": "report", "message": "12/30", "percentage": 40 } });
+        assert_eq!(
+            progress_update(&report),
+            ProgressUpdate {
+                token: "7".to_string(),
+                title: None,
+                detail: "12/30 40%".to_string(),
+                done: false,
+            }
+        );
+
+        let end = json!({ "token": "rustAnalyzer/Indexing", "value": { "kind": "end" } });
+        assert!(progress_update(&end).done);
+    }
+}
@@ -0,0 +1,191 @@
+//! What passed between the editor and one server: the JSON-RPC messages,
+//! recorded while the Language Servers panel asks for them, and the tail of
+//! the server's stderr, kept always.
+
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Messages kept per server; older ones are dropped first.
+const MESSAGE_LIMIT: usize = 500;
+/// Lines of stderr kept per server.
+const STDERR_LIMIT: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageKind {
+    Request,
+    Response,
+    Notification,
+}
+
+impl MessageKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            MessageKind::Request => "request",
+            MessageKind::Response => "response",
+            MessageKind::Notification => "notification",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TrafficEntry {
+    pub at: Instant,
+    /// Sent by the editor rather than the server.
+    pub outgoing: bool,
+    pub kind: MessageKind,
+    /// For responses, the method of the request they answer.
+    pub method: String,
+    pub id: Option<String>,
+    /// For responses, how long the request waited for them.
+    pub duration: Option<Duration>,
+    /// A response carrying an `error`.
+    pub failed: bool,
+    pub message: Value,
+}
+
+impl TrafficEntry {
+    /// Whether the method contains `filter`, ignoring case.
+    pub fn matches(&self, filter: &str) -> bool {
+        self.method
+            .to_lowercase()
+            .contains(&filter.trim().to_lowercase())
+    }
+}
+
+#[derive(Default)]
+pub struct Traffic {
+    pub recording: bool,
+    messages: VecDeque<Arc<TrafficEntry>>,
+    /// Requests not answered yet, by direction and id, with their method
+    /// and when they were sent.
+    open: HashMap<(bool, String), (String, Instant)>,
+    stderr: VecDeque<String>,
+}
+
+impl Traffic {
+    /// Notes a message going to (`outgoing`) or coming from the server.
+    pub fn record(&mut self, outgoing: bool, message: &Value, at: Instant) {
+        if !self.recording {
+            return;
+        }
+        let id = message.get("id").map(|id| match id {
+            Value::String(id) => id.clone(),
+            id => id.to_string(),
+        });
+        let method = message.get("method").and_then(Value::as_str);
+        let (kind, method, duration) = match (method, &id) {
+            (Some(method), Some(id)) => {
+                self.open
+                    .insert((outgoing, id.clone()), (method.to_string(), at));
+                (MessageKind::Request, method.to_string(), None)
+            }
+            (Some(method), None) => (MessageKind::Notification, method.to_string(), None),
+            (None, id) => {
+                let request = id
+                    .as_ref()
+                    .and_then(|id| self.open.remove(&(!outgoing, id.clone())));
+                let (method, duration) = match request {
+                    Some((method, sent)) => (method, Some(at.saturating_duration_since(sent))),
+                    None => (String::new(), None),
+                };
+                (MessageKind::Response, method, duration)
+            }
+        };
+        if self.messages.len() == MESSAGE_LIMIT {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(Arc::new(TrafficEntry {
+            at,
+            outgoing,
+            kind,
+            method,
+            id,
+            duration,
+            failed: message.get("error").is_some(),
+            message: message.clone(),
+        }));
+    }
+
+    /// Starts or stops recording. Stopping forgets what was recorded.
+    pub fn set_recording(&mut self, recording: bool) {
+        self.recording = recording;
+        if !recording {
+            self.clear();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.messages.clear();
+        self.open.clear();
+    }
+
+    pub fn messages(&self) -> Vec<Arc<TrafficEntry>> {
+        self.messages.iter().cloned().collect()
+    }
+
+    pub fn push_stderr(&mut self, line: &str) {
+        if self.stderr.len() == STDERR_LIMIT {
+            self.stderr.pop_front();
+        }
+        self.stderr.push_back(line.to_string());
+    }
+
+    pub fn stderr(&self) -> Vec<String> {
+        self.stderr.iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn responses_are_timed_against_their_requests() {
+        let mut traffic = Traffic::default();
+        let start = Instant::now();
+        traffic.record(true, &json!({ "id": 1, "method": "initialize" }), start);
+        assert!(traffic.messages().is_empty());
+
+        traffic.set_recording(true);
+        traffic.record(
+            true,
+            &json!({ "id": 2, "method": "textDocument/hover" }),
+            start,
+        );
+        traffic.record(
+            false,
+            &json!({ "id": 7, "method": "workspace/configuration" }),
+            start,
+        );
+        traffic.record(true, &json!({ "id": 7, "result": [] }), start);
+        traffic.record(
+            false,
+            &json!({ "id": 2, "error": { "message": "busy" } }),
+            start + Duration::from_millis(40),
+        );
+        traffic.record(false, &json!({ "method": "window/logMessage" }), start);
+
+        let messages = traffic.messages();
+        let kinds: Vec<MessageKind> = messages.iter().map(|entry| entry.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                MessageKind::Request,
+                MessageKind::Request,
+                MessageKind::Response,
+                MessageKind::Response,
+                MessageKind::Notification,
+            ]
+        );
+        assert_eq!(messages[2].method, "workspace/configuration");
+        assert_eq!(messages[3].method, "textDocument/hover");
+        assert_eq!(messages[3].duration, Some(Duration::from_millis(40)));
+        assert!(messages[3].failed);
+        assert_eq!(messages[3].id.as_deref(), Some("2"));
+        assert!(messages[3].matches("Hover "));
+        assert!(!messages[4].matches("hover"));
+    }
+}
@@ -0,0 +1,119 @@
+//! State for the Lua console pane: the scrollback, the input line and its
+//! history.
+
+use std::collections::VecDeque;
+
+/// Scrollback lines kept before the oldest are dropped.
+const MAX_LINES: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsoleLineKind {
+    /// Echo of what was submitted.
+    Input,
+    /// Something a script passed to `print`.
+    Output,
+    /// A value the input evaluated to.
+    Result,
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConsoleLine {
+    pub kind: ConsoleLineKind,
+    pub text: String,
+}
+
+#[derive(Debug, Default)]
+pub struct LuaConsole {
+    pub input: String,
+    pub lines: VecDeque<ConsoleLine>,
+    history: Vec<String>,
+    /// Position while browsing history; `None` when editing a fresh line.
+    history_pos: Option<usize>,
+    /// The line being typed before history browsing started.
+    draft: String,
+}
+
+impl LuaConsole {
+    pub fn push(&mut self, kind: ConsoleLineKind, text: impl Into<String>) {
+        self.lines.push_back(ConsoleLine {
+            kind,
+            text: text.into(),
+        });
+        while self.lines.len() > MAX_LINES {
+            self.lines.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+
+    /// Takes the input line for evaluation, recording it in history and
+    /// echoing it to the scrollback. Blank input returns `None`.
+    pub fn submit(&mut self) -> Option<String> {
+        let input = std::mem::take(&mut self.input);
+        self.history_pos = None;
+        self.draft.clear();
+        if input.trim().is_empty() {
+            return None;
+        }
+
+        if self.history.last() != Some(&input) {
+            self.history.push(input.clone());
+        }
+        self.push(ConsoleLineKind::Input, format!("> {input}"));
+        Some(input)
+    }
+
+    /// Moves through history: `-1` recalls the previous entry, `1` the next.
+    /// Moving past the newest entry restores the line being typed.
+    pub fn navigate_history(&mut self, delta: i32) {
+        if self.history.is_empty() {
+            return;
+        }
+
+        let next = match (self.history_pos, delta < 0) {
+            (None, true) => {
+                self.draft = self.input.clone();
+                Some(self.history.len() - 1)
+            }
+            (None, false) => return,
+            (Some(pos), true) => Some(pos.saturating_sub(1)),
+            (Some(pos), false) if pos + 1 < self.history.len() => Some(pos + 1),
+            (Some(_), false) => None,
+        };
+
+        self.history_pos = next;
+        self.input = match next {
+            Some(pos) => self.history[pos].clone(),
+            None => std::mem::take(&mut self.draft),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_recalls_entries_and_restores_draft() {
+        let mut console = LuaConsole::default();
+        for input in ["a", "b", "b", "  "] {
+            console.input = input.to_string();
+            console.submit();
+        }
+        assert_eq!(console.lines.len(), 3);
+
+        console.input = "draft".to_string();
+        console.navigate_history(-1);
+        assert_eq!(console.input, "b");
+        console.navigate_history(-1);
+        console.navigate_history(-1);
+        assert_eq!(console.input, "a");
+        console.navigate_history(1);
+        assert_eq!(console.input, "b");
+        console.navigate_history(1);
+        assert_eq!(console.input, "draft");
+    }
+}
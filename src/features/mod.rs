@@ -5,11 +5,16 @@ pub mod command_palette;
 pub mod editor_buffer;
 pub mod file_tree;
 pub mod find_replace;
+pub mod format;
 pub mod fuzzy_finder;
 pub mod icons;
 pub mod lsp;
+pub mod lua_console;
+pub mod problems;
 pub mod resources;
 pub mod search;
+pub mod statusline;
+pub mod symbols;
 pub mod syntax;
 pub mod terminal;
 pub mod updater;
@@ -0,0 +1,254 @@
+//! The Problems panel's model: language server diagnostics across every
+//! file, filtered by severity and ordered for next/previous navigation.
+
+use crate::features::lsp::InlineDiagnostic;
+use lsp_types::DiagnosticSeverity;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// The severities the panel can filter by. Hints count as info.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProblemSeverity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl ProblemSeverity {
+    pub fn of(diagnostic: &InlineDiagnostic) -> Self {
+        match diagnostic.severity {
+            DiagnosticSeverity::ERROR => ProblemSeverity::Error,
+            DiagnosticSeverity::WARNING => ProblemSeverity::Warning,
+            _ => ProblemSeverity::Info,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ProblemSeverity::Error => "Errors",
+            ProblemSeverity::Warning => "Warnings",
+            ProblemSeverity::Info => "Info",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProblemFilter {
+    pub errors: bool,
+    pub warnings: bool,
+    pub info: bool,
+}
+
+impl Default for ProblemFilter {
+    fn default() -> Self {
+        Self {
+            errors: true,
+            warnings: true,
+            info: true,
+        }
+    }
+}
+
+impl ProblemFilter {
+    pub fn shows(&self, severity: ProblemSeverity) -> bool {
+        match severity {
+            ProblemSeverity::Error => self.errors,
+            ProblemSeverity::Warning => self.warnings,
+            ProblemSeverity::Info => self.info,
+        }
+    }
+
+    pub fn toggle(&mut self, severity: ProblemSeverity) {
+        match severity {
+            ProblemSeverity::Error => self.errors = !self.errors,
+            ProblemSeverity::Warning => self.warnings = !self.warnings,
+            ProblemSeverity::Info => self.info = !self.info,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProblemCounts {
+    pub errors: usize,
+    pub warnings: usize,
+    pub info: usize,
+}
+
+impl ProblemCounts {
+    pub fn get(&self, severity: ProblemSeverity) -> usize {
+        match severity {
+            ProblemSeverity::Error => self.errors,
+            ProblemSeverity::Warning => self.warnings,
+            ProblemSeverity::Info => self.info,
+        }
+    }
+
+    /// The status bar text, e.g. `2 errors, 1 warning`; `None` when clean.
+    pub fn summary(&self) -> Option<String> {
+        let plural = |count: usize, word: &str| {
+            format!("{count} {word}{}", if count == 1 { "" } else { "s" })
+        };
+        let mut parts = Vec::new();
+        if self.errors > 0 {
+            parts.push(plural(self.errors, "error"));
+        }
+        if self.warnings > 0 {
+            parts.push(plural(self.warnings, "warning"));
+        }
+        (!parts.is_empty()).then(|| parts.join(", "))
+    }
+}
+
+pub fn count(diagnostics: &HashMap<PathBuf, Vec<InlineDiagnostic>>) -> ProblemCounts {
+    let mut counts = ProblemCounts::default();
+    for diagnostic in diagnostics.values().flatten() {
+        match ProblemSeverity::of(diagnostic) {
+            ProblemSeverity::Error => counts.errors += 1,
+            ProblemSeverity::Warning => counts.warnings += 1,
+            ProblemSeverity::Info => counts.info += 1,
+        }
+    }
+    counts
+}
+
+/// The files with problems the filter lets through, sorted by path, each
+/// with its problems sorted by position.
+pub fn grouped<'a>(
+    diagnostics: &'a HashMap<PathBuf, Vec<InlineDiagnostic>>,
+    filter: &ProblemFilter,
+) -> Vec<(&'a Path, Vec<&'a InlineDiagnostic>)> {
+    let mut files: Vec<_> = diagnostics
+        .iter()
+        .map(|(path, items)| {
+            let mut items: Vec<_> = items
+                .iter()
+                .filter(|d| filter.shows(ProblemSeverity::of(d)))
+                .collect();
+            items.sort_by_key(|d| (d.line, d.column));
+            (path.as_path(), items)
+        })
+        .filter(|(_, items)| !items.is_empty())
+        .collect();
+    files.sort_by(|a, b| a.0.cmp(b.0));
+    files
+}
+
+/// The problem after (or before) `from`, wrapping around the workspace.
+/// Positions are 1-based (line, column).
+pub fn step(
+    diagnostics: &HashMap<PathBuf, Vec<InlineDiagnostic>>,
+    filter: &ProblemFilter,
+    from: Option<(&Path, usize, usize)>,
+    forward: bool,
+) -> Option<(PathBuf, usize, usize)> {
+    let all: Vec<(&Path, usize, usize)> = grouped(diagnostics, filter)
+        .into_iter()
+        .flat_map(|(path, items)| items.into_iter().map(move |d| (path, d.line, d.column)))
+        .collect();
+    let found = match from {
+        Some(from) if forward => all.iter().find(|p| **p > from).or(all.first()),
+        Some(from) => all.iter().rev().find(|p| **p < from).or(all.last()),
+        None if forward => all.first(),
+        None => all.last(),
+    };
+    found.map(|(path, line, column)| (path.to_path_buf(), *line, *column))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostic(line: usize, column: usize, severity: DiagnosticSeverity) -> InlineDiagnostic {
+        InlineDiagnostic {
+            line,
+            column,
+            end_line: line,
+            end_column: column + 1,
+            severity,
+            message: String::new(),
+            source: None,
+            code: None,
+        }
+    }
+
+    fn workspace() -> HashMap<PathBuf, Vec<InlineDiagnostic>> {
+        HashMap::from([
+            (
+                PathBuf::from("/w/b.rs"),
+                vec![
+                    diagnostic(9, 1, DiagnosticSeverity::WARNING),
+                    diagnostic(2, 5, DiagnosticSeverity::ERROR),
+                ],
+            ),
+            (
+                PathBuf::from("/w/a.rs"),
+                vec![diagnostic(4, 2, DiagnosticSeverity::HINT)],
+            ),
+            (PathBuf::from("/w/c.rs"), Vec::new()),
+        ])
+    }
+
+    #[test]
+    fn counts_and_groups() {
+        let diagnostics = workspace();
+        let counts = count(&diagnostics);
+        assert_eq!(
+            counts,
+            ProblemCounts {
+                errors: 1,
+                warnings: 1,
+                info: 1
+            }
+        );
+        assert_eq!(counts.summary().as_deref(), Some("1 error, 1 warning"));
+        assert_eq!(ProblemCounts::default().summary(), None);
+
+        let all = grouped(&diagnostics, &ProblemFilter::default());
+        let files: Vec<_> = all
+            .iter()
+            .map(|(path, items)| (*path, items.len()))
+            .collect();
+        assert_eq!(
+            files,
+            [(Path::new("/w/a.rs"), 1), (Path::new("/w/b.rs"), 2)]
+        );
+        assert_eq!(all[1].1[0].line, 2);
+
+        let mut filter = ProblemFilter::default();
+        filter.toggle(ProblemSeverity::Info);
+        assert_eq!(grouped(&diagnostics, &filter).len(), 1);
+    }
+
+    #[test]
+    fn steps_wrap_around_files() {
+        let diagnostics = workspace();
+        let filter = ProblemFilter::default();
+        let at = |path: &str, line, column| Some((PathBuf::from(path), line, column));
+
+        let next = |from| step(&diagnostics, &filter, from, true);
+        assert_eq!(next(None), at("/w/a.rs", 4, 2));
+        assert_eq!(
+            next(Some((Path::new("/w/a.rs"), 4, 2))),
+            at("/w/b.rs", 2, 5)
+        );
+        assert_eq!(
+            next(Some((Path::new("/w/b.rs"), 3, 1))),
+            at("/w/b.rs", 9, 1)
+        );
+        assert_eq!(
+            next(Some((Path::new("/w/b.rs"), 9, 1))),
+            at("/w/a.rs", 4, 2)
+        );
+
+        let previous = |from| step(&diagnostics, &filter, from, false);
+        assert_eq!(
+            previous(Some((Path::new("/w/b.rs"), 2, 5))),
+            at("/w/a.rs", 4, 2)
+        );
+        assert_eq!(
+            previous(Some((Path::new("/w/a.rs"), 1, 1))),
+            at("/w/b.rs", 9, 1)
+        );
+        assert_eq!(step(&HashMap::new(), &filter, None, true), None);
+    }
+}
@@ -0,0 +1,315 @@
+//! The status bar's segment model: built-in segments computed from editor
+//! state, segments scripts add with `pinel.statusline.add`, and the order
+//! `preferences.lua` lists them in.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Ids of the segments the editor provides itself.
+pub const BUILTIN_SEGMENTS: [&str; 9] = [
+    "file",
+    "git_branch",
+    "problems",
+    "diagnostic",
+    "lsp",
+    "line_ending",
+    "encoding",
+    "language",
+    "cursor",
+];
+
+pub const DEFAULT_LEFT: [&str; 2] = ["file", "git_branch"];
+pub const DEFAULT_RIGHT: [&str; 7] = [
+    "problems",
+    "diagnostic",
+    "lsp",
+    "line_ending",
+    "encoding",
+    "language",
+    "cursor",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentAlign {
+    Left,
+    Right,
+}
+
+impl SegmentAlign {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "left" => Ok(SegmentAlign::Left),
+            "right" => Ok(SegmentAlign::Right),
+            other => Err(format!(
+                "unknown statusline align '{other}' (expected left or right)"
+            )),
+        }
+    }
+}
+
+/// Editor state the built-in segments show and scripted segments are given.
+/// Segments are only rendered again when this changes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StatusContext {
+    pub path: Option<PathBuf>,
+    pub file: String,
+    pub line: usize,
+    pub col: usize,
+    pub language: Option<String>,
+    pub encoding: Option<&'static str>,
+    pub line_ending: Option<&'static str>,
+    /// The language server for the active file, with its progress if busy.
+    pub lsp: Option<String>,
+    pub git_branch: Option<String>,
+    /// The diagnostic on the cursor line.
+    pub diagnostic: Option<String>,
+    /// Error and warning counts across the workspace.
+    pub problems: Option<String>,
+}
+
+impl StatusContext {
+    /// Text of a built-in segment; `None` hides it.
+    pub fn builtin(&self, id: &str) -> Option<String> {
+        let has_file = self.path.is_some();
+        match id {
+            "file" => Some(self.file.clone()).filter(|file| !file.is_empty()),
+            "git_branch" => self.git_branch.clone(),
+            "diagnostic" => self.diagnostic.clone(),
+            "problems" => self.problems.clone(),
+            "lsp" => self.lsp.clone(),
+            "line_ending" => self.line_ending.map(str::to_string),
+            "encoding" => self.encoding.map(str::to_string),
+            "language" if has_file => Some(
+                self.language
+                    .clone()
+                    .unwrap_or_else(|| "Plain Text".to_string()),
+            ),
+            "cursor" => Some(format!("Ln {}, Col {}", self.line, self.col)),
+            _ => None,
+        }
+    }
+}
+
+/// A segment added by a script; `render` is a Lua callback id.
+#[derive(Debug, Clone)]
+pub struct ScriptedSegment {
+    pub id: String,
+    pub align: SegmentAlign,
+    pub render: usize,
+}
+
+/// Where a shown segment's text comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Slot {
+    Builtin(String),
+    Scripted { id: String, render: usize },
+}
+
+#[derive(Debug, Default)]
+pub struct Statusline {
+    scripted: Vec<ScriptedSegment>,
+    /// The context the current texts were rendered for.
+    rendered_for: Option<StatusContext>,
+    dirty: bool,
+    /// The last error of each failing scripted segment, so it is reported
+    /// once rather than on every render.
+    errors: HashMap<String, String>,
+    pub left: Vec<String>,
+    pub right: Vec<String>,
+}
+
+impl Statusline {
+    /// Adds a scripted segment, replacing one with the same id.
+    pub fn add(&mut self, segment: ScriptedSegment) {
+        match self.scripted.iter_mut().find(|s| s.id == segment.id) {
+            Some(existing) => *existing = segment,
+            None => self.scripted.push(segment),
+        }
+        self.dirty = true;
+    }
+
+    pub fn clear_scripted(&mut self) {
+        self.scripted.clear();
+        self.errors.clear();
+        self.dirty = true;
+    }
+
+    /// Records a render error; returns whether it differs from the last one
+    /// seen for that segment.
+    pub fn note_error(&mut self, id: &str, error: &str) -> bool {
+        if self.errors.get(id).is_some_and(|last| last == error) {
+            return false;
+        }
+        self.errors.insert(id.to_string(), error.to_string());
+        true
+    }
+
+    /// Forces the next refresh to render every segment again.
+    pub fn invalidate(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Whether the segments need rendering for `context`, i.e. it changed
+    /// or something was invalidated since the last render.
+    pub fn needs_render(&self, context: &StatusContext) -> bool {
+        self.dirty || self.rendered_for.as_ref() != Some(context)
+    }
+
+    pub fn set_rendered(&mut self, context: StatusContext, left: Vec<String>, right: Vec<String>) {
+        self.rendered_for = Some(context);
+        self.dirty = false;
+        self.left = left;
+        self.right = right;
+    }
+
+    /// The segments to show on each side, in order. Ids listed in `left` and
+    /// `right` come first, built-in or scripted; scripted segments not listed
+    /// there follow on their own side, and unlisted built-ins are hidden.
+    pub fn layout(&self, left: &[String], right: &[String]) -> (Vec<Slot>, Vec<Slot>) {
+        let slot = |id: &String| -> Option<Slot> {
+            match self.scripted.iter().find(|s| &s.id == id) {
+                Some(segment) => Some(Slot::Scripted {
+                    id: segment.id.clone(),
+                    render: segment.render,
+                }),
+                None => BUILTIN_SEGMENTS
+                    .contains(&id.as_str())
+                    .then(|| Slot::Builtin(id.clone())),
+            }
+        };
+        let mut left_slots: Vec<Slot> = left.iter().filter_map(slot).collect();
+        let mut right_slots: Vec<Slot> = right.iter().filter_map(slot).collect();
+
+        for segment in &self.scripted {
+            if left.contains(&segment.id) || right.contains(&segment.id) {
+                continue;
+            }
+            let slot = Slot::Scripted {
+                id: segment.id.clone(),
+                render: segment.render,
+            };
+            match segment.align {
+                SegmentAlign::Left => left_slots.push(slot),
+                SegmentAlign::Right => right_slots.push(slot),
+            }
+        }
+
+        (left_slots, right_slots)
+    }
+}
+
+/// A display name for the language of `path`, from its extension.
+pub fn language_name(path: &Path) -> Option<String> {
+    let name = match path.extension()?.to_str()? {
+        "rs" => "Rust",
+        "py" => "Python",
+        "js" | "mjs" | "cjs" => "JavaScript",
+        "jsx" => "JavaScript React",
+        "ts" => "TypeScript",
+        "tsx" => "TypeScript React",
+        "lua" => "Lua",
+        "md" | "markdown" | "mdown" | "mdx" => "Markdown",
+        "json" => "JSON",
+        "toml" => "TOML",
+        "yaml" | "yml" => "YAML",
+        "html" | "htm" => "HTML",
+        "css" => "CSS",
+        "c" | "h" => "C",
+        "cpp" | "cc" | "cxx" | "hpp" => "C++",
+        "go" => "Go",
+        "java" => "Java",
+        "rb" => "Ruby",
+        "sh" | "bash" | "zsh" => "Shell",
+        "txt" => "Plain Text",
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+/// The checked-out branch of the repository containing `dir`, or the short
+/// commit hash when the head is detached.
+pub fn git_branch(dir: &Path) -> Option<String> {
+    let dot_git = dir
+        .ancestors()
+        .map(|d| d.join(".git"))
+        .find(|p| p.exists())?;
+    let git_dir = if dot_git.is_file() {
+        // Worktrees and submodules point at their git directory.
+        let pointer = fs::read_to_string(&dot_git).ok()?;
+        let target = PathBuf::from(pointer.strip_prefix("gitdir:")?.trim());
+        dot_git.parent()?.join(target)
+    } else {
+        dot_git
+    };
+    branch_from_head(&fs::read_to_string(git_dir.join("HEAD")).ok()?)
+}
+
+fn branch_from_head(head: &str) -> Option<String> {
+    let head = head.trim();
+    match head.strip_prefix("ref:") {
+        Some(reference) => {
+            let reference = reference.trim();
+            Some(
+                reference
+                    .strip_prefix("refs/heads/")
+                    .unwrap_or(reference)
+                    .to_string(),
+            )
+        }
+        None => head.get(..7).map(str::to_string),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(list: &[&str]) -> Vec<String> {
+        list.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn layout_follows_configured_order() {
+        let mut statusline = Statusline::default();
+        for (id, align, render) in [
+            ("clock", SegmentAlign::Left, 1),
+            ("words", SegmentAlign::Right, 2),
+        ] {
+            statusline.add(ScriptedSegment {
+                id: id.to_string(),
+                align,
+                render,
+            });
+        }
+
+        let (left, right) = statusline.layout(&ids(&["words", "file", "nope"]), &ids(&["cursor"]));
+        assert_eq!(
+            left,
+            vec![
+                Slot::Scripted {
+                    id: "words".to_string(),
+                    render: 2
+                },
+                Slot::Builtin("file".to_string()),
+                Slot::Scripted {
+                    id: "clock".to_string(),
+                    render: 1
+                },
+            ]
+        );
+        assert_eq!(right, vec![Slot::Builtin("cursor".to_string())]);
+    }
+
+    #[test]
+    fn head_names_branch_or_commit() {
+        assert_eq!(
+            branch_from_head("ref: refs/heads/feature/x\n").as_deref(),
+            Some("feature/x")
+        );
+        assert_eq!(
+            branch_from_head("0123456789abcdef\n").as_deref(),
+            Some("0123456")
+        );
+    }
+}
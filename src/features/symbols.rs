@@ -0,0 +1,369 @@
+//! Symbols for the Outline and the fuzzy finder's `@` mode.
+//!
+//! Language servers provide them when they can; otherwise they come from
+//! tree-sitter queries over the rust, javascript, typescript and python
+//! grammars.
+
+use lsp_types::SymbolKind;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tree_sitter::{Language, Node, Parser, Query, QueryCursor, StreamingIterator};
+
+/// Files larger than this are left out of the workspace symbol index.
+const MAX_INDEXED_FILE_BYTES: u64 = 1024 * 1024;
+
+/// A named definition in a file. Positions are 0-based `(line, column)` with
+/// columns counting characters, like [`TextEdit`](super::editor_buffer::TextEdit).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// A signature, or the symbol the definition sits in.
+    pub detail: Option<String>,
+    pub path: PathBuf,
+    /// Where the name starts; jumps land here.
+    pub start: (usize, usize),
+    /// The end of the whole definition.
+    pub end: (usize, usize),
+    /// Nesting in the outline, 0 at the top level.
+    pub depth: usize,
+}
+
+/// A short tag for a symbol kind, shown before its name.
+pub fn kind_label(kind: SymbolKind) -> &'static str {
+    match kind {
+        SymbolKind::FILE => "file",
+        SymbolKind::MODULE | SymbolKind::NAMESPACE | SymbolKind::PACKAGE => "mod",
+        SymbolKind::CLASS => "class",
+        SymbolKind::METHOD => "method",
+        SymbolKind::PROPERTY | SymbolKind::FIELD => "field",
+        SymbolKind::CONSTRUCTOR => "new",
+        SymbolKind::ENUM => "enum",
+        SymbolKind::INTERFACE => "trait",
+        SymbolKind::FUNCTION => "fn",
+        SymbolKind::VARIABLE => "var",
+        SymbolKind::CONSTANT => "const",
+        SymbolKind::ENUM_MEMBER => "variant",
+        SymbolKind::STRUCT => "struct",
+        SymbolKind::OBJECT => "impl",
+        SymbolKind::TYPE_PARAMETER => "type",
+        _ => "sym",
+    }
+}
+
+const RUST_QUERY: &str = r#"
+(function_item name: (identifier) @name) @function
+(function_signature_item name: (identifier) @name) @function
+(struct_item name: (type_identifier) @name) @struct
+(union_item name: (type_identifier) @name) @struct
+(enum_item name: (type_identifier) @name) @enum
+(enum_variant name: (identifier) @name) @member
+(field_declaration name: (field_identifier) @name) @field
+(trait_item name: (type_identifier) @name) @interface
+(type_item name: (type_identifier) @name) @type
+(impl_item) @impl
+(mod_item name: (identifier) @name) @module
+(macro_definition name: (identifier) @name) @macro
+(const_item name: (identifier) @name) @constant
+(static_item name: (identifier) @name) @constant
+"#;
+
+const PYTHON_QUERY: &str = r#"
+(class_definition name: (identifier) @name) @class
+(function_definition name: (identifier) @name) @function
+(module (expression_statement (assignment left: (identifier) @name) @variable))
+"#;
+
+const JAVASCRIPT_QUERY: &str = r#"
+(class_declaration name: (_) @name) @class
+(method_definition name: (_) @name) @method
+(function_declaration name: (identifier) @name) @function
+(generator_function_declaration name: (identifier) @name) @function
+(variable_declarator
+  name: (identifier) @name
+  value: [(arrow_function) (function_expression)]) @function
+(program (lexical_declaration (variable_declarator name: (identifier) @name) @variable))
+(program (export_statement (lexical_declaration (variable_declarator name: (identifier) @name) @variable)))
+"#;
+
+const TYPESCRIPT_QUERY: &str = r#"
+(abstract_class_declaration name: (_) @name) @class
+(interface_declaration name: (_) @name) @interface
+(type_alias_declaration name: (_) @name) @type
+(enum_declaration name: (_) @name) @enum
+(internal_module name: (_) @name) @module
+(method_signature name: (_) @name) @method
+(abstract_method_signature name: (_) @name) @method
+(public_field_definition name: (_) @name) @field
+"#;
+
+fn grammar(path: &Path) -> Option<(&'static str, Language, String)> {
+    let (name, language, query) = match path.extension()?.to_str()? {
+        "rs" => ("rust", tree_sitter_rust::LANGUAGE, RUST_QUERY.to_string()),
+        "py" | "pyi" => (
+            "python",
+            tree_sitter_python::LANGUAGE,
+            PYTHON_QUERY.to_string(),
+        ),
+        "js" | "mjs" | "cjs" | "jsx" => (
+            "javascript",
+            tree_sitter_javascript::LANGUAGE,
+            JAVASCRIPT_QUERY.to_string(),
+        ),
+        "ts" | "mts" | "cts" => (
+            "typescript",
+            tree_sitter_typescript::LANGUAGE_TYPESCRIPT,
+            format!("{JAVASCRIPT_QUERY}{TYPESCRIPT_QUERY}"),
+        ),
+        "tsx" => (
+            "tsx",
+            tree_sitter_typescript::LANGUAGE_TSX,
+            format!("{JAVASCRIPT_QUERY}{TYPESCRIPT_QUERY}"),
+        ),
+        _ => return None,
+    };
+    Some((name, language.into(), query))
+}
+
+/// Whether tree-sitter can find symbols in `path`.
+pub fn has_grammar(path: &Path) -> bool {
+    grammar(path).is_some()
+}
+
+/// Finds symbols with tree-sitter, keeping a parser and compiled query per
+/// language so a workspace can be indexed without recompiling them.
+#[derive(Default)]
+pub struct SyntaxSymbols {
+    parser: Parser,
+    queries: HashMap<&'static str, Query>,
+}
+
+impl SyntaxSymbols {
+    /// The outline of `text`, in document order, or `None` when there is no
+    /// grammar for the file type.
+    pub fn outline(&mut self, path: &Path, text: &str) -> Option<Vec<Symbol>> {
+        let (name, language, source) = grammar(path)?;
+        self.parser.set_language(&language).ok()?;
+        let tree = self.parser.parse(text, None)?;
+        let query = match self.queries.entry(name) {
+            std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                entry.insert(Query::new(&language, &source).ok()?)
+            }
+        };
+
+        // (definition node, name node, kind, pattern) for every match.
+        let mut found: Vec<(Node, Option<Node>, SymbolKind, usize)> = Vec::new();
+        let mut cursor = QueryCursor::new();
+        let mut matches = cursor.matches(query, tree.root_node(), text.as_bytes());
+        while let Some(m) = matches.next() {
+            let mut definition = None;
+            let mut name = None;
+            for capture in m.captures {
+                match query.capture_names()[capture.index as usize] {
+                    "name" => name = Some(capture.node),
+                    capture_name => definition = Some((capture.node, capture_kind(capture_name))),
+                }
+            }
+            if let Some((node, kind)) = definition {
+                found.push((node, name, kind, m.pattern_index));
+            }
+        }
+        // Outer definitions first; a node several patterns match keeps the first.
+        found.sort_by_key(|(node, _, _, pattern)| {
+            (
+                node.start_byte(),
+                std::cmp::Reverse(node.end_byte()),
+                *pattern,
+            )
+        });
+        found.dedup_by_key(|(node, ..)| node.byte_range());
+
+        let lines = LineStarts::new(text);
+        let mut symbols: Vec<Symbol> = Vec::new();
+        // End byte and kind of each definition enclosing the current one.
+        let mut enclosing: Vec<(usize, SymbolKind)> = Vec::new();
+        for (node, name_node, mut kind, _) in found {
+            while enclosing
+                .last()
+                .is_some_and(|(end, _)| *end <= node.start_byte())
+            {
+                enclosing.pop();
+            }
+            let parent = enclosing.last().map(|(_, kind)| *kind);
+            if kind == SymbolKind::FUNCTION
+                && matches!(
+                    parent,
+                    Some(SymbolKind::OBJECT | SymbolKind::INTERFACE | SymbolKind::CLASS)
+                )
+            {
+                kind = SymbolKind::METHOD;
+            }
+            let name = match name_node {
+                Some(name) => text[name.byte_range()].to_string(),
+                None => impl_name(node, text),
+            };
+            let anchor = name_node.unwrap_or(node);
+            symbols.push(Symbol {
+                name,
+                kind,
+                detail: None,
+                path: path.to_path_buf(),
+                start: lines.position(text, anchor.start_byte()),
+                end: lines.position(text, node.end_byte()),
+                depth: enclosing.len(),
+            });
+            enclosing.push((node.end_byte(), kind));
+        }
+        Some(symbols)
+    }
+
+    /// The top-level and nested symbols of every file tree-sitter can read,
+    /// each with the symbol it sits in as its detail.
+    pub fn index(&mut self, files: &[PathBuf]) -> Vec<Symbol> {
+        let mut symbols = Vec::new();
+        for path in files {
+            let small = std::fs::metadata(path).is_ok_and(|m| m.len() <= MAX_INDEXED_FILE_BYTES);
+            if !small || !has_grammar(path) {
+                continue;
+            }
+            let Ok(text) = std::fs::read_to_string(path) else {
+                continue;
+            };
+            let Some(outline) = self.outline(path, &text) else {
+                continue;
+            };
+            let mut parents: Vec<String> = Vec::new();
+            for mut symbol in outline {
+                parents.truncate(symbol.depth);
+                symbol.detail = parents.last().cloned();
+                parents.push(symbol.name.clone());
+                symbols.push(symbol);
+            }
+        }
+        symbols
+    }
+}
+
+fn capture_kind(capture: &str) -> SymbolKind {
+    match capture {
+        "function" => SymbolKind::FUNCTION,
+        "method" => SymbolKind::METHOD,
+        "struct" => SymbolKind::STRUCT,
+        "class" => SymbolKind::CLASS,
+        "enum" => SymbolKind::ENUM,
+        "member" => SymbolKind::ENUM_MEMBER,
+        "field" => SymbolKind::FIELD,
+        "interface" => SymbolKind::INTERFACE,
+        "type" => SymbolKind::TYPE_PARAMETER,
+        "impl" => SymbolKind::OBJECT,
+        "module" => SymbolKind::MODULE,
+        "constant" => SymbolKind::CONSTANT,
+        "macro" => SymbolKind::FUNCTION,
+        _ => SymbolKind::VARIABLE,
+    }
+}
+
+/// `impl Type` or `impl Trait for Type`, without generics' bounds.
+fn impl_name(node: Node, text: &str) -> String {
+    let field = |name| {
+        node.child_by_field_name(name)
+            .map(|n| &text[n.byte_range()])
+    };
+    match (field("trait"), field("type")) {
+        (Some(tr), Some(ty)) => format!("impl {tr} for {ty}"),
+        (None, Some(ty)) => format!("impl {ty}"),
+        _ => "impl".to_string(),
+    }
+}
+
+/// Converts byte offsets to `(line, character column)`.
+struct LineStarts(Vec<usize>);
+
+impl LineStarts {
+    fn new(text: &str) -> Self {
+        let starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(idx, _)| idx + 1))
+            .collect();
+        Self(starts)
+    }
+
+    fn position(&self, text: &str, byte: usize) -> (usize, usize) {
+        let line = self.0.partition_point(|start| *start <= byte) - 1;
+        (line, text[self.0[line]..byte].chars().count())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outline(file: &str, text: &str) -> Vec<(String, &'static str, usize, (usize, usize))> {
+        SyntaxSymbols::default()
+            .outline(Path::new(file), text)
+            .unwrap()
+            .into_iter()
+            .map(|s| (s.name, kind_label(s.kind), s.depth, s.start))
+            .collect()
+    }
+
+    fn entry(
+        name: &str,
+        kind: &'static str,
+        depth: usize,
+        start: (usize, usize),
+    ) -> (String, &'static str, usize, (usize, usize)) {
+        (name.to_string(), kind, depth, start)
+    }
+
+    #[test]
+    fn rust_outline_nests_impl_methods() {
+        let text = "struct Point { x: i32 }\n\nimpl Display for Point {\n    fn fmt(&self) {}\n}\n\nfn é() {}\n";
+        assert_eq!(
+            outline("lib.rs", text),
+            [
+                entry("Point", "struct", 0, (0, 7)),
+                entry("x", "field", 1, (0, 15)),
+                entry("impl Display for Point", "impl", 0, (2, 0)),
+                entry("fmt", "method", 1, (3, 7)),
+                entry("é", "fn", 0, (6, 3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn script_outlines() {
+        let python = "class A:\n    def run(self):\n        pass\n\nLIMIT = 3\n";
+        assert_eq!(
+            outline("a.py", python),
+            [
+                entry("A", "class", 0, (0, 6)),
+                entry("run", "method", 1, (1, 8)),
+                entry("LIMIT", "var", 0, (4, 0)),
+            ]
+        );
+
+        let typescript = "interface Shape { area(): number }\nexport const draw = (s: Shape) => s;\nclass Box { open() {} }\n";
+        assert_eq!(
+            outline("a.ts", typescript),
+            [
+                entry("Shape", "trait", 0, (0, 10)),
+                entry("area", "method", 1, (0, 18)),
+                entry("draw", "fn", 0, (1, 13)),
+                entry("Box", "class", 0, (2, 6)),
+                entry("open", "method", 1, (2, 12)),
+            ]
+        );
+        assert_eq!(
+            outline("a.tsx", "function App() {}\n"),
+            [entry("App", "fn", 0, (0, 9))]
+        );
+        assert_eq!(
+            outline("a.js", "function run() {}\n"),
+            [entry("run", "fn", 0, (0, 9))]
+        );
+        assert!(SyntaxSymbols::default()
+            .outline(Path::new("notes.txt"), "")
+            .is_none());
+    }
+}
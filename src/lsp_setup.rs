@@ -23,6 +23,7 @@
 //! | Bun-installed tools      | `~/.bun/bin`                                     |
 //! | pip-installed tools      | `~/.local/bin`, Python framework bins            |
 
+use crate::config::lsp_config::LspRegistry;
 use std::collections::LinkedList;
 use std::path::{Path, PathBuf};
 
@@ -180,24 +181,30 @@ pub fn find_lsp_server(binary: &str) -> Option<PathBuf> {
     None
 }
 
-/// Returns a human-readable summary of which supported LSP servers are
-/// available on the current `PATH`.
+/// Returns a human-readable summary of which configured LSP servers are
+/// available.
 ///
-/// The returned `Vec` contains `(server_key, Option<path>)` pairs for every
-/// server that Pinel can use.  Entries with `None` mean the server is not
-/// installed / not on `PATH`.
-pub fn lsp_server_status() -> Vec<(&'static str, Option<PathBuf>)> {
-    const SERVERS: &[(&str, &str)] = &[
-        ("rust-analyzer", "rust-analyzer"),
-        ("pyright", "pyright-langserver"),
-        ("typescript-language-server", "typescript-language-server"),
-        ("lua-language-server", "lua-language-server"),
-        ("gopls", "gopls"),
-    ];
-
-    SERVERS
-        .iter()
-        .map(|(key, bin)| (*key, find_lsp_server(bin)))
+/// The returned `Vec` contains `(server name, Option<path>)` pairs for every
+/// server in `lsp.lua` and the built-in list.  Commands given as a path are
+/// checked directly; others are looked up on `PATH`.  Entries with `None`
+/// mean the server is not installed / not on `PATH`.
+pub fn lsp_server_status(registry: &LspRegistry) -> Vec<(String, Option<PathBuf>)> {
+    registry
+        .servers()
+        .into_iter()
+        .map(|server| {
+            let program = server
+                .command
+                .first()
+                .map(String::as_str)
+                .unwrap_or_default();
+            let path = if program.contains('/') {
+                Some(PathBuf::from(program)).filter(|path| path.is_file())
+            } else {
+                find_lsp_server(program)
+            };
+            (server.name.clone(), path)
+        })
         .collect()
 }
 
@@ -305,8 +312,8 @@ mod tests {
 
     #[test]
     fn lsp_server_status_returns_all_known_servers() {
-        let status = lsp_server_status();
-        let keys: Vec<&str> = status.iter().map(|(k, _)| *k).collect();
+        let status = lsp_server_status(&LspRegistry::default());
+        let keys: Vec<&str> = status.iter().map(|(k, _)| k.as_str()).collect();
         assert!(keys.contains(&"rust-analyzer"));
         assert!(keys.contains(&"pyright"));
         assert!(keys.contains(&"typescript-language-server"));
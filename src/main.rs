@@ -46,7 +46,7 @@ fn run_gui(startup_path: Option<PathBuf>) -> iced::Result {
 
     let icon_data = include_bytes!("assets/icon.png"); // when an icon is created, set it to src/assets/icon.png
     let icon = window::icon::from_file_data(icon_data, None).expect("Failed to load icon.");
-    let prefs = config::preferences::load_preferences();
+    let prefs = config::preferences::load_preferences().unwrap_or_default();
     let window_width = prefs.window_width.max(640.0);
     let window_height = prefs.window_height.max(480.0);
 
@@ -17,6 +17,8 @@ pub enum Message {
     FileClicked(PathBuf),
     FileOpened(PathBuf, String),
     SensitiveFileOpenConfirm(bool),
+    /// Answers the unsaved-changes prompt shown on quit.
+    QuitConfirm(bool),
     FolderToggled(PathBuf),
 
     FileTreeRefresh,
@@ -61,6 +63,7 @@ pub enum Message {
 
     ToggleFullscreen(iced::window::Mode),
     EscapePressed,
+    RunKeymapAction(crate::scripting::keymap::KeymapAction),
 
     ToggleCommandPalette,
     CommandPaletteQueryChanged(String),
@@ -82,6 +85,7 @@ pub enum Message {
     ToggleCaseSensitive,
 
     ToggleSettings,
+    OpenSettingsSection(String),
     SettingsNavigate(String),
     SettingsTabSizeChanged(String),
     SettingsToggleUseSpaces,
@@ -91,14 +95,23 @@ pub enum Message {
     SettingsSelectTheme(String),
     SettingsReloadTheme,
     SettingsLineNumberWidthChanged(String),
+    SettingsVimEscapeChanged(String),
+    SettingsToggleInlayHints,
 
     ToggleCommandInput,
+    ToggleVimMode,
+    VimKey(VimKey),
+    VimClipboardRead(VimPaste, Option<String>),
     CommandInputChanged(String),
     CommandInputSubmit,
+    /// Tab in the command bar.
+    CommandInputComplete,
     WindowResized(u32, u32),
+    WindowFocusLost,
 
     NewFile,
     SaveAs,
+    Quit,
 
     WakaTimeApiKeyChanged(String),
     WakaTimeApiKeyHoverStart,
@@ -107,17 +120,80 @@ pub enum Message {
     SaveWakaTimeSettings,
 
     DismissNotification,
+    /// Periodic check for edits to init.lua, plugins and theme.lua.
+    ConfigWatchTick,
     ModifierStateChanged(iced::keyboard::Modifiers),
     LspTick,
     AutosaveTick,
     AutosaveFinished(PathBuf, String, Result<(), String>),
 
     ToggleDeveloperPanel,
+    FocusLuaConsole,
+    LuaConsoleInputChanged(String),
+    LuaConsoleSubmit,
+    LuaConsoleClear,
+    JobEvent(crate::scripting::jobs::JobEvent),
+    CancelJob(u64),
     ClearDeveloperLogs,
     SettingsToggleDeveloperMode,
+    SettingsTogglePlugin(String),
     ToggleLsp,
+    RestartLsp,
+    ToggleProblems,
+    ProblemsFilterToggled(crate::features::problems::ProblemSeverity),
+    ProblemClicked(PathBuf, usize, usize),
+    NextProblem,
+    PreviousProblem,
+    ToggleLspInspector,
+    /// Switches the Language Servers panel between status and traffic.
+    LspInspectorShowTraffic(bool),
+    LspInspectorServerSelected(usize),
+    LspTrafficFilterChanged(String),
+    LspTrafficSelected(usize),
+    LspTrafficClear,
+    LspRestartServer(usize),
+    LspStopServer(usize),
+    FindLocations(crate::features::lsp::LocationKind),
+    LocationClicked(PathBuf, usize, usize),
+    CloseLocations,
+    RenameSymbol,
+    RenameInputChanged(String),
+    RenameSubmit,
+    RenameApply,
+    RenameCancel,
+    ShowCodeActions,
+    CodeActionSelected(usize),
+    FormatDocument,
+    FormatFinished(PathBuf, Result<String, String>),
+    ToggleOutline,
+    ShowWorkspaceSymbols,
+    SymbolClicked(PathBuf, usize, usize),
+    SymbolIndexBuilt(u64, Vec<crate::features::symbols::Symbol>),
 
     CheckForUpdate,
     UpdateAvailable(crate::features::updater::UpdateInfo),
     DismissUpdateBanner,
 }
+
+/// A key press handed to vim while it is in a mode where keys are commands
+/// rather than text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VimKey {
+    Char(char),
+    /// A control chord, with the key lowercased.
+    Ctrl(char),
+    Enter,
+    Backspace,
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// A `p` or `P` from the `+` or `*` register, waiting on the clipboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VimPaste {
+    pub register: char,
+    pub before: bool,
+    pub count: usize,
+}
@@ -0,0 +1,188 @@
+//! `pinel.buf`: read and edit the active editor buffer from Lua.
+//!
+//! Lua cannot borrow the app while it runs, so the app hands the runtime a
+//! snapshot of the active buffer before each entry into Lua. Reads come from
+//! the snapshot, and every edit is applied to it immediately (so later reads
+//! see it) and queued as an [`EditorCommand::EditBuffer`] for the real editor.
+//!
+//! Lines and columns are 1-based on the Lua side and 0-based in Rust.
+
+use mlua::{Lua, Result as LuaResult, Table};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use super::EditorCommand;
+use crate::features::editor_buffer::{EditorBuffer, TextEdit};
+
+/// The active editor buffer as seen by scripts.
+#[derive(Debug, Clone)]
+pub struct BufferSnapshot {
+    pub path: Option<PathBuf>,
+    pub language: Option<String>,
+    pub buffer: EditorBuffer,
+    /// 0-based `(line, column)`.
+    pub cursor: (usize, usize),
+}
+
+pub(super) type SharedBuffer = Arc<Mutex<Option<BufferSnapshot>>>;
+
+fn no_buffer() -> mlua::Error {
+    mlua::Error::runtime("no active editor buffer")
+}
+
+/// Converts a 1-based Lua line number to a 0-based index.
+fn line_index(line: i64) -> LuaResult<usize> {
+    if line < 1 {
+        return Err(mlua::Error::runtime(format!(
+            "line numbers start at 1, got {line}"
+        )));
+    }
+    Ok(line as usize - 1)
+}
+
+fn position(snapshot: &BufferSnapshot, line: i64, col: i64) -> LuaResult<(usize, usize)> {
+    let line = line_index(line)?.min(snapshot.buffer.line_count() - 1);
+    let col = (col.max(1) as usize - 1).min(snapshot.buffer.line_len(line));
+    Ok((line, col))
+}
+
+fn push_edit(snapshot: &mut BufferSnapshot, commands: &Mutex<Vec<EditorCommand>>, edit: TextEdit) {
+    snapshot.buffer.apply(&edit);
+    snapshot.cursor = edit.map_position(snapshot.cursor);
+    commands.lock().unwrap().push(EditorCommand::EditBuffer {
+        edit,
+        cursor: snapshot.cursor,
+    });
+}
+
+pub(super) fn install(
+    lua: &Lua,
+    pinel: &Table,
+    commands: &Arc<Mutex<Vec<EditorCommand>>>,
+    buffer: &SharedBuffer,
+) -> LuaResult<()> {
+    let buf = lua.create_table()?;
+
+    {
+        let buffer = Arc::clone(buffer);
+        let f = lua.create_function(move |_, ()| -> LuaResult<String> {
+            let guard = buffer.lock().unwrap();
+            let snapshot = guard.as_ref().ok_or_else(no_buffer)?;
+            Ok(snapshot.buffer.text())
+        })?;
+        buf.set("get_text", f)?;
+    }
+
+    {
+        let buffer = Arc::clone(buffer);
+        let f = lua.create_function(
+            move |_, (start, end): (Option<i64>, Option<i64>)| -> LuaResult<Vec<String>> {
+                let guard = buffer.lock().unwrap();
+                let snapshot = guard.as_ref().ok_or_else(no_buffer)?;
+                let count = snapshot.buffer.line_count();
+                let start = line_index(start.unwrap_or(1))?;
+                let end = end.map_or(count, |end| (end.max(0) as usize).min(count));
+                Ok((start..end)
+                    .map(|line| {
+                        let text = snapshot.buffer.line(line);
+                        text.trim_end_matches(['\n', '\r']).to_string()
+                    })
+                    .collect())
+            },
+        )?;
+        buf.set("get_lines", f)?;
+    }
+
+    {
+        let buffer = Arc::clone(buffer);
+        let commands = Arc::clone(commands);
+        let f = lua.create_function(
+            move |_, (start, end, lines): (i64, i64, Vec<String>)| -> LuaResult<()> {
+                let mut guard = buffer.lock().unwrap();
+                let snapshot = guard.as_mut().ok_or_else(no_buffer)?;
+                let start = line_index(start)?;
+                let end = (end.max(0) as usize).max(start);
+                let edit = snapshot.buffer.lines_edit(start, end, &lines);
+                push_edit(snapshot, &commands, edit);
+                Ok(())
+            },
+        )?;
+        buf.set("set_lines", f)?;
+    }
+
+    {
+        let buffer = Arc::clone(buffer);
+        let commands = Arc::clone(commands);
+        let f = lua.create_function(
+            move |_, (text, line, col): (String, Option<i64>, Option<i64>)| -> LuaResult<()> {
+                let mut guard = buffer.lock().unwrap();
+                let snapshot = guard.as_mut().ok_or_else(no_buffer)?;
+                let at = match (line, col) {
+                    (Some(line), col) => position(snapshot, line, col.unwrap_or(1))?,
+                    (None, _) => snapshot.cursor,
+                };
+                let edit = TextEdit {
+                    start: at,
+                    end: at,
+                    text,
+                };
+                push_edit(snapshot, &commands, edit);
+                Ok(())
+            },
+        )?;
+        buf.set("insert", f)?;
+    }
+
+    {
+        let buffer = Arc::clone(buffer);
+        let f = lua.create_function(move |_, ()| -> LuaResult<(usize, usize)> {
+            let guard = buffer.lock().unwrap();
+            let snapshot = guard.as_ref().ok_or_else(no_buffer)?;
+            Ok((snapshot.cursor.0 + 1, snapshot.cursor.1 + 1))
+        })?;
+        buf.set("cursor", f)?;
+    }
+
+    {
+        let buffer = Arc::clone(buffer);
+        let commands = Arc::clone(commands);
+        let f =
+            lua.create_function(move |_, (line, col): (i64, Option<i64>)| -> LuaResult<()> {
+                let mut guard = buffer.lock().unwrap();
+                let snapshot = guard.as_mut().ok_or_else(no_buffer)?;
+                snapshot.cursor = position(snapshot, line, col.unwrap_or(1))?;
+                commands
+                    .lock()
+                    .unwrap()
+                    .push(EditorCommand::SetCursor(snapshot.cursor));
+                Ok(())
+            })?;
+        buf.set("set_cursor", f)?;
+    }
+
+    {
+        let buffer = Arc::clone(buffer);
+        let f = lua.create_function(move |_, ()| -> LuaResult<Option<String>> {
+            let guard = buffer.lock().unwrap();
+            Ok(guard
+                .as_ref()
+                .and_then(|snapshot| snapshot.path.as_ref())
+                .map(|path| path.to_string_lossy().to_string()))
+        })?;
+        buf.set("path", f)?;
+    }
+
+    {
+        let buffer = Arc::clone(buffer);
+        let f = lua.create_function(move |_, ()| -> LuaResult<Option<String>> {
+            let guard = buffer.lock().unwrap();
+            Ok(guard
+                .as_ref()
+                .and_then(|snapshot| snapshot.language.clone()))
+        })?;
+        buf.set("language", f)?;
+    }
+
+    pinel.set("buf", buf)?;
+    Ok(())
+}
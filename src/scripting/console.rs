@@ -0,0 +1,164 @@
+//! Support for the interactive Lua console: evaluating input against the
+//! persistent runtime, capturing `print`, and pretty-printing results.
+
+use mlua::{Lua, MultiValue, Result as LuaResult, Table, Value};
+use std::ffi::c_void;
+use std::sync::{Arc, Mutex};
+
+use super::ScriptRuntime;
+
+/// Nested tables deeper than this are shown as `{...}`.
+const MAX_DEPTH: usize = 6;
+
+/// Replaces the global `print` so output lands in `output` instead of stdout.
+pub(super) fn install_print(lua: &Lua, output: &Arc<Mutex<Vec<String>>>) -> LuaResult<()> {
+    let output = Arc::clone(output);
+    let print = lua.create_function(move |lua, args: MultiValue| {
+        let tostring: mlua::Function = lua.globals().get("tostring")?;
+        let mut parts = Vec::with_capacity(args.len());
+        for arg in args {
+            parts.push(tostring.call::<String>(arg)?);
+        }
+        output.lock().unwrap().push(parts.join("\t"));
+        Ok(())
+    })?;
+    lua.globals().set("print", print)
+}
+
+impl ScriptRuntime {
+    /// Evaluates a line typed into the console and returns its results,
+    /// pretty-printed. Input that parses as an expression is evaluated as
+    /// one, so `pinel.buf.cursor()` shows its values without a `return`.
+    pub fn eval_console(&self, input: &str) -> Result<Vec<String>, String> {
+        let function = self
+            .lua
+            .load(format!("return {input}"))
+            .set_name("=console")
+            .into_function()
+            .or_else(|_| self.lua.load(input).set_name("=console").into_function())
+            .map_err(|e| e.to_string())?;
+
+        let values = function.call::<MultiValue>(()).map_err(|e| e.to_string())?;
+        Ok(values.iter().map(pretty).collect())
+    }
+
+    /// Drains what scripts passed to `print` since the last call.
+    pub fn take_output(&self) -> Vec<String> {
+        std::mem::take(&mut *self.output.lock().unwrap())
+    }
+}
+
+/// Formats a value the way the console shows it: strings quoted, tables
+/// expanded over several lines with their keys sorted.
+pub fn pretty(value: &Value) -> String {
+    let mut out = String::new();
+    write_value(value, 0, &mut Vec::new(), &mut out);
+    out
+}
+
+fn write_value(value: &Value, depth: usize, seen: &mut Vec<*const c_void>, out: &mut String) {
+    match value {
+        Value::Nil => out.push_str("nil"),
+        Value::Boolean(b) => out.push_str(&b.to_string()),
+        Value::Integer(i) => out.push_str(&i.to_string()),
+        // Lua shows floats with a fractional part, e.g. `2.0`.
+        Value::Number(n) if n.is_finite() && n.fract() == 0.0 => out.push_str(&format!("{n:.1}")),
+        Value::Number(n) => out.push_str(&n.to_string()),
+        Value::String(s) => out.push_str(&format!("{:?}", s.to_string_lossy())),
+        Value::Table(table) => write_table(table, depth, seen, out),
+        other => out.push_str(&format!("{}: {:p}", other.type_name(), other.to_pointer())),
+    }
+}
+
+fn write_table(table: &Table, depth: usize, seen: &mut Vec<*const c_void>, out: &mut String) {
+    let pointer = table.to_pointer();
+    if seen.contains(&pointer) {
+        out.push_str("<cycle>");
+        return;
+    }
+    if depth >= MAX_DEPTH {
+        out.push_str("{...}");
+        return;
+    }
+
+    let len = table.raw_len();
+    let mut items: Vec<Value> = Vec::new();
+    let mut fields: Vec<(String, Value)> = Vec::new();
+    for (key, value) in table.clone().pairs::<Value, Value>().flatten() {
+        match key {
+            Value::Integer(i) if i >= 1 && (i as usize) <= len => {}
+            key => fields.push((field_key(&key), value)),
+        }
+    }
+    for i in 1..=len {
+        items.push(table.raw_get(i).unwrap_or(Value::Nil));
+    }
+    fields.sort_by(|a, b| a.0.cmp(&b.0));
+
+    if items.is_empty() && fields.is_empty() {
+        out.push_str("{}");
+        return;
+    }
+
+    seen.push(pointer);
+    let indent = "  ".repeat(depth + 1);
+    out.push_str("{\n");
+    for item in &items {
+        out.push_str(&indent);
+        write_value(item, depth + 1, seen, out);
+        out.push_str(",\n");
+    }
+    for (key, value) in &fields {
+        out.push_str(&indent);
+        out.push_str(key);
+        out.push_str(" = ");
+        write_value(value, depth + 1, seen, out);
+        out.push_str(",\n");
+    }
+    out.push_str(&"  ".repeat(depth));
+    out.push('}');
+    seen.pop();
+}
+
+/// `name` for identifier keys, `[key]` for everything else.
+fn field_key(key: &Value) -> String {
+    if let Value::String(s) = key {
+        let s = s.to_string_lossy();
+        let is_identifier = s
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if is_identifier {
+            return s;
+        }
+    }
+    format!("[{}]", pretty(key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn console_prints_expressions_and_tables() {
+        let runtime = ScriptRuntime::new().unwrap();
+        assert_eq!(runtime.eval_console("1 + 1").unwrap(), vec!["2"]);
+        assert_eq!(runtime.eval_console("x = 5").unwrap(), Vec::<String>::new());
+        assert_eq!(runtime.eval_console("x, 'a'").unwrap(), vec!["5", "\"a\""]);
+
+        let shown = runtime
+            .eval_console("t = { 'a', b = { c = true }, ['d e'] = 1 }; t.self = t; return t")
+            .unwrap();
+        assert_eq!(
+            shown,
+            vec![
+                "{\n  \"a\",\n  [\"d e\"] = 1,\n  b = {\n    c = true,\n  },\n  self = <cycle>,\n}"
+            ]
+        );
+
+        runtime.eval_console("print('hi', 1, nil)").unwrap();
+        assert_eq!(runtime.take_output(), vec!["hi\t1\tnil"]);
+        assert!(runtime.eval_console("error('boom')").is_err());
+    }
+}
@@ -0,0 +1,306 @@
+//! `pinel.job`: run external programs without blocking the UI.
+//!
+//! `pinel.job.spawn` only queues an [`EditorCommand::SpawnJob`]; the app keeps
+//! the running jobs and turns each into a subscription over [`run`], whose
+//! output comes back as [`JobEvent`] messages and is handed to the job's Lua
+//! callbacks. Dropping a job from the app's list drops its subscription, which
+//! kills the process.
+
+use iced::futures::channel::mpsc;
+use iced::futures::{SinkExt, Stream};
+use mlua::{Function, Lua, Result as LuaResult, Table};
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+
+use super::{store_callback, EditorCommand};
+
+/// Job ids stay unique across script reloads.
+static NEXT_JOB_ID: AtomicU64 = AtomicU64::new(1);
+
+/// A process requested by `pinel.job.spawn`. Callbacks are registry ids.
+#[derive(Debug, Clone, Hash)]
+pub struct JobSpec {
+    pub id: u64,
+    pub cmd: String,
+    pub args: Vec<String>,
+    pub cwd: Option<PathBuf>,
+    pub on_stdout: Option<usize>,
+    pub on_stderr: Option<usize>,
+    pub on_exit: Option<usize>,
+}
+
+impl JobSpec {
+    /// The command line as typed in a shell, for display.
+    pub fn command_line(&self) -> String {
+        std::iter::once(self.cmd.as_str())
+            .chain(self.args.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn callbacks(&self) -> impl Iterator<Item = usize> {
+        [self.on_stdout, self.on_stderr, self.on_exit]
+            .into_iter()
+            .flatten()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RunningJob {
+    pub spec: JobSpec,
+    pub started_at: Instant,
+}
+
+#[derive(Debug, Clone)]
+pub enum JobEvent {
+    Stdout {
+        id: u64,
+        line: String,
+    },
+    Stderr {
+        id: u64,
+        line: String,
+    },
+    /// The process finished. `code` is `None` if it was killed by a signal
+    /// or never started, in which case `error` says why.
+    Exit {
+        id: u64,
+        code: Option<i32>,
+        error: Option<String>,
+    },
+}
+
+/// Spawns the process and streams its output line by line, ending with
+/// [`JobEvent::Exit`].
+pub fn run(spec: &JobSpec) -> impl Stream<Item = JobEvent> {
+    let spec = spec.clone();
+    iced::stream::channel(64, move |mut sender: mpsc::Sender<JobEvent>| async move {
+        let id = spec.id;
+        let mut command = tokio::process::Command::new(&spec.cmd);
+        command
+            .args(&spec.args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        if let Some(cwd) = &spec.cwd {
+            command.current_dir(cwd);
+        }
+
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(err) => {
+                let error = Some(format!("failed to start `{}`: {err}", spec.cmd));
+                let _ = sender
+                    .send(JobEvent::Exit {
+                        id,
+                        code: None,
+                        error,
+                    })
+                    .await;
+                return;
+            }
+        };
+
+        let stdout = forward_lines(child.stdout.take(), sender.clone(), move |line| {
+            JobEvent::Stdout { id, line }
+        });
+        let stderr = forward_lines(child.stderr.take(), sender.clone(), move |line| {
+            JobEvent::Stderr { id, line }
+        });
+        let (_, _, status) = tokio::join!(stdout, stderr, child.wait());
+
+        let event = match status {
+            Ok(status) => JobEvent::Exit {
+                id,
+                code: status.code(),
+                error: None,
+            },
+            Err(err) => JobEvent::Exit {
+                id,
+                code: None,
+                error: Some(err.to_string()),
+            },
+        };
+        let _ = sender.send(event).await;
+    })
+}
+
+async fn forward_lines<R: AsyncRead + Unpin>(
+    reader: Option<R>,
+    mut sender: mpsc::Sender<JobEvent>,
+    event: impl Fn(String) -> JobEvent,
+) {
+    let Some(reader) = reader else {
+        return;
+    };
+    // Read raw lines so output that isn't valid UTF-8 doesn't stop the
+    // reader, which would leave the process blocked on a full pipe.
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line).await {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+        let text = line.strip_suffix(b"\n").unwrap_or(&line);
+        let text = text.strip_suffix(b"\r").unwrap_or(text);
+        let text = String::from_utf8_lossy(text).into_owned();
+        if sender.send(event(text)).await.is_err() {
+            return;
+        }
+    }
+}
+
+pub(super) fn install(
+    lua: &Lua,
+    pinel: &Table,
+    commands: &Arc<Mutex<Vec<EditorCommand>>>,
+) -> LuaResult<()> {
+    let job = lua.create_table()?;
+
+    {
+        let commands = Arc::clone(commands);
+        let f = lua.create_function(move |lua, spec: Table| -> LuaResult<u64> {
+            let cmd: String = spec
+                .get::<Option<String>>("cmd")?
+                .ok_or_else(|| mlua::Error::runtime("pinel.job.spawn needs a `cmd` string"))?;
+            let args = spec.get::<Option<Vec<String>>>("args")?.unwrap_or_default();
+            let cwd = spec.get::<Option<String>>("cwd")?.map(PathBuf::from);
+
+            let callback = |name: &str| -> LuaResult<Option<usize>> {
+                spec.get::<Option<Function>>(name)?
+                    .map(|f| store_callback(lua, f))
+                    .transpose()
+            };
+
+            let id = NEXT_JOB_ID.fetch_add(1, Ordering::Relaxed);
+            commands
+                .lock()
+                .unwrap()
+                .push(EditorCommand::SpawnJob(JobSpec {
+                    id,
+                    cmd,
+                    args,
+                    cwd,
+                    on_stdout: callback("on_stdout")?,
+                    on_stderr: callback("on_stderr")?,
+                    on_exit: callback("on_exit")?,
+                }));
+            Ok(id)
+        })?;
+        job.set("spawn", f)?;
+    }
+
+    {
+        let commands = Arc::clone(commands);
+        let f = lua.create_function(move |_, id: u64| {
+            commands.lock().unwrap().push(EditorCommand::CancelJob(id));
+            Ok(())
+        })?;
+        job.set("cancel", f)?;
+    }
+
+    pinel.set("job", job)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scripting::ScriptRuntime;
+    use iced::futures::StreamExt;
+
+    fn spec(cmd: &str, args: &[&str]) -> JobSpec {
+        JobSpec {
+            id: 7,
+            cmd: cmd.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            cwd: None,
+            on_stdout: None,
+            on_stderr: None,
+            on_exit: None,
+        }
+    }
+
+    fn collect(spec: &JobSpec) -> Vec<JobEvent> {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(run(spec).collect())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_streams_lines_then_exits() {
+        let events = collect(&spec(
+            "sh",
+            &[
+                "-c",
+                "printf 'one\\r\\nt\\377o\\nlast'; echo oops >&2; exit 3",
+            ],
+        ));
+
+        let stdout: Vec<&str> = events
+            .iter()
+            .filter_map(|event| match event {
+                JobEvent::Stdout { id: 7, line } => Some(line.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(stdout, ["one", "t\u{FFFD}o", "last"]);
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, JobEvent::Stderr { id: 7, line } if line == "oops")));
+        assert!(matches!(
+            events.last(),
+            Some(JobEvent::Exit {
+                id: 7,
+                code: Some(3),
+                error: None
+            })
+        ));
+        assert_eq!(events.len(), 5);
+    }
+
+    #[test]
+    fn run_reports_commands_that_fail_to_start() {
+        let events = collect(&spec("pinel-no-such-command", &[]));
+        assert!(matches!(
+            events.as_slice(),
+            [JobEvent::Exit { id: 7, code: None, error: Some(error) }]
+                if error.starts_with("failed to start `pinel-no-such-command`")
+        ));
+    }
+
+    #[test]
+    fn spawn_checks_its_arguments() {
+        let runtime = ScriptRuntime::new().unwrap();
+        let err = runtime
+            .exec("=test", "pinel.job.spawn({ args = { 'x' } })")
+            .unwrap_err();
+        assert!(
+            err.contains("pinel.job.spawn needs a `cmd` string"),
+            "{err}"
+        );
+        assert!(runtime
+            .exec("=test", "pinel.job.spawn({ cmd = 'ls', args = 'x' })")
+            .is_err());
+
+        let commands = runtime
+            .exec(
+                "=test",
+                "pinel.job.spawn({ cmd = 'ls', args = { '-l' }, cwd = '/tmp', on_exit = function() end })",
+            )
+            .unwrap();
+        let [EditorCommand::SpawnJob(spec)] = commands.as_slice() else {
+            panic!("expected one SpawnJob, got {commands:?}");
+        };
+        assert_eq!(spec.command_line(), "ls -l");
+        assert_eq!(spec.cwd, Some(PathBuf::from("/tmp")));
+        assert!(spec.on_exit.is_some() && spec.on_stdout.is_none());
+    }
+}
@@ -0,0 +1,467 @@
+//! User-defined key bindings registered from Lua via `pinel.keymap.set`.
+//!
+//! The keyboard subscription cannot capture application state, so the active
+//! keymap lives behind a global lock (the same way the theme does) and is
+//! consulted before the built-in shortcuts.
+
+use iced::keyboard::key::Named;
+use iced::keyboard::{Key, Modifiers};
+use once_cell::sync::Lazy;
+use std::fmt;
+use std::sync::RwLock;
+
+/// Editor mode a binding applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeymapMode {
+    /// Active regardless of the current editor mode.
+    Global,
+    Normal,
+    Insert,
+    Visual,
+}
+
+impl KeymapMode {
+    pub fn parse(mode: &str) -> Result<Self, String> {
+        match mode.trim().to_lowercase().as_str() {
+            "" | "*" | "global" => Ok(Self::Global),
+            "n" | "normal" => Ok(Self::Normal),
+            "i" | "insert" => Ok(Self::Insert),
+            "v" | "visual" => Ok(Self::Visual),
+            other => Err(format!(
+                "unknown keymap mode '{other}' (expected global, normal, insert or visual)"
+            )),
+        }
+    }
+}
+
+/// A single key press with its modifiers, e.g. `ctrl+shift+k`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyChord {
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+    pub cmd: bool,
+    /// Lowercased key name: a single character or a named key like `enter`.
+    pub key: String,
+}
+
+impl KeyChord {
+    /// Parses one chord such as `ctrl+shift+k` or `alt+enter`.
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let mut chord = KeyChord {
+            ctrl: false,
+            shift: false,
+            alt: false,
+            cmd: false,
+            key: String::new(),
+        };
+
+        let parts: Vec<&str> = input.split('+').collect();
+        let Some((key, modifiers)) = parts.split_last() else {
+            return Err(format!("empty key chord: '{input}'"));
+        };
+
+        for modifier in modifiers {
+            match modifier.trim().to_lowercase().as_str() {
+                "ctrl" | "control" => chord.ctrl = true,
+                "shift" => chord.shift = true,
+                "alt" | "option" | "opt" | "meta" => chord.alt = true,
+                "cmd" | "super" | "logo" => chord.cmd = true,
+                other => return Err(format!("unknown modifier '{other}' in '{input}'")),
+            }
+        }
+
+        let key = key.trim();
+        chord.key = match key.to_lowercase().as_str() {
+            "" => return Err(format!("missing key in '{input}'")),
+            "esc" => "escape".to_string(),
+            "return" | "cr" => "enter".to_string(),
+            "bs" => "backspace".to_string(),
+            "del" => "delete".to_string(),
+            "arrowup" => "up".to_string(),
+            "arrowdown" => "down".to_string(),
+            "arrowleft" => "left".to_string(),
+            "arrowright" => "right".to_string(),
+            "plus" => "+".to_string(),
+            name if name.chars().count() == 1 || is_named_key(name) => name.to_string(),
+            other => return Err(format!("unknown key '{other}' in '{input}'")),
+        };
+        if !shift_applies(&chord.key) {
+            chord.shift = false;
+        }
+
+        Ok(chord)
+    }
+
+    /// Builds a chord from a key press reported by iced.
+    pub fn from_event(key: &Key, modifiers: Modifiers) -> Option<Self> {
+        let name = match key {
+            Key::Character(c) => c.to_lowercase(),
+            Key::Named(named) => named_key_name(*named)?.to_string(),
+            Key::Unidentified => return None,
+        };
+
+        Some(KeyChord {
+            ctrl: modifiers.control(),
+            shift: modifiers.shift() && shift_applies(&name),
+            alt: modifiers.alt(),
+            cmd: modifiers.logo(),
+            key: name,
+        })
+    }
+
+    fn has_modifier(&self) -> bool {
+        self.ctrl || self.alt || self.cmd
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "ctrl+")?;
+        }
+        if self.cmd {
+            write!(f, "cmd+")?;
+        }
+        if self.alt {
+            write!(f, "alt+")?;
+        }
+        if self.shift {
+            write!(f, "shift+")?;
+        }
+        write!(f, "{}", self.key)
+    }
+}
+
+/// Whether shift is a modifier of `key` rather than part of it. Shifted
+/// symbols arrive already shifted (`?`, not `shift+/`), so bindings name
+/// the symbol itself: `ctrl+?`.
+fn shift_applies(key: &str) -> bool {
+    let mut chars = key.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => c.is_alphabetic(),
+        _ => true,
+    }
+}
+
+/// Parses a whitespace separated chord sequence such as `ctrl+k ctrl+s`.
+pub fn parse_sequence(input: &str) -> Result<Vec<KeyChord>, String> {
+    let sequence = input
+        .split_whitespace()
+        .map(KeyChord::parse)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if sequence.is_empty() {
+        return Err("empty key sequence".to_string());
+    }
+
+    Ok(sequence)
+}
+
+/// What happens when a binding fires.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeymapAction {
+    /// Runs a command palette entry by name.
+    Command(String),
+    /// Calls a Lua function stored in the script runtime under this id.
+    Lua(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct KeyBinding {
+    pub mode: KeymapMode,
+    pub sequence: Vec<KeyChord>,
+    pub action: KeymapAction,
+}
+
+/// Result of feeding one key press into the keymap.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeymapMatch {
+    /// A binding completed; run its action.
+    Action(KeymapAction),
+    /// The press is a prefix of a longer binding; wait for the next chord.
+    Pending,
+    /// No user binding applies; fall through to the built-in shortcuts.
+    None,
+}
+
+#[derive(Debug, Default)]
+pub struct Keymap {
+    bindings: Vec<KeyBinding>,
+    pending: Vec<KeyChord>,
+    /// Current editor mode; without modal editing everything is insert mode.
+    mode: Option<KeymapMode>,
+}
+
+impl Keymap {
+    /// Drops every binding and any half-typed sequence.
+    pub fn clear(&mut self) {
+        self.bindings.clear();
+        self.pending.clear();
+    }
+
+    /// Switches the mode bindings are looked up for; `None` while modal
+    /// editing is off.
+    pub fn set_mode(&mut self, mode: Option<KeymapMode>) {
+        if self.mode != mode {
+            self.pending.clear();
+            self.mode = mode;
+        }
+    }
+
+    pub fn mode(&self) -> Option<KeymapMode> {
+        self.mode
+    }
+
+    /// Adds a binding, replacing any existing binding for the same mode and keys.
+    pub fn set(&mut self, binding: KeyBinding) {
+        self.bindings
+            .retain(|b| !(b.mode == binding.mode && b.sequence == binding.sequence));
+        self.bindings.push(binding);
+    }
+
+    fn applies(&self, binding: &KeyBinding) -> bool {
+        binding.mode == KeymapMode::Global
+            || binding.mode == self.mode.unwrap_or(KeymapMode::Insert)
+    }
+
+    /// Feeds a key press and reports whether it completes or extends a binding.
+    pub fn feed(&mut self, chord: KeyChord) -> KeymapMatch {
+        // Plain typing never starts a sequence unless a binding asks for it
+        // in the current mode, so only consult the table when it could match.
+        if self.pending.is_empty() && !chord.has_modifier() && !self.starts_any(&chord) {
+            return KeymapMatch::None;
+        }
+
+        let mut candidate = self.pending.clone();
+        candidate.push(chord.clone());
+
+        match self.lookup(&candidate) {
+            KeymapMatch::None if !self.pending.is_empty() => {
+                // The sequence broke off; retry the key on its own.
+                self.pending.clear();
+                self.feed(chord)
+            }
+            KeymapMatch::Pending => {
+                self.pending = candidate;
+                KeymapMatch::Pending
+            }
+            result => {
+                self.pending.clear();
+                result
+            }
+        }
+    }
+
+    fn starts_any(&self, chord: &KeyChord) -> bool {
+        self.bindings
+            .iter()
+            .any(|b| self.applies(b) && b.sequence.first() == Some(chord))
+    }
+
+    fn lookup(&self, candidate: &[KeyChord]) -> KeymapMatch {
+        let mut prefix = false;
+        for binding in self.bindings.iter().rev().filter(|b| self.applies(b)) {
+            if binding.sequence == candidate {
+                return KeymapMatch::Action(binding.action.clone());
+            }
+            if binding.sequence.len() > candidate.len() && binding.sequence.starts_with(candidate) {
+                prefix = true;
+            }
+        }
+
+        if prefix {
+            KeymapMatch::Pending
+        } else {
+            KeymapMatch::None
+        }
+    }
+}
+
+pub static KEYMAP: Lazy<RwLock<Keymap>> = Lazy::new(|| RwLock::new(Keymap::default()));
+
+pub fn keymap_mut() -> std::sync::RwLockWriteGuard<'static, Keymap> {
+    KEYMAP.write().unwrap()
+}
+
+fn is_named_key(name: &str) -> bool {
+    matches!(
+        name,
+        "enter"
+            | "escape"
+            | "tab"
+            | "space"
+            | "backspace"
+            | "delete"
+            | "insert"
+            | "up"
+            | "down"
+            | "left"
+            | "right"
+            | "home"
+            | "end"
+            | "pageup"
+            | "pagedown"
+    ) || name
+        .strip_prefix('f')
+        .and_then(|n| n.parse::<u8>().ok())
+        .is_some_and(|n| (1..=12).contains(&n))
+}
+
+fn named_key_name(named: Named) -> Option<&'static str> {
+    Some(match named {
+        Named::Enter => "enter",
+        Named::Escape => "escape",
+        Named::Tab => "tab",
+        Named::Space => "space",
+        Named::Backspace => "backspace",
+        Named::Delete => "delete",
+        Named::Insert => "insert",
+        Named::ArrowUp => "up",
+        Named::ArrowDown => "down",
+        Named::ArrowLeft => "left",
+        Named::ArrowRight => "right",
+        Named::Home => "home",
+        Named::End => "end",
+        Named::PageUp => "pageup",
+        Named::PageDown => "pagedown",
+        Named::F1 => "f1",
+        Named::F2 => "f2",
+        Named::F3 => "f3",
+        Named::F4 => "f4",
+        Named::F5 => "f5",
+        Named::F6 => "f6",
+        Named::F7 => "f7",
+        Named::F8 => "f8",
+        Named::F9 => "f9",
+        Named::F10 => "f10",
+        Named::F11 => "f11",
+        Named::F12 => "f12",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binding(keys: &str, command: &str) -> KeyBinding {
+        KeyBinding {
+            mode: KeymapMode::Global,
+            sequence: parse_sequence(keys).unwrap(),
+            action: KeymapAction::Command(command.to_string()),
+        }
+    }
+
+    #[test]
+    fn parses_modifiers_and_named_keys() {
+        let chord = KeyChord::parse("ctrl+shift+K").unwrap();
+        assert!(chord.ctrl && chord.shift && !chord.alt);
+        assert_eq!(chord.key, "k");
+
+        assert_eq!(KeyChord::parse("alt+Return").unwrap().key, "enter");
+        assert!(KeyChord::parse("hyper+k").is_err());
+        assert!(KeyChord::parse("ctrl+nonsense").is_err());
+    }
+
+    #[test]
+    fn shifted_symbols_match_as_typed() {
+        let mut keymap = Keymap::default();
+        keymap.set(binding("ctrl+?", "Show Help"));
+        let help = KeymapMatch::Action(KeymapAction::Command("Show Help".to_string()));
+
+        // Shift+/ arrives as `?` with shift held.
+        let typed = KeyChord::from_event(
+            &Key::Character("?".into()),
+            Modifiers::CTRL | Modifiers::SHIFT,
+        )
+        .unwrap();
+        assert!(!typed.shift);
+        assert_eq!(keymap.feed(typed), help);
+        assert_eq!(keymap.feed(KeyChord::parse("ctrl+shift+?").unwrap()), help);
+
+        let letter = KeyChord::from_event(
+            &Key::Character("K".into()),
+            Modifiers::CTRL | Modifiers::SHIFT,
+        )
+        .unwrap();
+        assert_eq!(letter, KeyChord::parse("ctrl+shift+k").unwrap());
+        let enter = KeyChord::from_event(&Key::Named(Named::Enter), Modifiers::SHIFT).unwrap();
+        assert!(enter.shift);
+    }
+
+    #[test]
+    fn single_chord_binding_fires() {
+        let mut keymap = Keymap::default();
+        keymap.set(binding("ctrl+shift+k", "Toggle Terminal"));
+
+        let result = keymap.feed(KeyChord::parse("ctrl+shift+k").unwrap());
+        assert_eq!(
+            result,
+            KeymapMatch::Action(KeymapAction::Command("Toggle Terminal".to_string()))
+        );
+    }
+
+    #[test]
+    fn chord_sequences_wait_for_the_second_key() {
+        let mut keymap = Keymap::default();
+        keymap.set(binding("ctrl+k ctrl+s", "Save File"));
+
+        assert_eq!(
+            keymap.feed(KeyChord::parse("ctrl+k").unwrap()),
+            KeymapMatch::Pending
+        );
+        assert_eq!(
+            keymap.feed(KeyChord::parse("ctrl+s").unwrap()),
+            KeymapMatch::Action(KeymapAction::Command("Save File".to_string()))
+        );
+    }
+
+    #[test]
+    fn broken_sequence_falls_back_to_single_key() {
+        let mut keymap = Keymap::default();
+        keymap.set(binding("ctrl+k ctrl+s", "Save File"));
+        keymap.set(binding("ctrl+p", "Open File"));
+
+        assert_eq!(
+            keymap.feed(KeyChord::parse("ctrl+k").unwrap()),
+            KeymapMatch::Pending
+        );
+        assert_eq!(
+            keymap.feed(KeyChord::parse("ctrl+p").unwrap()),
+            KeymapMatch::Action(KeymapAction::Command("Open File".to_string()))
+        );
+    }
+
+    #[test]
+    fn plain_keys_are_ignored_without_bindings() {
+        let mut keymap = Keymap::default();
+        keymap.set(binding("ctrl+k", "Save File"));
+        assert_eq!(
+            keymap.feed(KeyChord::parse("j").unwrap()),
+            KeymapMatch::None
+        );
+    }
+
+    #[test]
+    fn mode_specific_bindings_only_apply_in_their_mode() {
+        let mut keymap = Keymap::default();
+        keymap.set(KeyBinding {
+            mode: KeymapMode::Normal,
+            sequence: parse_sequence("ctrl+d").unwrap(),
+            action: KeymapAction::Lua(1),
+        });
+
+        keymap.mode = Some(KeymapMode::Insert);
+        assert_eq!(
+            keymap.feed(KeyChord::parse("ctrl+d").unwrap()),
+            KeymapMatch::None
+        );
+
+        keymap.mode = Some(KeymapMode::Normal);
+        assert_eq!(
+            keymap.feed(KeyChord::parse("ctrl+d").unwrap()),
+            KeymapMatch::Action(KeymapAction::Lua(1))
+        );
+    }
+}
@@ -1,131 +1,533 @@
-use mlua::{Lua, Result as LuaResult};
+pub mod buffer;
+pub mod console;
+pub mod jobs;
+pub mod keymap;
+pub mod plugins;
+pub mod statusline;
+
+use mlua::{Function, IntoLuaMulti, Lua, Result as LuaResult, Table, Value};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
+use crate::features::editor_buffer::TextEdit;
+use buffer::{BufferSnapshot, SharedBuffer};
+use keymap::KeymapAction;
+
+/// Named registry slot holding Lua callbacks referenced by id from Rust.
+const CALLBACKS_KEY: &str = "pinel.callbacks";
+/// Named registry slot mapping hook event names to lists of handlers.
+const HOOKS_KEY: &str = "pinel.hooks";
+
+/// Editor events scripts can subscribe to with `pinel.on(event, fn)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookEvent {
+    /// An explicit save is about to write the active buffer.
+    BufWritePre,
+    /// A file was opened into a new tab.
+    BufOpen,
+    /// A different tab became active.
+    TabChanged,
+    /// The editor window lost focus.
+    FocusLost,
+}
+
+impl HookEvent {
+    pub const ALL: [HookEvent; 4] = [
+        HookEvent::BufWritePre,
+        HookEvent::BufOpen,
+        HookEvent::TabChanged,
+        HookEvent::FocusLost,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HookEvent::BufWritePre => "buf_write_pre",
+            HookEvent::BufOpen => "buf_open",
+            HookEvent::TabChanged => "tab_changed",
+            HookEvent::FocusLost => "focus_lost",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|event| event.name() == name)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum EditorCommand {
     UseBuiltinTheme(String),
-    SetThemeColor { name: String, value: String },
+    SetThemeColor {
+        name: String,
+        value: String,
+    },
     SetSidebarVisible(bool),
     SetSidebarWidth(f32),
+    SetKeymap {
+        mode: String,
+        keys: String,
+        action: KeymapAction,
+    },
+    /// Replaces text in the active editor, then moves its cursor (0-based).
+    EditBuffer {
+        edit: TextEdit,
+        cursor: (usize, usize),
+    },
+    /// Moves the active editor's cursor to a 0-based `(line, column)`.
+    SetCursor((usize, usize)),
+    RegisterCommand {
+        name: String,
+        description: String,
+        callback: usize,
+    },
+    SpawnJob(jobs::JobSpec),
+    CancelJob(u64),
+    AddStatusSegment {
+        id: String,
+        align: crate::features::statusline::SegmentAlign,
+        render: usize,
+    },
+    /// Renders every status bar segment again on the next update.
+    RefreshStatusline,
 }
 
-#[derive(Debug, Clone)]
 pub struct StartupScriptLoad {
     pub path: PathBuf,
     pub source: Option<String>,
     pub commands: Vec<EditorCommand>,
     pub error: Option<String>,
+    pub plugins: Vec<plugins::PluginStatus>,
+    /// Lua state the script ran in, kept so registered callbacks stay callable.
+    pub runtime: Option<ScriptRuntime>,
 }
 
 pub fn startup_script_path() -> PathBuf {
     crate::config::theme_manager::get_config_dir().join("init.lua")
 }
 
-pub fn load_startup_script() -> StartupScriptLoad {
+/// Loads enabled plugins and then `init.lua` into a fresh runtime, so the
+/// user's own config can override what plugins set up.
+pub fn load_startup_script(disabled_plugins: &[String]) -> StartupScriptLoad {
     let path = startup_script_path();
+    let source = fs::read_to_string(&path);
 
-    let source = match fs::read_to_string(&path) {
-        Ok(source) => source,
+    let runtime = match ScriptRuntime::new() {
+        Ok(runtime) => runtime,
         Err(err) => {
             return StartupScriptLoad {
                 path,
-                source: None,
+                source: source.ok(),
                 commands: Vec::new(),
-                error: Some(err.to_string()),
+                error: Some(err),
+                plugins: Vec::new(),
+                runtime: None,
             };
         }
     };
 
-    match eval_script(&source) {
-        Ok(commands) => StartupScriptLoad {
-            path,
-            source: Some(source),
+    let (plugins, mut commands) = runtime.load_plugins(&plugins::plugins_dir(), disabled_plugins);
+
+    let (source, error) = match source {
+        Ok(source) => {
+            let result = runtime.exec(&format!("@{}", path.display()), &source);
+            commands.extend(runtime.take_commands());
+            match result {
+                Ok(queued) => {
+                    commands.extend(queued);
+                    (Some(source), None)
+                }
+                Err(err) => (Some(source), Some(err)),
+            }
+        }
+        Err(err) => (None, Some(err.to_string())),
+    };
+
+    StartupScriptLoad {
+        path,
+        source,
+        commands,
+        error,
+        plugins,
+        runtime: Some(runtime),
+    }
+}
+
+/// Condenses a Lua error into one line for a notification, e.g.
+/// `init.lua:12: attempt to index a nil value`.
+///
+/// Errors raised from Rust API functions carry no position of their own, so
+/// the first Lua frame of the traceback supplies it. Paths are shown relative
+/// to `config_dir`.
+pub fn error_summary(err: &str, config_dir: &Path) -> String {
+    let prefix = format!("{}/", config_dir.display());
+    let mut lines = err.lines();
+    let first = lines.next().unwrap_or_default();
+    let message = first
+        .strip_prefix("runtime error: ")
+        .or_else(|| first.strip_prefix("syntax error: "))
+        .unwrap_or(first);
+
+    let has_location = message
+        .split(": ")
+        .next()
+        .and_then(|head| head.rsplit_once(':'))
+        .is_some_and(|(_, line)| line.parse::<u32>().is_ok());
+
+    let summary = if has_location {
+        message.to_string()
+    } else {
+        let location = lines
+            .map(str::trim)
+            .filter(|line| !line.starts_with("[C]"))
+            .find_map(|line| line.rsplit_once(": in ").map(|(location, _)| location));
+        match location {
+            Some(location) => format!("{location}: {message}"),
+            None => message.to_string(),
+        }
+    };
+
+    summary.replace(&prefix, "")
+}
+
+/// A Lua state with the `pinel` API installed.
+///
+/// Scripts queue [`EditorCommand`]s which the app drains and applies after
+/// each entry into Lua (running a chunk or calling a stored callback).
+pub struct ScriptRuntime {
+    lua: Lua,
+    commands: Arc<Mutex<Vec<EditorCommand>>>,
+    buffer: SharedBuffer,
+    /// Lines passed to `print`, shown in the Lua console.
+    output: Arc<Mutex<Vec<String>>>,
+}
+
+impl ScriptRuntime {
+    pub fn new() -> Result<Self, String> {
+        let lua = Lua::new();
+        let commands = Arc::new(Mutex::new(Vec::<EditorCommand>::new()));
+        let buffer = SharedBuffer::default();
+        let output = Arc::new(Mutex::new(Vec::new()));
+
+        install_api(&lua, &commands, &buffer).map_err(|e| e.to_string())?;
+        console::install_print(&lua, &output).map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            lua,
             commands,
-            error: None,
-        },
-        Err(err) => StartupScriptLoad {
-            path,
-            source: Some(source),
-            commands: Vec::new(),
-            error: Some(err),
-        },
+            buffer,
+            output,
+        })
+    }
+
+    /// Sets the buffer `pinel.buf` operates on during the next calls into Lua.
+    pub fn set_buffer(&self, snapshot: Option<BufferSnapshot>) {
+        *self.buffer.lock().unwrap() = snapshot;
+    }
+
+    /// Runs a chunk of Lua and returns the commands it queued.
+    ///
+    /// `chunk_name` is what Lua error messages report as the location,
+    /// e.g. `@/home/me/.config/pinel/init.lua`.
+    pub fn exec(&self, chunk_name: &str, source: &str) -> Result<Vec<EditorCommand>, String> {
+        self.lua
+            .load(source)
+            .set_name(chunk_name)
+            .exec()
+            .map_err(|e| e.to_string())?;
+        Ok(self.take_commands())
+    }
+
+    /// Calls a callback previously stored with [`store_callback`].
+    pub fn call_callback(
+        &self,
+        id: usize,
+        args: impl IntoLuaMulti,
+    ) -> Result<Vec<EditorCommand>, String> {
+        let callbacks: Table = self
+            .lua
+            .named_registry_value(CALLBACKS_KEY)
+            .map_err(|e| e.to_string())?;
+        let callback: Function = callbacks
+            .get(id)
+            .map_err(|_| format!("no Lua callback registered with id {id}"))?;
+        callback.call::<()>(args).map_err(|e| e.to_string())?;
+        Ok(self.take_commands())
+    }
+
+    /// Drops a callback that will not be called again. Its slot is kept so
+    /// the ids of later callbacks stay valid.
+    pub fn release_callback(&self, id: usize) {
+        if let Ok(callbacks) = self.lua.named_registry_value::<Table>(CALLBACKS_KEY) {
+            let _ = callbacks.raw_set(id, false);
+        }
+    }
+
+    /// Runs every handler registered for `event`.
+    ///
+    /// Handlers receive a table with `event` and, when there is one, the
+    /// buffer `path`. A failing handler does not stop the ones after it; its
+    /// error is returned alongside the commands the others queued.
+    pub fn fire(&self, event: HookEvent, path: Option<&Path>) -> (Vec<EditorCommand>, Vec<String>) {
+        let mut errors = Vec::new();
+
+        let handlers = self
+            .lua
+            .named_registry_value::<Table>(HOOKS_KEY)
+            .and_then(|hooks| hooks.get::<Option<Table>>(event.name()));
+
+        match handlers {
+            Ok(Some(handlers)) => {
+                for handler in handlers.sequence_values::<Function>() {
+                    let result = handler.and_then(|handler| {
+                        let args = self.lua.create_table()?;
+                        args.set("event", event.name())?;
+                        if let Some(path) = path {
+                            args.set("path", path.to_string_lossy().to_string())?;
+                        }
+                        handler.call::<()>(args)
+                    });
+                    if let Err(err) = result {
+                        errors.push(err.to_string());
+                    }
+                }
+            }
+            Ok(None) => {}
+            Err(err) => errors.push(err.to_string()),
+        }
+
+        (self.take_commands(), errors)
+    }
+
+    pub fn take_commands(&self) -> Vec<EditorCommand> {
+        std::mem::take(&mut *self.commands.lock().unwrap())
     }
 }
 
-pub fn eval_script(source: &str) -> Result<Vec<EditorCommand>, String> {
-    let lua = Lua::new();
-    let commands = Arc::new(Mutex::new(Vec::<EditorCommand>::new()));
+/// Keeps a Lua function alive in the registry and returns its id.
+fn store_callback(lua: &Lua, callback: Function) -> LuaResult<usize> {
+    let callbacks: Table = lua.named_registry_value(CALLBACKS_KEY)?;
+    let id = callbacks.raw_len() + 1;
+    callbacks.raw_set(id, callback)?;
+    Ok(id)
+}
+
+fn install_api(
+    lua: &Lua,
+    commands: &Arc<Mutex<Vec<EditorCommand>>>,
+    buffer: &SharedBuffer,
+) -> LuaResult<()> {
+    lua.set_named_registry_value(CALLBACKS_KEY, lua.create_table()?)?;
+    lua.set_named_registry_value(HOOKS_KEY, lua.create_table()?)?;
 
-    let pinel = lua.create_table().map_err(|e| e.to_string())?;
-    let theme = lua.create_table().map_err(|e| e.to_string())?;
-    let ui = lua.create_table().map_err(|e| e.to_string())?;
+    let pinel = lua.create_table()?;
+    let theme = lua.create_table()?;
+    let ui = lua.create_table()?;
+    let keymap = lua.create_table()?;
+    let command = lua.create_table()?;
 
     {
-        let commands = Arc::clone(&commands);
-        let f = lua
-            .create_function(move |_, name: String| -> LuaResult<()> {
-                commands
-                    .lock()
-                    .unwrap()
-                    .push(EditorCommand::UseBuiltinTheme(name));
-                Ok(())
-            })
-            .map_err(|e| e.to_string())?;
-        theme.set("use_builtin", f).map_err(|e| e.to_string())?;
+        let commands = Arc::clone(commands);
+        let f = lua.create_function(move |_, name: String| -> LuaResult<()> {
+            commands
+                .lock()
+                .unwrap()
+                .push(EditorCommand::UseBuiltinTheme(name));
+            Ok(())
+        })?;
+        theme.set("use_builtin", f)?;
     }
 
     {
-        let commands = Arc::clone(&commands);
-        let f = lua
-            .create_function(move |_, (name, value): (String, String)| -> LuaResult<()> {
+        let commands = Arc::clone(commands);
+        let f =
+            lua.create_function(move |_, (name, value): (String, String)| -> LuaResult<()> {
                 commands
                     .lock()
                     .unwrap()
                     .push(EditorCommand::SetThemeColor { name, value });
                 Ok(())
-            })
-            .map_err(|e| e.to_string())?;
-        theme.set("set_color", f).map_err(|e| e.to_string())?;
+            })?;
+        theme.set("set_color", f)?;
+    }
+
+    {
+        let commands = Arc::clone(commands);
+        let f = lua.create_function(move |_, visible: bool| -> LuaResult<()> {
+            commands
+                .lock()
+                .unwrap()
+                .push(EditorCommand::SetSidebarVisible(visible));
+            Ok(())
+        })?;
+        ui.set("show_sidebar", f)?;
     }
 
     {
-        let commands = Arc::clone(&commands);
-        let f = lua
-            .create_function(move |_, visible: bool| -> LuaResult<()> {
+        let commands = Arc::clone(commands);
+        let f = lua.create_function(move |_, width: f32| -> LuaResult<()> {
+            commands
+                .lock()
+                .unwrap()
+                .push(EditorCommand::SetSidebarWidth(width));
+            Ok(())
+        })?;
+        ui.set("set_sidebar_width", f)?;
+    }
+
+    {
+        let commands = Arc::clone(commands);
+        let f = lua.create_function(
+            move |lua, (mode, keys, action): (String, String, Value)| -> LuaResult<()> {
+                // Validate eagerly so mistakes surface as Lua errors with a line number.
+                keymap::KeymapMode::parse(&mode).map_err(mlua::Error::runtime)?;
+                keymap::parse_sequence(&keys).map_err(mlua::Error::runtime)?;
+
+                let action = match action {
+                    Value::String(name) => KeymapAction::Command(name.to_str()?.to_string()),
+                    Value::Function(callback) => KeymapAction::Lua(store_callback(lua, callback)?),
+                    other => {
+                        return Err(mlua::Error::runtime(format!(
+                            "keymap action must be a command name or a function, got {}",
+                            other.type_name()
+                        )));
+                    }
+                };
+
                 commands
                     .lock()
                     .unwrap()
-                    .push(EditorCommand::SetSidebarVisible(visible));
+                    .push(EditorCommand::SetKeymap { mode, keys, action });
                 Ok(())
-            })
-            .map_err(|e| e.to_string())?;
-        ui.set("show_sidebar", f).map_err(|e| e.to_string())?;
+            },
+        )?;
+        keymap.set("set", f)?;
     }
 
     {
-        let commands = Arc::clone(&commands);
-        let f = lua
-            .create_function(move |_, width: f32| -> LuaResult<()> {
-                commands
-                    .lock()
-                    .unwrap()
-                    .push(EditorCommand::SetSidebarWidth(width));
+        let f = lua.create_function(
+            |lua, (event, handler): (String, Function)| -> LuaResult<()> {
+                if HookEvent::parse(&event).is_none() {
+                    let known = HookEvent::ALL.map(HookEvent::name).join(", ");
+                    return Err(mlua::Error::runtime(format!(
+                        "unknown event '{event}' (expected one of: {known})"
+                    )));
+                }
+
+                let hooks: Table = lua.named_registry_value(HOOKS_KEY)?;
+                let handlers = match hooks.get::<Option<Table>>(event.as_str())? {
+                    Some(handlers) => handlers,
+                    None => {
+                        let handlers = lua.create_table()?;
+                        hooks.set(event.as_str(), &handlers)?;
+                        handlers
+                    }
+                };
+                handlers.push(handler)?;
                 Ok(())
-            })
-            .map_err(|e| e.to_string())?;
-        ui.set("set_sidebar_width", f).map_err(|e| e.to_string())?;
+            },
+        )?;
+        pinel.set("on", f)?;
     }
 
-    pinel.set("theme", theme).map_err(|e| e.to_string())?;
-    pinel.set("ui", ui).map_err(|e| e.to_string())?;
-    lua.globals()
-        .set("pinel", pinel)
-        .map_err(|e| e.to_string())?;
+    {
+        let commands = Arc::clone(commands);
+        let f = lua.create_function(move |lua, spec: Table| -> LuaResult<()> {
+            let name: String = spec
+                .get::<Option<String>>("name")?
+                .filter(|name| !name.trim().is_empty())
+                .ok_or_else(|| mlua::Error::runtime("command needs a non-empty `name`"))?;
+            let description = spec
+                .get::<Option<String>>("description")?
+                .unwrap_or_default();
+            let run: Function = spec.get::<Option<Function>>("run")?.ok_or_else(|| {
+                mlua::Error::runtime(format!("command '{name}' needs a `run` function"))
+            })?;
+
+            let callback = store_callback(lua, run)?;
+            commands
+                .lock()
+                .unwrap()
+                .push(EditorCommand::RegisterCommand {
+                    name,
+                    description,
+                    callback,
+                });
+            Ok(())
+        })?;
+        command.set("register", f)?;
+    }
 
-    lua.load(source).exec().map_err(|e| e.to_string())?;
+    buffer::install(lua, &pinel, commands, buffer)?;
+    jobs::install(lua, &pinel, commands)?;
+    statusline::install(lua, &pinel, commands)?;
 
-    let queued_commands = commands.lock().unwrap().clone();
-    Ok(queued_commands)
+    pinel.set("theme", theme)?;
+    pinel.set("ui", ui)?;
+    pinel.set("keymap", keymap)?;
+    pinel.set("command", command)?;
+    lua.globals().set("pinel", pinel)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_summary_keeps_line_numbers() {
+        let dir = Path::new("/home/u/.config/pinel");
+        let runtime = ScriptRuntime::new().unwrap();
+        let chunk = "@/home/u/.config/pinel/init.lua";
+
+        let err = runtime.exec(chunk, "x = = 1").unwrap_err();
+        assert_eq!(
+            error_summary(&err, dir),
+            "init.lua:1: unexpected symbol near '='"
+        );
+
+        let err = runtime.exec(chunk, "local t\nt.x = 1").unwrap_err();
+        assert_eq!(
+            error_summary(&err, dir),
+            "init.lua:2: attempt to index a nil value (local 't')"
+        );
+
+        let err = runtime
+            .exec(chunk, "\npinel.keymap.set('bogus', 'a', 'b')")
+            .unwrap_err();
+        assert!(error_summary(&err, dir).starts_with("init.lua:2: unknown keymap mode 'bogus'"));
+    }
+
+    #[test]
+    fn hooks_fire_only_their_event_and_survive_errors() {
+        let runtime = ScriptRuntime::new().unwrap();
+        runtime
+            .exec(
+                "=test",
+                r#"
+                pinel.on("buf_open", function() pinel.ui.show_sidebar(false) end)
+                pinel.on("buf_write_pre", function() error("boom") end)
+                pinel.on("buf_write_pre", function(args)
+                    pinel.ui.set_sidebar_width(#args.path)
+                end)
+                "#,
+            )
+            .unwrap();
+
+        let (commands, errors) = runtime.fire(HookEvent::BufWritePre, Some(Path::new("/a.rs")));
+        assert!(
+            matches!(commands.as_slice(), [EditorCommand::SetSidebarWidth(width)] if *width == 5.0),
+            "{commands:?}"
+        );
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("boom"), "{errors:?}");
+
+        let (commands, errors) = runtime.fire(HookEvent::BufOpen, None);
+        assert!(matches!(
+            commands.as_slice(),
+            [EditorCommand::SetSidebarVisible(false)]
+        ));
+        assert!(errors.is_empty());
+        assert!(runtime.fire(HookEvent::FocusLost, None).0.is_empty());
+    }
 }
@@ -0,0 +1,245 @@
+//! Plugins installed as folders under `~/.config/pinel/plugins/<name>/init.lua`.
+//!
+//! Every plugin runs in the shared runtime, so it can register keymaps,
+//! commands and hooks, but gets its own global environment (falling back to
+//! the real globals for reads) and a `require` that only searches its folder.
+
+use mlua::{Lua, Result as LuaResult, Table, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{EditorCommand, ScriptRuntime};
+
+/// A discovered plugin and how loading it went.
+#[derive(Debug, Clone)]
+pub struct PluginStatus {
+    pub name: String,
+    pub enabled: bool,
+    /// Load failure, if any. Disabled plugins are never loaded.
+    pub error: Option<String>,
+}
+
+pub fn plugins_dir() -> PathBuf {
+    crate::config::theme_manager::get_config_dir().join("plugins")
+}
+
+/// Lists plugin folders that contain an `init.lua`, sorted by name.
+pub fn discover_plugins(dir: &Path) -> Vec<(String, PathBuf)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut plugins: Vec<(String, PathBuf)> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir() && path.join("init.lua").is_file())
+        .filter_map(|path| {
+            let name = path.file_name()?.to_string_lossy().to_string();
+            (!name.starts_with('.')).then_some((name, path))
+        })
+        .collect();
+
+    plugins.sort_by(|a, b| a.0.cmp(&b.0));
+    plugins
+}
+
+impl ScriptRuntime {
+    /// Loads every plugin not named in `disabled`, in name order.
+    ///
+    /// A failing plugin is recorded in its status and does not stop the rest;
+    /// commands it queued before failing are still returned.
+    pub fn load_plugins(
+        &self,
+        dir: &Path,
+        disabled: &[String],
+    ) -> (Vec<PluginStatus>, Vec<EditorCommand>) {
+        let mut statuses = Vec::new();
+        let mut commands = Vec::new();
+
+        for (name, plugin_dir) in discover_plugins(dir) {
+            let enabled = !disabled.contains(&name);
+            let error = if enabled {
+                let result = self.load_plugin(&plugin_dir);
+                commands.extend(self.take_commands());
+                result.err()
+            } else {
+                None
+            };
+
+            statuses.push(PluginStatus {
+                name,
+                enabled,
+                error,
+            });
+        }
+
+        (statuses, commands)
+    }
+
+    fn load_plugin(&self, dir: &Path) -> Result<(), String> {
+        let env = plugin_env(&self.lua, dir).map_err(|e| e.to_string())?;
+        run_file(&self.lua, &dir.join("init.lua"), env)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Builds a fresh global table for one plugin with its own `require`.
+fn plugin_env(lua: &Lua, dir: &Path) -> LuaResult<Table> {
+    let env = lua.create_table()?;
+    let meta = lua.create_table()?;
+    meta.set("__index", lua.globals())?;
+    env.set_metatable(Some(meta));
+
+    let loaded = lua.create_table()?;
+    let root = dir.to_path_buf();
+    let require_env = env.clone();
+    let require = lua.create_function(move |lua, module: String| -> LuaResult<Value> {
+        if let Some(value) = loaded.get::<Option<Value>>(module.as_str())? {
+            return Ok(value);
+        }
+
+        let Some(candidates) = module_candidates(&root, &module) else {
+            return Err(mlua::Error::runtime(format!(
+                "invalid module name '{module}'"
+            )));
+        };
+        let Some(path) = candidates.iter().find(|path| path.is_file()) else {
+            return Err(mlua::Error::runtime(format!(
+                "module '{module}' not found in {}",
+                root.display()
+            )));
+        };
+
+        let value = match run_file(lua, path, require_env.clone())? {
+            Value::Nil => Value::Boolean(true),
+            value => value,
+        };
+        loaded.set(module.as_str(), &value)?;
+        Ok(value)
+    })?;
+    env.set("require", require)?;
+
+    Ok(env)
+}
+
+/// The files `require(module)` may load: `a.b` is `a/b.lua` or
+/// `a/b/init.lua` under `root`. Names that could reach outside the folder
+/// give `None`.
+fn module_candidates(root: &Path, module: &str) -> Option<[PathBuf; 2]> {
+    if module.is_empty()
+        || module.starts_with('.')
+        || module.ends_with('.')
+        || module.contains("..")
+        || module.contains(['/', '\\'])
+    {
+        return None;
+    }
+    let relative = module.replace('.', "/");
+    let candidates = [
+        root.join(format!("{relative}.lua")),
+        root.join(&relative).join("init.lua"),
+    ];
+    candidates
+        .iter()
+        .all(|path| path.starts_with(root))
+        .then_some(candidates)
+}
+
+fn run_file(lua: &Lua, path: &Path, env: Table) -> LuaResult<Value> {
+    let source = fs::read_to_string(path).map_err(mlua::Error::external)?;
+    lua.load(source)
+        .set_name(format!("@{}", path.display()))
+        .set_environment(env)
+        .call::<Value>(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn discovers_plugin_folders_with_init_lua() {
+        let dir = tempfile::tempdir().unwrap();
+        write(&dir.path().join("zeta/init.lua"), "");
+        write(&dir.path().join("alpha/init.lua"), "");
+        write(&dir.path().join(".hidden/init.lua"), "");
+        write(&dir.path().join("no-init/plugin.lua"), "");
+        write(&dir.path().join("loose.lua"), "");
+
+        let names: Vec<String> = discover_plugins(dir.path())
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, ["alpha", "zeta"]);
+        assert!(discover_plugins(&dir.path().join("missing")).is_empty());
+    }
+
+    #[test]
+    fn plugins_get_their_own_globals() {
+        let dir = tempfile::tempdir().unwrap();
+        write(
+            &dir.path().join("a/init.lua"),
+            "shared = 'a'\n_G.a_sees_print = print ~= nil",
+        );
+        write(
+            &dir.path().join("b/init.lua"),
+            "_G.b_sees_shared = shared ~= nil",
+        );
+        write(&dir.path().join("c/init.lua"), "_G.c_loaded = true");
+
+        let runtime = ScriptRuntime::new().unwrap();
+        let (statuses, _) = runtime.load_plugins(dir.path(), &["c".to_string()]);
+        assert!(statuses.iter().all(|status| status.error.is_none()));
+        assert!(!statuses[2].enabled);
+
+        let globals = runtime.lua.globals();
+        assert!(globals.get::<bool>("a_sees_print").unwrap());
+        assert!(!globals.get::<bool>("b_sees_shared").unwrap());
+        assert_eq!(globals.get::<Option<String>>("shared").unwrap(), None);
+        assert_eq!(globals.get::<Option<bool>>("c_loaded").unwrap(), None);
+    }
+
+    #[test]
+    fn require_only_loads_from_the_plugin_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let plugins = dir.path().join("plugins");
+        write(&dir.path().join("outside.lua"), "return 'escaped'");
+        write(
+            &plugins.join("p/lib/util.lua"),
+            "loads = (loads or 0) + 1\nreturn { value = 42 }",
+        );
+        write(&plugins.join("p/lib/pkg/init.lua"), "return 'pkg'");
+        write(
+            &plugins.join("p/init.lua"),
+            r#"
+            local util = require("lib.util")
+            _G.util_value = util.value
+            _G.cached = require("lib.util") == util and loads == 1
+            _G.pkg = require("lib.pkg")
+            _G.refused = 0
+            for _, name in ipairs({ "", ".outside", "..outside", "lib.", "lib/util", "lib\\util", "lib..util" }) do
+                local ok, err = pcall(require, name)
+                if not ok and tostring(err):find("invalid module name", 1, true) then
+                    _G.refused = _G.refused + 1
+                end
+            end
+            "#,
+        );
+
+        let runtime = ScriptRuntime::new().unwrap();
+        let (statuses, _) = runtime.load_plugins(&plugins, &[]);
+        assert_eq!(statuses[0].error, None);
+
+        let globals = runtime.lua.globals();
+        assert_eq!(globals.get::<i64>("util_value").unwrap(), 42);
+        assert!(globals.get::<bool>("cached").unwrap());
+        assert_eq!(globals.get::<String>("pkg").unwrap(), "pkg");
+        assert_eq!(globals.get::<i64>("refused").unwrap(), 7);
+    }
+}
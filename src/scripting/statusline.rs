@@ -0,0 +1,97 @@
+//! `pinel.statusline`: status bar segments rendered by Lua functions.
+
+use mlua::{Function, Lua, Result as LuaResult, Table, Value};
+use std::sync::{Arc, Mutex};
+
+use super::{store_callback, EditorCommand, ScriptRuntime, CALLBACKS_KEY};
+use crate::features::statusline::{SegmentAlign, StatusContext};
+
+pub(super) fn install(
+    lua: &Lua,
+    pinel: &Table,
+    commands: &Arc<Mutex<Vec<EditorCommand>>>,
+) -> LuaResult<()> {
+    let statusline = lua.create_table()?;
+
+    {
+        let commands = Arc::clone(commands);
+        let f = lua.create_function(move |lua, spec: Table| -> LuaResult<()> {
+            let id: String = spec
+                .get::<Option<String>>("id")?
+                .filter(|id| !id.trim().is_empty())
+                .ok_or_else(|| mlua::Error::runtime("statusline segment needs a non-empty `id`"))?;
+            let align = match spec.get::<Option<String>>("align")? {
+                Some(align) => SegmentAlign::parse(&align).map_err(mlua::Error::runtime)?,
+                None => SegmentAlign::Right,
+            };
+            let render: Function = spec.get::<Option<Function>>("render")?.ok_or_else(|| {
+                mlua::Error::runtime(format!(
+                    "statusline segment '{id}' needs a `render` function"
+                ))
+            })?;
+
+            let render = store_callback(lua, render)?;
+            commands
+                .lock()
+                .unwrap()
+                .push(EditorCommand::AddStatusSegment { id, align, render });
+            Ok(())
+        })?;
+        statusline.set("add", f)?;
+    }
+
+    {
+        let commands = Arc::clone(commands);
+        let f = lua.create_function(move |_, ()| {
+            commands
+                .lock()
+                .unwrap()
+                .push(EditorCommand::RefreshStatusline);
+            Ok(())
+        })?;
+        statusline.set("refresh", f)?;
+    }
+
+    pinel.set("statusline", statusline)?;
+    Ok(())
+}
+
+impl ScriptRuntime {
+    /// Calls a segment's `render` function with the editor state and returns
+    /// its text; `nil` or an empty string hides the segment.
+    pub fn render_status_segment(
+        &self,
+        render: usize,
+        context: &StatusContext,
+    ) -> Result<Option<String>, String> {
+        let call = || -> LuaResult<Value> {
+            let callbacks: Table = self.lua.named_registry_value(CALLBACKS_KEY)?;
+            let render: Function = callbacks.get(render)?;
+
+            let args = self.lua.create_table()?;
+            if let Some(path) = &context.path {
+                args.set("path", path.to_string_lossy().to_string())?;
+            }
+            args.set("line", context.line)?;
+            args.set("col", context.col)?;
+            args.set("language", context.language.clone())?;
+            args.set("encoding", context.encoding)?;
+            args.set("line_ending", context.line_ending)?;
+            args.set("lsp", context.lsp.clone())?;
+            args.set("problems", context.problems.clone())?;
+            args.set("git_branch", context.git_branch.clone())?;
+            render.call(args)
+        };
+
+        match call().map_err(|e| e.to_string())? {
+            Value::Nil => Ok(None),
+            Value::String(s) => Ok(Some(s.to_string_lossy()).filter(|s| !s.is_empty())),
+            Value::Integer(i) => Ok(Some(i.to_string())),
+            Value::Number(n) => Ok(Some(n.to_string())),
+            other => Err(format!(
+                "render must return a string or nil, got {}",
+                other.type_name()
+            )),
+        }
+    }
+}
@@ -1,14 +1,42 @@
 //! Keyboard event subscription handlers.
 
-use crate::message::Message;
-use iced::keyboard::Key;
+use crate::features::lsp::LocationKind;
+use crate::message::{Message, VimKey};
+use crate::scripting::keymap::{keymap_mut, KeyChord, KeymapMatch, KeymapMode, KEYMAP};
+use iced::keyboard::key::Named;
+use iced::keyboard::{Key, Modifiers};
 use iced::window;
 use iced::{Event, Subscription};
 
+/// Control chords vim takes over from the default shortcuts in normal and
+/// visual mode.
+const VIM_CTRL_KEYS: &str = "bdefioruvy";
+
 /// Emits keyboard shortcut messages for global editor actions.
+///
+/// User bindings from `pinel.keymap.set` take precedence over the defaults,
+/// and vim's normal and visual modes over both.
 pub fn shortcuts() -> Subscription<Message> {
     iced::event::listen_with(|event, _status, _id| match event {
         Event::Keyboard(iced::keyboard::Event::KeyPressed { key, modifiers, .. }) => {
+            if let Some(chord) = KeyChord::from_event(&key, modifiers) {
+                match keymap_mut().feed(chord) {
+                    KeymapMatch::Action(action) => return Some(Message::RunKeymapAction(action)),
+                    KeymapMatch::Pending => return None,
+                    KeymapMatch::None => {}
+                }
+            }
+
+            let vim_command_mode = matches!(
+                KEYMAP.read().unwrap().mode(),
+                Some(KeymapMode::Normal | KeymapMode::Visual)
+            );
+            if vim_command_mode {
+                if let Some(key) = vim_key(&key, modifiers) {
+                    return Some(Message::VimKey(key));
+                }
+            }
+
             let primary = modifiers.command() || modifiers.control();
             let navigation_msg = match &key {
                 Key::Named(iced::keyboard::key::Named::ArrowUp) => {
@@ -35,6 +63,8 @@ pub fn shortcuts() -> Subscription<Message> {
                         "p" | "P" => return Some(Message::ToggleCommandPalette),
                         "s" | "S" => return Some(Message::ToggleSettings),
                         "o" | "O" => return Some(Message::OpenFolderDialog),
+                        "m" | "M" => return Some(Message::ToggleProblems),
+                        "i" | "I" => return Some(Message::FormatDocument),
                         _ => {}
                     }
                 } else if primary {
@@ -47,15 +77,41 @@ pub fn shortcuts() -> Subscription<Message> {
                         "j" | "J" => return Some(Message::ToggleTerminal),
                         "f" | "F" => return Some(Message::ToggleFindReplace),
                         "n" | "N" => return Some(Message::NewFile),
+                        "." => return Some(Message::ShowCodeActions),
                         _ => {}
                     }
                 }
             }
 
+            if let Key::Named(Named::F12) = key {
+                return Some(if primary {
+                    Message::FindLocations(LocationKind::Implementation)
+                } else if modifiers.shift() {
+                    Message::FindLocations(LocationKind::References)
+                } else {
+                    Message::FindLocations(LocationKind::Definition)
+                });
+            }
+
             if !modifiers.command() && !modifiers.control() {
+                if let Key::Named(Named::F2) = key {
+                    return Some(Message::RenameSymbol);
+                }
+                if let Key::Named(Named::F8) = key {
+                    return Some(if modifiers.shift() {
+                        Message::PreviousProblem
+                    } else {
+                        Message::NextProblem
+                    });
+                }
                 if let Key::Named(iced::keyboard::key::Named::Escape) = key {
                     return Some(Message::EscapePressed);
                 }
+                if let Key::Named(iced::keyboard::key::Named::Tab) = key {
+                    if !modifiers.shift() {
+                        return Some(Message::CommandInputComplete);
+                    }
+                }
             }
 
             None
@@ -64,6 +120,33 @@ pub fn shortcuts() -> Subscription<Message> {
     })
 }
 
+fn vim_key(key: &Key, modifiers: Modifiers) -> Option<VimKey> {
+    if modifiers.logo() || modifiers.alt() {
+        return None;
+    }
+    match key {
+        Key::Character(c) => {
+            let mut chars = c.chars();
+            let ch = chars.next().filter(|_| chars.next().is_none())?;
+            if !modifiers.control() {
+                return Some(VimKey::Char(ch));
+            }
+            let ch = ch.to_ascii_lowercase();
+            (!modifiers.shift() && VIM_CTRL_KEYS.contains(ch)).then_some(VimKey::Ctrl(ch))
+        }
+        _ if modifiers.control() => None,
+        Key::Named(Named::Space) => Some(VimKey::Char(' ')),
+        Key::Named(Named::Tab) => Some(VimKey::Ctrl('i')),
+        Key::Named(Named::Enter) => Some(VimKey::Enter),
+        Key::Named(Named::Backspace) => Some(VimKey::Backspace),
+        Key::Named(Named::ArrowUp) => Some(VimKey::Up),
+        Key::Named(Named::ArrowDown) => Some(VimKey::Down),
+        Key::Named(Named::ArrowLeft) => Some(VimKey::Left),
+        Key::Named(Named::ArrowRight) => Some(VimKey::Right),
+        _ => None,
+    }
+}
+
 /// Emits raw keyboard and mouse input messages for developer logging.
 pub fn input_debug() -> Subscription<Message> {
     iced::event::listen_with(|event, _status, _id| match event {
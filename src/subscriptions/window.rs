@@ -16,9 +16,11 @@ pub fn resizes() -> Subscription<Message> {
 
 /// Refreshes workspace-derived state when the app regains focus
 /// This catches branch switches or filesystem changes when made outside Pinel
+/// Losing focus is forwarded too so scripts can react to it
 pub fn focus_refresh() -> Subscription<Message> {
     iced::event::listen_with(|event, _status, _id| match event {
         Event::Window(window::Event::Focused) => Some(Message::FileTreeRefresh),
+        Event::Window(window::Event::Unfocused) => Some(Message::WindowFocusLost),
         _ => None,
     })
 }
@@ -16,13 +16,30 @@ fn icon_widget<'a>(icon: IconAsset) -> Element<'a, Message> {
         .into()
 }
 
-pub fn view_sidebar<'a>(file_tree: Option<&'a FileTree>, width: f32) -> Element<'a, Message> {
+/// The file tree above the outline section, which takes two fifths of the
+/// height while expanded.
+pub fn view_sidebar<'a>(
+    file_tree: Option<&'a FileTree>,
+    outline: Element<'a, Message>,
+    outline_expanded: bool,
+    width: f32,
+) -> Element<'a, Message> {
     let sidebar_content: Element<'a, Message> = match file_tree {
         Some(tree) => view_file_tree(tree),
         None => view_empty_sidebar(),
     };
 
-    let sidebar = container(scrollable(sidebar_content).height(Length::Fill))
+    let tree = scrollable(sidebar_content);
+    let content = if outline_expanded {
+        column![
+            tree.height(Length::FillPortion(3)),
+            container(outline).height(Length::FillPortion(2)),
+        ]
+    } else {
+        column![tree.height(Length::Fill), outline]
+    };
+
+    let sidebar = container(content)
         .width(Length::Fixed(width))
         .height(Length::Fill)
         .padding(iced::Padding {
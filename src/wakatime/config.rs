@@ -1,5 +1,5 @@
+use crate::config::lua_config;
 use std::fs;
-use std::io::Write;
 use std::path::PathBuf;
 
 #[derive(Debug, Clone)]
@@ -26,57 +26,47 @@ fn get_wakatime_path() -> PathBuf {
     get_config_dir().join("wakatime.lua")
 }
 
-pub fn load() -> WakaTimeConfig {
-    let path = get_wakatime_path();
-    if let Ok(content) = fs::read_to_string(&path) {
-        from_lua(&content).unwrap_or_default()
-    } else {
-        WakaTimeConfig::default()
+/// Loads `wakatime.lua`; a missing file gives the defaults.
+pub fn load() -> Result<WakaTimeConfig, String> {
+    match fs::read_to_string(get_wakatime_path()) {
+        Ok(content) => from_lua(&content),
+        Err(_) => Ok(WakaTimeConfig::default()),
     }
 }
 
 pub fn save(cfg: &WakaTimeConfig) -> Result<(), std::io::Error> {
-    let path = get_wakatime_path();
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)?;
-    }
+    lua_config::save_fields(
+        &get_wakatime_path(),
+        &lua_fields(cfg),
+        |existing| from_lua(existing).map(|current| lua_fields(&current)),
+        || to_lua(cfg),
+    )
+}
 
-    let mut file = fs::File::create(path)?;
-    file.write_all(to_lua(cfg).as_bytes())?;
-    Ok(())
+fn lua_fields(cfg: &WakaTimeConfig) -> Vec<(&'static str, String)> {
+    vec![
+        ("api_key", lua_config::lua_string(&cfg.api_key)),
+        ("api_url", lua_config::lua_string(&cfg.api_url)),
+    ]
 }
 
 fn to_lua(cfg: &WakaTimeConfig) -> String {
     format!(
-        "return {{\n api_key = \"{}\",\n api_url = \"{}\",\n}}\n",
-        cfg.api_key, cfg.api_url
+        "return {{\n api_key = {},\n api_url = {},\n}}\n",
+        lua_config::lua_string(&cfg.api_key),
+        lua_config::lua_string(&cfg.api_url)
     )
 }
 
 fn from_lua(content: &str) -> Result<WakaTimeConfig, String> {
+    let table = lua_config::eval("wakatime.lua", content)?;
     let mut cfg = WakaTimeConfig::default();
 
-    for line in content.lines() {
-        let line = line.trim();
-        if line.is_empty() || line.starts_with("--") || line.starts_with("return") || line == "}" {
-            continue;
-        }
-
-        if let Some((key, value)) = line.split_once('=') {
-            let key = key.trim();
-            let value = value
-                .trim()
-                .trim_end_matches(',')
-                .trim_matches('"')
-                .trim_matches('\'')
-                .to_string();
-
-            match key {
-                "api_key" => cfg.api_key = value,
-                "api_url" => cfg.api_url = value,
-                _ => {}
-            }
-        }
+    if let Some(api_key) = table.string("api_key")? {
+        cfg.api_key = api_key;
+    }
+    if let Some(api_url) = table.string("api_url")? {
+        cfg.api_url = api_url;
     }
 
     Ok(cfg)